@@ -0,0 +1,65 @@
+//! Times the growth of the largest chains the board can hold. Run with
+//! `cargo bench`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use acquire::game::board::Board;
+use acquire::game::messages::{Merge, TilePlacement, TilePlacementImplication};
+use acquire::game::tile::Tile;
+use acquire::game::Company;
+
+/// The number of times each benchmark is run.
+const ITERATIONS: u32 = 10_000;
+
+/// Places every tile in `rows` as an unaffiliated tile, except for the last,
+/// which founds `company` and so converts the rest of the rows into it.
+fn fill_rows(board: &mut Board, rows: std::ops::RangeInclusive<u8>, company: Company) {
+    let mut tiles: Vec<_> = rows
+        .flat_map(|row| ('a'..=Tile::LAST_COL).map(move |col| Tile::new(row, col)))
+        .collect();
+    let founder = tiles.pop().unwrap();
+
+    for tile in tiles {
+        board.place_tile(TilePlacement { tile, implication: None });
+    }
+    board.place_tile(TilePlacement {
+        tile: founder,
+        implication: Some(TilePlacementImplication::FoundsCompany(company)),
+    });
+}
+
+/// Runs `f` [`ITERATIONS`] times, and prints how long it took on average.
+fn bench(name: &str, mut f: impl FnMut()) {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    println!("{name}: {:?} per iteration", start.elapsed() / ITERATIONS);
+}
+
+fn main() {
+    // Founds a company on a board that is otherwise full of unaffiliated
+    // tiles, so that the whole board joins the chain
+    bench("found company on full board", || {
+        let mut board = Board::new();
+        fill_rows(&mut board, 1..=Tile::NUM_ROWS, Company::Tower);
+        assert_eq!(black_box(board).company_sizes[Company::Tower], 108);
+    });
+
+    // Merges two companies that together fill every row but one
+    let mut merging = Board::new();
+    fill_rows(&mut merging, 1..=6, Company::American);
+    fill_rows(&mut merging, 8..=Tile::NUM_ROWS, Company::Luxor);
+    let merge = Merge::new(&[Company::Luxor], Company::American);
+    merging.place_tile(TilePlacement {
+        tile: Tile::new(7, 'a'),
+        implication: Some(TilePlacementImplication::MergesCompanies(merge)),
+    });
+
+    bench("merge two halves of the board", || {
+        let mut board = merging;
+        board.resolve_merge(&merge);
+        assert_eq!(black_box(board).company_sizes[Company::American], 100);
+    });
+}
@@ -0,0 +1,28 @@
+//! Times whole games played out by bots, which covers every update the board
+//! and the merge logic make in the course of a game. Run with `cargo bench`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use acquire::game::bot::{Bot, Difficulty};
+use acquire::game::kernel::sim::simulate;
+
+/// The number of games played by each benchmark, each dealt from its own
+/// seed.
+const GAMES: u64 = 200;
+
+/// Plays [`GAMES`] games with `players` players, and prints how long a game
+/// took on average.
+fn bench(name: &str, players: usize) {
+    let strategies = vec![Bot::new(Difficulty::Easy); players];
+    let start = Instant::now();
+    for seed in 0..GAMES {
+        black_box(simulate(seed, &strategies));
+    }
+    println!("{name}: {:?} per game", start.elapsed() / GAMES as u32);
+}
+
+fn main() {
+    bench("two player game", 2);
+    bench("six player game", 6);
+}
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use acquire::net::protocol;
+
+fuzz_target!(|data: &[u8]| {
+    // Anything accepted must survive being sent again
+    if let Ok(message) = protocol::parse_client_message(data) {
+        let json = serde_json::to_vec(&message).unwrap();
+        protocol::parse_client_message(&json).unwrap();
+    }
+});
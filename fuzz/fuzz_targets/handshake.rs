@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use acquire::net::protocol;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(handshake) = protocol::parse_handshake(data) {
+        assert!(handshake.capabilities.is_empty());
+        assert!(handshake.player_name.chars().count() <= protocol::MAX_NAME_LEN);
+    }
+});
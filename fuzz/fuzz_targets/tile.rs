@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use acquire::game::tile::Tile;
+
+fuzz_target!(|data: &str| {
+    if let Ok(tile) = data.parse::<Tile>() {
+        assert_eq!(tile.to_string().parse::<Tile>().unwrap(), tile);
+    }
+});
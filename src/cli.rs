@@ -1,28 +1,255 @@
-use clap::{Parser, Subcommand};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use acquire::client::lang::Lang;
+use acquire::net::{dial, frame, protocol};
+use acquire::game::policy::ChatPolicy;
+use acquire::server::clock::TimeOutPenalty;
+use acquire::server::invite::InviteCode;
+use acquire::server::pacing::Pacing;
+use acquire::server::vote::Majority;
+use acquire::server::webhook::WebhookUrl;
+use clap::{Args, Parser, Subcommand};
 
 /// Command-line interface for the legendary board game Acquire!
 #[derive(Debug, Parser)]
+#[command(version, long_version = long_version())]
 pub struct Cli {
     #[command(subcommand)]
     pub intent: HostIntent,
-    /// User name used when connecting to the server
-    #[arg(short, long)]
-    pub name: String,
+    /// User name used when connecting to the server. Needed to join or host
+    #[arg(short, long, value_parser = parse_name)]
+    pub name: Option<String>,
     /// If set, you will join the game as a spectator
     #[arg(short, long)]
     pub spectate: bool,
+    /// If set, moves that break the rules are explained rather than sent
+    #[arg(short, long)]
+    pub tutorial: bool,
+    /// Milliseconds spent searching for a move when asked for a hard hint
+    #[arg(long, value_name = "MS", default_value_t = 1000)]
+    pub hint_time: u64,
+    /// Language of the messages shown by the client: en or es
+    #[arg(long, default_value = "en")]
+    pub lang: Lang,
+    /// If set, the game is narrated as plain lines of text rather than drawn,
+    /// for use with a screen reader. Type /board to hear the board
+    #[arg(long)]
+    pub screen_reader: bool,
+    /// If set, important events such as your turn are shown only in the
+    /// chat, not also over a corner of the screen
+    #[arg(long)]
+    pub no_toasts: bool,
+    /// If set, the terminal bell rings once on your turn, twice for a merger
+    /// and three times when the game is over
+    #[arg(long)]
+    pub bell: bool,
+    /// Shell command run on your turn, for a merger and when the game is
+    /// over, such as "paplay ding.ogg". The event is given in $ACQUIRE_CUE
+    #[arg(long, value_name = "COMMAND", conflicts_with = "bell")]
+    pub sound_command: Option<String>,
+    /// File the chat and game events you see are appended to as plain text,
+    /// for a record of your own. Output for a screen reader is plain text
+    /// already, and can be saved with tee instead
+    #[arg(long, value_name = "PATH", conflicts_with = "screen_reader")]
+    pub log_game: Option<PathBuf>,
 }
 
 #[derive(Debug, Subcommand)]
 pub enum HostIntent {
     /// Join a game hosted elsewhere
     Join {
-        /// IP address to join
+        /// Address to join, such as example.com, 192.168.0.2:7070 or
+        /// [::1]:7070. The port is 7070 if not given
+        #[arg(value_parser = parse_address)]
         address: String,
+        /// If set, a server that isn't answering yet is tried again, waiting
+        /// longer after each attempt, until it answers or Ctrl-C is pressed
+        #[arg(long)]
+        retry: bool,
+        /// SOCKS5 proxy to join through, such as localhost:1080 for
+        /// `ssh -D 1080`. The port is 1080 if not given
+        #[arg(long, value_name = "PROXY")]
+        socks5: Option<String>,
+        /// Invite code given by the host, needed to join a game that is open
+        /// only by invitation
+        #[arg(long)]
+        code: Option<InviteCode>,
     },
     /// Host a game on your machine
     Host {
-        /// Port to which other players will connect to join
+        /// Port to which other players will connect to join. The server needs
+        /// no other port, so it can be reached through a single SSH tunnel.
+        /// If 0, any free port is picked, and printed once the server starts
+        #[arg(default_value_t = dial::DEFAULT_PORT)]
         port: u16,
-    }
+        /// If set, moves sent just before it is a player's turn to make them
+        /// are held until it is, rather than rejected
+        #[arg(long)]
+        queue_early_actions: bool,
+        /// Seconds each player has for the whole game, spent while the game
+        /// waits on them. If not set, players may take as long as they like
+        #[arg(long, value_name = "SECONDS")]
+        time_bank: Option<u64>,
+        /// What happens to a player who runs out of time: auto-move or
+        /// forfeit
+        #[arg(long, default_value = "auto-move")]
+        time_out_penalty: TimeOutPenalty,
+        /// Number of turns in a row a player may let their time run out on
+        /// before they are taken to be away, and the server plays their seat
+        /// for the rest of the game
+        #[arg(long, value_name = "TURNS", requires = "time_bank")]
+        afk_turns: Option<NonZeroUsize>,
+        /// How quickly the steps of a merger reach the players: instant, or
+        /// animated with a short pause after each so that they can be
+        /// followed
+        #[arg(long, default_value = "instant")]
+        pacing: Pacing,
+        /// Share of the players that must agree to end a game early, without
+        /// the admin: simple, two-thirds, or unanimous
+        #[arg(long, default_value = "simple")]
+        end_vote_majority: Majority,
+        /// If set, the order of the tiles is fixed when the game starts and a
+        /// hash of it sent to the players, who can check their deal against
+        /// it once the game is over
+        #[arg(long)]
+        commit_deal: bool,
+        /// If set, the order of play is drawn at random, rather than decided
+        /// by the tiles the players draw
+        #[arg(long)]
+        random_seating: bool,
+        /// If set, players may not ask for hints, as for a ranked game
+        #[arg(long)]
+        no_hints: bool,
+        /// If set, players may not hand their seat to the server with
+        /// /autopilot on
+        #[arg(long)]
+        no_autopilot: bool,
+        /// Who may chat while a game is in progress: open to everyone,
+        /// seated players only, or closed
+        #[arg(long, default_value = "open")]
+        chat: ChatPolicy,
+        /// Name shown to players as they join. Defaults to your player name
+        #[arg(long)]
+        server_name: Option<String>,
+        /// Message of the day shown to players as they join
+        #[arg(long)]
+        motd: Option<String>,
+        /// Port on which anyone can watch the game as plain text, as with
+        /// `nc <host> <port>`, giving the invite code first if there is one.
+        /// If not set, there is no such port
+        #[arg(long, value_name = "PORT")]
+        view_port: Option<u16>,
+        /// Directory in which the final board and standings of each game are
+        /// saved as SVG, for sharing
+        #[arg(long, value_name = "DIR")]
+        export_dir: Option<PathBuf>,
+        /// http:// URL to which the start and end of each game, and each
+        /// merger, are posted as JSON
+        #[arg(long, value_name = "URL")]
+        webhook_url: Option<WebhookUrl>,
+        /// http:// URL to which the final standings of each game are
+        /// submitted as JSON under the game's ID, as for a community
+        /// leaderboard
+        #[arg(long, value_name = "URL")]
+        submit_results: Option<WebhookUrl>,
+        /// Number of actions between the snapshots kept in each game's
+        /// history, from which those joining late catch up. Smaller is
+        /// quicker to seek through but takes more memory
+        #[arg(long, value_name = "ACTIONS")]
+        checkpoint_interval: Option<NonZeroUsize>,
+        #[command(flatten)]
+        saves: Box<SaveArgs>,
+        /// If set, a short invite code is made up and printed, and only those
+        /// who give it with `join --code` may join
+        #[arg(long)]
+        invite: bool,
+        /// Number of players for whom a game is started, with no admin
+        /// needed, once that many have typed /queue
+        #[arg(long, value_name = "PLAYERS", value_parser = clap::value_parser!(u8).range(2..=6))]
+        matchmaking: Option<u8>,
+        /// If set, no game client is started. You spectate, giving admin
+        /// commands and commands such as save and load on the terminal
+        #[arg(long)]
+        headless: bool,
+    },
+    /// Print a summary of the rules of Acquire
+    Rules,
+    /// Work with replays of finished games, saved as .acq files
+    Replay {
+        #[command(subcommand)]
+        command: ReplayCommand,
+    },
+    /// Gather statistics across a directory of replays, such as how often
+    /// each company is founded and when mergers happen, and print a report
+    Analyze {
+        /// Directory holding the .acq files to analyze
+        dir: PathBuf,
+    },
+}
+
+/// Where the games played on a hosted server are saved to disk.
+#[derive(Debug, Args)]
+pub struct SaveArgs {
+    /// Directory in which the game in progress is saved as it is played,
+    /// taking turns between a few files so that a crash loses only the
+    /// last few moves
+    #[arg(long, value_name = "DIR")]
+    pub autosave_dir: Option<PathBuf>,
+    /// Number of actions between autosaves
+    #[arg(long, value_name = "ACTIONS", default_value = "5", requires = "autosave_dir")]
+    pub autosave_actions: NonZeroUsize,
+    /// Minutes after which a game with actions unsaved is autosaved,
+    /// however few they are
+    #[arg(long, value_name = "MINUTES", requires = "autosave_dir")]
+    pub autosave_minutes: Option<u64>,
+    /// Directory in which each game is saved as a replay once it is over,
+    /// as a .acq file named after the game's ID that anyone can check with
+    /// `replay verify`
+    #[arg(long, value_name = "DIR")]
+    pub replay_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ReplayCommand {
+    /// Play a replay through from its start, checking that its moves lead to
+    /// the results it records, and that its tiles were dealt from its seed
+    Verify {
+        file: PathBuf,
+    },
+    /// Step through a replay, trying out moves of your own from any point
+    /// of it and comparing where they lead with the recorded game
+    Explore {
+        file: PathBuf,
+    },
+}
+
+/// Describes the build for `--version`: the version of the game, the version
+/// of the protocol it speaks, and how it was built. Clients and servers
+/// speaking different protocols can't play together.
+fn long_version() -> &'static str {
+    static LONG_VERSION: OnceLock<String> = OnceLock::new();
+    LONG_VERSION.get_or_init(|| {
+        let profile = if cfg!(debug_assertions) { "debug" } else { "release" };
+        format!(
+            "{}\nprotocol version {}\n{profile} build for {}-{}",
+            env!("CARGO_PKG_VERSION"),
+            frame::PROTOCOL_VERSION,
+            std::env::consts::ARCH,
+            std::env::consts::OS,
+        )
+    })
+}
+
+/// Checks a name is one the server will accept, so that a bad one is caught
+/// before connecting.
+fn parse_name(name: &str) -> Result<String, protocol::ProtocolError> {
+    protocol::check_name(name)?;
+    Ok(name.to_owned())
+}
+
+fn parse_address(address: &str) -> Result<String, dial::DialError> {
+    dial::split_address(address)?;
+    Ok(address.to_owned())
 }
@@ -0,0 +1,136 @@
+use std::convert::Infallible;
+use std::io::{self, BufRead};
+
+use tokio::sync::mpsc;
+
+use super::{admin_help, console_help, parse_admin_command, parse_console_command, time_left, ChatLog, CommandParseErr};
+use crate::game::messages::*;
+use crate::server::NewConnection;
+use crate::server::console::Console;
+
+/// Runs the host's console for a server hosted without a game client, reading
+/// a command from each line of standard input. Console commands go to
+/// `console`, and anything else is sent as an admin command from the host's
+/// `connection`. Enough of what happens on the server is printed to follow
+/// along. Runs until standard input closes or the server shuts down.
+pub async fn run(mut connection: NewConnection<Infallible>, console: Console) -> io::Result<()> {
+    println!("Type help for a list of commands, or Ctrl-D to shut the server down.");
+
+    // Standard input is read on a thread of its own, as it blocks
+    let (line_sender, mut lines) = mpsc::channel(1);
+    std::thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if line_sender.blocking_send(line).is_err() { break; }
+        }
+    });
+
+    let mut chat_log = ChatLog::new();
+    loop {
+        tokio::select! {
+            line = lines.recv() => {
+                let Some(line) = line else { break };
+                let Some(admin) = run_line(&console, line.trim()).await else { continue };
+                let sent = connection.interface.sender().send(ClientMessage::Admin(admin)).await;
+                if sent.is_err() { break; }
+            },
+            msg = connection.interface.recv() => {
+                let Some(Ok(msg)) = msg else { break };
+                if let ServerMessage::Shutdown = msg {
+                    println!("The server has shut down.");
+                    break;
+                }
+                print_event(&mut chat_log, msg);
+            },
+        }
+    }
+
+    match connection.interface.close().await {
+        Ok(()) => Ok(()),
+        Err(never) => match never {},
+    }
+}
+
+/// Runs a line typed at the console. Returns the admin command to send, if the
+/// line is one.
+async fn run_line(console: &Console, line: &str) -> Option<AdminCommand> {
+    if line.is_empty() { return None; }
+
+    if let Some(help) = admin_help(line) {
+        // `help` lists both kinds of command, and `help <command>` describes
+        // whichever kind it names
+        let help = match console_help(line) {
+            Some(console) if line == "help" => help.into_iter().chain(console).collect(),
+            Some(console) => console,
+            None => help,
+        };
+        help.iter().for_each(|line| println!("{line}"));
+        return None;
+    }
+
+    match parse_console_command(line) {
+        Ok(command) => match console.run(command).await {
+            Ok(done) => println!("{done}"),
+            Err(err) => println!("Couldn't do that, as {err}."),
+        },
+        Err(CommandParseErr::UnknownCommand(_)) => match parse_admin_command(line) {
+            Ok(admin) => return Some(admin),
+            Err(err) => println!("Invalid command: {err}"),
+        },
+        Err(err) => println!("Invalid command: {err}"),
+    }
+    None
+}
+
+/// Prints what the host of a server most needs to hear of.
+fn print_event(chat_log: &mut ChatLog, msg: ServerMessage) {
+    match msg {
+        ServerMessage::Chat { player_name, message, timestamp, sequence, channel } => {
+            if let Some(chat) = chat_log.receive(&player_name, &message, timestamp, sequence, channel) {
+                println!("CHAT: {chat}");
+            }
+        },
+        ServerMessage::Join { handshake } => println!("{} joined.", handshake.player_name),
+        ServerMessage::Quit { handshake, reason: Some(LeaveReason::Kicked) } => {
+            println!("{} was kicked.", handshake.player_name)
+        },
+        ServerMessage::Quit { handshake, reason: Some(_) } => println!("{} left.", handshake.player_name),
+        ServerMessage::Quit { handshake, reason: None } => {
+            println!("{} lost their connection.", handshake.player_name)
+        },
+        ServerMessage::GameStart { info, .. } => {
+            println!("The game has begun. Order of play: {}.", info.play_order.join(", "));
+        },
+        ServerMessage::GameOver { reason, results, .. } => {
+            println!("The game is over, as {reason}.");
+            for result in results.iter() {
+                println!("  {result}");
+            }
+        },
+        ServerMessage::Invalid { reason, .. } => println!("Refused: {reason}"),
+        ServerMessage::StartScheduled { seconds_left, .. } => {
+            println!("The next game starts in {}.", time_left(seconds_left * 1000));
+        },
+        ServerMessage::Queued { player_name, queued: true, waiting, table_size } => {
+            println!("{player_name} is waiting for a game ({waiting} of {table_size} players).");
+        },
+        ServerMessage::KickVoted { player_name, target, votes, needed } => {
+            println!("{player_name} votes to kick {target} ({votes} of {needed} votes needed).");
+        },
+        ServerMessage::Away { player_name, missed_turns } => {
+            println!("{player_name} missed {missed_turns} turns in a row, and is played by the server.");
+        },
+        ServerMessage::KickFailed { target } => println!("Too few players agreed, so {target} stays."),
+        ServerMessage::ServerError { message, recovery } => {
+            println!("The server failed ({message}), but is still running.");
+            match recovery {
+                Recovery::Unaffected => {},
+                Recovery::Restored { after_actions } => {
+                    println!("The game was restored from its autosave after {after_actions} actions.");
+                },
+                Recovery::Lost => println!("The game was lost."),
+            }
+        },
+        _ => {},
+    }
+}
@@ -0,0 +1,282 @@
+use std::io::{self, BufRead, Write};
+
+use super::viewer::draw_board;
+use super::{describe_board, describe_standings, parse_game_command};
+use crate::game::kernel::{self, GameUpdateResult};
+use crate::game::messages::*;
+use crate::game::replay::{Replay, VerifyError};
+
+/// Printed by `help`.
+const HELP: &str = "\
+next [N]      Play the next move, or the next N moves, of the recorded game
+back [N]      Take back the last move, or the last N moves
+goto N        Go to the point after the first N recorded moves
+board         Show the board and the players' worth
+play, buy or resolve ...
+              Try a move of your own for the player whose turn it is, as in
+              a game. The first one forks a branch off the recorded game
+compare       Compare the players' worth on the branch with the recorded
+              game, as many moves on from the fork
+return        Leave the branch for the recorded game, where it was forked
+quit          Stop exploring";
+
+/// Steps through a [`Replay`], and lets other lines of play be tried out from
+/// any point of it. The moves recorded are the main line. Playing a move of
+/// one's own forks a branch off it, played out on a copy of the game, which
+/// can be compared with the main line before returning to it.
+pub struct Explorer<'a> {
+    replay: &'a Replay,
+    /// The number of recorded moves played on the main line.
+    position: usize,
+    /// The game after them.
+    main_line: GameUpdateResult<kernel::Ambiguous>,
+    /// The line being tried out in place of the main line, if any.
+    branch: Option<Branch>,
+}
+
+/// A line of play forked off the main line.
+struct Branch {
+    /// The number of recorded moves played before the fork.
+    from: usize,
+    /// The moves played on the branch.
+    moves: Vec<TaggedPlayerAction>,
+    /// The game at the fork, then after each move of the branch.
+    games: Vec<GameUpdateResult<kernel::Ambiguous>>,
+}
+
+impl<'a> Explorer<'a> {
+    /// Starts exploring `replay` from the start of its game. Fails if its
+    /// moves can't be played through.
+    pub fn new(replay: &'a Replay) -> Result<Self, VerifyError> {
+        // Once the game plays through, each point of it can be reached
+        let _ = replay.position(replay.actions.len())?;
+        Ok(Self {
+            replay,
+            position: 0,
+            main_line: replay.position(0)?,
+            branch: None,
+        })
+    }
+
+    /// Runs a command, giving the lines to show in answer.
+    pub fn run_command(&mut self, command: &str) -> Vec<String> {
+        let mut words = command.split_whitespace();
+        let count = |word: Option<&str>| word.map_or(Ok(1), str::parse::<usize>);
+        let result = match words.next() {
+            None => return vec![],
+            Some("help") => return HELP.lines().map(str::to_owned).collect(),
+            Some("next") if self.branch.is_none() && self.position == self.replay.actions.len() => {
+                return vec!["There are no more recorded moves.".to_owned()];
+            },
+            Some("next") => count(words.next()).map(|n| self.goto(self.position.saturating_add(n))),
+            Some("back") => count(words.next()).map(|n| self.back(n)),
+            Some("goto") => match words.next().map(str::parse) {
+                Some(Ok(n)) => Ok(self.goto(n)),
+                _ => return vec!["goto needs the number of moves to go to.".to_owned()],
+            },
+            Some("board") => return self.board(),
+            Some("compare") => return self.compare(),
+            Some("return") => return self.leave_branch(),
+            Some(_) => return self.try_move(command),
+        };
+        match result {
+            Ok(lines) => lines,
+            Err(_) => vec!["That isn't a number of moves.".to_owned()],
+        }
+    }
+
+    /// Says where on the main line or a branch the game is, and what happens
+    /// next.
+    pub fn status(&self) -> String {
+        let (game, place) = match &self.branch {
+            Some(branch) => (
+                branch.games.last().expect("a branch has at least the game it was forked from"),
+                format!("On a branch forked after move {}, {} moves in.", branch.from, branch.moves.len()),
+            ),
+            None => (
+                &self.main_line,
+                format!("After move {} of {}.", self.position, self.replay.actions.len()),
+            ),
+        };
+        match game {
+            // The recorded game may have been ended by other means
+            Ok(_) if self.branch.is_none() && self.position == self.replay.actions.len() => {
+                format!("{place} The game is over, as {}.", self.replay.reason)
+            },
+            Ok(game) => format!("{place} It is {}'s turn.", game.active_player()),
+            Err(game_over) => format!("{place} The game is over, as {}.", game_over.reason()),
+        }
+    }
+
+    /// Moves along the main line to the point after `played` moves.
+    fn goto(&mut self, played: usize) -> Vec<String> {
+        if self.branch.is_some() {
+            return vec!["Return to the recorded game first.".to_owned()];
+        }
+        let played = played.min(self.replay.actions.len());
+        self.main_line = self.replay.position(played)
+            .expect("every point of a replay that plays through can be reached");
+        self.position = played;
+        match played.checked_sub(1) {
+            Some(last) => vec![format!("{}. {}", played, self.replay.actions[last])],
+            None => vec!["The game has just started.".to_owned()],
+        }
+    }
+
+    /// Takes back the last `n` moves of the branch, or of the main line if
+    /// there is no branch.
+    fn back(&mut self, n: usize) -> Vec<String> {
+        let Some(branch) = &mut self.branch else {
+            return self.goto(self.position.saturating_sub(n));
+        };
+        let n = n.min(branch.moves.len());
+        branch.moves.truncate(branch.moves.len() - n);
+        branch.games.truncate(branch.games.len() - n);
+        vec![format!("Took back {n} moves of the branch.")]
+    }
+
+    /// Plays a move of the user's own for the active player, forking a branch
+    /// if there is none.
+    fn try_move(&mut self, command: &str) -> Vec<String> {
+        let action = match parse_game_command(command) {
+            Ok(action) => action,
+            Err(why) => return vec![format!("Invalid command: {why}.")],
+        };
+        let game = match &self.branch {
+            Some(branch) => branch.games.last().expect("a branch has at least the game it was forked from"),
+            None => &self.main_line,
+        };
+        let Ok(game) = game else {
+            return vec!["The game is over here.".to_owned()];
+        };
+        let action = TaggedPlayerAction {
+            player_name: game.active_player().into(),
+            action,
+            number: None,
+        };
+        let next = match game.clone().try_advance_game(&action) {
+            Ok(next) => next,
+            Err((_, reason)) => return vec![format!("That move can't be played: {reason}.")],
+        };
+
+        let mut lines = vec![];
+        let branch = self.branch.get_or_insert_with(|| {
+            lines.push(format!("Forked a branch after move {}.", self.position));
+            Branch { from: self.position, moves: vec![], games: vec![self.main_line.clone()] }
+        });
+        lines.push(action.to_string());
+        branch.moves.push(action);
+        branch.games.push(next);
+        lines
+    }
+
+    /// Compares the players on the branch with the main line, as many moves
+    /// on from the fork.
+    fn compare(&self) -> Vec<String> {
+        let Some(branch) = &self.branch else {
+            return vec!["There is no branch to compare. Play a move to fork one.".to_owned()];
+        };
+        let played = (branch.from + branch.moves.len()).min(self.replay.actions.len());
+        let main_line = self.replay.position(played)
+            .expect("every point of a replay that plays through can be reached");
+
+        let mut lines = vec![format!("On the branch, {} moves in:", branch.moves.len())];
+        lines.extend(standings(branch.games.last().expect("a branch has at least the game it was forked from")));
+        lines.push(format!("In the recorded game, after move {played}:"));
+        lines.extend(standings(&main_line));
+        lines
+    }
+
+    /// Drops the branch, going back to the main line where it was forked.
+    fn leave_branch(&mut self) -> Vec<String> {
+        match self.branch.take() {
+            Some(branch) => vec![format!("Back in the recorded game, after move {}.", branch.from)],
+            None => vec!["You are in the recorded game already.".to_owned()],
+        }
+    }
+
+    /// Draws the board as it is on the branch, or on the main line if there
+    /// is no branch.
+    fn board(&self) -> Vec<String> {
+        let game = match &self.branch {
+            Some(branch) => branch.games.last().expect("a branch has at least the game it was forked from"),
+            None => &self.main_line,
+        };
+        let mut lines = match game {
+            Ok(game) => [draw_board(game), vec![String::new()], describe_board(game)].concat(),
+            Err(game_over) => [draw_board(game_over), vec![String::new()], describe_board(game_over)].concat(),
+        };
+        lines.extend(standings(game));
+        lines
+    }
+}
+
+/// Ranks the players of a game by their worth, or by the money they finished
+/// with if the game is over.
+fn standings(game: &GameUpdateResult<kernel::Ambiguous>) -> Vec<String> {
+    match game {
+        Ok(game) => describe_standings(game),
+        Err(game_over) => match game_over.clone().tally_results() {
+            Ok(results) => results.final_standings.iter()
+                .map(|result| format!("{}. {} finished with {}.", result.place, result.player_name, result.final_money))
+                .collect(),
+            Err(why) => vec![format!("The results can't be tallied, as {why}.")],
+        },
+    }
+}
+
+/// Explores `replay`, reading commands from `input` until it runs out or
+/// `quit` is typed.
+pub fn run(replay: &Replay, input: impl BufRead, mut out: impl Write) -> io::Result<()> {
+    let mut explorer = match Explorer::new(replay) {
+        Ok(explorer) => explorer,
+        Err(why) => return writeln!(out, "The replay can't be played through, as {why}."),
+    };
+    writeln!(out, "Type help for a list of commands.")?;
+    writeln!(out, "{}", explorer.status())?;
+    for line in input.lines() {
+        let line = line?;
+        if line.trim() == "quit" {
+            break;
+        }
+        for answer in explorer.run_command(&line) {
+            writeln!(out, "{answer}")?;
+        }
+        writeln!(out, "{}", explorer.status())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::messages::{GameOver, GameStart};
+    use crate::game::replay::Replay;
+
+    use super::Explorer;
+
+    #[test]
+    fn branches_leave_main_line_alone() {
+        let start = GameStart::two_player_test();
+        let replay = Replay {
+            version: Replay::VERSION,
+            game_id: None,
+            start,
+            deal_seed: None,
+            actions: Box::new([]),
+            substitutions: Box::new([]),
+            reason: GameOver::EndedEarly,
+            results: Box::new([]),
+        };
+        let mut explorer = Explorer::new(&replay).unwrap();
+        let main_line = explorer.status();
+
+        explorer.run_command("play 5e");
+        assert!(explorer.status().contains("1 moves in"));
+        assert!(explorer.run_command("goto 0")[0].contains("Return"));
+        assert!(explorer.run_command("play 5e")[0].contains("can't be played"));
+        assert!(explorer.run_command("compare").len() > 2);
+
+        explorer.run_command("return");
+        assert_eq!(explorer.status(), main_line);
+    }
+}
@@ -0,0 +1,413 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A language in which the client can show its messages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    English,
+    Spanish,
+}
+
+impl Lang {
+    /// Every language, in the order they are listed to the player.
+    pub const ALL: [Lang; 2] = [Lang::English, Lang::Spanish];
+
+    /// Gets the code that selects this language on the command line.
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::English => "en",
+            Lang::Spanish => "es",
+        }
+    }
+
+    /// Gets the template for a message in this language. Arguments are written
+    /// in place of `{0}`, `{1}`, and so on.
+    fn template(self, text: Text) -> &'static str {
+        match self {
+            Lang::English => english(text),
+            Lang::Spanish => spanish(text),
+        }
+    }
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Lang::ALL.into_iter()
+            .find(|lang| lang.code().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let codes: Vec<_> = Lang::ALL.iter().map(|lang| lang.code()).collect();
+                format!("unknown language '{s}', expected one of: {}", codes.join(", "))
+            })
+    }
+}
+
+/// Identifies each message the client shows to the player on its own account,
+/// rather than passing along from another player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Text {
+    /// `{0}`: the spectator given a seat.
+    Seated,
+    /// `{0}`: the player whose seat was taken.
+    Unseated,
+    /// `{0}`: the player. `{1}`: what they may now do as an admin.
+    Trusted,
+    /// `{0}`: the player no longer an admin.
+    Untrusted,
+    /// `{0}`: the old name. `{1}`: the new name.
+    Renamed,
+    /// `{0}`: the new player. `{1}`: the player who left.
+    Substituted,
+    /// `{0}`: what the client and server disagree on.
+    Desync,
+    /// `{0}`: why the game in progress couldn't be caught up to.
+    CatchUpFailed,
+    /// `{0}`: the player who proposed it.
+    EndProposed,
+    /// `{0}`: the player who voted.
+    EndVotedYes,
+    /// `{0}`: the player who voted.
+    EndVotedNo,
+    EndRejected,
+    /// `{0}`: the player. `{1}`: the turns they missed.
+    Away,
+    /// `{0}`: the player.
+    AutopilotOn,
+    /// `{0}`: the player.
+    AutopilotOff,
+    /// `{0}`: the player. `{1}`: the players waiting. `{2}`: the players a
+    /// game is started with.
+    Queued,
+    /// `{0}`: the player.
+    Unqueued,
+    /// `{0}`: the minutes and seconds left.
+    StartScheduled,
+    /// `{0}`: the player who voted. `{1}`: the player to kick. `{2}`: the
+    /// votes cast. `{3}`: the votes needed.
+    KickVoted,
+    /// `{0}`: the player who stays.
+    KickFailed,
+    /// `{0}`: the player.
+    Kicked,
+    YouWereKicked,
+    /// `{0}`: the number of actions. `{1}`: the file.
+    ActionLogSaved,
+    /// `{0}`: why.
+    ActionLogNotSaved,
+    /// `{0}`: the path of the log. `{1}`: why it couldn't be opened.
+    GameLogNotOpened,
+    /// `{0}`: the file.
+    FinalPositionExported,
+    /// `{0}`: why.
+    FinalPositionNotExported,
+    /// `{0}`: the seed.
+    DealVerified,
+    /// `{0}`: the ID of the game.
+    GameIdentified,
+    /// `{0}`: how the deal differs from the seed.
+    DealMismatch,
+    /// `{0}`: the seconds since the server was last heard from.
+    ConnectionStalled,
+    ConnectionRestored,
+    /// `{0}`: what went wrong.
+    ServerFailed,
+    /// `{0}`: the number of actions the game was restored to.
+    GameRestored,
+    GameLost,
+    /// `{0}`: the server. `{1}`: the players connected. `{2}`: the spectators.
+    Welcome,
+    GameInProgress,
+    /// `{0}`: the message of the day.
+    Motd,
+    /// `{0}`: the code those joining must give.
+    InviteCode,
+    /// `{0}`: the player. `{1}`: the dead tile.
+    TradedDeadTile,
+    /// `{0}`: the player. `{1}`: the tile.
+    DrewStartingTile,
+    SeatsDrawnAtRandom,
+    GameStarted,
+    /// `{0}`: the hand.
+    StartingHand,
+    /// `{0}`: the company.
+    CompanyDefunct,
+    /// `{0}`: the company.
+    NoFounderShareYou,
+    /// `{0}`: the founder. `{1}`: the company.
+    NoFounderShare,
+    /// `{0}`: the shareholder. `{1}`: the defunct company. `{2}`: the survivor.
+    ResolvingNext,
+    /// `{0}`: why the game ended.
+    GameOver,
+    /// `{0}`: the place. `{1}`: the player. `{2}`: their money.
+    FinalResult,
+    /// As [`Text::FinalResult`], for a place shared with another player.
+    FinalResultTied,
+    ScoreGraph,
+    YourTurnPlaceTile,
+    YourTurnBuyStock,
+    YourTurnResolve,
+    StatusLobby,
+    StatusPlacing,
+    StatusMerging,
+    StatusBuying,
+    /// `{0}`: the player whose turn it is.
+    StatusTurn,
+    StatusYourTurn,
+    /// `{0}`: the founder. `{1}`: the company.
+    ToastFounded,
+    /// `{0}`: the bonus. `{1}`: the defunct company.
+    ToastBonus,
+    CantAffordStock,
+    /// `{0}`: the tile.
+    DrewTile,
+    Resynced,
+    /// `{0}`: why the server rejected the message.
+    InvalidMessage,
+    /// `{0}`: why the move breaks the rules.
+    InvalidMove,
+    /// `{0}`: the command for the move the server refused. `{1}`: why.
+    MoveRefused,
+    ConfirmExit,
+    /// `{0}`: the command to send. `{1}`: why.
+    Hint,
+    NoHint,
+    HintsForbidden,
+    ChatInLobby,
+    ChatInRoom,
+    ChooseStock,
+    FoundWhichCompany,
+    ChooseSurvivor,
+    SelectTile,
+    NoCompaniesAvailable,
+}
+
+impl Text {
+    /// Every message.
+    pub const ALL: [Text; 80] = [
+        Text::Seated, Text::Unseated, Text::Trusted, Text::Untrusted, Text::Renamed, Text::Substituted, Text::Desync, Text::CatchUpFailed, Text::EndProposed,
+        Text::EndVotedYes, Text::EndVotedNo, Text::EndRejected, Text::Away, Text::AutopilotOn, Text::AutopilotOff, Text::Queued, Text::Unqueued,
+        Text::StartScheduled, Text::KickVoted, Text::KickFailed, Text::Kicked, Text::YouWereKicked,
+        Text::ActionLogSaved, Text::ActionLogNotSaved, Text::GameLogNotOpened,
+        Text::FinalPositionExported, Text::FinalPositionNotExported, Text::DealVerified,
+        Text::GameIdentified,
+        Text::DealMismatch, Text::ConnectionStalled, Text::ConnectionRestored,
+        Text::ServerFailed, Text::GameRestored, Text::GameLost,
+        Text::Welcome, Text::GameInProgress, Text::Motd, Text::InviteCode,
+        Text::TradedDeadTile, Text::DrewStartingTile, Text::SeatsDrawnAtRandom, Text::GameStarted, Text::StartingHand,
+        Text::CompanyDefunct, Text::NoFounderShareYou, Text::NoFounderShare,
+        Text::ResolvingNext, Text::GameOver, Text::FinalResult, Text::FinalResultTied,
+        Text::ScoreGraph,
+        Text::YourTurnPlaceTile, Text::YourTurnBuyStock, Text::YourTurnResolve,
+        Text::StatusLobby, Text::StatusPlacing, Text::StatusMerging, Text::StatusBuying,
+        Text::StatusTurn, Text::StatusYourTurn, Text::ToastFounded, Text::ToastBonus,
+        Text::CantAffordStock, Text::DrewTile, Text::Resynced,
+        Text::InvalidMessage, Text::InvalidMove, Text::MoveRefused, Text::ConfirmExit, Text::Hint,
+        Text::NoHint, Text::HintsForbidden, Text::ChatInLobby, Text::ChatInRoom, Text::ChooseStock,
+        Text::FoundWhichCompany,
+        Text::ChooseSurvivor, Text::SelectTile, Text::NoCompaniesAvailable,
+    ];
+}
+
+/// Looks up a message in a language, filling in its arguments.
+pub fn tr(lang: Lang, text: Text, args: &[&dyn fmt::Display]) -> String {
+    let mut message = lang.template(text).to_owned();
+    for (i, arg) in args.iter().enumerate() {
+        message = message.replace(&format!("{{{i}}}"), &arg.to_string());
+    }
+    message
+}
+
+fn english(text: Text) -> &'static str {
+    match text {
+        Text::Seated => "JOIN: {0} took a seat at the table.",
+        Text::Unseated => "JOIN: {0} gave up their seat to spectate.",
+        Text::Trusted => "ADMIN: {0} may now use admin commands for: {1}.",
+        Text::Untrusted => "ADMIN: {0} may no longer use admin commands.",
+        Text::Renamed => "JOIN: {0} is now known as {1}.",
+        Text::Substituted => "JOIN: {0} took over the seat of {1}.",
+        Text::Desync => "WARNING: desync detected, {0}. Requesting the game from the server.",
+        Text::CatchUpFailed => "ERROR: couldn't catch up to the game in progress, as {0}. Watching without a copy of the game.",
+        Text::EndProposed => "VOTE: {0} proposes ending the game now. Type /vote yes or /vote no.",
+        Text::EndVotedYes => "VOTE: {0} voted to end the game.",
+        Text::EndVotedNo => "VOTE: {0} voted to play on.",
+        Text::EndRejected => "VOTE: too few players agreed, so the game goes on.",
+        Text::AutopilotOn => "SERVER: {0} handed their seat to the server for now.",
+        Text::AutopilotOff => "SERVER: {0} took their seat back from the server.",
+        Text::Away => "SERVER: {0} let their time run out {1} turns in a row, so the server plays their seat until they type /autopilot off.",
+        Text::Queued => "SERVER: {0} is waiting for a game ({1} of {2} players).",
+        Text::Unqueued => "SERVER: {0} stopped waiting for a game.",
+        Text::StartScheduled => "SERVER: the next game starts in {0}.",
+        Text::KickVoted => "VOTE: {0} votes to kick {1} ({2} of {3} votes needed). Type /votekick {1} to agree.",
+        Text::KickFailed => "VOTE: too few players agreed, so {0} stays.",
+        Text::Kicked => "JOIN: {0} was kicked.",
+        Text::YouWereKicked => "SERVER: you were kicked from the server.",
+        Text::ActionLogSaved => "Saved the log of {0} actions to {1}.",
+        Text::ActionLogNotSaved => "Couldn't save the action log: {0}",
+        Text::GameLogNotOpened => "Couldn't open the game log {0}: {1}",
+        Text::FinalPositionExported => "Saved the final board to {0}.",
+        Text::FinalPositionNotExported => "Couldn't save the final board: {0}",
+        Text::DealVerified => "The tiles were dealt as the server committed to, from seed {0}.",
+        Text::GameIdentified => "The game was recorded as {0}.",
+        Text::DealMismatch => "WARNING: the tiles were not dealt as the server committed to: {0}.",
+        Text::ConnectionStalled => "WARNING: nothing heard from the server for {0} seconds.",
+        Text::ConnectionRestored => "The server is answering again.",
+        Text::ServerFailed => "ERROR: the server failed ({0}), but is still running.",
+        Text::GameRestored => "ERROR: the game was restored from its autosave after {0} actions. Any moves made since must be made again.",
+        Text::GameLost => "ERROR: the game couldn't be recovered, and has been abandoned.",
+        Text::Welcome => "SERVER: welcome to {0}, where {1} players and {2} spectators are connected.",
+        Text::GameInProgress => "SERVER: a game is in progress.",
+        Text::Motd => "SERVER: {0}",
+        Text::InviteCode => "SERVER: players join with the invite code {0}.",
+        Text::TradedDeadTile => "{0} traded in dead tile {1}.",
+        Text::DrewStartingTile => "{0} drew {1}.",
+        Text::SeatsDrawnAtRandom => "The seats were drawn at random, so the tiles don't decide the order of play.",
+        Text::GameStarted => "Game started!",
+        Text::StartingHand => "Your starting hand is: {0}.",
+        Text::CompanyDefunct => "Company {0} has gone defunct! Here are the results:",
+        Text::NoFounderShareYou => "You receive no founder's share of {0}, as every share is already held.",
+        Text::NoFounderShare => "{0} receives no founder's share of {1}, as every share is already held.",
+        Text::ResolvingNext => "{0} is resolving their stock in {1}, which is merging into {2}.",
+        Text::GameOver => "Game Over! {0}. Here are the results:",
+        Text::FinalResult => "  [{0}] {1} with {2}",
+        Text::FinalResultTied => "  [{0}] {1} with {2}, tied",
+        Text::ScoreGraph => "Net worth over the game:",
+        Text::YourTurnPlaceTile => "Your turn to place a tile!",
+        Text::YourTurnBuyStock => "Your turn to buy stock!",
+        Text::YourTurnResolve => "Your turn to resolve your stock!",
+        Text::StatusLobby => "Lobby",
+        Text::StatusPlacing => "Placing",
+        Text::StatusMerging => "Merging",
+        Text::StatusBuying => "Buying",
+        Text::StatusTurn => "{0}'s turn",
+        Text::StatusYourTurn => "your turn",
+        Text::ToastFounded => "{0} founded {1}!",
+        Text::ToastBonus => "You received a {0} bonus in {1}!",
+        Text::CantAffordStock => "You can't afford any stock!",
+        Text::DrewTile => "You drew tile {0}.",
+        Text::Resynced => "Game resynced with the server.",
+        Text::InvalidMessage => "Invalid message: {0}",
+        Text::InvalidMove => "Invalid move: {0}",
+        Text::MoveRefused => "Move '{0}' refused: {1}",
+        Text::ConfirmExit => "Type 'y' to confirm exit",
+        Text::Hint => "HINT: /{0} - {1}.",
+        Text::NoHint => "HINT: there is nothing for you to do right now.",
+        Text::HintsForbidden => "HINT: hints are turned off for this game.",
+        Text::ChatInLobby => "CHAT: now chatting in the lobby, with everyone on the server.",
+        Text::ChatInRoom => "CHAT: now chatting in the room, with the players seated in the game.",
+        Text::ChooseStock => "Choose which stock to buy",
+        Text::FoundWhichCompany => "Found which company?",
+        Text::ChooseSurvivor => "Choose the company to remain on the board.",
+        Text::SelectTile => "Select a tile to play",
+        Text::NoCompaniesAvailable => "No companies available.",
+    }
+}
+
+fn spanish(text: Text) -> &'static str {
+    match text {
+        Text::Seated => "ENTRADA: {0} tomó asiento en la mesa.",
+        Text::Unseated => "ENTRADA: {0} dejó su asiento para mirar.",
+        Text::Trusted => "ADMIN: {0} ahora puede usar los comandos de administración de: {1}.",
+        Text::Untrusted => "ADMIN: {0} ya no puede usar comandos de administración.",
+        Text::Renamed => "ENTRADA: {0} ahora se llama {1}.",
+        Text::Substituted => "ENTRADA: {0} ocupó el asiento de {1}.",
+        Text::Desync => "AVISO: desincronización detectada, {0}. Pidiendo la partida al servidor.",
+        Text::CatchUpFailed => "ERROR: no se pudo seguir la partida en curso, ya que {0}. Mirando sin copia de la partida.",
+        Text::EndProposed => "VOTO: {0} propone terminar la partida ahora. Escribe /vote yes o /vote no.",
+        Text::EndVotedYes => "VOTO: {0} votó por terminar la partida.",
+        Text::EndVotedNo => "VOTO: {0} votó por seguir jugando.",
+        Text::EndRejected => "VOTO: no hubo suficientes votos a favor, así que la partida sigue.",
+        Text::AutopilotOn => "SERVIDOR: {0} dejó su lugar al servidor por ahora.",
+        Text::AutopilotOff => "SERVIDOR: {0} retomó su lugar del servidor.",
+        Text::Away => "SERVIDOR: a {0} se le acabó el tiempo {1} turnos seguidos, así que el servidor juega en su lugar hasta que escriba /autopilot off.",
+        Text::Queued => "SERVIDOR: {0} espera una partida ({1} de {2} jugadores).",
+        Text::Unqueued => "SERVIDOR: {0} dejó de esperar una partida.",
+        Text::StartScheduled => "SERVIDOR: la próxima partida empieza en {0}.",
+        Text::KickVoted => "VOTO: {0} vota por expulsar a {1} ({2} de {3} votos necesarios). Escribe /votekick {1} para estar de acuerdo.",
+        Text::KickFailed => "VOTO: muy pocos jugadores estuvieron de acuerdo, así que {0} se queda.",
+        Text::Kicked => "ENTRADA: {0} fue expulsado.",
+        Text::YouWereKicked => "SERVIDOR: te expulsaron del servidor.",
+        Text::ActionLogSaved => "Se guardó el registro de {0} jugadas en {1}.",
+        Text::ActionLogNotSaved => "No se pudo guardar el registro de jugadas: {0}",
+        Text::GameLogNotOpened => "No se pudo abrir el registro de la partida {0}: {1}",
+        Text::FinalPositionExported => "Se guardó el tablero final en {0}.",
+        Text::FinalPositionNotExported => "No se pudo guardar el tablero final: {0}",
+        Text::DealVerified => "Las fichas se repartieron según el compromiso del servidor, con la semilla {0}.",
+        Text::GameIdentified => "La partida quedó registrada como {0}.",
+        Text::DealMismatch => "AVISO: las fichas no se repartieron según el compromiso del servidor: {0}.",
+        Text::ConnectionStalled => "AVISO: no se sabe nada del servidor desde hace {0} segundos.",
+        Text::ConnectionRestored => "El servidor vuelve a responder.",
+        Text::ServerFailed => "ERROR: el servidor falló ({0}), pero sigue en marcha.",
+        Text::GameRestored => "ERROR: la partida se restauró de su guardado automático tras {0} jugadas. Hay que repetir las jugadas hechas desde entonces.",
+        Text::GameLost => "ERROR: no se pudo recuperar la partida, y se abandonó.",
+        Text::Welcome => "SERVIDOR: bienvenido a {0}, donde hay {1} jugadores y {2} espectadores conectados.",
+        Text::GameInProgress => "SERVIDOR: hay una partida en curso.",
+        Text::Motd => "SERVIDOR: {0}",
+        Text::InviteCode => "SERVIDOR: los jugadores se unen con el código de invitación {0}.",
+        Text::TradedDeadTile => "{0} cambió la ficha muerta {1}.",
+        Text::DrewStartingTile => "{0} robó {1}.",
+        Text::SeatsDrawnAtRandom => "Los asientos se sortearon, así que las fichas no deciden el orden de juego.",
+        Text::GameStarted => "¡La partida ha comenzado!",
+        Text::StartingHand => "Tu mano inicial es: {0}.",
+        Text::CompanyDefunct => "¡La compañía {0} ha desaparecido! Estos son los resultados:",
+        Text::NoFounderShareYou => "No recibes la acción de fundador de {0}, ya que todas las acciones tienen dueño.",
+        Text::NoFounderShare => "{0} no recibe la acción de fundador de {1}, ya que todas las acciones tienen dueño.",
+        Text::ResolvingNext => "{0} está resolviendo sus acciones de {1}, que se fusiona con {2}.",
+        Text::GameOver => "¡Fin de la partida! {0}. Estos son los resultados:",
+        Text::FinalResult => "  [{0}] {1} con {2}",
+        Text::FinalResultTied => "  [{0}] {1} con {2}, empatado",
+        Text::ScoreGraph => "Patrimonio a lo largo de la partida:",
+        Text::YourTurnPlaceTile => "¡Te toca colocar una ficha!",
+        Text::YourTurnBuyStock => "¡Te toca comprar acciones!",
+        Text::YourTurnResolve => "¡Te toca resolver tus acciones!",
+        Text::StatusLobby => "Sala",
+        Text::StatusPlacing => "Colocando",
+        Text::StatusMerging => "Fusionando",
+        Text::StatusBuying => "Comprando",
+        Text::StatusTurn => "turno de {0}",
+        Text::StatusYourTurn => "tu turno",
+        Text::ToastFounded => "¡{0} fundó {1}!",
+        Text::ToastBonus => "¡Recibiste un bono de {0} en {1}!",
+        Text::CantAffordStock => "¡No te alcanza para ninguna acción!",
+        Text::DrewTile => "Robaste la ficha {0}.",
+        Text::Resynced => "Partida resincronizada con el servidor.",
+        Text::InvalidMessage => "Mensaje no válido: {0}",
+        Text::InvalidMove => "Jugada no válida: {0}",
+        Text::MoveRefused => "Jugada «{0}» rechazada: {1}",
+        Text::ConfirmExit => "Escribe 'y' para confirmar la salida",
+        Text::Hint => "PISTA: /{0} - {1}.",
+        Text::NoHint => "PISTA: no tienes nada que hacer ahora mismo.",
+        Text::HintsForbidden => "PISTA: las pistas están desactivadas en esta partida.",
+        Text::ChatInLobby => "CHAT: ahora hablas en el vestíbulo, con todos los del servidor.",
+        Text::ChatInRoom => "CHAT: ahora hablas en la sala, con los jugadores sentados en la partida.",
+        Text::ChooseStock => "Elige qué acciones comprar",
+        Text::FoundWhichCompany => "¿Qué compañía fundas?",
+        Text::ChooseSurvivor => "Elige la compañía que permanece en el tablero.",
+        Text::SelectTile => "Elige una ficha para jugar",
+        Text::NoCompaniesAvailable => "No hay compañías disponibles.",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{tr, Text, Lang};
+
+    #[test]
+    fn translations_take_the_same_arguments() {
+        let placeholders = |template: &str| {
+            (0..3).filter(|i| template.contains(&format!("{{{i}}}"))).collect::<Vec<_>>()
+        };
+
+        for text in Text::ALL {
+            let english = placeholders(Lang::English.template(text));
+            for lang in Lang::ALL {
+                assert_eq!(placeholders(lang.template(text)), english, "{text:?} in {lang:?}");
+            }
+        }
+
+        assert_eq!(tr(Lang::English, Text::DrewTile, &[&"4-c"]), "You drew tile 4-c.");
+        assert_eq!("ES".parse(), Ok(Lang::Spanish));
+    }
+}
@@ -1,127 +1,867 @@
-use crate::game::{messages::*, Company, tile::Hand};
-use crate::game::kernel::{Game, self, GameDisambiguation};
-use crate::game::tile::Tile;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::game::{messages::*, svg, Company, tile::Hand};
+use crate::game::board::Board;
+use crate::game::cells::Cells;
+use crate::game::bot::{Bot, Difficulty};
+use crate::game::deal::{DealMismatch, DealSeed};
+use crate::game::kernel::{Game, GameDisambiguation, GameSnapshot, ReplayError, self};
+use crate::game::policy::RulePolicy;
+use crate::game::tile::{FullHand, Tile};
+use crate::net::protocol::check_name;
 use crate::server::Handshake;
+use crate::server::capability::Capabilities;
+use crate::server::console::{Console, ConsoleCommand};
+
+use self::lang::Lang;
+use self::notifier::Notifier;
+use self::score_graph::ScoreHistory;
 
+pub mod console;
+pub mod explore;
+pub mod lang;
+pub mod normalize;
+pub mod notifier;
 pub mod primitive;
 pub mod robust;
+pub mod score_graph;
+pub mod session;
+pub mod tutorial;
+pub mod viewer;
+
+/// Options that change the behavior of a client.
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// If set, actions that break the rules are intercepted before they are
+    /// sent, and the broken rule is explained.
+    pub tutorial: bool,
+    /// The time spent looking for a hint at [`Difficulty::Hard`].
+    pub hint_time_budget: Duration,
+    /// The language in which the client shows its own messages.
+    pub lang: Lang,
+    /// If set, the final board and standings of each game are saved as SVG
+    /// in this directory.
+    pub export_dir: Option<PathBuf>,
+    /// The console of the server, if the client is the host's. Commands
+    /// typed after `!` are given to it.
+    pub console: Option<Console>,
+    /// If set, important events such as the player's turn are also shown for
+    /// a few seconds over a corner of the screen.
+    pub toasts: bool,
+    /// How the player's turn, mergers and the end of a game are sounded.
+    pub notifier: Notifier,
+    /// If set, the chat and game events shown are also appended to this
+    /// file.
+    pub game_log: Option<PathBuf>,
+}
 
-/// Decodes a text command into a [`PlayerActionKind`].
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            tutorial: false,
+            hint_time_budget: Bot::DEFAULT_TIME_BUDGET,
+            lang: Lang::default(),
+            export_dir: None,
+            console: None,
+            toasts: true,
+            notifier: Notifier::Off,
+            game_log: None,
+        }
+    }
+}
+
+/// The words of a command, read one at a time. Each word read is counted, so
+/// that an error can point out which word it was.
+struct Words<'a> {
+    words: std::str::SplitWhitespace<'a>,
+    /// The position of the word last read, counting from 1.
+    position: usize,
+}
+
+impl<'a> Words<'a> {
+    fn new(command: &'a str) -> Self {
+        Self { words: command.split_whitespace(), position: 0 }
+    }
+
+    /// Reads the next word, if there is one.
+    fn try_next(&mut self) -> Option<&'a str> {
+        let word = self.words.next()?;
+        self.position += 1;
+        Some(word)
+    }
+
+    /// Reads the next word, which should be `expected`.
+    fn next(&mut self, expected: &'static str) -> Result<&'a str, CommandParseErr> {
+        self.try_next().ok_or(CommandParseErr::Expected(expected))
+    }
+
+    /// Reads the next word as a `T`, which is described as `expected`.
+    fn parse<T: std::str::FromStr>(&mut self, expected: &'static str) -> Result<T, CommandParseErr> {
+        let word = self.next(expected)?;
+        self.read(word, expected)
+    }
+
+    /// Reads the word last read as a `T`.
+    fn read<T: std::str::FromStr>(&self, word: &str, expected: &'static str) -> Result<T, CommandParseErr> {
+        word.parse().map_err(|_| self.mismatch(expected, word))
+    }
+
+    /// Reports that the word last read was not the `expected` one.
+    fn mismatch(&self, expected: &'static str, word: &str) -> CommandParseErr {
+        CommandParseErr::Mismatch { expected, found: word.to_owned(), position: self.position }
+    }
+
+    /// Checks that every word has been read.
+    fn end(mut self) -> Result<(), CommandParseErr> {
+        match self.try_next() {
+            Some(word) => Err(CommandParseErr::UnexpectedArgument(word.to_owned())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Decodes a text command into a [`PlayerAction`], once its shorthand has been
+/// expanded by [`normalize::game_command`]. The shares in a `resolve` command
+/// may be given in any order, and any not given are taken to be 0, so that
+/// `resolve sell 2` sells two shares and does nothing else.
 pub fn parse_game_command(command: &str) -> Result<PlayerAction, CommandParseErr> {
     use CommandParseErr::*;
 
-    let mut tokens = command.split(" ");
+    let command = normalize::game_command(command);
+    let mut words = Words::new(&command);
 
-    match tokens.next() {
+    let action = match words.try_next() {
         // Play a tile
         Some("play") => {
-            let tile_str = tokens.next().ok_or(Expected("tile"))?;
-            let tile: Tile = tile_str.parse().or(Err(Expected("tile")))?;
+            let tile: Tile = words.parse("tile")?;
 
-            let implication = match tokens.next() {
+            let implication = match words.try_next() {
                 None => None,
                 Some("founding") => {
-                    let token = tokens.next().ok_or(Expected("company"))?;
-                    let company = token.parse::<Company>()
-                        .or(Err(Expected("company")))?;
+                    let company = words.parse("company")?;
                     Some(TilePlacementImplication::FoundsCompany(company))
                 },
                 Some("merging") => {
                     let mut defunct = vec![];
                     loop {
-                        let company: Company = match tokens.next() {
-                            Some("into") => break,
-                            Some(cmp_string) => cmp_string.parse().or(Err(Expected("company")))?,
-                            None => return Err(Expected("into")),
-                        };
-                        defunct.push(company);
+                        match words.next("into")? {
+                            "into" => break,
+                            word => defunct.push(words.read(word, "company")?),
+                        }
                     }
-                    let into: Company = match tokens.next() {
-                        Some(cmp_string) => cmp_string.parse().or(Err(Expected("company")))?,
-                        None => return Err(Expected("company")),
-                    };
+                    let into: Company = words.parse("company")?;
 
                     Some(TilePlacementImplication::MergesCompanies(Merge::new(&defunct, into)))
                 }
-                Some(_) => return Err(Expected("\"founding\" or \"merging\"")),
+                Some(word) => return Err(words.mismatch("\"founding\" or \"merging\"", word)),
             };
 
-            Ok(PlayerAction::PlayTile { placement: TilePlacement { tile, implication }})
+            PlayerAction::PlayTile { placement: TilePlacement { tile, implication }}
         },
         // Buy stock
         Some("buy") => {
             let mut stock = [None::<Company>; 3];
             for share in &mut stock {
-                match tokens.next() {
-                    Some(company) => {
-                        *share = Some(company.parse().or(Err(Expected("company")))?);
-                    },
+                match words.try_next() {
+                    Some(word) => *share = Some(words.read(word, "company")?),
                     None => break,
                 }
             }
-            Ok(PlayerAction::BuyStock { stock })
+            PlayerAction::BuyStock { stock }
         },
         Some("resolve") => {
             let mut sell = None;
             let mut trade = None;
             let mut keep = None;
 
-            for _ in 0..3 {
-                let keyword_error = Expected("\"trade\", \"sell\", or \"keep\"");
-                let keyword = tokens.next()
-                    .ok_or(keyword_error.clone())?;
-                let count: u8 = tokens.next().map(|token| token.parse().ok())
-                    .flatten()
-                    .ok_or(Expected("integer"))?;
+            while let Some(keyword) = words.try_next() {
                 let option = match keyword {
                     "sell" => &mut sell,
                     "trade" => &mut trade,
                     "keep" => &mut keep,
-                    _ => return Err(keyword_error),
+                    _ => return Err(words.mismatch("\"sell\", \"trade\", or \"keep\"", keyword)),
                 };
-                match option {
-                    Some(_) => return Err(DuplicateArgument(keyword.to_owned())),
-                    None => *option = Some(count),
+                let count: u8 = words.parse("number of shares")?;
+                if option.replace(count).is_some() {
+                    return Err(DuplicateArgument(keyword.to_owned()));
                 }
             }
-            Ok(PlayerAction::ResolveMergeStock {
-                selling: sell.unwrap(),
-                trading: trade.unwrap(),
-                keeping: keep.unwrap() 
-            })
+            PlayerAction::ResolveMergeStock {
+                selling: sell.unwrap_or(0),
+                trading: trade.unwrap_or(0),
+                keeping: keep.unwrap_or(0),
+            }
         },
-        Some(_) => return Err(Expected("\"play\", \"buy\", or \"resolve\"")),
+        Some(word) => return Err(words.mismatch("\"play\", \"buy\", or \"resolve\"", word)),
         None => return Err(EmptyInput),
+    };
+
+    words.end()?;
+    Ok(action)
+}
+
+/// Formats a [`PlayerAction`] as the game command that produces it. This is
+/// the inverse of [`parse_game_command`].
+pub fn format_game_command(action: &PlayerAction) -> String {
+    match action {
+        PlayerAction::PlayTile { placement } => {
+            let mut command = format!("play {}", placement.tile);
+            match placement.implication {
+                None => {},
+                Some(TilePlacementImplication::FoundsCompany(company)) => {
+                    command += &format!(" founding {company}");
+                },
+                Some(TilePlacementImplication::MergesCompanies(merge)) => {
+                    command += " merging";
+                    for defunct in merge.defunct() {
+                        command += &format!(" {defunct}");
+                    }
+                    command += &format!(" into {}", merge.into);
+                },
+            }
+            command.to_ascii_lowercase()
+        },
+        PlayerAction::BuyStock { stock } => {
+            let mut command = "buy".to_owned();
+            for company in stock.iter().flatten() {
+                command += &format!(" {company}");
+            }
+            command.to_ascii_lowercase()
+        },
+        PlayerAction::ResolveMergeStock { selling, trading, keeping } => {
+            format!("resolve sell {selling} trade {trading} keep {keeping}")
+        },
+    }
+}
+
+/// Describes the board in plain sentences, one per line, for players who
+/// cannot see it drawn: each company with its tiles, followed by the tiles
+/// belonging to no company.
+pub fn describe_board<S: kernel::State>(game: &Game<S>) -> Vec<String> {
+    let board = game.board();
+    let join = |cells: Cells| cells.iter().map(|tile| tile.to_string()).collect::<Vec<_>>().join(", ");
+
+    let mut lines = Vec::new();
+    let mut incorporated = Cells::EMPTY;
+    for (company, _) in game.stock_bank().iter() {
+        let cells = board.cells_of(company);
+        if cells.is_empty() { continue; }
+        incorporated = incorporated | cells;
+
+        let safe = if board.company_is_safe(company) { ", safe" } else { "" };
+        lines.push(format!(
+            "{company}: {} tiles{safe}, {} a share, {} shares left. Tiles {}.",
+            cells.len(),
+            board.stock_price(company),
+            game.stock_bank().available(company),
+            join(cells),
+        ));
+    }
+    if lines.is_empty() {
+        lines.push("No companies are on the board.".to_owned());
+    }
+
+    let unincorporated = board.played() - incorporated;
+    if unincorporated.is_empty() {
+        lines.push("No tiles are outside a company.".to_owned());
+    } else {
+        lines.push(format!("Tiles outside a company: {}.", join(unincorporated)));
     }
+    lines
+}
+
+/// Ranks the players by what they are worth right now, one per line, as with
+/// [`Game::net_worth_standings`].
+pub fn describe_standings(game: &Game<kernel::Ambiguous>) -> Vec<String> {
+    game.net_worth_standings().into_iter()
+        .enumerate()
+        .map(|(i, (name, worth))| {
+            format!("{}. {name} is worth {worth}, with {} in cash.", i + 1, game.players()[name].money)
+        })
+        .collect()
+}
+
+/// Formats a time, given in seconds since the Unix epoch, as hours and minutes
+/// in UTC.
+pub fn clock_time(timestamp: u64) -> String {
+    format!("{:02}:{:02}", timestamp / 3600 % 24, timestamp / 60 % 60)
+}
+
+/// Formats the time left on a player's clock, given in milliseconds, as
+/// minutes and seconds.
+pub fn time_left(ms: u64) -> String {
+    let secs = ms / 1000;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// Gets the time left to `player` on a clock received `elapsed` ago, in
+/// milliseconds.
+pub fn remaining_ms(clock: &ClockState, player: &str, elapsed: Duration) -> u64 {
+    let remaining = clock.remaining_ms.get(player).copied().unwrap_or_default();
+    match clock.running.as_deref() == Some(player) {
+        true => remaining.saturating_sub(elapsed.as_millis() as u64),
+        false => remaining,
+    }
+}
+
+/// Saves the action log sent by the server as JSON in the working directory,
+/// returning the path of the file.
+pub fn save_action_log(entries: &[LoggedAction]) -> io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let path = PathBuf::from(format!("action-log-{timestamp}.json"));
+    std::fs::write(&path, serde_json::to_string_pretty(entries)?)?;
+    Ok(path)
+}
+
+/// Saves the final board and standings of a game as an SVG file in `dir`,
+/// returning the path of the file.
+pub fn export_final_position(dir: &Path, board: &Board, standings: &[FinalResult]) -> io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("final-position-{timestamp}.svg"));
+    std::fs::write(&path, svg::final_position(board, standings))?;
+    Ok(path)
+}
+
+/// Keeps track of the chat messages a client has shown, so that none is shown
+/// twice, even if the server sends it again.
+#[derive(Debug, Default)]
+pub struct ChatLog {
+    /// The sequence number of the latest message shown.
+    last_shown: Option<u64>,
 }
 
+impl ChatLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Formats a chat message to be shown, such as `[14:03] <alice> hi`, or
+    /// `[14:03] (room) <alice> hi` for one said in the room of the game.
+    /// Returns [`None`] if the message has already been shown.
+    pub fn receive(
+        &mut self,
+        player_name: &str,
+        message: &str,
+        timestamp: u64,
+        sequence: u64,
+        channel: ChatChannel,
+    ) -> Option<String> {
+        if self.last_shown.is_some_and(|last| sequence <= last) {
+            return None;
+        }
+        self.last_shown = Some(sequence);
+        let time = clock_time(timestamp);
+        Some(match channel {
+            ChatChannel::Lobby => format!("[{time}] <{player_name}> {message}"),
+            ChatChannel::Room => format!("[{time}] ({channel}) <{player_name}> {message}"),
+        })
+    }
+}
+
+/// A plain-text record of the chat and game events a client has shown,
+/// appended to a file so that the player keeps it after the session ends.
+#[derive(Debug)]
+pub struct GameLog {
+    file: File,
+}
+
+impl GameLog {
+    /// Opens the log at `path`, creating it if need be, and marks where this
+    /// session starts in it.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        writeln!(file, "--- Session started at {} UTC ---", clock_time(now))?;
+        Ok(Self { file })
+    }
+
+    /// Appends a line to the log. A line that can't be written is dropped
+    /// rather than get in the way of the game.
+    pub fn record(&mut self, line: &str) {
+        writeln!(self.file, "{line}").ok();
+    }
+}
+
+/// Numbers the actions a client sends, and remembers the latest of them, so
+/// that the move or refusal the server answers one with can be matched to it.
+#[derive(Debug, Default)]
+pub struct Receipts {
+    next_id: RequestId,
+    /// The actions sent and not yet answered, oldest first.
+    pending: VecDeque<(RequestId, PlayerAction)>,
+}
+
+impl Receipts {
+    /// The most actions remembered at once. An action the server held on to
+    /// rather than answering is forgotten once this many more have been sent.
+    const MAX_PENDING: usize = 8;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gives an action about to be sent the next request ID. Any other message
+    /// is left as it is.
+    pub fn stamp(&mut self, msg: &mut ClientMessage) {
+        let ClientMessage::TakingTurn { action, request_id, .. } = msg else { return };
+        if self.pending.len() == Self::MAX_PENDING {
+            self.pending.pop_front();
+        }
+        *request_id = Some(self.next_id);
+        self.pending.push_back((self.next_id, *action));
+        self.next_id += 1;
+    }
+
+    /// Gets the action that was sent with a request ID, now that the server
+    /// has answered it.
+    pub fn settle(&mut self, request_id: Option<RequestId>) -> Option<PlayerAction> {
+        let request_id = request_id?;
+        let index = self.pending.iter().position(|(id, _)| *id == request_id)?;
+        self.pending.remove(index).map(|(_, action)| action)
+    }
+}
+
+/// How well messages are getting through to and from the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionQuality {
+    /// No ping has been answered yet.
+    Unknown,
+    /// The latest round trip took this long.
+    Good(Duration),
+    /// The latest round trip took this long, which is long enough for the
+    /// game to feel sluggish.
+    Slow(Duration),
+    /// Nothing has been heard from the server for this long, though a ping
+    /// is waiting on an answer.
+    Stalled(Duration),
+}
+
+impl ConnectionQuality {
+    /// A round trip longer than this is [`ConnectionQuality::Slow`].
+    pub const SLOW: Duration = Duration::from_millis(300);
+    /// Silence longer than this is [`ConnectionQuality::Stalled`].
+    pub const STALLED: Duration = Duration::from_secs(10);
+}
+
+impl fmt::Display for ConnectionQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionQuality::Unknown => write!(f, "-- ms"),
+            ConnectionQuality::Good(rtt) | ConnectionQuality::Slow(rtt) => {
+                write!(f, "{} ms", rtt.as_millis())
+            },
+            ConnectionQuality::Stalled(silence) => write!(f, "no reply for {}s", silence.as_secs()),
+        }
+    }
+}
+
+/// Times the round trip to the server with pings, and notices when the server
+/// stops answering.
+#[derive(Debug)]
+pub struct LatencyMeter {
+    next_ping: u64,
+    /// The ping that has yet to be answered, and when it was sent.
+    unanswered: Option<(u64, Instant)>,
+    round_trip: Option<Duration>,
+    /// When the latest message of any kind arrived from the server.
+    last_heard: Instant,
+}
+
+impl LatencyMeter {
+    /// How often the server is pinged.
+    pub const INTERVAL: Duration = Duration::from_secs(2);
+
+    pub fn new(now: Instant) -> Self {
+        Self {
+            next_ping: 0,
+            unanswered: None,
+            round_trip: None,
+            last_heard: now,
+        }
+    }
+
+    /// Records that a message arrived from the server. Any message shows that
+    /// the connection is alive, not only an answer to a ping.
+    pub fn heard(&mut self, now: Instant) {
+        self.last_heard = now;
+    }
+
+    /// Makes the next ping to send, unless the last is still unanswered.
+    pub fn ping(&mut self, now: Instant) -> Option<ClientMessage> {
+        if self.unanswered.is_some() { return None; }
+        let ping = self.next_ping;
+        self.next_ping += 1;
+        self.unanswered = Some((ping, now));
+        Some(ClientMessage::Ping { ping })
+    }
+
+    /// Records the answer to a ping. Answers to pings other than the latest
+    /// are ignored.
+    pub fn pong(&mut self, pong: u64, now: Instant) {
+        self.heard(now);
+        if let Some((ping, sent)) = self.unanswered {
+            if ping == pong {
+                self.round_trip = Some(now.saturating_duration_since(sent));
+                self.unanswered = None;
+            }
+        }
+    }
+
+    pub fn quality(&self, now: Instant) -> ConnectionQuality {
+        let silence = now.saturating_duration_since(self.last_heard);
+        if self.unanswered.is_some() && silence > ConnectionQuality::STALLED {
+            return ConnectionQuality::Stalled(silence);
+        }
+        match self.round_trip {
+            None => ConnectionQuality::Unknown,
+            Some(rtt) if rtt > ConnectionQuality::SLOW => ConnectionQuality::Slow(rtt),
+            Some(rtt) => ConnectionQuality::Good(rtt),
+        }
+    }
+}
+
+/// Commands typed into the game command prompt that are handled by the client
+/// itself rather than sent to the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalCommand {
+    /// Suggests an action to the player, searching as hard as the
+    /// [`Difficulty`] demands.
+    Hint(Difficulty),
+    /// Describes the board in words, as with [`describe_board`].
+    Board,
+    /// Ranks the players by net worth, as with [`describe_standings`].
+    Standings,
+    /// Says the chat messages typed from now on in a channel.
+    Channel(ChatChannel),
+}
+
+/// Decodes a game command that is handled locally. Returns [`None`] if the
+/// command should instead be parsed by [`parse_game_command`].
+pub fn parse_local_command(command: &str) -> Option<LocalCommand> {
+    match command.trim() {
+        "hint" | "hint easy" => Some(LocalCommand::Hint(Difficulty::Easy)),
+        "hint hard" => Some(LocalCommand::Hint(Difficulty::Hard)),
+        "board" => Some(LocalCommand::Board),
+        "standings" => Some(LocalCommand::Standings),
+        "channel lobby" => Some(LocalCommand::Channel(ChatChannel::Lobby)),
+        "channel room" => Some(LocalCommand::Channel(ChatChannel::Room)),
+        _ => None,
+    }
+}
+
+/// Decodes a game command that concerns the player's connection rather than
+/// the game: `rename <name>` to change the name they go by, `resync` to be
+/// sent the game afresh when the local copy seems wrong, `queue` and
+/// `unqueue` to wait for a game to be made up, or stop waiting, or
+/// `autopilot on` and `autopilot off` to hand the player's seat to the server
+/// while they step away, and take it back. Returns [`None`] if the command
+/// should instead be parsed by [`parse_game_command`].
+pub fn parse_connection_command(command: &str) -> Option<ClientMessage> {
+    match command.trim() {
+        "resync" => Some(ClientMessage::RequestResync),
+        "queue" => Some(ClientMessage::Queue { queued: true }),
+        "unqueue" => Some(ClientMessage::Queue { queued: false }),
+        "autopilot on" => Some(ClientMessage::SetAutopilot { autopilot: true }),
+        "autopilot off" => Some(ClientMessage::SetAutopilot { autopilot: false }),
+        command => {
+            let new_name = command.strip_prefix("rename ")?.trim();
+            Some(ClientMessage::Rename { new_name: new_name.into() })
+        },
+    }
+}
+
+/// Decodes a game command that votes on ending the game early, or
+/// `votekick <name>` to vote to kick a player. Returns [`None`] if the command
+/// should instead be parsed by [`parse_game_command`].
+pub fn parse_vote_command(command: &str) -> Option<ClientMessage> {
+    match command.trim() {
+        "propose end" | "vote yes" => Some(ClientMessage::ProposeEnd { end_game: true }),
+        "vote no" => Some(ClientMessage::ProposeEnd { end_game: false }),
+        command => {
+            let target = command.strip_prefix("votekick ")?.trim();
+            Some(ClientMessage::VoteKick { votekick: target.into() })
+        },
+    }
+}
+
+/// An admin command as typed: its name, the arguments it takes, and how the
+/// command is built from them once they have been checked.
+struct AdminCommandSpec {
+    name: &'static str,
+    /// What each argument is, as shown in help. Every argument but
+    /// `capabilities` and `minutes` is a player's name, and is checked as one.
+    args: &'static [&'static str],
+    summary: &'static str,
+    /// Builds the command from exactly as many arguments as `args` lists.
+    build: fn(Vec<Box<str>>) -> AdminCommand,
+}
+
+/// Every admin command, in the order help lists them.
+const ADMIN_COMMANDS: &[AdminCommandSpec] = &[
+    AdminCommandSpec {
+        name: "start",
+        args: &[],
+        summary: "Starts a game with everyone seated.",
+        build: |_| AdminCommand::StartGame,
+    },
+    AdminCommandSpec {
+        name: "schedule",
+        args: &["minutes"],
+        summary: "Starts a game with everyone seated in so many minutes, counting down to it.",
+        build: |args| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+            let minutes: u64 = args[0].parse().unwrap();
+            AdminCommand::ScheduleStart { at: now + minutes * 60 }
+        },
+    },
+    AdminCommandSpec {
+        name: "end",
+        args: &[],
+        summary: "Ends the game in progress.",
+        build: |_| AdminCommand::EndGame,
+    },
+    AdminCommandSpec {
+        name: "log",
+        args: &[],
+        summary: "Saves the log of every action in the latest game.",
+        build: |_| AdminCommand::ExportLog,
+    },
+    AdminCommandSpec {
+        name: "seat",
+        args: &["player"],
+        summary: "Gives a spectator a seat at the table.",
+        build: |args| {
+            let [player_name] = <[_; 1]>::try_from(args).unwrap();
+            AdminCommand::Seat { player_name }
+        },
+    },
+    AdminCommandSpec {
+        name: "unseat",
+        args: &["player"],
+        summary: "Moves a player from the table to spectate.",
+        build: |args| {
+            let [player_name] = <[_; 1]>::try_from(args).unwrap();
+            AdminCommand::Unseat { player_name }
+        },
+    },
+    AdminCommandSpec {
+        name: "substitute",
+        args: &["seat", "player"],
+        summary: "Gives the seat of a player in the game to someone else.",
+        build: |args| {
+            let [seat, player_name] = <[_; 2]>::try_from(args).unwrap();
+            AdminCommand::Substitute { seat, player_name }
+        },
+    },
+    AdminCommandSpec {
+        name: "kick",
+        args: &["player"],
+        summary: "Disconnects a player.",
+        build: |args| {
+            let [player_name] = <[_; 1]>::try_from(args).unwrap();
+            AdminCommand::Kick { player_name }
+        },
+    },
+    AdminCommandSpec {
+        name: "trust",
+        args: &["player", "capabilities"],
+        summary: "Sets what a player may do as an admin, as in trust bob start,end or trust bob none.",
+        build: |args| {
+            let [player_name, capabilities] = <[_; 2]>::try_from(args).unwrap();
+            AdminCommand::Trust { player_name, capabilities: capabilities.parse().unwrap() }
+        },
+    },
+    AdminCommandSpec {
+        name: "shutdown",
+        args: &[],
+        summary: "Shuts the server down.",
+        build: |_| AdminCommand::Shutdown,
+    },
+];
+
+impl AdminCommandSpec {
+    /// Shows how the command is typed, as in `seat <player>`.
+    fn usage(&self) -> String {
+        self.args.iter().fold(self.name.to_owned(), |usage, arg| usage + &format!(" <{arg}>"))
+    }
+}
+
+/// Splits a command into its words. A word in double quotes may hold spaces,
+/// as in `seat "mary ann"`.
+fn tokenize(command: &str) -> Result<Vec<String>, CommandParseErr> {
+    let mut tokens = Vec::new();
+    let mut token: Option<String> = None;
+    let mut quoted = false;
+    for c in command.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                token.get_or_insert_with(String::new);
+            },
+            c if c.is_whitespace() && !quoted => tokens.extend(token.take()),
+            c => token.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quoted {
+        return Err(CommandParseErr::UnclosedQuote);
+    }
+    tokens.extend(token);
+    Ok(tokens)
+}
+
+/// Decodes an admin command, such as `start` or `seat "mary ann"`.
 pub fn parse_admin_command(command: &str) -> Result<AdminCommand, CommandParseErr> {
     use CommandParseErr::*;
-    let command_message = "\"shutdown\", \"silencechat\", \"start\", \"end\", or \"kick\"";
-
-    Ok(match command {
-        "shutdown" => AdminCommand::Shutdown,
-        "silencechat" => AdminCommand::SilenceChat,
-        "start" => AdminCommand::StartGame,
-        "end" => AdminCommand::EndGame,
-        other => {
-            let space = other.find(" ");
-            let space = match space {
-                Some(v) => v,
-                None => {
-                    return if command == "kick" {
-                        Err(Expected("player name"))
-                    } else {
-                        Err(Expected(command_message))
-                    };
-                },
-            };
-            let name = &command[(space+1)..];
-            AdminCommand::Kick { player_name: name.to_owned().into_boxed_str() }
+
+    let mut tokens = tokenize(command)?.into_iter();
+    let name = tokens.next().ok_or(EmptyInput)?;
+    let spec = ADMIN_COMMANDS.iter()
+        .find(|spec| spec.name == name)
+        .ok_or(UnknownCommand(name))?;
+
+    let args: Vec<Box<str>> = tokens.map(String::into_boxed_str).collect();
+    if let Some(missing) = spec.args.get(args.len()) {
+        return Err(Expected(missing));
+    }
+    if let Some(extra) = args.get(spec.args.len()) {
+        return Err(UnexpectedArgument(extra.to_string()));
+    }
+    for (i, (arg, kind)) in args.iter().zip(spec.args).enumerate() {
+        match *kind {
+            // The command's name is the first word
+            "capabilities" => arg.parse::<Capabilities>().map(drop).map_err(|_| Mismatch {
+                expected: "capabilities such as start,end",
+                found: arg.to_string(),
+                position: i + 2,
+            })?,
+            "minutes" => arg.parse::<u16>().map(drop).map_err(|_| Mismatch {
+                expected: "a number of minutes",
+                found: arg.to_string(),
+                position: i + 2,
+            })?,
+            _ => check_name(arg).map_err(|reason| InvalidName {
+                name: arg.to_string(),
+                reason: reason.to_string(),
+            })?,
         }
+    }
+    Ok((spec.build)(args))
+}
+
+/// Answers an admin command of `help`, listing every admin command, or of
+/// `help <command>`, describing one. Returns [`None`] if the command should
+/// instead be parsed by [`parse_admin_command`].
+pub fn admin_help(command: &str) -> Option<Vec<String>> {
+    let tokens = tokenize(command).ok()?;
+    match tokens.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["help"] => {
+            let mut lines = vec!["Admin commands:".to_owned()];
+            lines.extend(ADMIN_COMMANDS.iter().map(|spec| format!("  {}: {}", spec.usage(), spec.summary)));
+            lines.push("  help <command>: Describes a command.".to_owned());
+            lines.push("Put a name with spaces in double quotes, as in seat \"mary ann\".".to_owned());
+            Some(lines)
+        },
+        ["help", name] => Some(vec![match ADMIN_COMMANDS.iter().find(|spec| spec.name == name) {
+            Some(spec) => format!("{}: {}", spec.usage(), spec.summary),
+            None => format!("There is no admin command \"{name}\"."),
+        }]),
+        _ => None,
+    }
+}
+
+/// The commands given at the host's console, which no remote admin can give:
+/// each one's name, the one argument it takes, and what it does.
+const CONSOLE_COMMANDS: [(&str, &str, &str); 3] = [
+    ("save", "file", "Saves the game in progress to a file."),
+    ("load", "file", "Carries on the game saved in a file, in place of any in progress."),
+    ("seed", "seed", "Deals the next game from a seed of 64 hexadecimal digits."),
+];
+
+/// Decodes a console command, such as `save game.json` or
+/// `load "my games/game.json"`.
+pub fn parse_console_command(command: &str) -> Result<ConsoleCommand, CommandParseErr> {
+    use CommandParseErr::*;
+
+    let mut tokens = tokenize(command)?.into_iter();
+    let name = tokens.next().ok_or(EmptyInput)?;
+    let Some(&(_, arg, _)) = CONSOLE_COMMANDS.iter().find(|(console_name, ..)| *console_name == name) else {
+        return Err(UnknownCommand(name));
+    };
+    let value = tokens.next().ok_or(Expected(arg))?;
+    if let Some(extra) = tokens.next() {
+        return Err(UnexpectedArgument(extra));
+    }
+
+    Ok(match name.as_str() {
+        "save" => ConsoleCommand::Save(value.into()),
+        "load" => ConsoleCommand::Load(value.into()),
+        _ => ConsoleCommand::Seed(value.parse().map_err(|_| Mismatch {
+            expected: "a seed of 64 hexadecimal digits",
+            found: value,
+            position: 2,
+        })?),
     })
 }
 
+/// Answers a console command of `help`, as [`admin_help`] does for admin
+/// commands.
+pub fn console_help(command: &str) -> Option<Vec<String>> {
+    let tokens = tokenize(command).ok()?;
+    let usage = |(name, arg, summary): &(&str, &str, &str)| format!("{name} <{arg}>: {summary}");
+    match tokens.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["help"] => {
+            let mut lines = vec!["Console commands:".to_owned()];
+            lines.extend(CONSOLE_COMMANDS.iter().map(|command| format!("  {}", usage(command))));
+            Some(lines)
+        },
+        ["help", name] => CONSOLE_COMMANDS.iter()
+            .find(|(console_name, ..)| *console_name == name)
+            .map(|command| vec![usage(command)]),
+        _ => None,
+    }
+}
+
+/// Indicates that the client's game no longer matches the server's.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum ClientDesyncError {
+    #[error("received a move while no game is in progress")]
+    NoGameInProgress,
+    #[error("received a move the local game rejected ({0})")]
+    RejectedAction(InvalidMessageReason),
+    #[error("tile {0} was played from your hand, but you do not have it")]
+    TileNotInHand(Tile),
+    #[error("game state hash {found:016x} does not match the server's {expected:016x}")]
+    StateMismatch { expected: u64, found: u64 },
+}
+
+/// Indicates that the history of the game in progress, as sent by the server,
+/// could not be played through.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum CatchUpError {
+    #[error(transparent)]
+    Replay(#[from] ReplayError),
+    #[error("{player_name} couldn't take the seat of {seat} ({reason})")]
+    Substitution {
+        seat: Box<str>,
+        player_name: Box<str>,
+        reason: InvalidMessageReason,
+    },
+    #[error("the history ends the game, which is still in progress")]
+    GameOver,
+}
+
 #[derive(Debug, Clone, thiserror::Error)]
 pub enum CommandParseErr {
     #[error("empty command")]
@@ -130,6 +870,23 @@ pub enum CommandParseErr {
     Expected(&'static str),
     #[error("argument \"{0}\" appeared twice")]
     DuplicateArgument(String),
+    #[error("unknown command \"{0}\"; type help for a list")]
+    UnknownCommand(String),
+    #[error("unexpected argument \"{0}\"")]
+    UnexpectedArgument(String),
+    #[error("expected {expected} as word {position}, but found \"{found}\"")]
+    Mismatch {
+        expected: &'static str,
+        found: String,
+        position: usize,
+    },
+    #[error("a quote was left unclosed")]
+    UnclosedQuote,
+    #[error("\"{name}\" cannot be a player's name, as the {reason}")]
+    InvalidName {
+        name: String,
+        reason: String,
+    },
 }
 
 /// Handles the client side of a game. Tracks when a game is in progress and
@@ -138,6 +895,19 @@ pub struct ClientGame {
     client: Handshake,
     _impl: Option
     <ClientGameImpl>,
+    /// The start of the latest game, and the client's opening hand in it if
+    /// they were dealt one, kept to be checked against the seed of the deal.
+    opening: Option<(GameStart, Option<FullHand>)>,
+    /// The tiles drawn in the latest game after the opening hands, also kept
+    /// to be checked against the seed. [`None`] if the client missed some of
+    /// them, having joined or resynced partway through the game.
+    draws: Option<Draws>,
+    /// What each player was worth at the end of every turn of the latest
+    /// game, kept past its end to be graphed.
+    scores: ScoreHistory,
+    /// The number of the action the server last asked of the client, until
+    /// an action is played.
+    awaited: Option<ActionNumber>,
 }
 
 struct ClientGameImpl {
@@ -145,21 +915,77 @@ struct ClientGameImpl {
     hand: Option<Hand>,
 }
 
+/// The tiles drawn in a game after the opening hands. Every player draws a
+/// tile as their turn ends and as they trade in a dead tile, so the client
+/// can count the draws of others without seeing them.
+#[derive(Debug, Default)]
+struct Draws {
+    /// The number of tiles drawn by anyone.
+    drawn: usize,
+    /// The tiles the client drew, each with the number drawn before it.
+    own: Vec<(usize, Tile)>,
+}
+
 impl ClientGame {
 
-    /// Creates a new [`ClientGame`] with no game in progress.
-    pub fn new(client: Handshake, history: Option<GameHistory>) -> Self {
+    /// Creates a new [`ClientGame`], caught up to the game in progress if
+    /// there is one. `hand` is the client's hand in that game, if they are
+    /// rejoining it.
+    ///
+    /// If the history cannot be played through, the client is left with no
+    /// game of its own, as a spectator would be until the next resync, and the
+    /// reason is returned alongside it.
+    pub fn new(client: Handshake, history: Option<PublicGameHistory>, hand: Option<Hand>)
+        -> (Self, Option<CatchUpError>)
+    {
+        let opening = history.as_ref().map(|history| (history.start.clone(), None));
+        let (_impl, error) = match history.map(Self::catch_up).transpose() {
+            Ok(game) => (game.map(|game| ClientGameImpl { game, hand }), None),
+            Err(error) => (None, Some(error)),
+        };
+
+        // The turns before the client joined are not known
+        let mut scores = ScoreHistory::default();
+        if let Some(game_impl) = &_impl {
+            scores.record(&game_impl.game);
+        }
+
+        (Self { client, _impl, opening, draws: None, scores, awaited: None }, error)
+    }
+
+    /// Plays through the history of a game in progress.
+    fn catch_up(history: PublicGameHistory) -> Result<Game<kernel::Ambiguous>, CatchUpError> {
+        let (mut game, mut played): (Game<kernel::Ambiguous>, _) = match history.checkpoint {
+            Some(checkpoint) => ((*checkpoint.snapshot).into(), checkpoint.after_actions),
+            None => (Game::start(&history.start).into(), 0),
+        };
 
-        let _impl = history.map(|history| {
-            let game: Game<kernel::Ambiguous> = Game::start(&history.start).into();
-            let game = game.speed_play(history.actions.into_vec()).unwrap().unwrap();
-            ClientGameImpl {
-                game,
-                hand: None,
+        // Errors name the action by its place in the whole game
+        fn replay<I>(game: Game<kernel::Ambiguous>, actions: I, played: usize)
+            -> Result<Game<kernel::Ambiguous>, CatchUpError>
+            where I: IntoIterator<Item = TaggedPlayerAction>
+        {
+            match game.speed_play(actions) {
+                Ok(Ok(game)) => Ok(game),
+                Ok(Err(_)) => Err(CatchUpError::GameOver),
+                Err(error) => Err(ReplayError { index: error.index + played, ..error }.into()),
             }
-        });
+        }
 
-        Self { client, _impl }
+        // Seats change hands between the actions they were made after
+        let mut actions = history.actions.into_vec().into_iter();
+        for substitution in history.substitutions.iter() {
+            let before = actions.by_ref().take(substitution.after_actions - played);
+            game = replay(game, before, played)?;
+            game.substitute(&substitution.seat, substitution.player_name.clone())
+                .map_err(|reason| CatchUpError::Substitution {
+                    seat: substitution.seat.clone(),
+                    player_name: substitution.player_name.clone(),
+                    reason,
+                })?;
+            played = substitution.after_actions;
+        }
+        replay(game, actions, played)
     }
 
     /// Starts a new game.
@@ -169,88 +995,187 @@ impl ClientGame {
     /// This function assumes that the server knows the
     /// state, and it will panic if the server requests to start a game when one
     /// is already in progress.
-    pub fn start(&mut self, game: Game<kernel::Ambiguous>, hand: Option<Hand>) {
+    pub fn start(&mut self, info: &GameStart, hand: Option<FullHand>) {
         assert!(self._impl.as_ref().is_none(),
             "Server requested the start of a game when one is already in progress"
         );
-        self._impl = Some(ClientGameImpl { game, hand });
+        let game = Game::start(info).into();
+        self.awaited = None;
+        self.scores.clear();
+        self.scores.record(&game);
+        self._impl = Some(ClientGameImpl {
+            game,
+            hand: hand.map(Into::into),
+        });
+        self.opening = Some((info.clone(), hand));
+        self.draws = Some(Draws::default());
     }
 
-    /// Updates the client's game. Returns a mutable reference to the new game.
-    /// 
-    /// # Panics
-    /// 
-    /// This function assumes that all the validation work has been properly
-    /// done server-side. Any unexpected/invalid [`PlayerAction`] will panic.
-    /// 
-    /// TODO: remove this panic condition and return a proper error.
-    pub fn update(&mut self, action: &TaggedPlayerAction) {
-        
-        // Take ownership of this instance's game
-        let game_impl = self._impl.take()
-            .expect("Game updated when not in progress.");
-        let game = game_impl.game;
-        let mut hand = game_impl.hand;
+    /// Gets the rule policy of the game in progress. With none in progress,
+    /// nothing is forbidden.
+    pub fn policy(&self) -> RulePolicy {
+        match (&self._impl, &self.opening) {
+            (Some(_), Some((start, _))) => start.policy,
+            _ => RulePolicy::default(),
+        }
+    }
 
-        let new_game: Option<Game<kernel::Ambiguous>> = match &action.action {
-            PlayerAction::PlayTile { placement } => {
-    
-                // The game should be in the placing tile phase
-                let stated_game = match game.disambiguate() {
-                    GameDisambiguation::PlacingTile(g) => g,
-                    _ => panic!("received tile placement when not in placing tile mode"),
-                };
-    
-                let advancer = stated_game.check_player_action(action)
-                    .expect("Incorrect move received from server");
-
-                // Update the player's hand if the client placed the tile
-                if &action.player_name == &self.client.player_name {
-                    let success = hand.as_mut()
-                        .expect("Spectator placed a tile")
-                        .remove_tile(placement.tile);
-                    assert!(success);
+    /// Checks if the rule policy of the game in progress forbids a message
+    /// the client is about to send, as the server would refuse it.
+    pub fn forbids(&self, msg: &ClientMessage) -> bool {
+        self.policy().forbids(msg, self.hand().is_some())
+    }
+
+    /// Checks the deal of the latest game against the seed revealed at its
+    /// end. Returns [`None`] if the client never saw that game start.
+    pub fn check_deal(&self, seed: &DealSeed) -> Option<Result<(), DealMismatch>> {
+        let (start, hand) = self.opening.as_ref()?;
+        let hand = hand.as_ref().map(|hand| (&*self.client.player_name, hand));
+        let draws = self.draws.as_ref().map_or(&[][..], |draws| &draws.own);
+        Some(seed.verify(start, hand, draws))
+    }
+
+    /// Notes a tile the server dealt the client, to be checked against the
+    /// seed of the deal. The draw was already counted as the turn that led
+    /// to it ended, or as the dead tile it replaces was traded in.
+    pub fn record_draw(&mut self, tile: Tile) {
+        if let Some(draws) = &mut self.draws {
+            draws.own.push((draws.drawn.saturating_sub(1), tile));
+        }
+    }
+
+    /// Counts a tile drawn by anyone.
+    fn count_draw(&mut self) {
+        if let Some(draws) = &mut self.draws {
+            draws.drawn += 1;
+        }
+    }
+
+    /// Updates the client's game with an action broadcast by the server. If
+    /// the action cannot be applied, the game is left as it was and a
+    /// [`ClientDesyncError`] is returned. This means the client no longer
+    /// agrees with the server on the state of the game, and should request a
+    /// resync.
+    pub fn update(&mut self, action: &TaggedPlayerAction) -> Result<(), ClientDesyncError> {
+        let game_impl = self._impl.as_ref()
+            .ok_or(ClientDesyncError::NoGameInProgress)?;
+
+        // Update the player's hand if the client placed the tile
+        let placed_tile = match action.action {
+            PlayerAction::PlayTile { placement } if action.player_name == self.client.player_name => {
+                let in_hand = game_impl.hand
+                    .map(|hand| hand.iter().any(|&t| t == placement.tile))
+                    .unwrap_or(false);
+                if !in_hand {
+                    return Err(ClientDesyncError::TileNotInHand(placement.tile));
                 }
+                Some(placement.tile)
+            },
+            _ => None,
+        };
 
-                Some(stated_game.advance_game(advancer).into())
+        // Take ownership of this instance's game. It must be put back before
+        // returning, even on failure.
+        let ClientGameImpl { game, mut hand } = self._impl.take().unwrap();
+
+        // Buying stock ends a turn, and the player draws a tile
+        if let PlayerAction::BuyStock { .. } = action.action {
+            self.count_draw();
+        }
+        match game.try_advance_game(action) {
+            Ok(Ok(game)) => {
+                self.awaited = None;
+                if let (Some(tile), Some(hand)) = (placed_tile, hand.as_mut()) {
+                    hand.remove_tile(tile);
+                }
+                // Buying stock ends a turn
+                if let PlayerAction::BuyStock { .. } = action.action {
+                    self.scores.record(&game);
+                }
+                self._impl = Some(ClientGameImpl { game, hand });
+                Ok(())
             },
-            PlayerAction::BuyStock{ stock: _ } => {
-    
-                // The game should be in buying stock phase
-                let stated_game = match game.disambiguate() {
-                    GameDisambiguation::BuyingStock(g) => g,
-                    _ => panic!("received stock when not in buying stock mode"),
-                };
-    
-                let advancer = stated_game.check_player_action(action)
-                    .expect("Invalid purchase received from server");
-                stated_game.advance_game(advancer).ok().map(|g| g.into())
+            // The game is over, and the server will follow up with the results
+            Ok(Err(_)) => {
+                self.awaited = None;
+                Ok(())
             },
-            PlayerAction::ResolveMergeStock {
-                selling: _,
-                trading: _,
-                keeping: _ 
-            } => {
-    
-                // The game should be in resolving merge phase
-                let stated_game = match game.disambiguate() {
-                    GameDisambiguation::ResolvingMerge(game) => game,
-                    _ => panic!("Received merge resolution when not in buying stock mode"),
-                };
-    
-                let advancer = stated_game.check_player_action(&action)
-                    .expect("Invalid merge resolution received from server");
-                stated_game.step_merge(advancer).ok()
+            Err((game, reason)) => {
+                self._impl = Some(ClientGameImpl { game, hand });
+                Err(ClientDesyncError::RejectedAction(reason))
             },
-        };
+        }
+    }
+
+    /// Hands a seat over to the player the server says has taken it.
+    pub fn substitute(&mut self, substitution: &Substitution) -> Result<(), ClientDesyncError> {
+        let game = self.game_mut().ok_or(ClientDesyncError::NoGameInProgress)?;
+        game.substitute(&substitution.seat, substitution.player_name.clone())
+            .map_err(ClientDesyncError::RejectedAction)
+    }
+
+    /// Gets the name the client goes by.
+    pub fn player_name(&self) -> &str {
+        &self.client.player_name
+    }
+
+    /// Follows a rename, if it was the client that was renamed.
+    pub fn rename(&mut self, old_name: &str, new_name: &str) {
+        if *self.client.player_name == *old_name {
+            self.client.player_name = new_name.into();
+        }
+    }
+
+    /// Removes a dead tile from the client's hand if it was the client that
+    /// traded it in. The server sends the replacement separately.
+    pub fn trade_in_dead_tile(&mut self, player_name: &str, tile: Tile) {
+        self.count_draw();
+        if player_name != &*self.client.player_name { return; }
+        if let Some(hand) = self.hand_mut() {
+            hand.remove_tile(tile);
+        }
+    }
+
+    /// Checks the client's game against the hash the server sent of its own
+    /// game. If no game is in progress, there is nothing to check.
+    pub fn verify(&self, state_hash: u64) -> Result<(), ClientDesyncError> {
+        match self.game().map(|game| game.state_hash()) {
+            Some(found) if found != state_hash => {
+                Err(ClientDesyncError::StateMismatch { expected: state_hash, found })
+            },
+            _ => Ok(()),
+        }
+    }
 
-        // Put the game, mutated or not, back into this game object. Note to
-        // future Will: when implementing a proper error for this function,
-        // don't use the ? operator to return, otherwise the game is dropped, as
-        // it is owned by a local variable until this is called.
-        self._impl = new_game.map(|game| {
-            ClientGameImpl { game, hand }
+    /// Replaces the client's game with the state sent by the server in
+    /// response to [`ClientMessage::RequestResync`].
+    pub fn resync(&mut self, snapshot: Option<Box<GameSnapshot>>, hand: Option<Hand>) {
+        self._impl = snapshot.map(|snapshot| ClientGameImpl {
+            game: (*snapshot).into(),
+            hand,
         });
+        self.awaited = None;
+        self.draws = None;
+    }
+
+    /// Remembers the number of the action the server has asked the client
+    /// for, to send the action with.
+    pub fn await_action(&mut self, number: ActionNumber) {
+        self.awaited = Some(number);
+    }
+
+    /// Gets the number of the action the server last asked of the client, if
+    /// no action has been played since.
+    pub fn awaited(&self) -> Option<ActionNumber> {
+        self.awaited
+    }
+
+    /// Numbers an action about to be sent with [`awaited`](Self::awaited).
+    /// Any other message is left as it is.
+    pub fn number_action(&self, msg: &mut ClientMessage) {
+        if let ClientMessage::TakingTurn { number, .. } = msg {
+            *number = self.awaited;
+        }
     }
 
     /// Ends this game.
@@ -264,6 +1189,12 @@ impl ClientGame {
         game_impl.game.end_early()
     }
     
+    /// Gets what each player was worth over the latest game, which is kept
+    /// after it ends.
+    pub fn scores(&self) -> &ScoreHistory {
+        &self.scores
+    }
+
     pub fn game(&self) -> Option<&Game<kernel::Ambiguous>> {
         self._impl.as_ref().map(|i| &i.game)
     }
@@ -279,4 +1210,380 @@ impl ClientGame {
     pub fn hand_mut(&mut self) -> Option<&mut Hand> {
         self._impl.as_mut().map(|i| i.hand.as_mut()).flatten()
     }
+
+    /// Checks whether the server would accept a [`PlayerAction`] taken by this
+    /// client, without modifying the game.
+    pub fn check_action(&self, action: &PlayerAction) -> Result<(), InvalidMessageReason> {
+        let game = self.game().ok_or(InvalidMessageReason::NoGameStarted)?;
+
+        let action = TaggedPlayerAction {
+            player_name: self.client.player_name.clone(),
+            action: *action,
+            number: None,
+        };
+
+        match game.clone().disambiguate() {
+            GameDisambiguation::PlacingTile(game) => {
+                game.check_player_action(&action)?;
+
+                if let PlayerAction::PlayTile { placement } = action.action {
+                    let in_hand = self.hand()
+                        .map(|hand| hand.iter().any(|&t| t == placement.tile))
+                        .unwrap_or(false);
+                    if !in_hand {
+                        return Err(InvalidMessageReason::TileNotFound);
+                    }
+                }
+
+                Ok(())
+            },
+            GameDisambiguation::ResolvingMerge(game) => {
+                game.check_player_action(&action).map(|_| ())
+            },
+            GameDisambiguation::BuyingStock(game) => {
+                game.check_player_action(&action).map(|_| ())
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::server::feature::Features;
+    use crate::server::{Server, ServerOptions};
+
+    #[tokio::test]
+    async fn rejoining_behind_a_checkpoint_catches_up() {
+        let handshake = |name: &str| Handshake {
+            player_name: name.into(),
+            spectating: false,
+            capabilities: Capabilities::NONE,
+            features: Features::NONE,
+        };
+        let options = ServerOptions {
+            checkpoint_interval: std::num::NonZeroUsize::new(2),
+            ..Default::default()
+        };
+        let (server, mut alice) = Server::start(options, Handshake {
+            capabilities: Capabilities::ALL,
+            ..handshake("alice")
+        });
+        let tiles = [
+            "1-a", "12-i",
+            "1-b", "5-e", "6-f", "7-g", "8-h", "9-c",
+            "10-c", "11-e", "3-g", "5-a", "7-c", "9-g",
+            "3-e", "4-i",
+        ];
+        server.stack_tiles(tiles.iter().map(|tile| tile.parse().unwrap()).collect()).await;
+        let bob = server.connect_player(handshake("bob")).await.unwrap();
+
+        // Three actions are played, the first two of them before the
+        // checkpoint
+        let play = |tile: &str, implication| ClientMessage::TakingTurn {
+            action: PlayerAction::PlayTile {
+                placement: TilePlacement { tile: tile.parse().unwrap(), implication },
+            },
+            request_id: None,
+            number: None,
+        };
+        let found = Some(TilePlacementImplication::FoundsCompany(Company::Continental));
+        alice.interface.sender().send(ClientMessage::Admin(AdminCommand::StartGame)).await.unwrap();
+        alice.interface.sender().send(play("1-b", found)).await.unwrap();
+        alice.interface.sender().send(ClientMessage::TakingTurn {
+            action: PlayerAction::BuyStock { stock: [None; 3] },
+            request_id: None,
+            number: None,
+        }).await.unwrap();
+        let mut moves = 0;
+        let state_hash = loop {
+            let message = alice.interface.recv().await.expect("the server shut down");
+            if let Ok(ServerMessage::PlayerMove { state_hash, .. }) = message {
+                moves += 1;
+                match moves {
+                    2 => bob.interface.sender().send(play("10-c", None)).await.unwrap(),
+                    3 => break state_hash,
+                    _ => {},
+                }
+            }
+        };
+
+        // The seat is held for bob once the server has seen the connection go
+        drop(bob);
+        let mut rejoined = server.connect_player(handshake("bob")).await;
+        for _ in 0..100 {
+            if rejoined.is_ok() { break; }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            rejoined = server.connect_player(handshake("bob")).await;
+        }
+        let bob = rejoined.unwrap();
+        let history = bob.server_state.game_history.unwrap();
+        assert_eq!(history.checkpoint.as_ref().map(|checkpoint| checkpoint.after_actions), Some(2));
+        assert_eq!(history.actions.len(), 1);
+
+        let (game, error) = ClientGame::new(handshake("bob"), Some(history), bob.server_state.hand);
+        assert!(error.is_none(), "{error:?}");
+        assert_eq!(game.game().unwrap().state_hash(), state_hash);
+    }
+
+    #[test]
+    fn desync_keeps_game() {
+        let client = Handshake {
+            player_name: "alice".into(),
+            spectating: false,
+            capabilities: Capabilities::NONE,
+            features: Features::NONE,
+        };
+        let history = PublicGameHistory {
+            start: GameStart::two_player_test(),
+            checkpoint: None,
+            actions: Box::new([]),
+            substitutions: Box::new([]),
+        };
+        let (mut game, _) = ClientGame::new(client, Some(history), None);
+
+        // Buying stock is out of turn while a tile must be placed
+        let action = TaggedPlayerAction {
+            player_name: "alice".into(),
+            action: PlayerAction::BuyStock { stock: [None; 3] },
+            number: None,
+        };
+        assert!(matches!(game.update(&action), Err(ClientDesyncError::RejectedAction(_))));
+        assert!(game.game().is_some());
+
+        // The hash of the server's game must match exactly
+        let state_hash = game.game().unwrap().state_hash();
+        assert!(game.verify(state_hash).is_ok());
+        assert!(matches!(
+            game.verify(state_hash ^ 1),
+            Err(ClientDesyncError::StateMismatch { .. })
+        ));
+
+        // The client has no hand, so it cannot have placed a tile
+        let action = TaggedPlayerAction {
+            player_name: "alice".into(),
+            action: PlayerAction::PlayTile {
+                placement: TilePlacement { tile: Tile::new(5, 'e'), implication: None },
+            },
+            number: None,
+        };
+        assert!(matches!(game.update(&action), Err(ClientDesyncError::TileNotInHand(_))));
+
+        // A resync with no game in progress ends the local game
+        game.resync(None, None);
+        assert!(matches!(game.update(&action), Err(ClientDesyncError::NoGameInProgress)));
+    }
+
+    #[test]
+    fn actions_checked_as_the_server_would() {
+        let handshake = |name: &str| Handshake {
+            player_name: name.into(),
+            spectating: false,
+            capabilities: Capabilities::NONE,
+            features: Features::NONE,
+        };
+        let history = PublicGameHistory {
+            start: GameStart::two_player_test(),
+            checkpoint: None,
+            actions: Box::new([]),
+            substitutions: Box::new([]),
+        };
+        let mut hand = Hand::default();
+        hand.insert_tile(Tile::new(5, 'e')).unwrap();
+        let (alice, _) = ClientGame::new(handshake("alice"), Some(history.clone()), Some(hand));
+        let play = |tile| PlayerAction::PlayTile {
+            placement: TilePlacement { tile, implication: None },
+        };
+
+        // Alice may only place a tile she holds, and nothing else yet
+        assert!(alice.check_action(&play(Tile::new(5, 'e'))).is_ok());
+        assert!(matches!(
+            alice.check_action(&play(Tile::new(6, 'f'))),
+            Err(InvalidMessageReason::TileNotFound)
+        ));
+        assert!(alice.check_action(&PlayerAction::BuyStock { stock: [None; 3] }).is_err());
+
+        // It is not bob's turn, and there is nothing to check without a game
+        let (bob, _) = ClientGame::new(handshake("bob"), Some(history), None);
+        assert!(matches!(
+            bob.check_action(&play(Tile::new(6, 'f'))),
+            Err(InvalidMessageReason::OutOfTurn)
+        ));
+        let (carol, _) = ClientGame::new(handshake("carol"), None, None);
+        assert!(matches!(
+            carol.check_action(&play(Tile::new(6, 'f'))),
+            Err(InvalidMessageReason::NoGameStarted)
+        ));
+    }
+
+    #[test]
+    fn history_replays_substitutions() {
+        let client = Handshake {
+            player_name: "carol".into(),
+            spectating: false,
+            capabilities: Capabilities::NONE,
+            features: Features::NONE,
+        };
+        let play = |player: &str, tile| TaggedPlayerAction {
+            player_name: player.into(),
+            action: PlayerAction::PlayTile {
+                placement: TilePlacement { tile, implication: None },
+            },
+            number: None,
+        };
+        let history = PublicGameHistory {
+            start: GameStart::two_player_test(),
+            checkpoint: None,
+            actions: Box::new([
+                play("alice", Tile::new(5, 'e')),
+                TaggedPlayerAction {
+                    player_name: "alice".into(),
+                    action: PlayerAction::BuyStock { stock: [None; 3] },
+                    number: None,
+                },
+                play("carol", Tile::new(7, 'g')),
+            ]),
+            substitutions: Box::new([Substitution {
+                seat: "bob".into(),
+                player_name: "carol".into(),
+                after_actions: 1,
+            }]),
+        };
+
+        // A history sent from a checkpoint catches up to the same game
+        let mut partial = history.clone();
+        partial.actions = partial.actions[..2].into();
+        let (partial, _) = ClientGame::new(client.clone(), Some(partial), None);
+        let checkpoint = Checkpoint {
+            after_actions: 2,
+            snapshot: Box::new(partial.game().unwrap().snapshot()),
+        };
+        let (from_checkpoint, _) = ClientGame::new(client.clone(), Some(history.clone().since(checkpoint.clone())), None);
+
+        // Seeking through the full history starts from the latest checkpoint
+        // before the point sought
+        let full = GameHistory {
+            start: history.start.clone(),
+            actions: history.actions.clone(),
+            substitutions: history.substitutions.clone(),
+            checkpoints: Box::new([checkpoint]),
+        };
+        let sought = full.seek(1);
+        assert!(sought.checkpoint.is_none());
+        assert_eq!((sought.actions.len(), sought.substitutions.len()), (1, 1));
+        let sought = full.seek(3);
+        assert_eq!(sought.checkpoint.as_ref().map(|c| c.after_actions), Some(2));
+        assert_eq!((sought.actions.len(), sought.substitutions.len()), (1, 0));
+        let (sought, _) = ClientGame::new(client.clone(), Some(sought), None);
+
+        // An action the history can't have held leaves the client watching,
+        // told which action it was
+        let mut broken = history.clone();
+        broken.actions[2] = play("alice", Tile::new(7, 'g'));
+        let (spectating, error) = ClientGame::new(client.clone(), Some(broken), None);
+        assert!(spectating.game().is_none());
+        assert!(matches!(error, Some(CatchUpError::Replay(ReplayError { index: 2, .. }))), "{error:?}");
+
+        let (game, error) = ClientGame::new(client, Some(history), None);
+        assert!(error.is_none());
+        let game = game.game().unwrap();
+        assert_eq!(game.play_order(), ["alice", "carol"]);
+        assert_eq!(game.active_player(), "carol");
+        assert_eq!(game.players()["alice"].next_player, "carol".into());
+        assert_eq!(from_checkpoint.game().unwrap().state_hash(), game.state_hash());
+        assert_eq!(sought.game().unwrap().state_hash(), game.state_hash());
+    }
+
+    #[test]
+    fn game_commands_parsed() {
+        assert!(matches!(
+            parse_game_command("resolve keep 1  sell 2"),
+            Ok(PlayerAction::ResolveMergeStock { selling: 2, trading: 0, keeping: 1 })
+        ));
+        assert!(matches!(
+            parse_game_command("resolve"),
+            Ok(PlayerAction::ResolveMergeStock { selling: 0, trading: 0, keeping: 0 })
+        ));
+        assert!(matches!(parse_game_command("resolve sell 1 sell 2"), Err(CommandParseErr::DuplicateArgument(_))));
+        assert!(matches!(parse_game_command("resolve trade"), Err(CommandParseErr::Expected(_))));
+
+        let err = parse_game_command("play 5-e merging luxor toward tower").unwrap_err();
+        assert!(matches!(&err, CommandParseErr::Mismatch { position: 5, found, .. } if found == "toward"), "{err:?}");
+        assert_eq!(err.to_string(), "expected company as word 5, but found \"toward\"");
+        assert!(matches!(parse_game_command("buy luxor luxor luxor luxor"), Err(CommandParseErr::UnexpectedArgument(_))));
+    }
+
+    #[test]
+    fn admin_commands_parsed() {
+        assert!(matches!(parse_admin_command("start"), Ok(AdminCommand::StartGame)));
+        assert!(matches!(
+            parse_admin_command(r#"substitute  bob "mary ann""#),
+            Ok(AdminCommand::Substitute { seat, player_name }) if &*seat == "bob" && &*player_name == "mary ann"
+        ));
+
+        // A typo is caught rather than taken for a kick
+        assert!(matches!(parse_admin_command("strat now"), Err(CommandParseErr::UnknownCommand(_))));
+        assert!(matches!(parse_admin_command("seat"), Err(CommandParseErr::Expected("player"))));
+        assert!(matches!(parse_admin_command("end now"), Err(CommandParseErr::UnexpectedArgument(_))));
+        assert!(matches!(parse_admin_command(r#"seat "mary"#), Err(CommandParseErr::UnclosedQuote)));
+        assert!(matches!(parse_admin_command(r#"seat """#), Err(CommandParseErr::InvalidName { .. })));
+        assert!(matches!(
+            parse_admin_command("trust bob start,end"),
+            Ok(AdminCommand::Trust { capabilities, .. }) if capabilities.to_string() == "start,end"
+        ));
+        assert!(matches!(parse_admin_command("trust bob everything"), Err(CommandParseErr::Mismatch { position: 3, .. })));
+        assert!(matches!(parse_admin_command("schedule 5"), Ok(AdminCommand::ScheduleStart { .. })));
+        assert!(matches!(parse_admin_command("schedule soon"), Err(CommandParseErr::Mismatch { position: 2, .. })));
+
+        assert_eq!(admin_help("help").unwrap().len(), ADMIN_COMMANDS.len() + 3);
+        assert_eq!(admin_help("help seat").unwrap(), ["seat <player>: Gives a spectator a seat at the table."]);
+        assert!(admin_help("seat help").is_none());
+    }
+
+    #[test]
+    fn latency_measured_by_pings() {
+        let start = Instant::now();
+        let mut meter = LatencyMeter::new(start);
+        assert_eq!(meter.quality(start), ConnectionQuality::Unknown);
+
+        let Some(ClientMessage::Ping { ping }) = meter.ping(start) else { panic!("no ping") };
+        assert!(meter.ping(start).is_none(), "pinged again before an answer");
+        meter.pong(ping, start + Duration::from_millis(40));
+        assert_eq!(meter.quality(start), ConnectionQuality::Good(Duration::from_millis(40)));
+
+        // An unanswered ping, with nothing else arriving, stalls the connection
+        let later = start + Duration::from_secs(1);
+        meter.ping(later).unwrap();
+        meter.heard(later);
+        let silent = later + ConnectionQuality::STALLED * 2;
+        assert_eq!(meter.quality(silent), ConnectionQuality::Stalled(ConnectionQuality::STALLED * 2));
+    }
+
+    #[test]
+    fn board_described() {
+        let game = Game::start(&GameStart::two_player_test());
+
+        assert_eq!(describe_board(&game), [
+            "No companies are on the board.",
+            "Tiles outside a company: 1-a, 9-i.",
+        ]);
+    }
+
+    #[test]
+    fn chat_shown_once() {
+        let mut log = ChatLog::new();
+        // 2023-11-14 22:13:20 UTC
+        let timestamp = 1_700_000_000;
+
+        let lobby = ChatChannel::Lobby;
+
+        assert_eq!(log.receive("alice", "hi", timestamp, 0, lobby).as_deref(), Some("[22:13] <alice> hi"));
+        assert_eq!(log.receive("bob", "hey", timestamp + 60, 1, lobby).as_deref(), Some("[22:14] <bob> hey"));
+        assert!(log.receive("alice", "hi", timestamp, 0, lobby).is_none());
+        assert!(log.receive("bob", "hey", timestamp + 60, 1, lobby).is_none());
+
+        let room = log.receive("alice", "psst", timestamp, 2, ChatChannel::Room);
+        assert_eq!(room.as_deref(), Some("[22:13] (room) <alice> psst"));
+        assert_eq!(parse_local_command("channel room"), Some(LocalCommand::Channel(ChatChannel::Room)));
+    }
 }
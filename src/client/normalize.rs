@@ -0,0 +1,117 @@
+use crate::game::CompanyMap;
+
+/// The game commands, each with the letter it may be shortened to.
+const VERBS: [(&str, &str); 3] = [("p", "play"), ("b", "buy"), ("r", "resolve")];
+/// The words that may follow the tile of a `play` command.
+const PLAY_KEYWORDS: [&str; 3] = ["founding", "merging", "into"];
+/// The shares of a `resolve` command, each with the letter that may stand in
+/// for it, as in `s3` for `sell 3`.
+const SHARES: [(char, &str); 3] = [('s', "sell"), ('t', "trade"), ('k', "keep")];
+
+/// Gets the names of the companies, as typed in commands.
+fn company_names() -> Vec<String> {
+    CompanyMap::new(&()).iter()
+        .map(|(company, _)| company.to_string().to_ascii_lowercase())
+        .collect()
+}
+
+/// Expands the shorthand in a game command into the words that
+/// [`parse_game_command`](super::parse_game_command) reads. Commands may be
+/// typed in any case, shortened to their first letter, and name tiles without
+/// the dash and companies by any part of their name from its start, so that
+/// `/p 5c founding lux` is read as `/play 5-c founding luxor`. The shares of a
+/// `resolve` command may also be given as `s3 t2 k1`.
+pub fn game_command(command: &str) -> String {
+    let command = command.to_ascii_lowercase();
+    let mut words = command.split_whitespace();
+    let Some(verb) = words.next() else { return String::new() };
+    let verb = expand_verb(verb);
+
+    let mut expanded = vec![verb.to_owned()];
+    for word in words {
+        match verb {
+            "resolve" => match expand_share(word) {
+                Some((keyword, count)) => expanded.extend([keyword.to_owned(), count.to_owned()]),
+                None => expanded.push(word.to_owned()),
+            },
+            _ => expanded.push(expand_tile(word).or_else(|| expand_company(word)).unwrap_or_else(|| word.to_owned())),
+        }
+    }
+    expanded.join(" ")
+}
+
+fn expand_verb(word: &str) -> &str {
+    VERBS.iter().find(|(alias, _)| *alias == word).map_or(word, |(_, verb)| verb)
+}
+
+/// Expands a tile typed without its dash, such as `5c`.
+fn expand_tile(word: &str) -> Option<String> {
+    let split = word.find(|c: char| !c.is_ascii_digit())?;
+    let (row, col) = word.split_at(split);
+    let valid = !row.is_empty() && col.len() == 1 && matches!(col.chars().next(), Some('a'..='i'));
+    valid.then(|| format!("{row}-{col}"))
+}
+
+/// Expands the start of a company's name to the whole of it. No two companies
+/// start with the same letter, so any start names at most one.
+fn expand_company(word: &str) -> Option<String> {
+    company_names().into_iter().find(|name| name.starts_with(word))
+}
+
+/// Splits a share typed as a letter and a count, such as `s3`.
+fn expand_share(word: &str) -> Option<(&'static str, &str)> {
+    let mut chars = word.chars();
+    let letter = chars.next()?;
+    let count = chars.as_str();
+    let (_, keyword) = SHARES.iter().find(|(alias, _)| *alias == letter)?;
+    let valid = !count.is_empty() && count.chars().all(|c| c.is_ascii_digit());
+    valid.then_some((keyword, count))
+}
+
+/// Completes the last word of a game command, if it is the start of exactly
+/// one word that could go there. Returns the command with that word completed
+/// and followed by a space, ready for the next, or [`None`] if there is
+/// nothing to complete.
+pub fn complete(command: &str) -> Option<String> {
+    if command.is_empty() || command.ends_with(char::is_whitespace) { return None; }
+    let (before, last) = command.rsplit_once(char::is_whitespace).unwrap_or(("", command));
+    let last = last.to_ascii_lowercase();
+
+    let candidates: Vec<String> = match before.split_whitespace().next() {
+        None => VERBS.iter().map(|(_, verb)| verb.to_string()).collect(),
+        Some(verb) => match expand_verb(&verb.to_ascii_lowercase()) {
+            "play" => PLAY_KEYWORDS.iter().map(|keyword| keyword.to_string()).chain(company_names()).collect(),
+            "buy" => company_names(),
+            "resolve" => SHARES.iter().map(|(_, keyword)| keyword.to_string()).collect(),
+            _ => return None,
+        },
+    };
+
+    let mut matching = candidates.into_iter().filter(|candidate| candidate.starts_with(&last));
+    let completion = matching.next()?;
+    if matching.next().is_some() { return None; }
+    let separator = if before.is_empty() { "" } else { " " };
+    Some(format!("{}{separator}{completion} ", before.trim_end()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{complete, game_command};
+
+    #[test]
+    fn shorthand_expanded() {
+        assert_eq!(game_command("p 5C Founding lux"), "play 5-c founding luxor");
+        assert_eq!(game_command("b lux t  am"), "buy luxor tower american");
+        assert_eq!(game_command("r s3 t2 k1"), "resolve sell 3 trade 2 keep 1");
+        assert_eq!(game_command("play 12-i merging i w into c"), "play 12-i merging imperial worldwide into continental");
+        // Words that aren't shorthand are left for the parser to reject
+        assert_eq!(game_command("play 5j"), "play 5j");
+
+        assert_eq!(complete("pl").as_deref(), Some("play "));
+        assert_eq!(complete("p 5c fo").as_deref(), Some("p 5c founding "));
+        assert_eq!(complete("buy lu").as_deref(), Some("buy luxor "));
+        assert_eq!(complete("resolve s3 k").as_deref(), Some("resolve s3 keep "));
+        assert_eq!(complete("play 5c "), None);
+        assert_eq!(complete("hint e"), None);
+    }
+}
@@ -0,0 +1,93 @@
+use std::io::{self, Write};
+use std::process::Stdio;
+
+use crate::game::messages::{PlayerAction, ServerMessage, TilePlacementImplication};
+
+/// Something in the game worth drawing the player's ear to, for when they
+/// have looked away from the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cue {
+    YourTurn,
+    Merger,
+    GameOver,
+}
+
+impl Cue {
+    /// Gets the cue a message from the server calls for, if any.
+    pub fn of(msg: &ServerMessage) -> Option<Self> {
+        match msg {
+            ServerMessage::YourTurn { .. } => Some(Cue::YourTurn),
+            ServerMessage::PlayerMove { action, .. } => match &action.action {
+                PlayerAction::PlayTile { placement } => matches!(
+                    placement.implication,
+                    Some(TilePlacementImplication::MergesCompanies(_))
+                ).then_some(Cue::Merger),
+                _ => None,
+            },
+            ServerMessage::GameOver { .. } => Some(Cue::GameOver),
+            _ => None,
+        }
+    }
+
+    /// Gets the name a sound command is given the cue by, in the
+    /// `ACQUIRE_CUE` environment variable.
+    pub fn name(self) -> &'static str {
+        match self {
+            Cue::YourTurn => "your_turn",
+            Cue::Merger => "merger",
+            Cue::GameOver => "game_over",
+        }
+    }
+
+    /// Gets the number of times the terminal bell rings for the cue, so that
+    /// each can be told apart by ear.
+    fn rings(self) -> usize {
+        match self {
+            Cue::YourTurn => 1,
+            Cue::Merger => 2,
+            Cue::GameOver => 3,
+        }
+    }
+}
+
+/// How the client sounds a [`Cue`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Notifier {
+    /// Cues make no sound.
+    #[default]
+    Off,
+    /// Rings the terminal bell: once for the player's turn, twice for a
+    /// merger and three times when the game is over.
+    Bell,
+    /// Runs a command through the shell, such as `paplay ding.ogg`, with the
+    /// name of the cue in the `ACQUIRE_CUE` environment variable. The client
+    /// doesn't wait for the command, nor mind if it fails.
+    Command(Box<str>),
+}
+
+impl Notifier {
+    /// Sounds a cue.
+    pub fn cue(&self, cue: Cue) {
+        match self {
+            Notifier::Off => {},
+            Notifier::Bell => {
+                let mut stdout = io::stdout();
+                stdout.write_all("\x07".repeat(cue.rings()).as_bytes()).ok();
+                stdout.flush().ok();
+            },
+            Notifier::Command(command) => {
+                // The command must not write over the screen or read the
+                // player's keys
+                tokio::process::Command::new("sh")
+                    .arg("-c")
+                    .arg(&**command)
+                    .env("ACQUIRE_CUE", cue.name())
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .ok();
+            },
+        }
+    }
+}
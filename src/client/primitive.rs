@@ -1,33 +1,62 @@
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
 use tokio::sync::{mpsc, oneshot};
 
-use super::{CommandParseErr, parse_game_command, parse_admin_command, ClientGame};
-use crate::game::{tile::Tile, kernel::Game, messages::*};
+use super::{CommandParseErr, parse_game_command, parse_admin_command, admin_help, ClientGame, ChatLog};
+use super::{ClientConfig, LocalCommand, parse_local_command, format_game_command, describe_board, describe_standings};
+use super::{time_left, parse_connection_command, parse_vote_command, save_action_log, export_final_position};
+use super::notifier::{Cue, Notifier};
+use super::score_graph::GRAPH_WIDTH;
+use crate::game::bot::Bot;
+use crate::game::{tile::{Hand, Tile}, messages::*};
 use crate::server::{Interface, Handshake, NewConnection};
 
 /// Runs this client. This client is "owned" by the receiver, meaning it will
 /// run until the receiver is dropped or closed.
-pub async fn run<E: Send + 'static>(mut connection: NewConnection<E>) -> Result<(), E> {
+///
+/// Everything is written as plain lines, one after another, without moving
+/// the cursor, so the game can be followed with a screen reader.
+pub async fn run<E: Send + 'static>(
+    mut connection: NewConnection<E>,
+    config: ClientConfig,
+) -> Result<(), E> {
+    if let Some(info) = &connection.server_state.info {
+        println!("Welcome to {}, where {} players and {} spectators are connected.",
+            info.name, info.players, info.spectators);
+        if info.in_progress {
+            println!("A game is in progress.");
+        }
+        if let Some(motd) = &info.motd {
+            println!("{motd}");
+        }
+        if let Some(code) = &info.invite_code {
+            println!("Players join with the invite code {code}.");
+        }
+    }
+
     // Game objects passed to the two processes
-    let game = Arc::new(Mutex::new(
-        ClientGame::new(
-            connection.handshake.clone(),
-            connection.server_state.game_history.take()
-        )));
+    let (game, catch_up_error) = ClientGame::new(
+        connection.handshake.clone(),
+        connection.server_state.game_history.take(),
+        connection.server_state.hand,
+    );
+    if let Some(error) = catch_up_error {
+        println!("Couldn't catch up to the game in progress, as {error}. Watching without a copy of the game.");
+    }
+    let game = Arc::new(Mutex::new(game));
     let game_copy = Arc::clone(&game);
     
     // Exit handlers
     let (exit_sender, exit_recv) = oneshot::channel();
 
-    let player_name_clone = connection.handshake.player_name
-        .to_string()
-        .into_boxed_str();
     let sender = connection.interface.sender().clone();
+    let export_dir = config.export_dir.clone();
+    let notifier = config.notifier.clone();
     let event_loop = tokio::spawn(async move {
 
         // Run the loop
-        event_loop(game_copy, connection.handshake, &mut connection.interface).await?;
+        event_loop(game_copy, connection.handshake, export_dir, notifier, &mut connection.interface).await?;
 
         // Once the event loop exits, notify the io loop. Unwrap works here, as
         // the exit receiver should never close.
@@ -39,7 +68,7 @@ pub async fn run<E: Send + 'static>(mut connection: NewConnection<E>) -> Result<
     });
 
     let io_loop = tokio::task::spawn_blocking(|| {
-        io_loop(game, player_name_clone, sender, exit_recv);
+        io_loop(game, sender, exit_recv, config);
     });
 
     let (result, ()) = tokio::try_join!(event_loop, io_loop).unwrap();
@@ -51,32 +80,76 @@ pub async fn run<E: Send + 'static>(mut connection: NewConnection<E>) -> Result<
 async fn event_loop<E>(
     game: Arc<Mutex<ClientGame>>,
     player_handshake: Handshake,
+    export_dir: Option<PathBuf>,
+    notifier: Notifier,
     interface: &mut Interface<E>
 ) -> Result<(), E> {
+    let mut chat_log = ChatLog::new();
     while let Some(msg) = interface.recv().await {
-        match msg? {
+        let msg = msg?;
+        if let Some(cue) = Cue::of(&msg) {
+            notifier.cue(cue);
+        }
+        match msg {
             ServerMessage::Chat {
                 player_name,
-                message
+                message,
+                timestamp,
+                sequence,
+                channel,
             } => {
-                println!("CHAT: <{}> {}", player_name, message);
+                if let Some(chat) = chat_log.receive(&player_name, &message, timestamp, sequence, channel) {
+                    println!("CHAT: {chat}");
+                }
             }
-            ServerMessage::GameOver {reason, results } => {
+            ServerMessage::GameOver {reason, results, deal_seed, game_id } => {
                 println!("Game Over ({reason})!\nBelow are the results:");
-                for result in results.into_iter() {
+                for result in results.iter() {
                     println!("  {}", result);
                 }
+                let mut scores = game.lock().unwrap().scores().clone();
+                scores.finish(&results);
+                let graph = scores.render(GRAPH_WIDTH);
+                if !graph.is_empty() {
+                    println!("Net worth over the game:");
+                    graph.iter().for_each(|line| println!("{line}"));
+                }
+                let board = game.lock().unwrap().game().map(|game| *game.board());
+                if let (Some(dir), Some(board)) = (&export_dir, board) {
+                    match export_final_position(dir, &board, &results) {
+                        Ok(path) => println!("Saved the final board to {}.", path.display()),
+                        Err(err) => println!("Couldn't save the final board: {err}"),
+                    }
+                }
+                let checked = deal_seed.and_then(|seed| {
+                    game.lock().unwrap().check_deal(&seed).map(|result| (seed, result))
+                });
+                match checked {
+                    Some((seed, Ok(()))) => println!("The tiles were dealt as committed, from seed {seed}."),
+                    Some((_, Err(mismatch))) => println!("WARNING: the tiles were not dealt as committed: {mismatch}."),
+                    None => {},
+                }
+                if let Some(game_id) = game_id {
+                    println!("The game was recorded as {game_id}.");
+                }
             }
             ServerMessage::DeadTile { player_name: player, dead_tile } => {
+                game.lock().unwrap().trade_in_dead_tile(&player, dead_tile);
                 println!("{player} traded in dead tile {dead_tile}.");
             }
+            ServerMessage::StartingDraw { draws, random_seating } => {
+                for draw in draws.iter() {
+                    println!("{} drew {}.", draw.player_name, draw.tile);
+                }
+                if random_seating {
+                    println!("The seats were drawn at random, so the tiles don't decide the order of play.");
+                }
+            },
             ServerMessage::GameStart {
                 info,
                 initial_hand,
             } => {
-                let new_game = Game::start(&info);
-
-                println!("The game has begun! Each player starts with ${}", info.starting_cash);
+                println!("The game has begun! Each player starts with {}", info.starting_cash);
                 println!("The order of play is {}", info.play_order.join(", "));
                 println!("The board begins with {} on the board",
                 // TODO: optimize
@@ -88,10 +161,7 @@ async fn event_loop<E>(
                     );
                 }
 
-                game.lock().unwrap().start(
-                    new_game.into(),
-                    initial_hand.map(|h| h.into()),
-                )
+                game.lock().unwrap().start(&info, initial_hand)
             },
             ServerMessage::CompanyDefunct { defunct, results } => {
                 println!("Company {} has gone defunct!", defunct);
@@ -99,6 +169,12 @@ async fn event_loop<E>(
                     println!("  {}", result);
                 }
             },
+            ServerMessage::NoFounderShare { player_name, company } => {
+                println!("{player_name} receives no founder's share of {company}, as every share is already held.");
+            },
+            ServerMessage::ResolvingNext { player, defunct, into } => {
+                println!("{player} is resolving their stock in {defunct}, which is merging into {into}.");
+            },
             ServerMessage::Join { handshake } => {
                 print!("{} joined", handshake.player_name);
                 if handshake.spectating {
@@ -107,41 +183,151 @@ async fn event_loop<E>(
                     println!(".");
                 }
             }
-            ServerMessage::Quit { handshake } => {
-                println!("{} disconnected.", handshake.player_name)
+            ServerMessage::Quit { handshake, reason: Some(LeaveReason::Kicked) } => {
+                println!("{} was kicked.", handshake.player_name)
+            },
+            ServerMessage::Quit { handshake, reason: Some(_) } => {
+                println!("{} left.", handshake.player_name)
+            },
+            ServerMessage::Quit { handshake, reason: None } => {
+                println!("{} lost their connection.", handshake.player_name)
+            },
+            ServerMessage::Seated { handshake } => {
+                if handshake.spectating {
+                    println!("{} gave up their seat to spectate.", handshake.player_name);
+                } else {
+                    println!("{} took a seat at the table.", handshake.player_name);
+                }
+            },
+            ServerMessage::Trusted { handshake } => match handshake.capabilities.is_empty() {
+                true => println!("{} may no longer use admin commands.", handshake.player_name),
+                false => println!("{} may now use admin commands for: {}.",
+                    handshake.player_name, handshake.capabilities),
+            },
+            ServerMessage::Renamed { old_name, new_name } => {
+                println!("{old_name} is now known as {new_name}.");
+                game.lock().unwrap().rename(&old_name, &new_name);
             },
             ServerMessage::Shutdown => {
                 println!("Server is shutting down. Press Enter to exit.");
                 break;
             },
-            ServerMessage::PlayerMove { action } => {
+            ServerMessage::Substituted { substitution, state_hash } => {
+                println!("{} took over the seat of {}.", substitution.player_name, substitution.seat);
 
-                // Decide how to update the game board
-                game.lock().unwrap().update(&action);
+                let result = {
+                    let mut game = game.lock().unwrap();
+                    game.substitute(&substitution).and_then(|()| game.verify(state_hash))
+                };
+                if let Err(desync) = result {
+                    println!("Desync detected: {desync}. Requesting the game from the server.");
+                    interface.sender().send(ClientMessage::RequestResync).await.ok();
+                }
+            },
+            ServerMessage::PlayerMove { action, state_hash, .. } => {
 
                 println!("{}", action);
+
+                // Decide how to update the game board
+                let result = {
+                    let mut game = game.lock().unwrap();
+                    game.update(&action).and_then(|()| game.verify(state_hash))
+                };
+                if let Err(desync) = result {
+                    println!("Desync detected: {desync}. Requesting the game from the server.");
+                    interface.sender().send(ClientMessage::RequestResync).await.ok();
+                }
             },
-            ServerMessage::YourTurn { request: action } => {
-                print!("Your turn to ");
+            ServerMessage::YourTurn { request: action, number } => {
+                game.lock().unwrap().await_action(number);
+                print!("Turn {}: your turn to ", number.turn);
                 match action {
                     ActionRequest::PlayTile => {
-                        println!("place a tile!")
+                        println!("place a tile!");
+                        if let Some(hand) = game.lock().unwrap().hand() {
+                            println!("Your tiles are: {}.", list_tiles(hand));
+                        }
                     },
                     ActionRequest::BuyStock => {
                         println!("buy stock!")
                     },
                     ActionRequest::ResolveMergeStock {
                         defunct,
-                        into
-                    } => println!("resolve the merge of {} into {}!", defunct, into),
+                        into,
+                        shares,
+                    } => println!("resolve your {} shares in the merge of {} into {}!", shares, defunct, into),
                 }
             },
             ServerMessage::TileDraw { tile } => {
+                game.lock().unwrap().record_draw(tile);
                 println!("You drew tile {tile}.");
             }
-            ServerMessage::Invalid { reason } => {
+            ServerMessage::Invalid { reason, .. } => {
                 println!("Invalid message sent: {}", reason);
             },
+            ServerMessage::Resync { snapshot, hand, clock } => {
+                game.lock().unwrap().resync(snapshot, hand);
+                println!("Game resynced with the server.");
+                if let Some(clock) = clock {
+                    print_clock(&clock);
+                }
+            },
+            ServerMessage::Clock { clock } => print_clock(&clock),
+            ServerMessage::EndProposed { player_name } => {
+                println!("{player_name} proposes ending the game now. Type /vote yes or /vote no.");
+            },
+            ServerMessage::EndVoted { player_name, end_game: true } => {
+                println!("{player_name} voted to end the game.");
+            },
+            ServerMessage::EndVoted { player_name, end_game: false } => {
+                println!("{player_name} voted to play on.");
+            },
+            ServerMessage::EndRejected => {
+                println!("Too few players agreed, so the game goes on.");
+            },
+            ServerMessage::Autopilot { player_name, autopilot: true } => {
+                println!("{player_name} handed their seat to the server for now.");
+            },
+            ServerMessage::Autopilot { player_name, autopilot: false } => {
+                println!("{player_name} took their seat back from the server.");
+            },
+            ServerMessage::Away { player_name, missed_turns } => {
+                println!("{player_name} let their time run out {missed_turns} turns in a row, so the server plays their seat until they type /autopilot off.");
+            },
+            ServerMessage::StartScheduled { seconds_left, .. } => {
+                println!("The next game starts in {}.", time_left(seconds_left * 1000));
+            },
+            ServerMessage::Queued { player_name, queued: true, waiting, table_size } => {
+                println!("{player_name} is waiting for a game ({waiting} of {table_size} players).");
+            },
+            ServerMessage::Queued { player_name, queued: false, .. } => {
+                println!("{player_name} stopped waiting for a game.");
+            },
+            ServerMessage::KickVoted { player_name, target, votes, needed } => {
+                println!("{player_name} votes to kick {target} ({votes} of {needed} votes needed). Type /votekick {target} to agree.");
+            },
+            ServerMessage::KickFailed { target } => {
+                println!("Too few players agreed, so {target} stays.");
+            },
+            ServerMessage::Kicked => {
+                println!("You were kicked from the server.");
+            },
+            // This client sends no pings
+            ServerMessage::Pong { .. } => {},
+            ServerMessage::ServerError { message, recovery } => {
+                println!("The server failed ({message}), but is still running.");
+                match recovery {
+                    Recovery::Unaffected => {},
+                    Recovery::Restored { after_actions } => {
+                        println!("The game was restored from its autosave after {after_actions} actions.");
+                    },
+                    Recovery::Lost => println!("The game couldn't be recovered, and has been abandoned."),
+                }
+            },
+            ServerMessage::ActionLog { entries } => match save_action_log(&entries) {
+                Ok(path) => println!("Saved the log of {} actions to {}.", entries.len(), path.display()),
+                Err(err) => println!("Couldn't save the action log: {err}"),
+            },
         };
     }
     Ok(())
@@ -151,25 +337,37 @@ async fn event_loop<E>(
 /// command sender encounters a SendError.
 fn io_loop(
     game: Arc<Mutex<ClientGame>>,
-    player_name: Box<str>,
     command_sender: mpsc::Sender<ClientMessage>,
     mut exit_notifier: oneshot::Receiver<()>,
+    config: ClientConfig,
 ) {
     let stdin = std::io::stdin();
     let mut buffer: String = String::new();
+    let mut chat_channel = ChatChannel::Lobby;
 
     loop {
-        stdin.read_line(&mut buffer).unwrap();
+        // Nothing more can be read once the input is closed, so the client
+        // leaves, saying why
+        if let Ok(0) | Err(_) = stdin.read_line(&mut buffer) {
+            let msg = ClientMessage::Leaving { reason: LeaveReason::EndOfInput };
+            command_sender.blocking_send(msg).ok();
+            break;
+        }
 
         if let Ok(()) = exit_notifier.try_recv() {
             break;
         }
 
+        let game = game.lock().unwrap();
         let kind = parse_text(
             std::mem::replace(&mut buffer, String::new()),
-            &player_name,
-            &*game.lock().unwrap(),
+            game.player_name(),
+            &game,
+            &config,
+            &mut chat_channel,
         );
+        let forbidden = matches!(&kind, Ok(Some(msg)) if game.forbids(msg));
+        drop(game);
         let kind = match kind {
             Ok(v) => v,
             Err(err) => {
@@ -180,6 +378,10 @@ fn io_loop(
             },
         };
         if let Some(kind) = kind {
+            if forbidden {
+                println!("Invalid message: {}", InvalidMessageReason::ForbiddenByPolicy);
+                continue;
+            }
             let result = command_sender.blocking_send(kind);
             match result {
                 Ok(()) => {},
@@ -192,7 +394,9 @@ fn io_loop(
 fn parse_text(
     line: String,
     player_name: &str,
-    game: &ClientGame
+    game: &ClientGame,
+    config: &ClientConfig,
+    chat_channel: &mut ChatChannel,
 ) -> Result<Option<ClientMessage>, CommandParseErr> {
     enum CharDelim {
         Chat,
@@ -220,22 +424,45 @@ fn parse_text(
     let line = &line[1..(line.len()-1)];
 
     Ok(match command_delim {
-        Chat => Some(ClientMessage::Chat { message: line.to_owned().into_boxed_str() }),
+        Chat => Some(ClientMessage::Chat { message: line.to_owned().into_boxed_str(), channel: *chat_channel }),
         PlayerAction => {
+            if let Some(command) = parse_local_command(line) {
+                match command {
+                    LocalCommand::Channel(channel) => {
+                        *chat_channel = channel;
+                        println!("Chatting in the {channel} from now on.");
+                    },
+                    command => run_local_command(command, player_name, game, config),
+                }
+                return Ok(None);
+            }
+            if let Some(vote) = parse_vote_command(line) {
+                return Ok(Some(vote));
+            }
+            if let Some(message) = parse_connection_command(line) {
+                return Ok(Some(message));
+            }
             let action = parse_game_command(line)?;
-            Some(ClientMessage::TakingTurn(action))
+            Some(ClientMessage::TakingTurn { action, request_id: None, number: game.awaited() })
         },
         AdminCommand => {
+            if let Some(help) = admin_help(line) {
+                for line in help {
+                    println!("{line}");
+                }
+                return Ok(None);
+            }
             let command = parse_admin_command(line)?;
             Some(ClientMessage::Admin(command))
         },
         GameDisplay => {
-            if let Some(game_obj) = game.game() {
+            if game.game().is_some() {
                 match &*line {
-                    "board" => println!("{:?}", game_obj.board()),
+                    "board" => run_local_command(LocalCommand::Board, player_name, game, config),
+                    "standings" => run_local_command(LocalCommand::Standings, player_name, game, config),
                     "tiles" => {
-                        if let Some(tiles) = game.hand() {
-                            println!("{:?}", tiles)
+                        if let Some(hand) = game.hand() {
+                            println!("Your tiles are: {}.", list_tiles(hand));
                         }
                     }
                     _ => {}
@@ -247,3 +474,58 @@ fn parse_text(
         }
     })
 }
+
+/// Runs a command that is handled by the client itself.
+fn run_local_command(
+    command: LocalCommand,
+    player_name: &str,
+    game: &ClientGame,
+    config: &ClientConfig,
+) {
+    let Some(game_obj) = game.game() else {
+        println!("no game in progress");
+        return;
+    };
+    match command {
+        LocalCommand::Hint(difficulty) => {
+            if game.policy().no_hints {
+                println!("HINT: hints are turned off for this game.");
+                return;
+            }
+            let bot = Bot::new(difficulty).with_time_budget(config.hint_time_budget);
+            let hint = game.hand()
+                .and_then(|hand| bot.choose_action(game_obj, player_name, hand));
+            match hint {
+                Some(hint) => println!(
+                    "HINT: /{} - {}.", format_game_command(&hint.action), hint.rationale
+                ),
+                None => println!("HINT: there is nothing for you to do right now."),
+            }
+        },
+        LocalCommand::Board => {
+            for line in describe_board(game_obj) {
+                println!("{line}");
+            }
+        },
+        LocalCommand::Standings => {
+            for line in describe_standings(game_obj) {
+                println!("{line}");
+            }
+        },
+        // Switched by the caller, as it needs no game
+        LocalCommand::Channel(_) => {},
+    }
+}
+
+/// Prints the time left to the player whose clock is running.
+fn print_clock(clock: &ClockState) {
+    if let Some(player) = &clock.running {
+        let ms = clock.remaining_ms.get(player).copied().unwrap_or_default();
+        println!("{player} has {} left on the clock.", time_left(ms));
+    }
+}
+
+/// Lists the tiles of a hand, separated by commas.
+fn list_tiles(hand: &Hand) -> String {
+    hand.iter().map(Tile::to_string).collect::<Vec<_>>().join(", ")
+}
@@ -1,9 +1,22 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::client::GameLog;
+
 use super::terminal::{TermPanel, OverflowMode};
 
+/// How long each paced message waits after the one before it.
+const LINE_DELAY: Duration = Duration::from_millis(400);
+
 #[derive(Debug)]
 pub(super) struct ChatPanel {
     panel: Option<TermPanel>,
     buffer: Vec<Box<str>>,
+    /// Messages waiting to be shown one at a time, and when the next is due.
+    pending: VecDeque<Box<str>>,
+    next_line: Instant,
+    /// The file every message is also written to, if the player keeps one.
+    log: Option<GameLog>,
 }
 
 impl ChatPanel {
@@ -13,15 +26,69 @@ impl ChatPanel {
         Self {
             panel: None,
             buffer: Vec::new(),
+            pending: VecDeque::new(),
+            next_line: Instant::now(),
+            log: None,
         }
     }
 
-    /// Adds a message to the chat panel and re-renders the panel.
+    /// Writes every message from now on to `log` as well.
+    pub fn keep_log(&mut self, log: GameLog) {
+        self.log = Some(log);
+    }
+
+    /// Adds a message to the chat panel and re-renders the panel. If paced
+    /// messages are still waiting, the message waits behind them.
     pub fn add_message(&mut self, msg: Box<str>) {
+        if let Some(log) = &mut self.log {
+            log.record(&msg);
+        }
+        if !self.pending.is_empty() {
+            self.pending.push_back(msg);
+            return;
+        }
         self.buffer.push(msg);
         self.render();
     }
 
+    /// Adds messages that are shown one at a time, with a short delay before
+    /// each, so that a cascade such as the payouts of a merger can be
+    /// followed.
+    pub fn add_paced(&mut self, msgs: impl IntoIterator<Item = Box<str>>) {
+        if self.pending.is_empty() {
+            self.next_line = Instant::now() + LINE_DELAY;
+        }
+        for msg in msgs {
+            if let Some(log) = &mut self.log {
+                log.record(&msg);
+            }
+            self.pending.push_back(msg);
+        }
+    }
+
+    /// Gets when the next paced message is due, if one is waiting.
+    pub fn next_line(&self) -> Option<Instant> {
+        (!self.pending.is_empty()).then_some(self.next_line)
+    }
+
+    /// Shows the next paced message.
+    pub fn reveal_line(&mut self) {
+        if let Some(msg) = self.pending.pop_front() {
+            self.buffer.push(msg);
+            self.next_line = Instant::now() + LINE_DELAY;
+            self.render();
+        }
+    }
+
+    /// Shows every message still waiting at once. Returns `false` if none
+    /// were.
+    pub fn flush(&mut self) -> bool {
+        if self.pending.is_empty() { return false; }
+        self.buffer.extend(self.pending.drain(..));
+        self.render();
+        true
+    }
+
     pub fn render(&mut self) {
 
         if let Some(panel) = &mut self.panel {
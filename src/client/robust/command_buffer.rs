@@ -3,6 +3,7 @@ use std::fmt;
 use termion::event::Key;
 
 use super::terminal::{TermPanel, NiceFgColor, OverflowMode, TermWriteError};
+use crate::client::normalize;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(super) enum BufferMode {
@@ -12,6 +13,8 @@ pub(super) enum BufferMode {
     Command,
     /// The user is typing an admin command.
     Admin,
+    /// The host is typing a console command.
+    Console,
 }
 
 impl BufferMode {
@@ -20,6 +23,7 @@ impl BufferMode {
             BufferMode::Chat => '>',
             BufferMode::Command => '/',
             BufferMode::Admin => '#',
+            BufferMode::Console => '!',
         }
     }
 }
@@ -31,6 +35,7 @@ impl termion::color::Color for BufferMode {
             BufferMode::Chat => White.write_fg(f),
             BufferMode::Command => Cyan.write_fg(f),
             BufferMode::Admin => Yellow.write_fg(f),
+            BufferMode::Console => Magenta.write_fg(f),
         }
     }
     
@@ -40,6 +45,7 @@ impl termion::color::Color for BufferMode {
             BufferMode::Chat => White.write_bg(f),
             BufferMode::Command => Blue.write_bg(f),
             BufferMode::Admin => Yellow.write_bg(f),
+            BufferMode::Console => Magenta.write_bg(f),
         }
     }
 }
@@ -51,6 +57,7 @@ impl NiceFgColor for BufferMode {
             BufferMode::Chat => White.write_nice_fg(f),
             BufferMode::Command => Blue.write_nice_fg(f),
             BufferMode::Admin => Yellow.write_nice_fg(f),
+            BufferMode::Console => Magenta.write_nice_fg(f),
         }
     }
 }
@@ -119,6 +126,12 @@ impl CommandBuffer {
             Key::Char('#') if self.buffer_mode().is_none() => {
                 self.set_buffer_mode(BufferMode::Admin);
             },
+            Key::Char('!') if self.buffer_mode().is_none() => {
+                self.set_buffer_mode(BufferMode::Console);
+            },
+            Key::Char('\t') if self.buffer_mode() == Some(BufferMode::Command) => {
+                self.complete();
+            },
             Key::Char(char) => {
                 // Ensure the character is in ASCII range
                 match char {
@@ -197,6 +210,21 @@ impl CommandBuffer {
         Ok(())
     }
 
+    /// Completes the last word of a game command, if the cursor is at its
+    /// end and only one word could go there.
+    fn complete(&mut self) {
+        if self.cursor_pos != self.buffer.len() { return; }
+        let Some(completed) = normalize::complete(&self.buffer) else { return };
+        if completed.len() >= self.buffer.capacity() { return; }
+
+        // The buffer is refilled in place, keeping the capacity that sizes it
+        // to its panel
+        self.buffer.clear();
+        self.buffer.push_str(&completed);
+        self.cursor_pos = self.buffer.len();
+        self.render();
+    }
+
     /// Removes a char from the buffer. Returns true if a character was
     /// successfully deleted.
     pub fn delete_char(&mut self) {
@@ -0,0 +1,53 @@
+use std::collections::VecDeque;
+
+use super::terminal::{TermPanel, OverflowMode};
+
+/// The number of errors kept, and the height of the panel that shows them.
+pub(super) const HISTORY_LEN: u16 = 3;
+
+/// Keeps the latest errors in view once the command buffer has moved on, the
+/// newest at the top.
+#[derive(Debug)]
+pub(super) struct ErrorPanel {
+    panel: Option<TermPanel>,
+    errors: VecDeque<Box<str>>,
+}
+
+impl ErrorPanel {
+    /// Constructs a new [`ErrorPanel`] with no panel. To begin rendering, call
+    /// the [`resize`] function.
+    pub fn new() -> Self {
+        Self {
+            panel: None,
+            errors: VecDeque::new(),
+        }
+    }
+
+    /// Adds an error to the history, dropping the oldest if it is full, and
+    /// re-renders the panel. The error must already be printable, as from
+    /// [`TermPanel::printable`]. Each error is kept to one line.
+    pub fn add_error(&mut self, error: &str) {
+        if self.errors.len() == HISTORY_LEN as usize {
+            self.errors.pop_back();
+        }
+        self.errors.push_front(error.replace('\n', " ").into_boxed_str());
+        self.render();
+    }
+
+    pub fn render(&mut self) {
+        let Some(panel) = &mut self.panel else { return };
+        panel.clear();
+        panel.write(OverflowMode::Truncate, |writer| {
+            for error in &self.errors {
+                // Printable errors cannot fail to be written
+                writer.write_fg_colored(&**error, termion::color::Red).ok();
+                writer.new_line();
+            }
+        });
+    }
+
+    pub fn resize(&mut self, new_panel: TermPanel) {
+        self.panel = Some(new_panel);
+        self.render();
+    }
+}
@@ -1,21 +1,28 @@
-use crate::client::ClientGame;
-use crate::game::kernel::{Game, GameDisambiguation};
+use std::time::Instant;
+
+use crate::client::{ClientGame, ClientDesyncError};
+use crate::client::lang::{tr, Lang, Text};
+use crate::game::kernel::{GameDisambiguation, GameSnapshot};
 use crate::game::{messages::*, CompanyMap};
-use crate::game::tile::{Tile, FullHand};
+use crate::game::tile::{Tile, FullHand, Hand};
 use crate::server::ConnectionManager;
 
 use self::action_panel::{ActionPanel, ActionPanelRequest};
 use self::board_panel::BoardPanel;
+use self::connections_panel::ConnectionsPanel;
 
 use super::terminal::TermPanel;
 
 mod action_panel;
 mod board_panel;
+mod connections_panel;
 
 /// Keeps track of the state of the game and the players who are connected.
 pub struct GamePanels<'c> {
     action_panel: ActionPanel,
     board_panel: BoardPanel<'c>,
+    connections_panel: ConnectionsPanel,
+    lang: Lang,
 }
 
 impl<'c> GamePanels<'c> {
@@ -24,10 +31,13 @@ impl<'c> GamePanels<'c> {
     pub fn new(
         game: ClientGame,
         connections: &'c mut ConnectionManager,
+        lang: Lang,
     ) -> Self {
         Self {
-            action_panel: ActionPanel::new(),
+            action_panel: ActionPanel::new(lang),
             board_panel: BoardPanel::new(game, connections),
+            connections_panel: ConnectionsPanel::new(),
+            lang,
         }
     }
 
@@ -41,29 +51,105 @@ impl<'c> GamePanels<'c> {
         info: &GameStart,
         player_tiles: Option<FullHand>,
     ) {
-        let game = Game::start(info).into();
-        self.board_panel.game.start(game, player_tiles.map(|h| h.into()));
+        self.board_panel.game.start(info, player_tiles);
+        self.board_panel.clock = None;
+        self.board_panel.skip_animation();
         self.board_panel.render();
+        self.render_connections();
     }
 
     /// Forcibly ends this game and updates the panel correspondingly. If there
     /// was no game in progress, this function does nothing and returns `false`.
     pub fn end_game(&mut self) {
-        self.board_panel.game.end();
+        // A game that ended on its last move is already over
+        if self.board_panel.game.game().is_some() {
+            self.board_panel.game.end();
+        }
+        self.board_panel.clock = None;
         self.cancel_action();
         self.board_panel.render();
+        self.render_connections();
     }
 
-    /// Accepts a player action and re-renders the board panel.
-    /// 
-    /// # Panics
-    /// 
-    /// If there is no game in progress, this function panics.
+    /// Accepts a player action and plays out its change to the board. The
+    /// game is then checked against the `state_hash` sent by the server.
     pub fn update_game(&mut self,
-        action: &TaggedPlayerAction
-    ){
-        self.board_panel.game.update(action);
+        action: &TaggedPlayerAction,
+        state_hash: u64,
+    ) -> Result<(), ClientDesyncError> {
+        let game = &mut self.board_panel.game;
+        let before = game.game().map(|game| *game.board());
+        let result = game.update(action).and_then(|()| game.verify(state_hash));
+        if let (Ok(()), Some(before)) = (&result, before) {
+            let placed = match &action.action {
+                PlayerAction::PlayTile { placement } => Some(placement.tile),
+                _ => None,
+            };
+            self.board_panel.animate(before, placed);
+        }
+        self.board_panel.render();
+        result
+    }
+
+    /// Hands a seat over to another player, checking the game against the
+    /// server's afterward, and re-renders the board panel.
+    pub fn substitute(&mut self,
+        substitution: &Substitution,
+        state_hash: u64,
+    ) -> Result<(), ClientDesyncError> {
+        let game = &mut self.board_panel.game;
+        let result = game.substitute(substitution).and_then(|()| game.verify(state_hash));
+        self.board_panel.render();
+        self.render_connections();
+        result
+    }
+
+    /// Replaces the game with the state sent by the server, and re-renders
+    /// the board panel.
+    pub fn resync_game(&mut self,
+        snapshot: Option<Box<GameSnapshot>>,
+        hand: Option<Hand>,
+        clock: Option<ClockState>,
+    ) {
+        self.board_panel.game.resync(snapshot, hand);
+        self.board_panel.clock = clock.map(|clock| (clock, Instant::now()));
+        self.board_panel.skip_animation();
+        self.cancel_action();
         self.board_panel.render();
+        self.render_connections();
+    }
+
+    /// Updates the time left to each player, and re-renders the board panel.
+    pub fn set_clock(&mut self, clock: ClockState) {
+        self.board_panel.clock = Some((clock, Instant::now()));
+        self.board_panel.render();
+    }
+
+    /// Gets when the next frame of a move being played out on the board is
+    /// due, if one is.
+    pub fn next_frame(&self) -> Option<Instant> {
+        self.board_panel.next_frame()
+    }
+
+    /// Draws the next frame of the move being played out on the board.
+    pub fn advance_animation(&mut self) {
+        self.board_panel.advance_animation();
+    }
+
+    /// Draws the board as it is, cutting short any move being played out.
+    /// Returns `false` if none was.
+    pub fn skip_animation(&mut self) -> bool {
+        let skipped = self.board_panel.skip_animation();
+        if skipped {
+            self.board_panel.render();
+        }
+        skipped
+    }
+
+    /// Remembers the number of the action the server has asked the player
+    /// for, which the action is sent with.
+    pub fn await_action(&mut self, number: ActionNumber) {
+        self.board_panel.game.await_action(number);
     }
 
     /// Requests an action from the player.
@@ -78,7 +164,9 @@ impl<'c> GamePanels<'c> {
                     available_companies,
                 }
             },
-            ActionRequest::ResolveMergeStock { defunct, into } => todo!(),
+            ActionRequest::ResolveMergeStock { shares, .. } => {
+                ActionPanelRequest::ResolveMergeStock { count: shares }
+            },
         };
 
         self.action_panel.request_action(request, self.board_panel.game.hand())
@@ -95,6 +183,20 @@ impl<'c> GamePanels<'c> {
         let hand = self.board_panel.game.hand_mut()
             .expect("Called draw_tile on a ClientGame not faciliating a game");
         hand.insert_tile(new_tile).unwrap();
+        self.board_panel.game.record_draw(new_tile);
+        self.render();
+    }
+
+    /// Removes a dead tile from the hand, if this client traded it in.
+    pub fn trade_in_dead_tile(&mut self, player_name: &str, tile: Tile) {
+        self.board_panel.game.trade_in_dead_tile(player_name, tile);
+        self.render();
+    }
+
+    /// Follows a connection changing its name, which may be this client's.
+    pub fn rename(&mut self, old_name: &str, new_name: &str) {
+        self.board_panel.game.rename(old_name, new_name);
+        self.board_panel.connections.rename(old_name, new_name.into()).ok();
         self.render();
     }
 
@@ -109,6 +211,13 @@ impl<'c> GamePanels<'c> {
     {
         op(&mut self.board_panel.connections);
         self.board_panel.render();
+        self.render_connections();
+    }
+
+    /// Re-renders the list of connections, which also shows who has left the
+    /// game in progress.
+    fn render_connections(&mut self) {
+        self.connections_panel.render(self.board_panel.connections, &self.board_panel.game);
     }
 
     /// Processes a key sent to this panel. This key may simply update the state
@@ -166,7 +275,7 @@ impl<'c> GamePanels<'c> {
                             match count {
                                 0 => {
                                     self.request_action(ActionRequest::PlayTile);
-                                    Some(Err("No companies available.".to_owned()))
+                                    Some(Err(tr(self.lang, Text::NoCompaniesAvailable, &[])))
                                 },
                                 1 => Some(Ok(PlayerAction::PlayTile {
                                     placement: TilePlacement {
@@ -198,7 +307,23 @@ impl<'c> GamePanels<'c> {
                 }
             },
             Some(PlayerAction::ResolveMergeStock { selling, trading, keeping }) => {
-                todo!();
+
+                // If a merge is being resolved, the game should be waiting on
+                // a shareholder to resolve their stock.
+                let game = self.board_panel.game.game().unwrap();
+                let request = game.needed_action();
+                let game = match game.clone().disambiguate() {
+                    GameDisambiguation::ResolvingMerge(g) => g,
+                    _ => panic!(),
+                };
+
+                match game.check_merge_resolution(selling, keeping, trading) {
+                    Ok(_) => Some(Ok(PlayerAction::ResolveMergeStock { selling, trading, keeping })),
+                    Err(why) => {
+                        self.request_action(request);
+                        Some(Err(why.to_string()))
+                    },
+                }
             }
             None => None,
         };
@@ -211,10 +336,13 @@ impl<'c> GamePanels<'c> {
     pub fn render(&mut self) {
         self.board_panel.render();
         self.action_panel.render(self.board_panel.game.hand());
+        self.render_connections();
     }
 
     /// Resizes the panel and re-renders.
-    pub fn resize(&mut self, new_panel: TermPanel) {
+    pub fn resize(&mut self, mut new_panel: TermPanel) {
+        self.connections_panel.resize(&mut new_panel);
+        self.render_connections();
 
         // Decide which way to split the panels
         let (board_display, action_display) = if new_panel.dim().size.0 < new_panel.dim().size.1 {
@@ -1,3 +1,4 @@
+use crate::client::lang::{tr, Lang, Text};
 use crate::client::robust::terminal::{OverflowMode, NiceFgColor, TermWriter};
 use crate::client::robust::terminal::TermPanel;
 use crate::game::{messages::*, Company, CompanyMap};
@@ -9,16 +10,18 @@ pub struct ActionPanel {
     action: Option<ActionState>,
     tile_layout: TileLayout,
     panel: Option<TermPanel>,
+    lang: Lang,
 }
 
 impl ActionPanel {
 
     /// Creates a new [`ActionPanel`] of size zero. It must be resized later.
-    pub fn new() -> Self {
+    pub fn new(lang: Lang) -> Self {
         Self {
             panel: None,
             action: None,
             tile_layout: TileLayout::Column,
+            lang,
         }
     }
 
@@ -192,7 +195,7 @@ impl ActionPanel {
                         if let Some(ref mut panel) = self.panel {
                             panel.write(OverflowMode::Truncate, |writer| {
                                 let hand = hand.unwrap(); // The hand should be [`Some`] if we're working with tiles.
-                                write_tiles(writer, hand, Some(new_offset as usize), self.tile_layout);
+                                write_tiles(writer, hand, Some(new_offset as usize), self.tile_layout, self.lang);
                             })
                         }
                         
@@ -286,25 +289,26 @@ impl ActionPanel {
         if let Some(ref mut panel) = self.panel {
             use ActionState::*;
 
+            let lang = self.lang;
             panel.clear();
             panel.write(OverflowMode::Truncate, |writer| {
                 if let Some(hand) = hand {
 
                     match &self.action {
                         None => {
-                            write_tiles(writer, hand, None, self.tile_layout);
+                            write_tiles(writer, hand, None, self.tile_layout, lang);
                         },
                         Some(ChoosingTile(tile_index)) => {
-                            write_tiles(writer, hand, Some(*tile_index), self.tile_layout);
+                            write_tiles(writer, hand, Some(*tile_index), self.tile_layout, lang);
                         },
                         Some(BuyingStock { purchases: _, index, chooser }) => {
-                            write_tiles(writer, hand, None, self.tile_layout);
+                            write_tiles(writer, hand, None, self.tile_layout, lang);
         
                             writer.set_overflow_mode(OverflowMode::Wrap);
         
                             writer.new_line();
                             writer.new_line();
-                            writer.write_str("Choose which stock to buy").unwrap();
+                            writer.write_str(&tr(lang, Text::ChooseStock, &[])).unwrap();
                             writer.new_line();
                             writer.new_line();
         
@@ -316,16 +320,16 @@ impl ActionPanel {
                             );
                         },
                         Some(FoundingCompany { tile_placed, chooser }) => {
-                            write_tiles(writer, hand, None, self.tile_layout);
+                            write_tiles(writer, hand, None, self.tile_layout, lang);
         
-                            writer.write_str("Found which company?").unwrap();
+                            writer.write_str(&tr(lang, Text::FoundWhichCompany, &[])).unwrap();
 
                             write_company_chooser(writer, chooser.selected_company())
                         },
                         Some(Merging { tile_placed: _, chooser}) => {
-                            write_tiles(writer, hand, None, self.tile_layout);
+                            write_tiles(writer, hand, None, self.tile_layout, lang);
         
-                            writer.write_str("Choose the company to remain on the board.").unwrap();
+                            writer.write_str(&tr(lang, Text::ChooseSurvivor, &[])).unwrap();
 
                             write_company_chooser(writer, chooser.selected_company())
                         },
@@ -352,6 +356,7 @@ fn write_tiles(
     hand: &Hand,
     tile_index: Option<usize>,
     tile_layout: TileLayout,
+    lang: Lang,
 ) {
     let mut hand_iter = hand.iter();
 
@@ -396,7 +401,7 @@ fn write_tiles(
         writer.set_overflow_mode(OverflowMode::Wrap);
         writer.new_line();
         writer.write_fg_colored(
-            "Select a tile to play\n", termion::color::LightWhite
+            &*(tr(lang, Text::SelectTile, &[]) + "\n"), termion::color::LightWhite
         ).unwrap();
         writer.set_overflow_mode(mode);
     }
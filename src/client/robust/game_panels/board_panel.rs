@@ -1,12 +1,36 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
 use crate::client::robust::terminal::{TermPanel, OverflowMode};
-use crate::client::ClientGame;
+use crate::client::{remaining_ms, time_left, ClientGame};
+use crate::game::board::{Board, BoardScale};
+use crate::game::kernel::GameDisambiguation;
+use crate::game::messages::ClockState;
+use crate::game::tile::Tile;
 use crate::server::ConnectionManager;
 
+/// Lines kept free below the board for the turn, the players and the
+/// progress of a merge.
+const STATUS_LINES: u16 = 11;
+/// How long each frame of a change to the board is shown.
+const FRAME: Duration = Duration::from_millis(120);
+/// How many times a placed tile flashes before the chain it joins spreads.
+const FLASHES: usize = 2;
+
 pub struct BoardPanel<'c> {
     panel: Option<TermPanel>,
+    /// How large the board is drawn, chosen to fit the panel.
+    scale: BoardScale,
     /// Stores the game in progress.
     pub game: ClientGame,
     pub connections: &'c mut ConnectionManager,
+    /// The clock last sent by the server and when it arrived, if the game is
+    /// played on the clock.
+    pub clock: Option<(ClockState, Instant)>,
+    /// The boards still to be drawn in place of the game's own while a change
+    /// to it is played out, and when the next one is due.
+    animation: VecDeque<Board>,
+    next_frame: Instant,
 }
 
 impl<'c> BoardPanel<'c> {
@@ -17,26 +41,131 @@ impl<'c> BoardPanel<'c> {
     ) -> Self {
         Self {
             panel: None,
+            scale: BoardScale::Small,
             game,
             connections,
+            clock: None,
+            animation: VecDeque::new(),
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// Plays out the change from `before` to the game's board. The tile
+    /// `placed`, if any, flashes, then whatever changed spreads out from it,
+    /// or from the tile that set off a merger.
+    pub fn animate(&mut self, before: Board, placed: Option<Tile>) {
+        let Some(game) = self.game.game() else { return };
+        let Some(origin) = placed.or(before.merger_tile()) else { return };
+
+        let boards = game.board().spreading_from(&before, origin);
+        self.animation.clear();
+        if placed.is_some() {
+            for _ in 0..FLASHES {
+                self.animation.push_back(boards[0]);
+                self.animation.push_back(before);
+            }
         }
+        // The last board is the game's own, drawn once the animation is over
+        self.animation.extend(&boards[..boards.len() - 1]);
+        self.next_frame = Instant::now() + FRAME;
+    }
+
+    /// Gets when the next frame of the change being played out is due, if
+    /// one is.
+    pub fn next_frame(&self) -> Option<Instant> {
+        (!self.animation.is_empty()).then_some(self.next_frame)
+    }
+
+    /// Moves on to the next frame of the change being played out.
+    pub fn advance_animation(&mut self) {
+        self.animation.pop_front();
+        self.next_frame = Instant::now() + FRAME;
+        self.render();
+    }
+
+    /// Stops playing out a change, so that the game's board is drawn as it
+    /// is. Returns `false` if no change was being played out.
+    pub fn skip_animation(&mut self) -> bool {
+        let playing = !self.animation.is_empty();
+        self.animation.clear();
+        playing
     }
 
     pub fn render(&mut self) {
         if let Some(ref mut panel) = self.panel {
             if let Some(game) = self.game.game() {
+                let board = self.animation.front().unwrap_or(game.board());
 
                 // Render the board
                 panel.clear();
                 panel.write(OverflowMode::Wrap, |writer| {
                     // Print the board
-                    writer.write(game.board()).unwrap();
+                    writer.write(&board.scaled(self.scale)).unwrap();
+
+                    // During a merge, the shareholders resolve their stock
+                    // before the turn moves on
+                    let merge = match game.clone().disambiguate() {
+                        GameDisambiguation::ResolvingMerge(game) => Some(game),
+                        _ => None,
+                    };
+                    let resolving = merge.as_ref().map(|merge| merge.resolving_player());
+                    let active = game.active_player();
+                    let next = &*game.players()[active].next_player;
 
-                    // Print the players
-                    game.players().iter()
-                    .for_each(|(player, data)| {
-                        writer.write_str(&format!("{} ${}\n", player, data.money)).unwrap();
-                    });
+                    writer.write_fg_colored(
+                        &*format!("TURN {}", game.next_action_number().turn),
+                        termion::color::LightWhite
+                    ).unwrap();
+                    writer.new_line();
+
+                    // Print the players in order of play, highlighting whose
+                    // turn it is
+                    for player in game.play_order() {
+                        let mut line = format!("{} {}", player, game.players()[player].money);
+                        if let Some((clock, received)) = &self.clock {
+                            let ms = remaining_ms(clock, player, received.elapsed());
+                            line += &format!(" [{}]", time_left(ms));
+                        }
+                        if player == active {
+                            writer.write_fg_colored(&*format!("> {line}"), termion::color::LightYellow)
+                        } else {
+                            writer.write_str(&format!("  {line}"))
+                        }.unwrap();
+
+                        if resolving == Some(player) {
+                            writer.write_str(" (resolving)").unwrap();
+                        } else if player == next && player != active {
+                            writer.write_str(" (next)").unwrap();
+                        }
+                        writer.new_line();
+                    }
+
+                    // Print the progress of the merge, one shareholder at a
+                    // time
+                    if let Some(merge) = &merge {
+                        let (defunct, into) = merge.current_merge();
+                        writer.new_line();
+                        writer.write_fg_colored(
+                            &*format!("MERGING {defunct} INTO {into}"),
+                            termion::color::LightWhite
+                        ).unwrap();
+
+                        let mut done = true;
+                        for result in merge.principle_shareholders() {
+                            writer.new_line();
+                            let marker = if Some(&*result.player) == resolving {
+                                done = false;
+                                ">"
+                            } else if done {
+                                "+"
+                            } else {
+                                " "
+                            };
+                            writer.write_str(&format!(
+                                "{marker} {} ({} shares)", result.player, result.shares
+                            )).unwrap();
+                        }
+                    }
                 });
 
             } else {
@@ -65,8 +194,10 @@ impl<'c> BoardPanel<'c> {
         }
     }
 
-    /// Resizes and renders the panel.
+    /// Resizes and renders the panel, drawing the board as large as will fit.
     pub fn resize(&mut self, new_panel: TermPanel) {
+        let (width, height) = new_panel.dim().size;
+        self.scale = BoardScale::fitting((width, height.saturating_sub(STATUS_LINES)));
         self.panel = Some(new_panel);
         self.render();
     }
@@ -0,0 +1,80 @@
+use crate::client::robust::terminal::{TermPanel, OverflowMode};
+use crate::client::ClientGame;
+use crate::server::ConnectionManager;
+
+/// The number of lines the connections are listed across, below a border.
+const HEIGHT: u16 = 2;
+
+/// Marks a connection that may give admin commands.
+const ADMIN: char = '@';
+/// Marks a connection that is watching rather than playing.
+const SPECTATOR: char = '~';
+/// Marks a seat in the game in progress whose player has left, which is kept
+/// until they rejoin or someone takes it over.
+const DISCONNECTED: char = '!';
+
+/// Lists every connection on a line or two, the players before the
+/// spectators, each marked with what it is.
+pub struct ConnectionsPanel {
+    panel: Option<TermPanel>,
+}
+
+impl ConnectionsPanel {
+    /// Creates a new connections panel of size zero. It must be resized later.
+    pub fn new() -> Self {
+        Self { panel: None }
+    }
+
+    pub fn render(&mut self, connections: &ConnectionManager, game: &ClientGame) {
+        let Some(panel) = &mut self.panel else { return };
+
+        let mut handshakes: Vec<_> = connections.handshakes().collect();
+        handshakes.sort_by(|a, b| (a.spectating, &a.player_name).cmp(&(b.spectating, &b.player_name)));
+
+        // The seats of players who left are kept in the game, so they can be
+        // found there
+        let mut departed: Vec<_> = game.game()
+            .map(|game| game.play_order().into_iter()
+                .filter(|seat| connections.get_handshake(seat).is_none())
+                .collect())
+            .unwrap_or_default();
+        departed.sort();
+
+        panel.clear();
+        panel.write(OverflowMode::Wrap, |writer| {
+            for handshake in &handshakes {
+                let mut entry = String::new();
+                if !handshake.capabilities.is_empty() {
+                    entry.push(ADMIN);
+                }
+                if handshake.spectating {
+                    entry.push(SPECTATOR);
+                }
+                entry.push_str(&handshake.player_name);
+                match handshake.spectating {
+                    true => writer.write_fg_colored(&*entry, termion::color::Blue),
+                    false => writer.write_fg_colored(&*entry, termion::color::LightBlue),
+                }.ok();
+                writer.write_str("  ").ok();
+            }
+            for seat in departed {
+                writer.write_fg_colored(&*format!("{DISCONNECTED}{seat}"), termion::color::LightBlack).ok();
+                writer.write_str("  ").ok();
+            }
+        });
+    }
+
+    /// Takes the bottom lines of `panel` for the connections, leaving it
+    /// with the rest. The border above them says what each mark means.
+    pub fn resize(&mut self, panel: &mut TermPanel) {
+        let (_, mut connections) = panel.shave_vert(0, HEIGHT + 1).unwrap();
+        let (mut border, _) = connections.shave_vert(1, 0).unwrap();
+        border.write(OverflowMode::Truncate, |writer| {
+            writer.write_str(&format!(
+                "- CONNECTIONS ({ADMIN} admin, {SPECTATOR} spectating, {DISCONNECTED} left) "
+            )).ok();
+            while writer.can_write_char() { writer.write_char('-').ok(); }
+        });
+        self.panel = Some(connections);
+    }
+}
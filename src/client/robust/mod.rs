@@ -1,73 +1,107 @@
 use std::io;
+use std::time::Instant;
 
-use crate::game::{messages::*, CompanyMap};
-use crate::server::{ConnectionManager, NewConnection};
+use tokio::sync::mpsc;
+
+use crate::game::{messages::*, CompanyMap, Money};
+use crate::game::kernel::GameDisambiguation;
+use crate::server::{ConnectionManager, NewConnection, ServerInfo};
 
 use self::game_panels::GamePanels;
 use self::chat_panel::ChatPanel;
 use self::command_buffer::CommandBuffer;
-use self::terminal::{TermPanel, OverflowMode, TermWriteError};
+use self::error_panel::ErrorPanel;
+use self::terminal::{TermPanel, OverflowMode};
+use self::toast_panel::ToastPanel;
 
 /// The chat panel is responsible for printing chat and in-game messages.
 mod chat_panel;
 /// The command buffer manages the user typing and sending commands.
 mod command_buffer;
+/// The error panel keeps the latest errors in view after they are written over.
+mod error_panel;
 /// This shows both the board and the menu the player uses to input moves.
 mod game_panels;
 pub mod terminal;
 mod panels;
+/// The toast panel shows important events over a corner for a few seconds.
+mod toast_panel;
 
 use termion::event::Key;
 
-use super::{CommandParseErr, parse_game_command, parse_admin_command, ClientGame};
+use super::{CommandParseErr, parse_game_command, parse_admin_command, admin_help, ClientGame, ClientConfig, GameLog};
+use super::{parse_console_command, console_help};
+use super::{LocalCommand, parse_local_command, format_game_command, describe_board, describe_standings, ChatLog, Receipts};
+use super::{export_final_position, parse_connection_command, parse_vote_command, save_action_log, time_left, ConnectionQuality, LatencyMeter};
+use crate::game::bot::Bot;
+use crate::game::hint::Hint;
+use crate::server::console::ConsoleError;
+use super::score_graph::GRAPH_WIDTH;
+use super::tutorial;
+use super::lang::{tr, Text};
+use super::notifier::Cue;
 
 /// Starts the client for a [`FallibleInterface`] that throws I/O errors.
 #[inline]
-pub async fn run_io(connection: NewConnection<io::Error>) -> io::Result<()> {
-    match run(connection).await {
+pub async fn run_io(connection: NewConnection<io::Error>, config: ClientConfig) -> io::Result<()> {
+    match run(connection, config).await {
         Ok(result) => result,
         Err(err) => Err(err),
     }
 }
     
 /// Starts the client for the specified player interface.
-pub async fn run<E>(mut connection: NewConnection<E>) -> io::Result<Result<(), E>> {
+pub async fn run<E>(mut connection: NewConnection<E>, config: ClientConfig) -> io::Result<Result<(), E>> {
 
     dbg!(&connection.server_state);
 
-    dbg!("terminal making");
-
     let (term, mut keys) = TermPanel::new()?;
 
-    dbg!("terminal made");
-
-    std::thread::sleep(std::time::Duration::from_secs(1));
-
     // Print the panels
     //print_panels(&mut term, (100, 32)).unwrap();
 
     // Create the game
-    let game = ClientGame::new(
+    let (game, catch_up_error) = ClientGame::new(
         connection.handshake,
-        connection.server_state.game_history
+        connection.server_state.game_history,
+        connection.server_state.hand,
     );
+    let info = connection.server_state.info.take();
     let connections = &mut connection.server_state.connections;
 
-    let mut panels = ClientPanels::new(term, game, connections)?;
+    let lang = config.lang;
+    let (events, mut local_events) = mpsc::unbounded_channel();
+    let mut panels = ClientPanels::new(term, game, connections, config, events)?;
+    if let Some(info) = info {
+        panels.welcome(&info);
+    }
+    if let Some(error) = catch_up_error {
+        let msg = tr(lang, Text::CatchUpFailed, &[&error]);
+        panels.chat_panel.add_message(msg.into_boxed_str());
+    }
 
     panels.rerender_panels();
 
+    let mut ping_timer = tokio::time::interval(LatencyMeter::INTERVAL);
     loop {
         let msg = tokio::select! {
             key = keys.recv() => {
-                let key = match key {
-                    Some(v) => v,
-                    None => break,
+                let msg = match key {
+                    None => Err(LeaveReason::EndOfInput),
+                    // A key cuts short whatever is being played out, and
+                    // does nothing else
+                    Some(_) if panels.skip_animations() => Ok(None),
+                    Some(key) => panels.process_key(key)?.ok_or(LeaveReason::Quit),
                 };
-
-                match panels.process_key(key)? {
-                    Some(option) => option,
-                    None => break,
+                match msg {
+                    Ok(msg) => msg,
+                    Err(reason) => {
+                        // Tell the server the client is leaving on purpose,
+                        // rather than leave it to look like a lost connection
+                        let msg = ClientMessage::Leaving { reason };
+                        connection.interface.sender().send(msg).await.ok();
+                        break;
+                    },
                 }
             },
             msg = connection.interface.recv() => {
@@ -80,14 +114,34 @@ pub async fn run<E>(mut connection: NewConnection<E>) -> io::Result<Result<(), E
                     Err(e) => return Ok(Err(e)),
                 };
 
-                match panels.process_msg(msg)? {
+                let msg = match panels.process_msg(msg)? {
                     Some(option) => option,
                     None => break,
-                }
-            }
+                };
+                panels.render_title();
+                msg
+            },
+            _ = ping_timer.tick() => panels.check_connection(),
+            Some(event) = local_events.recv() => {
+                panels.handle_local_event(event);
+                None
+            },
+            _ = sleep_until(panels.next_frame()) => {
+                panels.advance_animations();
+                None
+            },
         };
 
-        if let Some(msg) = msg {
+        // Whatever was drawn this time around may have covered the toast
+        panels.toast_panel.render();
+
+        if let Some(mut msg) = msg {
+
+            // Explain why a message would be refused instead of sending it
+            if panels.intercept(&msg) { continue; }
+            panels.receipts.stamp(&mut msg);
+            panels.game_panel.game().number_action(&mut msg);
+
             let result = connection.interface.sender().send(msg).await;
             if result.is_err() { break; }
         }
@@ -96,11 +150,49 @@ pub async fn run<E>(mut connection: NewConnection<E>) -> io::Result<Result<(), E
     Ok(connection.interface.close().await)
 }
 
+/// Sleeps until the next frame is due. With nothing being played out, this
+/// never wakes.
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
 struct ClientPanels<'c> {
     command_buf: CommandBuffer,
     game_panel: GamePanels<'c>,
     chat_panel: ChatPanel,
+    error_panel: ErrorPanel,
+    toast_panel: ToastPanel,
+    chat_log: ChatLog,
+    /// Where the chat messages the player types are said.
+    chat_channel: ChatChannel,
+    /// The actions sent from either the action panel or the command line,
+    /// waiting to be answered.
+    receipts: Receipts,
     keystroke_demander: KeystrokeDemander,
+    config: ClientConfig,
+    latency: LatencyMeter,
+    /// Whether the player has been warned that the server stopped answering.
+    stalled: bool,
+    /// The bottom border, where the connection quality is shown.
+    status_border: Option<TermPanel>,
+    /// The top border, where the state of the game is shown.
+    title_border: Option<TermPanel>,
+    /// The name the server gave itself, if it said.
+    server_name: Option<Box<str>>,
+    /// Posts the outcome of work done off the event loop back to it.
+    events: mpsc::UnboundedSender<LocalEvent>,
+}
+
+/// The outcome of work done off the event loop, such as a hint search, which
+/// takes too long to wait on.
+enum LocalEvent {
+    /// The hint found for the player, if any.
+    Hint(Option<Hint>),
+    /// What the server did with a command typed at the console line.
+    Console(Result<Box<str>, ConsoleError>),
 }
 
 enum KeystrokeDemander {
@@ -115,30 +207,360 @@ impl<'c> ClientPanels<'c> {
         panel: TermPanel,
         game: ClientGame,
         connection_manager: &'c mut ConnectionManager,
+        config: ClientConfig,
+        events: mpsc::UnboundedSender<LocalEvent>,
     ) -> io::Result<Self> {
         // Create the panels with zero size
         let mut me = Self {
             command_buf: CommandBuffer::new(),
             game_panel: GamePanels::new(
                 game,
-                connection_manager
+                connection_manager,
+                config.lang,
             ),
             chat_panel: ChatPanel::new(),
+            error_panel: ErrorPanel::new(),
+            toast_panel: ToastPanel::new(),
+            chat_log: ChatLog::new(),
+            chat_channel: ChatChannel::Lobby,
+            receipts: Receipts::new(),
             keystroke_demander: KeystrokeDemander::ActionPanel,
+            config,
+            latency: LatencyMeter::new(Instant::now()),
+            stalled: false,
+            status_border: None,
+            title_border: None,
+            server_name: None,
+            events,
         };
 
         // ...then size and render accordingly
         me.resize(panel)?;
 
+        if let Some(path) = &me.config.game_log {
+            match GameLog::open(path) {
+                Ok(log) => me.chat_panel.keep_log(log),
+                Err(why) => {
+                    let msg = tr(me.config.lang, Text::GameLogNotOpened, &[&path.display(), &why]);
+                    me.chat_panel.add_message(msg.into_boxed_str());
+                },
+            }
+        }
+
         Ok(me)
     }
 
-    /// Writes an error message onto the client.
-    pub fn write_error(&mut self, error: &str)
-        -> Result<(), TermWriteError>
-    {
+    /// Writes an error message over the command buffer, and keeps it in the
+    /// error panel once it has been written over. Characters the terminal
+    /// can't show are replaced rather than failing the write.
+    pub fn write_error(&mut self, error: &str) {
+        let error = TermPanel::printable(error);
+        self.error_panel.add_error(&error);
+        self.write_prompt(&error);
+    }
+
+    /// Writes a prompt over the command buffer, or clears it if `prompt` is
+    /// empty, without keeping it as an error.
+    fn write_prompt(&mut self, prompt: &str) {
         self.keystroke_demander = KeystrokeDemander::ActionPanel;
-        self.command_buf.write_error(error)
+        // Nothing unprintable is left to fail on
+        self.command_buf.write_error(&TermPanel::printable(prompt)).ok();
+    }
+
+    /// Writes the explanation of a broken rule into the chat panel. Returns
+    /// `false` if there is no rule to explain.
+    fn explain_rule(&mut self, reason: &InvalidMessageReason) -> bool {
+        match tutorial::explain(reason) {
+            Some(explanation) => {
+                for line in explanation.lines() {
+                    self.chat_panel.add_message(line.into_boxed_str());
+                }
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Checks a message about to be sent to the server against the rule
+    /// policy of the game, and in tutorial mode against the rules themselves.
+    /// If the server would reject the message, the player is told why and
+    /// `true` is returned, indicating that the message should not be sent.
+    fn intercept(&mut self, msg: &ClientMessage) -> bool {
+        if self.game_panel.game().forbids(msg) {
+            let reason = InvalidMessageReason::ForbiddenByPolicy;
+            self.write_error(&tr(self.config.lang, Text::InvalidMessage, &[&reason]));
+            return true;
+        }
+        if !self.config.tutorial { return false; }
+
+        match tutorial::check(self.game_panel.game(), msg) {
+            Ok(()) => false,
+            Err(reason) => {
+                if !self.explain_rule(&reason) { return false; }
+
+                self.write_error(&tr(self.config.lang, Text::InvalidMove, &[&reason]));
+
+                // Give the player a chance to try again
+                match msg {
+                    ClientMessage::TakingTurn { action: PlayerAction::PlayTile { .. }, .. } => {
+                        self.game_panel.request_action(ActionRequest::PlayTile);
+                    },
+                    ClientMessage::TakingTurn { action: PlayerAction::BuyStock { .. }, .. } => {
+                        self.game_panel.request_action(ActionRequest::BuyStock);
+                    },
+                    _ => {},
+                }
+                true
+            },
+        }
+    }
+
+    /// Writes what the server says about itself into the chat panel.
+    fn welcome(&mut self, info: &ServerInfo) {
+        let lang = self.config.lang;
+        self.server_name = Some(info.name.clone());
+        let mut lines = vec![tr(lang, Text::Welcome, &[&info.name, &info.players, &info.spectators])];
+        if info.in_progress {
+            lines.push(tr(lang, Text::GameInProgress, &[]));
+        }
+        if let Some(motd) = &info.motd {
+            lines.push(tr(lang, Text::Motd, &[motd]));
+        }
+        if let Some(code) = &info.invite_code {
+            lines.push(tr(lang, Text::InviteCode, &[code]));
+        }
+        for line in lines {
+            self.chat_panel.add_message(line.into_boxed_str());
+        }
+    }
+
+    /// Pings the server, warning the player if it has stopped answering, and
+    /// shows how well the connection is doing. Returns the ping to send.
+    fn check_connection(&mut self) -> Option<ClientMessage> {
+        let now = Instant::now();
+        let ping = self.latency.ping(now);
+
+        let quality = self.latency.quality(now);
+        let stalled = matches!(quality, ConnectionQuality::Stalled(_));
+        if stalled != self.stalled {
+            let msg = match quality {
+                ConnectionQuality::Stalled(silence) => {
+                    tr(self.config.lang, Text::ConnectionStalled, &[&silence.as_secs()])
+                },
+                _ => tr(self.config.lang, Text::ConnectionRestored, &[]),
+            };
+            self.chat_panel.add_message(msg.into_boxed_str());
+            self.stalled = stalled;
+        }
+        self.render_status();
+
+        ping
+    }
+
+    /// Gets when the next frame of anything being played out is due: a move
+    /// on the board, the lines of a merger in the chat panel, or a toast
+    /// going away.
+    fn next_frame(&self) -> Option<Instant> {
+        [self.game_panel.next_frame(), self.chat_panel.next_line(), self.toast_panel.expiry()]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    /// Draws the next frame of whatever is due to be played out.
+    fn advance_animations(&mut self) {
+        let now = Instant::now();
+        if self.game_panel.next_frame().is_some_and(|due| due <= now) {
+            self.game_panel.advance_animation();
+        }
+        if self.chat_panel.next_line().is_some_and(|due| due <= now) {
+            self.chat_panel.reveal_line();
+        }
+        // The toast is drawn over the game panel
+        if self.toast_panel.expire(now) {
+            self.game_panel.render();
+        }
+    }
+
+    /// Shows an important event as a toast, unless the player turned them
+    /// off.
+    fn toast(&mut self, toast: &str) {
+        if self.config.toasts {
+            self.toast_panel.show(TermPanel::printable(toast).into_boxed_str());
+        }
+    }
+
+    /// Cuts short everything being played out, showing where it ends up.
+    /// Returns `false` if nothing was.
+    fn skip_animations(&mut self) -> bool {
+        let board = self.game_panel.skip_animation();
+        let chat = self.chat_panel.flush();
+        board || chat
+    }
+
+    /// Shows in the top border the server, the player's name, what the game
+    /// is waiting on, whose turn it is and the player's cash, so that none
+    /// of it is lost in the chat.
+    fn render_title(&mut self) {
+        let Some(border) = self.title_border.as_mut() else { return };
+        let lang = self.config.lang;
+        let client = self.game_panel.game();
+        let name = client.player_name();
+
+        let mut fields = Vec::new();
+        if let Some(server_name) = &self.server_name {
+            fields.push(server_name.to_string());
+        }
+        fields.push(name.to_owned());
+        // The turn stands out when it's the player's own
+        let mut highlighted = None;
+        match client.game() {
+            Some(game) => {
+                let phase = match game.clone().disambiguate() {
+                    GameDisambiguation::PlacingTile(_) => Text::StatusPlacing,
+                    GameDisambiguation::ResolvingMerge(_) => Text::StatusMerging,
+                    GameDisambiguation::BuyingStock(_) => Text::StatusBuying,
+                };
+                fields.push(tr(lang, phase, &[]));
+
+                let active = game.active_player();
+                if active == name {
+                    highlighted = Some(fields.len());
+                    fields.push(tr(lang, Text::StatusYourTurn, &[]));
+                } else {
+                    fields.push(tr(lang, Text::StatusTurn, &[&active]));
+                }
+                if let Some(player) = game.players().get(name) {
+                    fields.push(player.money.to_string());
+                }
+            },
+            None => fields.push(tr(lang, Text::StatusLobby, &[])),
+        }
+
+        border.fill('=').unwrap();
+        border.write(OverflowMode::Truncate, |writer| {
+            writer.write_str("==[ ").unwrap();
+            for (i, field) in fields.iter().enumerate() {
+                if i > 0 { writer.write_str(" | ").unwrap(); }
+                if highlighted == Some(i) {
+                    writer.write_fg_colored(&**field, termion::color::LightYellow)
+                } else {
+                    writer.write_str(field)
+                }.unwrap();
+            }
+            writer.write_str(" ]").unwrap();
+        });
+    }
+
+    /// Shows the quality of the connection in the bottom border.
+    fn render_status(&mut self) {
+        let Some(border) = self.status_border.as_mut() else { return };
+        let quality = self.latency.quality(Instant::now());
+        border.fill('=').unwrap();
+        border.write(OverflowMode::Truncate, |writer| {
+            writer.write_str("==[ ").unwrap();
+            let text = quality.to_string();
+            match quality {
+                ConnectionQuality::Unknown => writer.write_str(&text),
+                ConnectionQuality::Good(_) => writer.write_fg_colored(&*text, termion::color::Green),
+                ConnectionQuality::Slow(_) => writer.write_fg_colored(&*text, termion::color::Yellow),
+                ConnectionQuality::Stalled(_) => writer.write_fg_colored(&*text, termion::color::Red),
+            }.unwrap();
+            writer.write_str(" ]").unwrap();
+        });
+    }
+
+    /// Runs a command that is handled by the client itself.
+    fn run_local_command(&mut self, command: LocalCommand) {
+        match command {
+            LocalCommand::Hint(difficulty) => {
+                let client = self.game_panel.game();
+                if client.policy().no_hints {
+                    let msg = tr(self.config.lang, Text::HintsForbidden, &[]);
+                    self.chat_panel.add_message(msg.into_boxed_str());
+                    return;
+                }
+                let bot = Bot::new(difficulty).with_time_budget(self.config.hint_time_budget);
+                let Some((game, hand)) = client.game().zip(client.hand()) else {
+                    return self.handle_local_event(LocalEvent::Hint(None));
+                };
+                let (game, hand) = (game.clone(), *hand);
+                let player_name = client.client.player_name.clone();
+
+                // The search takes the whole of its time budget, so it runs
+                // on a thread of its own, and the hint comes back as an event
+                let events = self.events.clone();
+                tokio::task::spawn_blocking(move || {
+                    let hint = bot.choose_action(&game, &player_name, &hand);
+                    events.send(LocalEvent::Hint(hint)).ok();
+                });
+            },
+            LocalCommand::Board => {
+                if let Some(game) = self.game_panel.game().game() {
+                    for line in describe_board(game) {
+                        self.chat_panel.add_message(line.into_boxed_str());
+                    }
+                }
+            },
+            LocalCommand::Standings => {
+                if let Some(game) = self.game_panel.game().game() {
+                    for line in describe_standings(game) {
+                        self.chat_panel.add_message(line.into_boxed_str());
+                    }
+                }
+            },
+            LocalCommand::Channel(channel) => {
+                self.chat_channel = channel;
+                let key = match channel {
+                    ChatChannel::Lobby => Text::ChatInLobby,
+                    ChatChannel::Room => Text::ChatInRoom,
+                };
+                let msg = tr(self.config.lang, key, &[]);
+                self.chat_panel.add_message(msg.into_boxed_str());
+            },
+        }
+    }
+
+    /// Shows the outcome of work done off the event loop.
+    fn handle_local_event(&mut self, event: LocalEvent) {
+        match event {
+            LocalEvent::Hint(hint) => {
+                let lang = self.config.lang;
+                let msg = match hint {
+                    Some(hint) => tr(lang, Text::Hint, &[
+                        &format_game_command(&hint.action),
+                        &hint.rationale,
+                    ]),
+                    None => tr(lang, Text::NoHint, &[]),
+                };
+                self.chat_panel.add_message(msg.into_boxed_str());
+            },
+            LocalEvent::Console(Ok(done)) => self.chat_panel.add_message(done),
+            LocalEvent::Console(Err(err)) => self.write_error(&err.to_string()),
+        }
+    }
+
+    /// Runs a command typed at the host's console line. The server's answer
+    /// comes back as an event once it has carried the command out.
+    fn run_console_command(&mut self, command: &str) {
+        let Some(console) = self.config.console.clone() else { return };
+        if let Some(help) = console_help(command) {
+            for line in help {
+                self.chat_panel.add_message(line.into_boxed_str());
+            }
+            return;
+        }
+
+        match parse_console_command(command) {
+            Ok(command) => {
+                let events = self.events.clone();
+                tokio::spawn(async move {
+                    let result = console.run(command).await;
+                    events.send(LocalEvent::Console(result)).ok();
+                });
+            },
+            Err(err) => self.write_error(&err.to_string()),
+        }
     }
 
     /// Returns a client message that may have been produced.
@@ -161,21 +583,27 @@ impl<'c> ClientPanels<'c> {
                         let none = self.command_buf.process_key(key);
                         assert!(none.is_none());
                     },
+                    // Only the host has a console to give commands to
+                    Key::Char('!') if self.config.console.is_some() => {
+                        self.keystroke_demander = KeystrokeDemander::ChatPanel;
+                        let none = self.command_buf.process_key(key);
+                        assert!(none.is_none());
+                    },
                     // Ask to confirm the exit request
                     Key::Esc => {
-                        self.write_error("Type 'y' to confirm exit").unwrap();
+                        self.write_prompt(&tr(self.config.lang, Text::ConfirmExit, &[]));
                         self.keystroke_demander = KeystrokeDemander::Exiting;
                     }
                     _ => {
                         let msg = match self.game_panel.process_key(key) {
                             Some(Ok(msg)) => Some(msg),
                             Some(Err(why)) => {
-                                self.write_error(&why).unwrap();
+                                self.write_error(&why);
                                 None
                             }
                             None => None,
                         };
-                        let msg = msg.map(|m| ClientMessage::TakingTurn(m));
+                        let msg = msg.map(|action| ClientMessage::TakingTurn { action, request_id: None, number: None });
                         
                         return Ok(Some(msg));
                     }
@@ -185,16 +613,46 @@ impl<'c> ClientPanels<'c> {
 
                 let option = self.command_buf.process_key(key);
 
+                // Commands handled by the client are run without producing a
+                // message for the server.
+                let local = option.as_ref().and_then(|(command, mode)| {
+                    match mode {
+                        command_buffer::BufferMode::Command => parse_local_command(command),
+                        _ => None,
+                    }
+                });
+                if let Some(local) = local {
+                    self.run_local_command(local);
+                    self.keystroke_demander = KeystrokeDemander::ActionPanel;
+                    return Ok(Some(None));
+                }
+                if let Some((command, command_buffer::BufferMode::Console)) = &option {
+                    self.keystroke_demander = KeystrokeDemander::ActionPanel;
+                    self.run_console_command(command);
+                    return Ok(Some(None));
+                }
+                let help = option.as_ref().and_then(|(command, mode)| match mode {
+                    command_buffer::BufferMode::Admin => admin_help(command),
+                    _ => None,
+                });
+                if let Some(help) = help {
+                    for line in help {
+                        self.chat_panel.add_message(line.into_boxed_str());
+                    }
+                    self.keystroke_demander = KeystrokeDemander::ActionPanel;
+                    return Ok(Some(None));
+                }
+
                 // Handle the command, or write an error if the command failed
-                let msg = option.map(|(command, mode)| {
-                    match parse_command(mode, command.into_boxed_str()) {
+                let msg = option.and_then(|(command, mode)| {
+                    match parse_command(mode, command.into_boxed_str(), self.chat_channel) {
                         Ok(cmd) => Some(cmd),
                         Err(e) => {
-                            self.write_error(&e.to_string()).unwrap();
+                            self.write_error(&e.to_string());
                             None
                         }
                     }
-                }).flatten();
+                });
 
                 // If a command was produced, then focus should be shifted away
                 // from the buffer.
@@ -210,7 +668,7 @@ impl<'c> ClientPanels<'c> {
                     return Ok(None);
                 } else {
                     // Stop trying to exit
-                    self.write_error("").unwrap();
+                    self.write_prompt("");
                     self.keystroke_demander = KeystrokeDemander::ActionPanel;
                 }
             }
@@ -230,93 +688,213 @@ impl<'c> ClientPanels<'c> {
     fn process_msg(&mut self, msg: ServerMessage)
         -> io::Result<Option<Option<ClientMessage>>>
     {
+        let lang = self.config.lang;
+        self.latency.heard(Instant::now());
+        if let Some(cue) = Cue::of(&msg) {
+            self.config.notifier.cue(cue);
+        }
         match dbg!(msg) {
-            ServerMessage::Chat { player_name, message } => {
-                let chat = format!("<{player_name}> {message}");
-                self.chat_panel.add_message(chat.into_boxed_str());
+            ServerMessage::Chat { player_name, message, timestamp, sequence, channel } => {
+                if let Some(chat) = self.chat_log.receive(&player_name, &message, timestamp, sequence, channel) {
+                    self.chat_panel.add_message(chat.into_boxed_str());
+                }
             },
+            // Comings and goings are shown in the connections panel
             ServerMessage::Join { handshake } => {
-
-                // Broadcast the message
-                let spectate_msg = if handshake.spectating { " as a spectator" } else { "" };
-                let chat = format!("JOIN: {} joined the game{spectate_msg}.", handshake.player_name);
-                self.chat_panel.add_message(chat.into_boxed_str());
-
-                // Connect the player
                 self.game_panel.connections_mut(
                     |connections| connections.connect(handshake).unwrap()
                 );
             },
-            ServerMessage::Quit { handshake } => {
-
-                // Disconnect the player
+            ServerMessage::Quit { handshake, reason } => {
+                if reason == Some(LeaveReason::Kicked) {
+                    let msg = tr(lang, Text::Kicked, &[&handshake.player_name]);
+                    self.chat_panel.add_message(msg.into_boxed_str());
+                }
                 self.game_panel.connections_mut(
                     |connections| assert!(connections.disconnect(&handshake.player_name))
                 );
+            },
+            ServerMessage::Seated { handshake } => {
+                let key = if handshake.spectating { Text::Unseated } else { Text::Seated };
+                let chat = tr(lang, key, &[&handshake.player_name]);
+                self.chat_panel.add_message(chat.into_boxed_str());
 
-                let chat = format!("JOIN: {} left the game.", &handshake.player_name);
+                // The server has already checked that the seat is free
+                self.game_panel.connections_mut(|connections| {
+                    connections.set_spectating(&handshake.player_name, handshake.spectating).ok();
+                });
+            },
+            ServerMessage::Trusted { handshake } => {
+                let chat = match handshake.capabilities.is_empty() {
+                    true => tr(lang, Text::Untrusted, &[&handshake.player_name]),
+                    false => tr(lang, Text::Trusted, &[&handshake.player_name, &handshake.capabilities]),
+                };
+                self.chat_panel.add_message(chat.into_boxed_str());
+                self.game_panel.connections_mut(|connections| {
+                    connections.set_capabilities(&handshake.player_name, handshake.capabilities).ok();
+                });
+            },
+            ServerMessage::Renamed { old_name, new_name } => {
+                let chat = tr(lang, Text::Renamed, &[&old_name, &new_name]);
+                self.chat_panel.add_message(chat.into_boxed_str());
+                self.game_panel.rename(&old_name, &new_name);
+            },
+            ServerMessage::Substituted { substitution, state_hash } => {
+                let chat = tr(lang, Text::Substituted, &[
+                    &substitution.player_name,
+                    &substitution.seat,
+                ]);
                 self.chat_panel.add_message(chat.into_boxed_str());
+
+                self.game_panel.connections_mut(|connections| {
+                    connections.set_spectating(&substitution.player_name, false).ok();
+                });
+                if let Err(desync) = self.game_panel.substitute(&substitution, state_hash) {
+                    let msg = tr(lang, Text::Desync, &[&desync]);
+                    self.chat_panel.add_message(msg.into_boxed_str());
+                    return Ok(Some(Some(ClientMessage::RequestResync)));
+                }
             },
-            ServerMessage::PlayerMove { action } => {
+            ServerMessage::PlayerMove { action, state_hash, request_id } => {
+                self.receipts.settle(request_id);
+                if let PlayerAction::PlayTile { placement: TilePlacement {
+                    implication: Some(TilePlacementImplication::FoundsCompany(company)), ..
+                } } = &action.action {
+                    self.toast(&tr(lang, Text::ToastFounded, &[&action.player_name, company]));
+                }
                 self.chat_panel.add_message(
                     action.to_string().into_boxed_str()
                 );
-                self.game_panel.update_game(&action);
+                let desync = self.game_panel.update_game(&action, state_hash);
 
                 // EDGE CASE: if the action panel is trying to produce an action
                 // but the player uses the command buffer to send the action
                 // instead, the action panel will become outdated. To fix this,
                 // we clear the action panel upon receipt of a player action.
                 self.game_panel.cancel_action();
+
+                // Rather than carry on with the wrong game, ask for the
+                // server's copy of it.
+                if let Err(desync) = desync {
+                    let msg = tr(lang, Text::Desync, &[&desync]);
+                    self.chat_panel.add_message(msg.into_boxed_str());
+                    return Ok(Some(Some(ClientMessage::RequestResync)));
+                }
             },
             ServerMessage::DeadTile { player_name: player, dead_tile } => {
-                let msg = format!("{player} traded in dead tile {dead_tile}.");
+                self.game_panel.trade_in_dead_tile(&player, dead_tile);
+                let msg = tr(lang, Text::TradedDeadTile, &[&player, &dead_tile]);
                 self.chat_panel.add_message(msg.into_boxed_str());
             }
+            ServerMessage::StartingDraw { draws, random_seating } => {
+                for draw in draws.iter() {
+                    let msg = tr(lang, Text::DrewStartingTile, &[&draw.player_name, &draw.tile]);
+                    self.chat_panel.add_message(msg.into_boxed_str());
+                }
+                if random_seating {
+                    let msg = tr(lang, Text::SeatsDrawnAtRandom, &[]);
+                    self.chat_panel.add_message(msg.into_boxed_str());
+                }
+            },
             ServerMessage::GameStart { info, initial_hand } => {
 
                 self.game_panel.start_game(&info, initial_hand);
 
-                let msg = "Game started!".to_owned().into_boxed_str();
+                let msg = tr(lang, Text::GameStarted, &[]).into_boxed_str();
                 self.chat_panel.add_message(msg);
                 if let Some(initial_hand) = initial_hand {
-                    let msg = format!("Your starting hand is: {}.", initial_hand).into_boxed_str();
+                    let msg = tr(lang, Text::StartingHand, &[&initial_hand]).into_boxed_str();
                     self.chat_panel.add_message(msg);
                 }
             },
             ServerMessage::CompanyDefunct { defunct, results } => {
-                let msg = format!(
-                    "Company {defunct} has gone defunct! Here are the results:"
-                ).into_boxed_str();
+                let msg = tr(lang, Text::CompanyDefunct, &[&defunct]).into_boxed_str();
                 self.chat_panel.add_message(msg);
-                for result in results.iter() {
-                    let msg = result.to_string().into_boxed_str();
-                    self.chat_panel.add_message(msg);
+                // The payouts are stepped through, so they can be followed
+                let lines = results.iter().map(|result| result.to_string().into_boxed_str());
+                self.chat_panel.add_paced(lines);
+
+                let player_name = self.game_panel.game().player_name();
+                let bonus = results.iter()
+                    .find(|result| *result.player == *player_name && result.prize > Money::ZERO)
+                    .map(|result| result.prize);
+                if let Some(bonus) = bonus {
+                    self.toast(&tr(lang, Text::ToastBonus, &[&bonus, &defunct]));
                 }
             },
-            ServerMessage::GameOver { reason, results } => {
+            ServerMessage::NoFounderShare { player_name, company } => {
+                let msg = if player_name == self.game_panel.game().client.player_name {
+                    tr(lang, Text::NoFounderShareYou, &[&company])
+                } else {
+                    tr(lang, Text::NoFounderShare, &[&player_name, &company])
+                };
+                self.chat_panel.add_message(msg.into_boxed_str());
+            },
+            ServerMessage::ResolvingNext { player, defunct, into } => {
+                let msg = tr(lang, Text::ResolvingNext, &[&player, &defunct, &into]).into_boxed_str();
+                self.chat_panel.add_message(msg);
+            },
+            ServerMessage::GameOver { reason, results, deal_seed, game_id } => {
 
+                let exported = match (&self.config.export_dir, self.game_panel.game().game()) {
+                    (Some(dir), Some(game)) => Some(export_final_position(dir, game.board(), &results)),
+                    _ => None,
+                };
                 self.game_panel.end_game();
 
-                let msg = format!("Game Over! {reason}. Here are the results:").into_boxed_str();
+                let msg = tr(lang, Text::GameOver, &[&reason]).into_boxed_str();
                 self.chat_panel.add_message(msg);
-                results.into_iter().for_each(|result| {
-                    let msg = format!("  [{}] {} with ${}",
-                        result.place, result.player_name, result.final_money);
+                results.iter().for_each(|result| {
+                    let tied = results.iter().filter(|other| other.place == result.place).count() > 1;
+                    let text = if tied { Text::FinalResultTied } else { Text::FinalResult };
+                    let msg = tr(lang, text, &[
+                        &result.place, &result.player_name, &result.final_money
+                    ]);
                     self.chat_panel.add_message(msg.into_boxed_str())
                 });
+
+                let mut scores = self.game_panel.game().scores().clone();
+                scores.finish(&results);
+                let graph = scores.render(GRAPH_WIDTH);
+                if !graph.is_empty() {
+                    self.chat_panel.add_message(tr(lang, Text::ScoreGraph, &[]).into_boxed_str());
+                    graph.into_iter().for_each(|line| self.chat_panel.add_message(line.into_boxed_str()));
+                }
+
+                let checked = deal_seed.and_then(|seed| {
+                    self.game_panel.game().check_deal(&seed).map(|result| (seed, result))
+                });
+                let msg = match checked {
+                    Some((seed, Ok(()))) => Some(tr(lang, Text::DealVerified, &[&seed])),
+                    Some((_, Err(mismatch))) => Some(tr(lang, Text::DealMismatch, &[&mismatch])),
+                    None => None,
+                };
+                if let Some(msg) = msg {
+                    self.chat_panel.add_message(msg.into_boxed_str());
+                }
+                if let Some(game_id) = game_id {
+                    self.chat_panel.add_message(tr(lang, Text::GameIdentified, &[&game_id]).into_boxed_str());
+                }
+                let msg = match exported {
+                    Some(Ok(path)) => Some(tr(lang, Text::FinalPositionExported, &[&path.display()])),
+                    Some(Err(err)) => Some(tr(lang, Text::FinalPositionNotExported, &[&err])),
+                    None => None,
+                };
+                if let Some(msg) = msg {
+                    self.chat_panel.add_message(msg.into_boxed_str());
+                }
             },
             ServerMessage::Shutdown => return Ok(None),
-            ServerMessage::YourTurn { request } => {
+            ServerMessage::YourTurn { request, number } => {
+                self.game_panel.await_action(number);
                 self.game_panel.request_action(request);
-                let request_msg = match request {
-                    ActionRequest::PlayTile => "place a tile",
-                    ActionRequest::BuyStock => "buy stock",
-                    ActionRequest::ResolveMergeStock {
-                        defunct: _, into: _
-                    } => "resolve your stock",
+                let key = match request {
+                    ActionRequest::PlayTile => Text::YourTurnPlaceTile,
+                    ActionRequest::BuyStock => Text::YourTurnBuyStock,
+                    ActionRequest::ResolveMergeStock { .. } => Text::YourTurnResolve,
                 };
-                let msg = format!("Your turn to {request_msg}!");
+                let msg = tr(lang, key, &[]);
+                self.toast(&msg);
                 self.chat_panel.add_message(msg.into_boxed_str());
 
                 if matches!(request, ActionRequest::BuyStock) {
@@ -325,9 +903,11 @@ impl<'c> ClientPanels<'c> {
                     let none_exist = CompanyMap::new(&()).map(|cmp, _| game.board().company_exists(cmp))
                         .iter().all(|(_, exists)| !exists);
                     if none_exist {
-                        return Ok(Some(Some(ClientMessage::TakingTurn(
-                            PlayerAction::BuyStock { stock: [None; 3] })))
-                        )
+                        return Ok(Some(Some(ClientMessage::TakingTurn {
+                            action: PlayerAction::BuyStock { stock: [None; 3] },
+                            request_id: None,
+                            number: None,
+                        })))
                     }
 
                     // SHORT CIRCUIT: if the player can't afford stock, then skip
@@ -339,22 +919,110 @@ impl<'c> ClientPanels<'c> {
                         .iter().all(|(_, &too_expensive)| too_expensive);
 
                     if cant_afford {
-                        let msg = "You can't afford any stock!".to_owned().into_boxed_str();
+                        let msg = tr(lang, Text::CantAffordStock, &[]).into_boxed_str();
                         self.chat_panel.add_message(msg);
-                        return Ok(Some(Some(ClientMessage::TakingTurn(
-                            PlayerAction::BuyStock { stock: [None; 3] })))
-                        )
+                        return Ok(Some(Some(ClientMessage::TakingTurn {
+                            action: PlayerAction::BuyStock { stock: [None; 3] },
+                            request_id: None,
+                            number: None,
+                        })))
                     }
                 }
             },
             ServerMessage::TileDraw { tile } => {
                 self.game_panel.draw_tile(tile);
 
-                let msg = format!("You drew tile {}.", tile).into_boxed_str();
+                let msg = tr(lang, Text::DrewTile, &[&tile]).into_boxed_str();
                 self.chat_panel.add_message(msg);
             }
-            ServerMessage::Invalid { reason } => {
-                self.write_error(&format!("Invalid message: {reason}")).unwrap();
+            ServerMessage::Invalid { reason, request_id } => {
+                // Name the move that was refused, as another may have been
+                // sent since
+                let msg = match self.receipts.settle(request_id) {
+                    Some(action) => tr(lang, Text::MoveRefused, &[&format_game_command(&action), &reason]),
+                    None => tr(lang, Text::InvalidMessage, &[&reason]),
+                };
+                self.write_error(&msg);
+                if self.config.tutorial {
+                    self.explain_rule(&reason);
+                }
+            },
+            ServerMessage::Resync { snapshot, hand, clock } => {
+                self.game_panel.resync_game(snapshot, hand, clock);
+                let msg = tr(lang, Text::Resynced, &[]).into_boxed_str();
+                self.chat_panel.add_message(msg);
+            },
+            ServerMessage::Clock { clock } => {
+                self.game_panel.set_clock(clock);
+            },
+            ServerMessage::EndProposed { player_name } => {
+                let msg = tr(lang, Text::EndProposed, &[&player_name]).into_boxed_str();
+                self.chat_panel.add_message(msg);
+            },
+            ServerMessage::EndVoted { player_name, end_game } => {
+                let text = if end_game { Text::EndVotedYes } else { Text::EndVotedNo };
+                let msg = tr(lang, text, &[&player_name]).into_boxed_str();
+                self.chat_panel.add_message(msg);
+            },
+            ServerMessage::EndRejected => {
+                let msg = tr(lang, Text::EndRejected, &[]).into_boxed_str();
+                self.chat_panel.add_message(msg);
+            },
+            ServerMessage::Autopilot { player_name, autopilot } => {
+                let text = if autopilot { Text::AutopilotOn } else { Text::AutopilotOff };
+                let msg = tr(lang, text, &[&player_name]);
+                self.chat_panel.add_message(msg.into_boxed_str());
+            },
+            ServerMessage::Away { player_name, missed_turns } => {
+                let msg = tr(lang, Text::Away, &[&player_name, &missed_turns]);
+                self.chat_panel.add_message(msg.into_boxed_str());
+            },
+            ServerMessage::StartScheduled { seconds_left, .. } => {
+                let msg = tr(lang, Text::StartScheduled, &[&time_left(seconds_left * 1000)]);
+                self.chat_panel.add_message(msg.into_boxed_str());
+            },
+            ServerMessage::Queued { player_name, queued, waiting, table_size } => {
+                let msg = match queued {
+                    true => tr(lang, Text::Queued, &[&player_name, &waiting, &table_size]),
+                    false => tr(lang, Text::Unqueued, &[&player_name]),
+                };
+                self.chat_panel.add_message(msg.into_boxed_str());
+            },
+            ServerMessage::KickVoted { player_name, target, votes, needed } => {
+                let msg = tr(lang, Text::KickVoted, &[&player_name, &target, &votes, &needed]);
+                self.chat_panel.add_message(msg.into_boxed_str());
+            },
+            ServerMessage::KickFailed { target } => {
+                let msg = tr(lang, Text::KickFailed, &[&target]);
+                self.chat_panel.add_message(msg.into_boxed_str());
+            },
+            ServerMessage::Kicked => {
+                let msg = tr(lang, Text::YouWereKicked, &[]);
+                self.chat_panel.add_message(msg.into_boxed_str());
+            },
+            ServerMessage::ActionLog { entries } => {
+                let msg = match save_action_log(&entries) {
+                    Ok(path) => tr(lang, Text::ActionLogSaved, &[&entries.len(), &path.display()]),
+                    Err(err) => tr(lang, Text::ActionLogNotSaved, &[&err]),
+                };
+                self.chat_panel.add_message(msg.into_boxed_str());
+            },
+            ServerMessage::Pong { pong } => {
+                self.latency.pong(pong, Instant::now());
+                self.render_status();
+            },
+            ServerMessage::ServerError { message, recovery } => {
+                self.write_error(&tr(lang, Text::ServerFailed, &[&message]));
+                let msg = match recovery {
+                    Recovery::Unaffected => None,
+                    Recovery::Restored { after_actions } => {
+                        Some(tr(lang, Text::GameRestored, &[&after_actions]))
+                    },
+                    Recovery::Lost => Some(tr(lang, Text::GameLost, &[])),
+                };
+                if let Some(msg) = msg {
+                    self.chat_panel.add_message(msg.into_boxed_str());
+                }
             },
         }
 
@@ -365,63 +1033,87 @@ impl<'c> ClientPanels<'c> {
     /// resizes each sub-panel and re-renders everything.
     fn resize(&mut self, mut new_panel: TermPanel) -> io::Result<()> {
         // Create the panels for the borders
-        let (mut top_border, mut bottom_border) = new_panel.shave_vert(1, 1).unwrap();
+        let (top_border, bottom_border) = new_panel.shave_vert(1, 1).unwrap();
         let (mut left_border, mut right_border) = new_panel.shave_horiz(2, 2).unwrap();
 
         // Split the panel in two, generate the middle padding
         let (mut left, right) = new_panel.split_horiz(0.5);
         let (_, mut middle_border) = left.shave_horiz(0, 1).unwrap();
 
-        // Split the right panel into chat and cmd
+        // Split the right panel into chat, errors and cmd
         let mut chat = right;
-        let (_, mut cmd) = chat.shave_vert(0, 2).unwrap();
-        let (mut chat_cmd_border, _) = cmd.shave_vert(1, 0).unwrap();
+        let (_, mut errors) = chat.shave_vert(0, error_panel::HISTORY_LEN + 3).unwrap();
+        let (_, mut cmd) = errors.shave_vert(0, 2).unwrap();
+        let (mut chat_errors_border, _) = errors.shave_vert(1, 0).unwrap();
+        let (mut errors_cmd_border, _) = cmd.shave_vert(1, 0).unwrap();
 
         // Print into the border panels
-        top_border.fill('=').unwrap();
-        bottom_border.fill('=').unwrap();
         left_border.fill('|').unwrap();
         middle_border.fill('|').unwrap();
         right_border.fill('|').unwrap();
-        chat_cmd_border.write(OverflowMode::Truncate, |writer| {
+        chat_errors_border.write(OverflowMode::Truncate, |writer| {
+            writer.write_str("- ERRORS ").unwrap();
+            while writer.can_write_char() { writer.write_char('-').unwrap(); }
+        });
+        errors_cmd_border.write(OverflowMode::Truncate, |writer| {
             writer.write_str("- CHAT ").unwrap();
             while writer.can_write_char() { writer.write_char('-').unwrap(); }
         });
 
         self.chat_panel.resize(chat);
+        self.error_panel.resize(errors);
+        self.toast_panel.resize(&left);
         self.game_panel.resize(left);
         self.command_buf.resize(cmd);
+        self.status_border = Some(bottom_border);
+        self.render_status();
+        self.title_border = Some(top_border);
+        self.render_title();
 
         Ok(())
     }
 
     pub fn rerender_panels(&mut self) {
+        self.render_title();
         self.game_panel.render();
         self.chat_panel.render();
+        self.error_panel.render();
         self.command_buf.render();
     }
 }
 
 /// Parses a command produced by the command buffer. Sends the mode in which the
-/// buffer was produced.
-fn parse_command(buffer_mode: command_buffer::BufferMode, command: Box<str>)
+/// buffer was produced, and the channel chat is said in.
+fn parse_command(buffer_mode: command_buffer::BufferMode, command: Box<str>, channel: ChatChannel)
     -> Result<ClientMessage, CommandParseErr>
 {
     match buffer_mode {
         command_buffer::BufferMode::Chat => {
             Ok(ClientMessage::Chat {
-                message: command
+                message: command,
+                channel,
             })
         },
         command_buffer::BufferMode::Command => {
-            Ok(ClientMessage::TakingTurn(
-                parse_game_command(&command)?
-            ))
+            if let Some(vote) = parse_vote_command(&command) {
+                return Ok(vote);
+            }
+            if let Some(message) = parse_connection_command(&command) {
+                return Ok(message);
+            }
+            Ok(ClientMessage::TakingTurn {
+                action: parse_game_command(&command)?,
+                request_id: None,
+                number: None,
+            })
         },
         command_buffer::BufferMode::Admin => {
             Ok(ClientMessage::Admin(
                 parse_admin_command(&command)?
             ))
         },
+        command_buffer::BufferMode::Console => {
+            unreachable!("console commands are run before any message is parsed")
+        },
     }
 }
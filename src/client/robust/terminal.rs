@@ -10,6 +10,16 @@ struct TermControls {
     terminal: HideCursor<RawTerminal<Stdout>>,
 }
 
+/// Leaves the screen blank for whatever runs in the terminal next. The cursor
+/// is shown and the terminal taken out of raw mode once the terminal itself is
+/// dropped, just after.
+impl Drop for TermControls {
+    fn drop(&mut self) {
+        write!(self.terminal, "{}{}", termion::clear::All, termion::cursor::Goto(1, 1)).ok();
+        self.terminal.flush().ok();
+    }
+}
+
 impl Write for TermControls {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         self.terminal.write(buf)
@@ -65,8 +75,9 @@ impl TermPanel {
             let stdin = io::stdin();
             let mut stdin = stdin.lock().keys();
 
-            loop {
-                let key = stdin.next().unwrap().unwrap();
+            // The receiver hears of the input closing by the sender being
+            // dropped
+            while let Some(Ok(key)) = stdin.next() {
                 let result = key_sender.blocking_send(key);
                 
                 // SendError means the receiver is closed, ergo this task should end.
@@ -98,6 +109,13 @@ impl TermPanel {
         }
     }
 
+    /// Replaces each character that [`test_char`](Self::test_char) rejects
+    /// with a `?`, so that text from elsewhere, such as a translation or the
+    /// server, can always be written.
+    pub fn printable(text: &str) -> String {
+        text.chars().map(|c| if Self::test_char(c).is_ok() { c } else { '?' }).collect()
+    }
+
     pub fn dim(&self) -> PanelDim {
         self.dim
     }
@@ -194,6 +212,21 @@ impl TermPanel {
         })
     }
 
+    /// Creates a panel over the top right corner of this one, no larger than
+    /// `size`. Whatever is written to it is drawn over what this panel has
+    /// drawn there.
+    pub fn corner(&self, size: (u16, u16)) -> Self {
+        let width = size.0.min(self.dim.size.0);
+        let height = size.1.min(self.dim.size.1);
+        TermPanel {
+            controls: Rc::clone(&self.controls),
+            dim: PanelDim {
+                top_left: (self.dim.top_left.0 + self.dim.size.0 - width, self.dim.top_left.1),
+                size: (width, height),
+            },
+        }
+    }
+
     /// Reduces the size of this panel. This operation will fail unless the new
     /// panel dimensions are fully contained within the old panel dimensions.
     pub fn reduce_size(&mut self, new_dim: PanelDim) -> bool {
@@ -0,0 +1,75 @@
+use std::time::{Duration, Instant};
+
+use super::terminal::{TermPanel, OverflowMode};
+
+/// How long a toast stays up.
+const SHOWN_FOR: Duration = Duration::from_secs(4);
+/// The size of a toast: a line of text inside a border.
+const SIZE: (u16, u16) = (36, 3);
+
+/// Shows the odd important event, such as the player's turn coming around,
+/// in a box drawn over a corner of the screen. Unlike the chat, the box goes
+/// away by itself after a few seconds.
+#[derive(Debug)]
+pub(super) struct ToastPanel {
+    panel: Option<TermPanel>,
+    /// The toast being shown, and when it goes away.
+    toast: Option<(Box<str>, Instant)>,
+}
+
+impl ToastPanel {
+    /// Constructs a new [`ToastPanel`] with no panel. To begin rendering, call
+    /// the [`resize`] function.
+    pub fn new() -> Self {
+        Self {
+            panel: None,
+            toast: None,
+        }
+    }
+
+    /// Shows a toast, replacing any that is already up. The toast must
+    /// already be printable, as from [`TermPanel::printable`].
+    pub fn show(&mut self, toast: Box<str>) {
+        self.toast = Some((toast, Instant::now() + SHOWN_FOR));
+        self.render();
+    }
+
+    /// Gets when the toast goes away, if one is up.
+    pub fn expiry(&self) -> Option<Instant> {
+        self.toast.as_ref().map(|(_, expiry)| *expiry)
+    }
+
+    /// Takes the toast down if its time is up. Returns `true` if it was, in
+    /// which case whatever it covered must be drawn again.
+    pub fn expire(&mut self, now: Instant) -> bool {
+        if self.expiry().is_none_or(|expiry| expiry > now) {
+            return false;
+        }
+        self.toast = None;
+        true
+    }
+
+    /// Draws the toast, if one is up, over whatever is under it.
+    pub fn render(&mut self) {
+        let (Some(panel), Some((toast, _))) = (&mut self.panel, &self.toast) else { return };
+        let width = panel.dim().size.0 as usize;
+        let inner = width.saturating_sub(4);
+        let text: String = toast.chars().take(inner).collect();
+        let edge = format!("+{}+", "-".repeat(width.saturating_sub(2)));
+
+        panel.write(OverflowMode::Truncate, |writer| {
+            let color = termion::color::LightYellow;
+            writer.write_fg_colored(&*edge, color).ok();
+            writer.new_line();
+            writer.write_fg_colored(&*format!("| {text:<inner$} |"), color).ok();
+            writer.new_line();
+            writer.write_fg_colored(&*edge, color).ok();
+        });
+    }
+
+    /// Places the toast over the top right corner of `area`.
+    pub fn resize(&mut self, area: &TermPanel) {
+        self.panel = Some(area.corner(SIZE));
+        self.render();
+    }
+}
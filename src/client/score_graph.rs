@@ -0,0 +1,106 @@
+use crate::game::kernel::{self, Game};
+use crate::game::messages::FinalResult;
+use crate::game::Money;
+
+/// The widest a graph is drawn, in characters. Longer games are sampled down
+/// to fit.
+pub const GRAPH_WIDTH: usize = 40;
+
+/// The marks a graph is drawn with, from least to most money. They are kept
+/// to ASCII, as that is all the terminal panels can print.
+const BARS: [char; 8] = ['_', '.', ':', '-', '=', '+', '*', '#'];
+
+/// What each seat of a game was worth at the end of every turn, kept so that
+/// the course of the game can be graphed once it is over. Seats are kept in
+/// order of play, so that a seat keeps its line when it changes hands.
+#[derive(Debug, Clone, Default)]
+pub struct ScoreHistory {
+    names: Vec<Box<str>>,
+    worth: Vec<Vec<Money>>,
+}
+
+impl ScoreHistory {
+    /// Forgets every turn recorded, as when a new game starts.
+    pub fn clear(&mut self) {
+        self.names.clear();
+        self.worth.clear();
+    }
+
+    /// Records what each player in `game` is worth right now.
+    pub fn record(&mut self, game: &Game<kernel::Ambiguous>) {
+        let play_order = game.play_order();
+        self.names.resize(play_order.len(), Default::default());
+        self.worth.resize(play_order.len(), Vec::new());
+        for (seat, name) in play_order.into_iter().enumerate() {
+            self.worth[seat].push(game.net_worth(name));
+            self.names[seat] = name.into();
+        }
+    }
+
+    /// Records the money each player finished the game with. Players are
+    /// matched to their seats by name, and players who were never seen in a
+    /// recorded turn are left off.
+    pub fn finish(&mut self, results: &[FinalResult]) {
+        for result in results {
+            if let Some(seat) = self.names.iter().position(|name| *name == result.player_name) {
+                self.worth[seat].push(result.final_money);
+            }
+        }
+    }
+
+    /// Draws a sparkline of each seat's worth over the game, one line per
+    /// seat, no wider than `width`. Every line shares the same scale, so that
+    /// the players can be compared at a glance. Gives nothing if no turn was
+    /// recorded.
+    pub fn render(&self, width: usize) -> Vec<String> {
+        let all = self.worth.iter().flatten().copied();
+        let (Some(low), Some(high)) = (all.clone().min(), all.max()) else {
+            return Vec::new();
+        };
+        let range = (high - low).dollars().max(1) as u64;
+        let name_width = self.names.iter().map(|name| name.chars().count()).max().unwrap_or(0);
+
+        self.names.iter().zip(&self.worth)
+            .map(|(name, worth)| {
+                let columns = worth.len().min(width.max(1));
+                let line: String = (0..columns)
+                    .map(|column| {
+                        // Spread the columns evenly from the first turn to the last
+                        let turn = match columns {
+                            1 => worth.len() - 1,
+                            _ => column * (worth.len() - 1) / (columns - 1),
+                        };
+                        let height = (worth[turn] - low).dollars() as u64 * (BARS.len() - 1) as u64 / range;
+                        BARS[height as usize]
+                    })
+                    .collect();
+                let last = worth.last().copied().unwrap_or_default();
+                format!("  {name:<name_width$} {line} {last}")
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::Money;
+
+    use super::ScoreHistory;
+
+    #[test]
+    fn shares_one_scale() {
+        let history = ScoreHistory {
+            names: vec!["alice".into(), "bob".into()],
+            worth: vec![
+                [6000, 7000, 8000, 9000, 10000].map(Money::new).to_vec(),
+                [6000, 6000, 6000, 6000, 6000].map(Money::new).to_vec(),
+            ],
+        };
+        assert_eq!(history.render(5), vec![
+            "  alice _.-+# $10,000",
+            "  bob   _____ $6,000",
+        ]);
+        // Sampled down to the first turn, the middle and the last
+        assert_eq!(history.render(3)[0], "  alice _-# $10,000");
+    }
+}
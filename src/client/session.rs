@@ -0,0 +1,68 @@
+use std::fs::{OpenOptions, Permissions};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::PathBuf;
+
+use serde::{Serialize, Deserialize};
+
+use crate::server::invite::InviteCode;
+use crate::server::resume::ResumeToken;
+
+/// The name of the file the session is kept in, in the home directory.
+const FILE_NAME: &str = ".acquire-session.json";
+
+/// What the client remembers of the server it last joined, kept until the
+/// player leaves it, so that a session cut short by a crash or a dropped SSH
+/// connection can be resumed on the next launch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub address: Box<str>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<Box<str>>,
+    pub player_name: Box<str>,
+    pub spectating: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invite_code: Option<InviteCode>,
+    /// The token the client joined with, which the seat it holds is kept
+    /// for.
+    pub resume_token: ResumeToken,
+}
+
+impl Session {
+    /// Gets where the session is kept, if the home directory is known.
+    fn path() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(FILE_NAME))
+    }
+
+    /// Loads the session left behind by a client that didn't exit cleanly,
+    /// if there is one.
+    pub fn load() -> Option<Self> {
+        let contents = std::fs::read(Self::path()?).ok()?;
+        serde_json::from_slice(&contents).ok()
+    }
+
+    /// Keeps the session until [`Session::clear`] is called. The resume
+    /// token is as good as the seat it holds, so on Unix only the user may
+    /// read the file.
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(path)?;
+
+        // A file left by an older client may be readable by others
+        #[cfg(unix)]
+        file.set_permissions(Permissions::from_mode(0o600))?;
+        file.write_all(&serde_json::to_vec_pretty(self)?)
+    }
+
+    /// Forgets the session, once the player has left it.
+    pub fn clear() {
+        if let Some(path) = Self::path() {
+            std::fs::remove_file(path).ok();
+        }
+    }
+}
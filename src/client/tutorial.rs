@@ -0,0 +1,215 @@
+use crate::game::messages::*;
+
+use super::ClientGame;
+
+/// An explanation of a rule of Acquire, shown to a player in tutorial mode
+/// when they attempt an action that breaks it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleExplanation {
+    /// One-line description of what went wrong.
+    pub summary: String,
+    /// Explanation of the rule that was broken.
+    pub rule: &'static str,
+    /// The section of the official rules that covers the broken rule.
+    pub reference: &'static str,
+}
+
+impl RuleExplanation {
+    /// Produces the lines to be written into the chat panel.
+    pub fn lines(&self) -> [String; 3] {
+        [
+            format!("TUTORIAL: {}", self.summary),
+            format!("  {}", self.rule),
+            format!("  See the official rules: \"{}\".", self.reference),
+        ]
+    }
+}
+
+/// Checks a message before it is sent to the server. If the message would be
+/// rejected as invalid, the reason is returned so that it can be explained to
+/// the player rather than sent.
+pub fn check(game: &ClientGame, msg: &ClientMessage) -> Result<(), InvalidMessageReason> {
+    match msg {
+        ClientMessage::TakingTurn { action, .. } => game.check_action(action),
+        ClientMessage::DeadTile { dead_tile } => {
+            let game_obj = game.game().ok_or(InvalidMessageReason::NoGameStarted)?;
+
+            game_obj.check_dead_tile_trade(&game.client.player_name)?;
+
+            let in_hand = game.hand()
+                .map(|hand| hand.iter().any(|t| t == dead_tile))
+                .unwrap_or(false);
+            if !in_hand {
+                return Err(InvalidMessageReason::TileNotFound);
+            }
+
+            if !game_obj.board().dead_tile(*dead_tile) {
+                return Err(InvalidMessageReason::NotDeadTile);
+            }
+
+            Ok(())
+        },
+        ClientMessage::Chat { .. }
+            | ClientMessage::Admin(_)
+            | ClientMessage::ProposeEnd { .. }
+            | ClientMessage::Ping { .. }
+            | ClientMessage::Rename { .. }
+            | ClientMessage::Leaving { .. }
+            | ClientMessage::Queue { .. }
+            | ClientMessage::VoteKick { .. }
+            | ClientMessage::SetAutopilot { .. }
+            | ClientMessage::RequestResync => Ok(()),
+    }
+}
+
+/// Explains the rule behind an [`InvalidMessageReason`]. Returns [`None`] if
+/// the reason has nothing to do with the rules of the game.
+pub fn explain(reason: &InvalidMessageReason) -> Option<RuleExplanation> {
+    use InvalidMessageReason::*;
+
+    let (rule, reference) = match reason {
+        OutOfTurn => (
+            "Players take turns in the order set at the start of the game. On \
+            your turn, you place a tile, resolve any merger, then buy stock.",
+            "Playing the Game",
+        ),
+        TileNotFound => (
+            "You may only play, or trade in, a tile from your own hand.",
+            "Playing the Game",
+        ),
+        NotDeadTile => (
+            "A tile may only be traded in if it is permanently unplayable, \
+            meaning it would merge two safe hotel chains.",
+            "Unplayable Tiles",
+        ),
+        DeadTileOutOfTurn => (
+            "A dead tile may be traded in for a new one on your turn, before \
+            you place a tile.",
+            "Unplayable Tiles",
+        ),
+        DeadTileLimit => (
+            "The game was started with a limit on the number of dead tiles \
+            that can be traded in on each turn.",
+            "Unplayable Tiles",
+        ),
+        IncorrectTileImplication(implication) => explain_implication(implication),
+        BuyStockError(error) => explain_buy_stock(error),
+        MergeResolveError(error) => explain_merge_resolve(error),
+        GameAlreadyStarted | NoGameStarted | PermissionDenied | PlayerNotConnected | NameTaken
+            | NoSeatFree | SeatOccupied | NotInGame | AlreadyInGame | NoEndProposed | NotInRoom | NoMatchmaking
+            | StartTimePassed | SeatsClosed | NotSeated | KickVoteOpen | KickCooldown
+            | TooFewToKick | AdminPresent | MoneyOverflow
+            | StaleAction { .. } | ForbiddenByPolicy | JsonParseErr(_) | MalformedMessage(_) => {
+            return None
+        },
+    };
+
+    Some(RuleExplanation {
+        summary: reason.to_string(),
+        rule,
+        reference,
+    })
+}
+
+fn explain_implication(implication: &IncorrectImplication) -> (&'static str, &'static str) {
+    use IncorrectImplication::*;
+
+    match implication {
+        DeadTile => (
+            "A hotel chain with 11 or more tiles is safe and can never be \
+            merged. A tile that would merge two safe chains is dead and cannot \
+            be played.",
+            "Unplayable Tiles",
+        ),
+        CompanyTaken => (
+            "Each hotel chain can only be on the board once. A new chain must \
+            be founded using a chain that is not already in play.",
+            "Founding a Hotel Chain",
+        ),
+        ShouldFoundCompany => (
+            "A tile placed next to one or more unaffiliated tiles founds a new \
+            hotel chain, and you must choose which chain to found.",
+            "Founding a Hotel Chain",
+        ),
+        LargeIntoSmall => (
+            "When chains merge, the larger chain always survives and acquires \
+            the smaller ones. Ties may be broken by the merging player.",
+            "Mergers",
+        ),
+        ShouldMerge | IncorrectDefunct(_) | MissedDefunct(_) | IncorrectSurvivor(_) => (
+            "A tile touching two or more hotel chains merges every one of them \
+            into the largest chain.",
+            "Mergers",
+        ),
+        ShouldBeNone => (
+            "A tile touching no other tiles, or touching exactly one chain, \
+            neither founds nor merges a chain.",
+            "Playing the Game",
+        ),
+    }
+}
+
+fn explain_buy_stock(error: &BuyStockError) -> (&'static str, &'static str) {
+    match error {
+        BuyStockError::NonexistentCompany { .. } => (
+            "Stock can only be bought in hotel chains that are on the board.",
+            "Buying Stock",
+        ),
+        BuyStockError::OutOfStock => (
+            "Each hotel chain has only 25 shares of stock. Once they have all \
+            been bought, no more can be purchased.",
+            "Buying Stock",
+        ),
+        BuyStockError::InsufficientFunds { .. } => (
+            "You may buy up to three shares per turn, but you must be able to \
+            pay for all of them with the cash you have on hand.",
+            "Buying Stock",
+        ),
+    }
+}
+
+fn explain_merge_resolve(error: &MergeResolveError) -> (&'static str, &'static str) {
+    match error {
+        MergeResolveError::ResolvesNonexistentStock => (
+            "Every share you own in the defunct chain must be sold, traded, or \
+            kept, and you cannot dispose of more shares than you own.",
+            "Disposing of Stock",
+        ),
+        MergeResolveError::TradesInOddStock => (
+            "Defunct stock is traded for stock in the surviving chain at a rate \
+            of two to one, so only an even number of shares can be traded.",
+            "Disposing of Stock",
+        ),
+        MergeResolveError::OutOfStock => (
+            "Defunct stock can only be traded in while the surviving chain has \
+            shares left to give.",
+            "Disposing of Stock",
+        ),
+        MergeResolveError::MoneyOverflow => (
+            "No sale of stock or bonus may leave a player with more money than \
+            the game can count.",
+            "Disposing of Stock",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::Money;
+
+    #[test]
+    fn rule_errors_are_explained() {
+        let reasons = [
+            InvalidMessageReason::IncorrectTileImplication(IncorrectImplication::DeadTile),
+            InvalidMessageReason::MergeResolveError(MergeResolveError::TradesInOddStock),
+            InvalidMessageReason::BuyStockError(BuyStockError::InsufficientFunds { deficit: Money::new(100) }),
+        ];
+        for reason in reasons {
+            let explanation = explain(&reason).unwrap();
+            assert_eq!(explanation.summary, reason.to_string());
+        }
+
+        assert!(explain(&InvalidMessageReason::PermissionDenied).is_none());
+    }
+}
@@ -0,0 +1,259 @@
+use std::collections::VecDeque;
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use super::{describe_board, time_left, ChatLog, ClientGame};
+use crate::game::kernel::{self, Game};
+use crate::game::messages::*;
+use crate::game::tile::Tile;
+use crate::server::NewConnection;
+
+/// How many of the latest events are shown below the board.
+const LOG_LEN: usize = 12;
+/// Clears the screen and moves the cursor home, so that each frame replaces
+/// the last in a terminal. Anything else shows the frames one after another.
+const CLEAR: &str = "\x1b[2J\x1b[H";
+
+/// Follows the game as a spectator, writing the board and the latest events
+/// to `out` as a frame of plain text every time something happens. Nothing is
+/// read from the viewer, so a socket can be handed straight to anyone with
+/// `nc`. Runs until the server shuts down or `out` can no longer be written
+/// to.
+pub async fn run<E, W>(mut connection: NewConnection<E>, out: &mut W) -> Result<(), E>
+    where W: AsyncWrite + Unpin
+{
+    let title = match &connection.server_state.info {
+        Some(info) => format!("Watching {}", info.name),
+        None => "Watching".to_owned(),
+    };
+    let (mut game, catch_up_error) = ClientGame::new(
+        connection.handshake.clone(),
+        connection.server_state.game_history.take(),
+        None,
+    );
+    let mut viewer = Viewer {
+        title,
+        status: match game.game() {
+            Some(_) => "A game is in progress.",
+            None => "Waiting for a game to start.",
+        },
+        board: Vec::new(),
+        log: VecDeque::new(),
+        chat_log: ChatLog::new(),
+    };
+    if let Some(error) = catch_up_error {
+        viewer.log(format!("Couldn't catch up to the game in progress, as {error}."));
+    }
+
+    viewer.update_board(&game);
+    let mut open = viewer.draw(out).await;
+    while open {
+        let Some(msg) = connection.interface.recv().await else { break };
+        let msg = msg?;
+        if matches!(msg, ServerMessage::Shutdown) {
+            viewer.log("The server has shut down.".to_owned());
+            viewer.draw(out).await;
+            break;
+        }
+        if viewer.process_msg(&mut game, msg) {
+            connection.interface.sender().send(ClientMessage::RequestResync).await.ok();
+        }
+        viewer.update_board(&game);
+        open = viewer.draw(out).await;
+    }
+
+    connection.interface.close().await
+}
+
+struct Viewer {
+    title: String,
+    status: &'static str,
+    /// The board as last drawn, kept once the game is over.
+    board: Vec<String>,
+    log: VecDeque<String>,
+    chat_log: ChatLog,
+}
+
+impl Viewer {
+    fn log(&mut self, line: String) {
+        if self.log.len() == LOG_LEN {
+            self.log.pop_front();
+        }
+        self.log.push_back(line);
+    }
+
+    /// Applies a message to the game and the log. Returns `true` if the game
+    /// no longer agrees with the server's, and a resync is needed.
+    fn process_msg(&mut self, game: &mut ClientGame, msg: ServerMessage) -> bool {
+        let mut desync = None;
+        match msg {
+            ServerMessage::Chat { player_name, message, timestamp, sequence, channel } => {
+                if let Some(chat) = self.chat_log.receive(&player_name, &message, timestamp, sequence, channel) {
+                    self.log(chat.to_string());
+                }
+            },
+            ServerMessage::Join { handshake } => self.log(format!("{} joined.", handshake.player_name)),
+            ServerMessage::Quit { handshake, reason: Some(LeaveReason::Kicked) } => {
+                self.log(format!("{} was kicked.", handshake.player_name));
+            },
+            ServerMessage::Quit { handshake, reason: Some(_) } => self.log(format!("{} left.", handshake.player_name)),
+            ServerMessage::Quit { handshake, reason: None } => {
+                self.log(format!("{} lost their connection.", handshake.player_name))
+            },
+            ServerMessage::Seated { handshake } => match handshake.spectating {
+                true => self.log(format!("{} is now spectating.", handshake.player_name)),
+                false => self.log(format!("{} took a seat.", handshake.player_name)),
+            },
+            ServerMessage::Trusted { handshake } => match handshake.capabilities.is_empty() {
+                true => self.log(format!("{} is no longer an admin.", handshake.player_name)),
+                false => self.log(format!("{} is now an admin for: {}.", handshake.player_name, handshake.capabilities)),
+            },
+            ServerMessage::Renamed { old_name, new_name } => {
+                game.rename(&old_name, &new_name);
+                self.log(format!("{old_name} is now known as {new_name}."));
+            },
+            ServerMessage::StartingDraw { draws, .. } => {
+                for draw in draws.iter() {
+                    self.log(format!("{} drew {}.", draw.player_name, draw.tile));
+                }
+            },
+            ServerMessage::GameStart { info, .. } => {
+                if game.game().is_some() {
+                    game.end();
+                }
+                game.start(&info, None);
+                self.status = "A game is in progress.";
+                self.log(format!("The game has begun. Order of play: {}.", info.play_order.join(", ")));
+            },
+            ServerMessage::PlayerMove { action, state_hash, .. } => {
+                self.log(action.to_string());
+                desync = game.update(&action).and_then(|()| game.verify(state_hash)).err();
+            },
+            ServerMessage::Substituted { substitution, state_hash } => {
+                self.log(format!("{} took over the seat of {}.", substitution.player_name, substitution.seat));
+                desync = game.substitute(&substitution).and_then(|()| game.verify(state_hash)).err();
+            },
+            ServerMessage::DeadTile { player_name, dead_tile } => {
+                self.log(format!("{player_name} traded in dead tile {dead_tile}."));
+            },
+            ServerMessage::CompanyDefunct { defunct, results } => {
+                self.log(format!("{defunct} has gone defunct."));
+                for result in results.iter() {
+                    self.log(format!("  {result}"));
+                }
+            },
+            ServerMessage::GameOver { reason, results, .. } => {
+                if game.game().is_some() {
+                    game.end();
+                }
+                self.status = "The game is over.";
+                self.log(format!("The game is over, as {reason}."));
+                for result in results.iter() {
+                    self.log(format!("  {result}"));
+                }
+            },
+            ServerMessage::Resync { snapshot, .. } => game.resync(snapshot, None),
+            ServerMessage::EndProposed { player_name } => {
+                self.log(format!("{player_name} proposes ending the game now."));
+            },
+            ServerMessage::EndRejected => self.log("The game goes on.".to_owned()),
+            ServerMessage::Autopilot { player_name, autopilot: true } => {
+                self.log(format!("The server plays for {player_name} for now."));
+            },
+            ServerMessage::Autopilot { player_name, autopilot: false } => {
+                self.log(format!("{player_name} is back at their seat."));
+            },
+            ServerMessage::Away { player_name, .. } => {
+                self.log(format!("{player_name} is away. The server plays their seat."));
+            },
+            ServerMessage::StartScheduled { seconds_left, .. } => {
+                self.log(format!("The next game starts in {}.", time_left(seconds_left * 1000)));
+            },
+            ServerMessage::ServerError { message, recovery } => {
+                self.log(format!("The server failed ({message})."));
+                match recovery {
+                    Recovery::Unaffected => {},
+                    Recovery::Restored { after_actions } => {
+                        self.log(format!("The game was restored to where it was after {after_actions} actions."));
+                    },
+                    Recovery::Lost => {
+                        self.status = "Waiting for a game to start.";
+                        self.log("The game was lost.".to_owned());
+                    },
+                }
+            },
+            ServerMessage::EndVoted { .. }
+                | ServerMessage::NoFounderShare { .. }
+                | ServerMessage::ResolvingNext { .. }
+                | ServerMessage::Clock { .. }
+                | ServerMessage::YourTurn { .. }
+                | ServerMessage::TileDraw { .. }
+                | ServerMessage::Invalid { .. }
+                | ServerMessage::ActionLog { .. }
+                | ServerMessage::Pong { .. }
+                | ServerMessage::Queued { .. }
+                | ServerMessage::KickVoted { .. }
+                | ServerMessage::KickFailed { .. }
+                | ServerMessage::Kicked
+                | ServerMessage::Shutdown => {},
+        }
+
+        if let Some(desync) = &desync {
+            self.log(format!("Out of step with the server, as {desync}. Catching up."));
+        }
+        desync.is_some()
+    }
+
+    /// Redraws the board, if a game is in progress.
+    fn update_board(&mut self, game: &ClientGame) {
+        let Some(game) = game.game() else { return };
+        self.board = draw_board(game);
+        self.board.push(String::new());
+        self.board.extend(describe_board(game));
+        self.board.push(standings(game));
+    }
+
+    /// Writes a frame. Returns `false` if the viewer can no longer be written
+    /// to.
+    async fn draw<W: AsyncWrite + Unpin>(&self, out: &mut W) -> bool {
+        let mut frame = format!("{CLEAR}{}\n{}\n\n", self.title, self.status);
+        for line in self.board.iter().chain([&String::new()]).chain(&self.log) {
+            frame.push_str(line);
+            frame.push('\n');
+        }
+        out.write_all(frame.as_bytes()).await.is_ok() && out.flush().await.is_ok()
+    }
+}
+
+/// Draws the board as a grid of characters: `.` for an empty cell, `#` for a
+/// tile in no company, and otherwise the initial of the company a tile is in.
+pub(super) fn draw_board<S: kernel::State>(game: &Game<S>) -> Vec<String> {
+    let board = game.board();
+    let mut lines = vec![(1..=Tile::NUM_ROWS).fold("  ".to_owned(), |line, row| line + &format!("{row:>3}"))];
+    for col in 'a'..=Tile::LAST_COL {
+        let mut line = format!("{col} ");
+        for row in 1..=Tile::NUM_ROWS {
+            let tile = Tile::new(row, col);
+            let cell = match board[tile] {
+                Some(Some(company)) => company.char(),
+                Some(None) => '#',
+                None => '.',
+            };
+            line.push_str(&format!("{cell:>3}"));
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Lists the players in order of play with their cash, marking whose turn it
+/// is.
+fn standings(game: &Game<kernel::Ambiguous>) -> String {
+    let players: Vec<_> = game.play_order().into_iter()
+        .map(|name| {
+            let turn = if name == game.active_player() { "*" } else { "" };
+            format!("{turn}{name} {}", game.players()[name].money)
+        })
+        .collect();
+    format!("Cash: {}.", players.join(", "))
+}
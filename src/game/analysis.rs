@@ -0,0 +1,123 @@
+use std::fmt;
+
+use super::messages::{PlayerAction, TilePlacementImplication};
+use super::replay::{Replay, VerifyError};
+use super::{CompanyMap, Money};
+
+/// The number of turns each bar of the merger histogram covers.
+const TURNS_PER_BAR: u32 = 5;
+/// The length of the longest bar of the merger histogram.
+const BAR_LEN: usize = 40;
+
+/// Statistics gathered across many [`Replay`]s, printed as a report. Each
+/// replay is verified and played through before it is counted, so that a
+/// replay that was tampered with can't skew them.
+#[derive(Debug, Default, Clone)]
+pub struct Report {
+    /// The number of games counted.
+    pub games: u32,
+    /// The number of times each company was founded.
+    pub foundings: CompanyMap<u32>,
+    /// The number of games in which each company was founded at least once.
+    pub games_founded: CompanyMap<u32>,
+    /// The money each winner finished with, summed.
+    pub winner_cash: u64,
+    /// The turn on which each merger happened, counting from 1.
+    pub merger_turns: Vec<u32>,
+}
+
+impl Report {
+    /// Counts a replay, once it has been verified.
+    pub fn add(&mut self, replay: &Replay) -> Result<(), VerifyError> {
+        replay.verify()?;
+
+        let mut foundings: CompanyMap<u32> = Default::default();
+        let mut merger_turns = vec![];
+        // How the game ended was checked along with the rest
+        let _ = replay.play_through(replay.actions.len(), |game, action| {
+            let PlayerAction::PlayTile { placement } = &action.action else { return };
+            match placement.implication {
+                Some(TilePlacementImplication::FoundsCompany(company)) => foundings[company] += 1,
+                Some(TilePlacementImplication::MergesCompanies(_)) => {
+                    merger_turns.push(game.next_action_number().turn);
+                },
+                None => {},
+            }
+        })?;
+
+        self.games += 1;
+        for (company, &count) in foundings.iter() {
+            self.foundings[company] += count;
+            self.games_founded[company] += (count > 0) as u32;
+        }
+        self.merger_turns.extend(merger_turns);
+        if let Some(winner) = replay.results.first() {
+            self.winner_cash += winner.final_money.dollars() as u64;
+        }
+        Ok(())
+    }
+
+    /// Gets the money the winners finished with on average.
+    pub fn average_winner_cash(&self) -> Option<Money> {
+        let average = self.winner_cash.checked_div(self.games as u64)?;
+        Some(Money::new(average as u32))
+    }
+
+    /// Counts the mergers that happened in each span of
+    /// [`TURNS_PER_BAR`] turns, from the first turn to the last on which a
+    /// merger happened.
+    pub fn merger_histogram(&self) -> Vec<u32> {
+        let Some(&last) = self.merger_turns.iter().max() else { return vec![] };
+        let mut bars = vec![0; ((last - 1) / TURNS_PER_BAR + 1) as usize];
+        for &turn in &self.merger_turns {
+            bars[((turn - 1) / TURNS_PER_BAR) as usize] += 1;
+        }
+        bars
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Games: {}", self.games)?;
+        if self.games == 0 {
+            return Ok(());
+        }
+        if let Some(cash) = self.average_winner_cash() {
+            writeln!(f, "Average winner's cash: {cash}")?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "Foundings per company:")?;
+        let mut companies: Vec<_> = self.foundings.iter().map(|(company, &count)| (company, count)).collect();
+        companies.sort_by(|(_, a), (_, b)| b.cmp(a));
+        for (company, count) in companies {
+            let share = self.games_founded[company] * 100 / self.games;
+            writeln!(f, "  {:<12}{count:>6} times, in {share}% of games", company.to_string())?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "Mergers by turn ({} in all):", self.merger_turns.len())?;
+        let bars = self.merger_histogram();
+        let most = bars.iter().copied().max().unwrap_or(0).max(1);
+        for (i, &count) in bars.iter().enumerate() {
+            let first = i as u32 * TURNS_PER_BAR + 1;
+            let turns = format!("{first}-{}", first + TURNS_PER_BAR - 1);
+            let bar = "#".repeat((count as usize * BAR_LEN).div_ceil(most as usize));
+            writeln!(f, "  {turns:>7} {count:>5} {bar}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Report;
+
+    #[test]
+    fn mergers_binned_by_turn() {
+        let report = Report { merger_turns: vec![1, 5, 6, 14], ..Default::default() };
+        assert_eq!(report.merger_histogram(), [2, 1, 1]);
+        assert_eq!(Report::default().merger_histogram(), [] as [u32; 0]);
+        assert_eq!(Report::default().average_winner_cash(), None);
+    }
+}
@@ -1,20 +1,26 @@
 use std::collections::HashSet;
 use std::ops;
 
+use serde::{Serialize, Deserialize};
+use termion::color;
+
 use crate::client::robust::terminal::{TermRender, TermWriteError, TermWriter};
 
+use super::cells::Cells;
 use super::messages::*;
 use super::tile::Tile;
-use super::{Company, CompanyMap};
+use super::{Company, CompanyMap, Money};
 
 /// Contains all the common knowledge pertaining to the game board. This struct
 /// is intended for use by both the client and the server.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(into = "BoardRepr", from = "BoardRepr")]
 pub struct Board {
-    /// Arranged in ascending order by number, with the letter breaking ties.
-    /// [`None`] represents an empty cell, [`Some(None)`] represents a cell not
-    /// belonging to a company. TODO: consider a HashMap?
-    tiles: [Option<Option<Company>>; 108],
+    /// Every cell that has been played, whether or not it belongs to a
+    /// company.
+    played: Cells,
+    /// The cells belonging to each company. No cell belongs to more than one.
+    companies: CompanyMap<Cells>,
     /// The sizes of each company on the board
     pub company_sizes: CompanyMap<u8>,
     /// The location of a merger happening on the board, if there is one.
@@ -23,11 +29,18 @@ pub struct Board {
     headquarters: CompanyMap<Option<Tile>>,
 }
 
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Board {
     /// Creates a new empty board.
     pub fn new() -> Self {
         Self {
-            tiles: [None; 108],
+            played: Cells::EMPTY,
+            companies: Default::default(),
             company_sizes: Default::default(),
             merger_tile: None,
             headquarters: Default::default(),
@@ -62,23 +75,19 @@ impl Board {
     /// the company sizes accordingly. This function does not check if the move
     /// is legal.
     fn set_tile(&mut self, tile: Tile, affiliation: Option<Company>) {
-
-        let num_cols = Tile::col_as_num(Tile::LAST_COL);
-        let pos = &mut self.tiles[
-            ((tile.row()-1) * num_cols + (Tile::col_as_num(tile.col())-1)) as usize
-        ];
-        
-        let replaced = std::mem::replace(pos, Some(affiliation));
+        self.played.insert(tile);
 
         // Update company size for the replaced company
-        if let Some(replaced) = replaced {
-            if let Some(company) = replaced {
+        for (company, cells) in &mut self.companies {
+            if cells.contains(tile) {
+                cells.remove(tile);
                 self.company_sizes[company] -= 1;
             }
         }
 
         // Update company size for the company added to
         if let Some(company) = affiliation {
+            self.companies[company].insert(tile);
             self.company_sizes[company] += 1;
         }
     }
@@ -92,26 +101,18 @@ impl Board {
     /// function will panic.
     fn place_non_merging_tile(&mut self, tile: Tile) {
 
-        // Look at the surrounding companies to determine affiliation
+        // Look at the surrounding companies to determine affiliation. If more
+        // than one company borders the tile, that means there's a merge.
+        let border = Cells::single(tile).border();
         let mut affiliation = None;
-        self.for_each_neighbor(tile, |neighbor| {
-
-            // Skip empty tiles
-            if let Some(neighbor_state) = self[neighbor] {
-            
-                // If this tile's expected affiliation is different from that of
-                // the neighbor's, that means there's a merge.
-                if let Some(neighbor_affil) = neighbor_state {
-                    if let Some(this_affil) = affiliation {
-                        if this_affil != neighbor_affil {
-                            panic!("Merger tile {tile} placed as a non-merger");
-                        }
-                    }
-
-                    affiliation = Some(neighbor_affil);
+        for (company, &cells) in &self.companies {
+            if !(cells & border).is_empty() {
+                if affiliation.is_some() {
+                    panic!("Merger tile {tile} placed as a non-merger");
                 }
+                affiliation = Some(company);
             }
-        });
+        }
 
         // Place the tile
         self.set_tile(tile, affiliation);
@@ -160,39 +161,79 @@ impl Board {
         self.update_chain(tile, company);
     }
 
-    /// Calls a function for each neighbor of the passed tile
-    fn for_each_neighbor<F>(&self, tile: Tile, mut f: F)
-        where F: FnMut(Tile)
-    {
-        tile.next_row().map(|tile| f(tile));
-        tile.next_col().map(|tile| f(tile));
-        tile.prev_row().map(|tile| f(tile));
-        tile.prev_col().map(|tile| f(tile));
+    /// Gets the cells bordering a tile, whether or not they have been played.
+    /// Cells along the edges of the board have fewer than four neighbors.
+    pub fn neighbors(tile: Tile) -> impl Iterator<Item = Tile> {
+        [tile.next_row(), tile.next_col(), tile.prev_row(), tile.prev_col()]
+            .into_iter()
+            .flatten()
     }
 
-    /// Calls a function for each neighbor of the passed tile
-    fn for_each_neighbor_mut<F>(&mut self, tile: Tile, mut f: F)
-        where F: FnMut(&mut Board, Tile)
-    {
-        tile.next_row().map(|tile| f(self, tile));
-        tile.next_col().map(|tile| f(self, tile));
-        tile.prev_row().map(|tile| f(self, tile));
-        tile.prev_col().map(|tile| f(self, tile));
+    /// Gets every tile belonging to a company, in ascending order.
+    pub fn tiles_of(&self, company: Company) -> impl Iterator<Item = Tile> {
+        self.companies[company].iter()
     }
 
-    /// Updates this tile and all of its neighbors to belong the passed company.
-    fn update_chain(&mut self, tile: Tile, company: Company) {
+    /// Gets the cells belonging to a company.
+    #[inline]
+    pub fn cells_of(&self, company: Company) -> Cells {
+        self.companies[company]
+    }
 
-        // Update this tile
-        self.set_tile(tile, Some(company));
+    /// Gets every cell that has been played, whether or not it belongs to a
+    /// company.
+    #[inline]
+    pub fn played(&self) -> Cells {
+        self.played
+    }
+
+    /// Gets the chain of played tiles connected to a tile, including the tile
+    /// itself. If the tile hasn't been played, the chain is empty.
+    pub fn chain_containing(&self, tile: Tile) -> Cells {
+        if !self.played.contains(tile) { return Cells::EMPTY; }
+        Cells::single(tile).flood(self.played)
+    }
 
-        self.for_each_neighbor_mut(tile, |board, tile| {
-            // Update placed tiles that aren't already part of the company.
-            if board[tile].is_some() && board[tile] != Some(Some(company)) {
-                // Make a recursive call
-                board.update_chain(tile, company);
+    /// Gets the boards on the way from `before` to this board, in which the
+    /// cells that changed spread out from `origin` one step at a time. The
+    /// last board is always this one.
+    pub fn spreading_from(&self, before: &Board, origin: Tile) -> Vec<Board> {
+        let changed: Cells = self.played.iter()
+            .filter(|&tile| self[tile] != before[tile])
+            .collect();
+
+        let mut boards = Vec::new();
+        let mut reached = Cells::single(origin) & changed;
+        loop {
+            let grown = reached.border() & changed;
+            if reached.is_empty() || grown.is_empty() { break; }
+
+            let mut board = *before;
+            for tile in reached.iter() {
+                board.set_tile(tile, self[tile].flatten());
             }
-        });
+            board.headquarters = self.headquarters;
+            boards.push(board);
+            reached = reached | grown;
+        }
+        boards.push(*self);
+        boards
+    }
+
+    /// Updates this tile and every placed tile connected to it to belong to the
+    /// passed company.
+    fn update_chain(&mut self, tile: Tile, company: Company) {
+        self.played.insert(tile);
+        let chain = Cells::single(tile).flood(self.played);
+
+        for (_, cells) in &mut self.companies {
+            *cells = *cells - chain;
+        }
+        self.companies[company] = self.companies[company] | chain;
+
+        for (company, cells) in &self.companies {
+            self.company_sizes[company] = cells.len();
+        }
     }
 
     /// Checks if a tile is "dead".
@@ -204,11 +245,11 @@ impl Board {
     #[inline]
     pub fn dead_tile(&self, tile: Tile) -> bool {
         let mut neighbors: CompanyMap<u8> = Default::default();
-        self.for_each_neighbor(tile, |neighbor| {
+        for neighbor in Self::neighbors(tile) {
             if let Some(Some(affil)) = self[neighbor] {
                 neighbors[affil] = 1;
             }
-        });
+        }
 
         let neighbor_count: u8 = neighbors.into_iter().map(|(_, num)| num).sum();
         neighbor_count > 1
@@ -220,11 +261,11 @@ impl Board {
     {
         let mut bordering_tiles = HashSet::new();
 
-        self.for_each_neighbor(placement.tile, |neighbor| {
-            self[neighbor].map(|c| {
-                bordering_tiles.insert(c)
-            });
-        });
+        for neighbor in Self::neighbors(placement.tile) {
+            if let Some(c) = self[neighbor] {
+                bordering_tiles.insert(c);
+            }
+        }
 
         // See if the tile was placed with any neighboring tiles
         if bordering_tiles.is_empty() {
@@ -285,6 +326,11 @@ impl Board {
                             }
                         }
 
+                        // Ensure the surviving company borders the tile
+                        if !bordering_tiles.remove(&Some(merge.into)) {
+                            return Err(IncorrectImplication::IncorrectSurvivor(merge.into))
+                        }
+
                         // Ensure all the bordering companies are listed in the defunct
                         if let Some(missed) = bordering_tiles.into_iter().next() {
                             // Unwrap never panics, as the only None element was removed earlier.
@@ -309,6 +355,13 @@ impl Board {
         Ok(())
     }
 
+    /// Gets the tile that began the merge in progress, if there is one. Until
+    /// the merge is resolved, this tile belongs to no company.
+    #[inline]
+    pub fn merger_tile(&self) -> Option<Tile> {
+        self.merger_tile
+    }
+
     /// Determines if a company exists.
     #[inline]
     pub fn company_exists(&self, company: Company) -> bool {
@@ -323,7 +376,7 @@ impl Board {
     
     /// Gets the stock price per share of a given company. If the company
     /// doesn't exist, zero will be returned.
-    pub fn stock_price(&self, company: Company) -> u32 {
+    pub fn stock_price(&self, company: Company) -> Money {
         let base_price = match self.company_sizes[company] {
             2 => 200,
             3 => 300,
@@ -334,13 +387,13 @@ impl Board {
             21..=30 => 800,
             31..=40 => 900,
             41.. => 1000,
-            _ => return 0,
+            _ => return Money::ZERO,
         };
-        match company {
+        Money::new(match company {
             Company::Continental | Company::Imperial => base_price + 200,
             Company::American | Company::Festival | Company::Worldwide => base_price + 100,
             Company::Luxor | Company::Tower => base_price,
-        }
+        })
     }
 }
 
@@ -407,11 +460,254 @@ impl TermRender for Board {
     }
 }
 
+/// How large each cell of the board is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoardScale {
+    /// One character per cell.
+    Small,
+    /// Three characters per cell, with the initial of each company on every
+    /// one of its tiles.
+    Medium,
+    /// Five characters and two lines per cell, with the label of every tile
+    /// along with the initial of its company.
+    Large,
+}
+
+impl BoardScale {
+    /// Gets the number of columns and lines the board takes up when drawn at
+    /// this scale.
+    pub fn size(self) -> (u16, u16) {
+        let rows = Tile::NUM_ROWS as u16;
+        let cols = Tile::col_as_num(Tile::LAST_COL) as u16;
+        match self {
+            BoardScale::Small => (rows + 1, cols + 1),
+            BoardScale::Medium => (rows * 3 + 2, cols + 1),
+            BoardScale::Large => (rows * 5, cols * 2),
+        }
+    }
+
+    /// Gets the largest scale at which the board fits within `size`, falling
+    /// back to [`BoardScale::Small`] if none does.
+    pub fn fitting(size: (u16, u16)) -> Self {
+        [BoardScale::Large, BoardScale::Medium]
+            .into_iter()
+            .find(|scale| {
+                let (width, height) = scale.size();
+                width <= size.0 && height <= size.1
+            })
+            .unwrap_or(BoardScale::Small)
+    }
+}
+
+/// A board drawn at a particular [`BoardScale`].
+#[derive(Debug, Clone, Copy)]
+pub struct ScaledBoard<'b> {
+    board: &'b Board,
+    scale: BoardScale,
+}
+
+impl Board {
+    /// Prepares the board to be drawn at a scale.
+    pub fn scaled(&self, scale: BoardScale) -> ScaledBoard<'_> {
+        ScaledBoard { board: self, scale }
+    }
+}
+
+impl<'b> TermRender for ScaledBoard<'b> {
+    fn render(&self, term: &mut TermWriter) -> Result<(), TermWriteError> {
+        let board = self.board;
+        match self.scale {
+            BoardScale::Small => board.render(term),
+            BoardScale::Medium => {
+                // Write the legend for column numbers
+                term.write_str("  ")?;
+                for r in 1..=Tile::NUM_ROWS {
+                    term.write_str(&format!("{r:^3}"))?;
+                }
+
+                for c in 'a'..=Tile::LAST_COL {
+                    term.new_line();
+                    term.write_str(&format!("{c} "))?;
+
+                    for r in 1..=Tile::NUM_ROWS {
+                        let tile = Tile::new(r, c);
+                        match board[tile] {
+                            Some(Some(company)) => {
+                                let text = if board.headquarters[company] == Some(tile) {
+                                    format!("[{}]", company.char())
+                                } else {
+                                    format!(" {} ", company.char())
+                                };
+                                term.write_bg_colored(&*text, company)?;
+                            },
+                            Some(None) => term.write_str(" 0 ")?,
+                            None => term.write_str(" . ")?,
+                        }
+                    }
+                }
+                term.new_line();
+                Ok(())
+            },
+            BoardScale::Large => {
+                for c in 'a'..=Tile::LAST_COL {
+                    // Each cell spans two lines: the label of the tile, then
+                    // what it belongs to
+                    for line in 0..2 {
+                        for r in 1..=Tile::NUM_ROWS {
+                            let tile = Tile::new(r, c);
+                            let text = match (line, board[tile]) {
+                                (0, _) => format!("{:<4}", tile.to_string()),
+                                (_, Some(Some(company))) => {
+                                    let mark = if board.headquarters[company] == Some(tile) {
+                                        "HQ"
+                                    } else if board.company_is_safe(company) {
+                                        "++"
+                                    } else {
+                                        ""
+                                    };
+                                    format!("{} {mark:<2}", company.char())
+                                },
+                                _ => "    ".to_owned(),
+                            };
+
+                            match board[tile] {
+                                Some(Some(company)) => term.write_bg_colored(&*text, company)?,
+                                Some(None) => term.write_bg_colored(&*text, color::LightBlack)?,
+                                None => term.write_fg_colored(&*text, color::LightBlack)?,
+                            }
+                            term.write_char(' ')?;
+                        }
+                        term.new_line();
+                    }
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
 impl ops::Index<Tile> for Board {
     type Output = Option<Option<Company>>;
 
     fn index(&self, index: Tile) -> &Self::Output {
-        let num_cols = Tile::col_as_num(Tile::LAST_COL);
-        &self.tiles[((index.row()-1) * num_cols + (Tile::col_as_num(index.col())-1)) as usize]
+        /// The state of a cell belonging to each company, in order of
+        /// [`Company::id`].
+        static AFFILIATED: [Option<Option<Company>>; 7] = [
+            Some(Some(Company::Continental)),
+            Some(Some(Company::Imperial)),
+            Some(Some(Company::American)),
+            Some(Some(Company::Festival)),
+            Some(Some(Company::Worldwide)),
+            Some(Some(Company::Luxor)),
+            Some(Some(Company::Tower)),
+        ];
+
+        if !self.played.contains(index) {
+            return &None;
+        }
+        match self.companies.iter().find(|(_, cells)| cells.contains(index)) {
+            Some((company, _)) => &AFFILIATED[company.id()],
+            None => &Some(None),
+        }
+    }
+}
+
+/// The form in which a [`Board`] is serialized. Only occupied cells are
+/// listed, since formats such as JSON write both [`None`] and [`Some(None)`]
+/// as `null`.
+#[derive(Serialize, Deserialize)]
+struct BoardRepr {
+    tiles: Vec<(Tile, Option<Company>)>,
+    company_sizes: CompanyMap<u8>,
+    merger_tile: Option<Tile>,
+    headquarters: CompanyMap<Option<Tile>>,
+}
+
+impl From<Board> for BoardRepr {
+    fn from(board: Board) -> Self {
+        let tiles = board.played.iter()
+            .map(|tile| (tile, board[tile].flatten()))
+            .collect();
+
+        Self {
+            tiles,
+            company_sizes: board.company_sizes,
+            merger_tile: board.merger_tile,
+            headquarters: board.headquarters,
+        }
+    }
+}
+
+impl From<BoardRepr> for Board {
+    fn from(repr: BoardRepr) -> Self {
+        let mut board = Board::new();
+        for (tile, cell) in repr.tiles {
+            board.set_tile(tile, cell);
+        }
+        board.company_sizes = repr.company_sizes;
+        board.merger_tile = repr.merger_tile;
+        board.headquarters = repr.headquarters;
+        board
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::cells::Cells;
+    use crate::game::messages::{TilePlacement, TilePlacementImplication};
+    use crate::game::tile::Tile;
+    use crate::game::Company;
+
+    use super::{Board, BoardScale};
+
+    #[test]
+    fn chain_queries() {
+        let mut board = Board::new();
+        for tile in [Tile::new(1, 'a'), Tile::new(3, 'a'), Tile::new(3, 'b')] {
+            board.place_tile(TilePlacement { tile, implication: None });
+        }
+        board.place_tile(TilePlacement {
+            tile: Tile::new(2, 'a'),
+            implication: Some(TilePlacementImplication::FoundsCompany(Company::Luxor)),
+        });
+        board.place_tile(TilePlacement { tile: Tile::new(5, 'a'), implication: None });
+
+        let luxor: Vec<_> = board.tiles_of(Company::Luxor).collect();
+        assert_eq!(luxor, [Tile::new(1, 'a'), Tile::new(2, 'a'), Tile::new(3, 'a'), Tile::new(3, 'b')]);
+
+        let chain: Vec<_> = board.chain_containing(Tile::new(3, 'b')).iter().collect();
+        assert_eq!(chain, luxor);
+        assert_eq!(board.chain_containing(Tile::new(5, 'a')), Cells::single(Tile::new(5, 'a')));
+        assert!(board.chain_containing(Tile::new(4, 'a')).is_empty());
+
+        assert_eq!(Board::neighbors(Tile::new(1, 'a')).count(), 2);
+        assert_eq!(Board::neighbors(Tile::new(2, 'b')).count(), 4);
+    }
+
+    #[test]
+    fn founding_spreads_from_the_tile_placed() {
+        let mut before = Board::new();
+        for tile in [Tile::new(1, 'a'), Tile::new(3, 'a'), Tile::new(4, 'a')] {
+            before.place_tile(TilePlacement { tile, implication: None });
+        }
+        let mut after = before;
+        after.place_tile(TilePlacement {
+            tile: Tile::new(2, 'a'),
+            implication: Some(TilePlacementImplication::FoundsCompany(Company::Tower)),
+        });
+
+        let boards = after.spreading_from(&before, Tile::new(2, 'a'));
+        let sizes: Vec<_> = boards.iter().map(|board| board.company_sizes[Company::Tower]).collect();
+        assert_eq!(sizes, [1, 3, 4]);
+        assert_eq!(boards[0][Tile::new(4, 'a')], Some(None));
+    }
+
+    #[test]
+    fn board_scale_fits_panel() {
+        assert_eq!(BoardScale::fitting((13, 10)), BoardScale::Small);
+        assert_eq!(BoardScale::fitting((38, 10)), BoardScale::Medium);
+        assert_eq!(BoardScale::fitting((60, 17)), BoardScale::Medium);
+        assert_eq!(BoardScale::fitting((60, 18)), BoardScale::Large);
+        assert_eq!(BoardScale::fitting((0, 0)), BoardScale::Small);
     }
 }
@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use super::hint::{self, Hint};
+use super::kernel::{Game, Ambiguous};
+use super::tile::Hand;
+
+/// Declares the search used by [`Difficulty::Hard`].
+mod monte_carlo;
+
+/// How much effort a [`Bot`] puts into choosing its actions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    /// Plays whatever the hint engine suggests, without looking ahead.
+    #[default]
+    Easy,
+    /// Plays out randomized games from every candidate action, and chooses
+    /// the action that fares best on average.
+    Hard,
+}
+
+/// A computer player, able to choose an action for any player in a game.
+#[derive(Debug, Clone)]
+pub struct Bot {
+    pub difficulty: Difficulty,
+    /// The time a [`Difficulty::Hard`] bot may spend choosing a single
+    /// action.
+    pub time_budget: Duration,
+}
+
+impl Bot {
+    pub const DEFAULT_TIME_BUDGET: Duration = Duration::from_secs(1);
+
+    pub fn new(difficulty: Difficulty) -> Self {
+        Self {
+            difficulty,
+            time_budget: Self::DEFAULT_TIME_BUDGET,
+        }
+    }
+
+    /// Sets the time this bot may spend choosing a single action.
+    pub fn with_time_budget(mut self, time_budget: Duration) -> Self {
+        self.time_budget = time_budget;
+        self
+    }
+
+    /// Chooses an action for `player`, whose hand is `hand`. Returns [`None`]
+    /// if no action is needed from the player, or if the player has no
+    /// playable tile.
+    pub fn choose_action(&self, game: &Game<Ambiguous>, player: &str, hand: &Hand)
+        -> Option<Hint>
+    {
+        match self.difficulty {
+            Difficulty::Easy => hint::suggest(game, player, Some(hand)),
+            Difficulty::Hard => monte_carlo::search(game, player, hand, self.time_budget),
+        }
+    }
+}
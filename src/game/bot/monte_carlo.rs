@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::game::hint::{self, Hint};
+use crate::game::kernel::{Game, Ambiguous};
+use crate::game::messages::*;
+use crate::game::tile::{Boneyard, Hand, Tile};
+use crate::game::Money;
+
+/// The number of rounds of play simulated after each candidate action. The
+/// heuristic used to play out the rest of the game grows less reliable the
+/// further it looks ahead, so rollouts are kept short.
+const ROLLOUT_ROUNDS: usize = 3;
+
+/// Chooses an action for `player` by playing out randomized games from each
+/// legal action until the `time_budget` runs out. Every candidate is played
+/// out at least once, however long that takes.
+pub(super) fn search(
+    game: &Game<Ambiguous>,
+    player: &str,
+    hand: &Hand,
+    time_budget: Duration,
+) -> Option<Hint> {
+    let deadline = Instant::now() + time_budget;
+
+    match game.needed_action() {
+        // Rollouts stop as soon as a merge begins, so they cannot tell one
+        // resolution of defunct stock from another.
+        ActionRequest::ResolveMergeStock { .. } => {
+            return hint::suggest(game, player, Some(hand));
+        },
+        ActionRequest::PlayTile | ActionRequest::BuyStock => {
+            if game.active_player() != player { return None; }
+        },
+    }
+    let candidates = game.legal_actions(Some(hand));
+
+    if candidates.len() <= 1 {
+        return candidates.into_iter().next().map(|action| Hint {
+            action,
+            rationale: "It is the only legal move".to_owned(),
+        });
+    }
+
+    let max_actions = game.players().len() * 2 * ROLLOUT_ROUNDS;
+    let mut totals = vec![0u64; candidates.len()];
+    let mut rollouts = 0;
+
+    loop {
+        for (&action, total) in candidates.iter().zip(&mut totals) {
+            let rollout = Rollout::deal(game, player, hand);
+            *total += rollout.play_out(player, action, max_actions).dollars() as u64;
+        }
+        rollouts += 1;
+
+        if Instant::now() >= deadline { break; }
+    }
+
+    let (action, total) = candidates.into_iter()
+        .zip(totals)
+        .max_by_key(|(_, total)| *total)
+        .unwrap();
+
+    Some(Hint {
+        action,
+        rationale: format!(
+            "It averaged {} in net worth over {rollouts} simulated games",
+            Money::new((total / rollouts) as u32)
+        ),
+    })
+}
+
+/// A copy of a game in which the information hidden from a player, the hands
+/// of their opponents and the contents of the boneyard, is filled in at
+/// random.
+struct Rollout {
+    game: Game<Ambiguous>,
+    hands: HashMap<Box<str>, Hand>,
+    boneyard: Boneyard<Tile>,
+}
+
+impl Rollout {
+    /// Deals random hands to the opponents of `player` from the tiles that
+    /// `player` cannot see.
+    fn deal(game: &Game<Ambiguous>, player: &str, hand: &Hand) -> Self {
+        let unseen = (1..=Tile::NUM_ROWS)
+            .flat_map(|row| ('a'..=Tile::LAST_COL).map(move |col| Tile::new(row, col)))
+            .filter(|&tile| game.board()[tile].is_none())
+            .filter(|tile| !hand.iter().any(|held| held == tile))
+            .collect();
+        let mut boneyard = Boneyard::new(unseen);
+
+        let hands = game.players().keys()
+            .map(|name| {
+                let hand = if &**name == player {
+                    *hand
+                } else {
+                    Hand::from_boneyard(&mut boneyard)
+                        .map(Hand::from)
+                        .unwrap_or_else(|partial| partial)
+                };
+                (name.clone(), hand)
+            })
+            .collect();
+
+        Self { game: game.clone(), hands, boneyard }
+    }
+
+    /// Plays `first` as `player`, then plays out the game with the hint
+    /// engine for up to `max_actions` actions. Returns the net worth of
+    /// `player` at the end of the rollout.
+    fn play_out(mut self, player: &str, first: PlayerAction, max_actions: usize) -> Money {
+        let mut action = TaggedPlayerAction {
+            player_name: player.into(),
+            action: first,
+            number: None,
+        };
+
+        for _ in 0..max_actions {
+            self.game = match self.game.try_advance_game(&action) {
+                Ok(Ok(game)) => game,
+                Ok(Err(game_over)) => {
+                    // A game that can't be tallied is judged as it stands
+                    let worth = game_over.net_worth(player);
+                    return game_over.tally_results().map_or(worth, |results| {
+                        results.final_standings.iter()
+                            .find(|result| &*result.player_name == player)
+                            .map_or(Money::ZERO, |result| result.final_money)
+                    });
+                },
+                Err((game, _)) => {
+                    self.game = game;
+                    break;
+                },
+            };
+            self.draw(&action);
+
+            // Play stops at the start of a merge, whose principle shareholder
+            // bonuses have already been paid out.
+            if let ActionRequest::ResolveMergeStock { .. } = self.game.needed_action() {
+                break;
+            }
+
+            let actor = self.game.active_player();
+            action = match hint::suggest(&self.game, actor, self.hands.get(actor)) {
+                Some(hint) => TaggedPlayerAction {
+                    player_name: actor.into(),
+                    action: hint.action,
+                    number: None,
+                },
+                None => break,
+            };
+        }
+
+        self.game.net_worth(player)
+    }
+
+    /// Updates the hand of the player that took `action`.
+    fn draw(&mut self, action: &TaggedPlayerAction) {
+        let hand = match self.hands.get_mut(&action.player_name) {
+            Some(hand) => hand,
+            None => return,
+        };
+
+        match action.action {
+            PlayerAction::PlayTile { placement } => {
+                hand.remove_tile(placement.tile);
+            },
+            PlayerAction::BuyStock { .. } => {
+                if let Some(tile) = self.boneyard.remove() {
+                    let _ = hand.insert_tile(tile);
+                }
+            },
+            PlayerAction::ResolveMergeStock { .. } => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::game::bot::{Bot, Difficulty};
+    use crate::game::kernel::Game;
+    use crate::game::messages::*;
+    use crate::game::tile::{Hand, Tile};
+
+    #[test]
+    fn hard_bot_plays_legally() {
+        let game = Game::start(&GameStart::two_player_test());
+
+        let mut hand = Hand::default();
+        for tile in [Tile::new(5, 'e'), Tile::new(1, 'b'), Tile::new(12, 'a')] {
+            hand.insert_tile(tile).unwrap();
+        }
+
+        let bot = Bot::new(Difficulty::Hard).with_time_budget(Duration::from_millis(50));
+        let hint = bot.choose_action(&game.clone().into(), "alice", &hand).unwrap();
+
+        let action = TaggedPlayerAction {
+            player_name: "alice".into(),
+            action: hint.action,
+            number: None,
+        };
+        assert!(game.check_player_action(&action).is_ok());
+        match hint.action {
+            PlayerAction::PlayTile { placement } => {
+                assert!(hand.iter().any(|&tile| tile == placement.tile));
+            },
+            _ => panic!("expected a tile placement"),
+        }
+
+        // No action is chosen out of turn
+        assert!(bot.choose_action(&game.into(), "bob", &hand).is_none());
+    }
+}
@@ -0,0 +1,163 @@
+use std::ops;
+
+use super::tile::Tile;
+
+/// The number of columns on the board.
+const NUM_COLS: u32 = Tile::LAST_COL as u32 - 'a' as u32 + 1;
+/// The number of cells on the board.
+const NUM_CELLS: u32 = Tile::NUM_ROWS as u32 * NUM_COLS;
+
+/// Every cell on the board.
+const ALL: u128 = (1 << NUM_CELLS) - 1;
+/// The cells in the first column of the board.
+const FIRST_COL: u128 = column_mask(0);
+/// The cells in the last column of the board.
+const LAST_COL: u128 = column_mask(NUM_COLS - 1);
+
+const fn column_mask(col: u32) -> u128 {
+    let mut mask = 0;
+    let mut row = 0;
+    while row < Tile::NUM_ROWS as u32 {
+        mask |= 1 << (row * NUM_COLS + col);
+        row += 1;
+    }
+    mask
+}
+
+/// A set of cells on the board, stored as one bit per cell.
+///
+/// Cells are numbered in ascending order by row, with the column breaking
+/// ties, so neighboring cells in a row are one bit apart and neighboring cells
+/// in a column are [`NUM_COLS`] bits apart. This makes membership tests, set
+/// operations, and growing a set by its neighbors a handful of instructions
+/// each.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Cells(u128);
+
+impl Cells {
+    /// The set containing no cells.
+    pub const EMPTY: Cells = Cells(0);
+    /// The set containing every cell on the board.
+    pub const ALL: Cells = Cells(ALL);
+
+    /// Gets the set containing only `tile`.
+    pub fn single(tile: Tile) -> Self {
+        Cells(1 << index(tile))
+    }
+
+    pub fn contains(&self, tile: Tile) -> bool {
+        self.0 & (1 << index(tile)) != 0
+    }
+
+    pub fn insert(&mut self, tile: Tile) {
+        self.0 |= 1 << index(tile);
+    }
+
+    pub fn remove(&mut self, tile: Tile) {
+        self.0 &= !(1 << index(tile));
+    }
+
+    /// Gets the number of cells in the set.
+    pub fn len(&self) -> u8 {
+        self.0.count_ones() as u8
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Gets every cell that borders a cell in this set but isn't in it.
+    pub fn border(self) -> Self {
+        let bits = self.0;
+        let grown = bits << NUM_COLS
+            | bits >> NUM_COLS
+            | (bits & !LAST_COL) << 1
+            | (bits & !FIRST_COL) >> 1;
+        Cells(grown & ALL & !bits)
+    }
+
+    /// Gets the cells of `within` that are connected to this set through
+    /// other cells of `within`, along with this set itself.
+    pub fn flood(self, within: Cells) -> Self {
+        let mut filled = self;
+        loop {
+            let grown = filled.border() & within;
+            if grown.is_empty() { return filled; }
+            filled = filled | grown;
+        }
+    }
+
+    /// Iterates over the cells of the set, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = Tile> {
+        let mut bits = self.0;
+        std::iter::from_fn(move || {
+            if bits == 0 { return None; }
+            let index = bits.trailing_zeros();
+            bits &= bits - 1;
+            Some(Tile::new((index / NUM_COLS + 1) as u8, (b'a' + (index % NUM_COLS) as u8) as char))
+        })
+    }
+}
+
+/// Gets the bit that represents a tile.
+fn index(tile: Tile) -> u32 {
+    (tile.row() as u32 - 1) * NUM_COLS + (tile.col() as u32 - 'a' as u32)
+}
+
+impl FromIterator<Tile> for Cells {
+    fn from_iter<T: IntoIterator<Item = Tile>>(iter: T) -> Self {
+        let mut cells = Cells::EMPTY;
+        for tile in iter {
+            cells.insert(tile);
+        }
+        cells
+    }
+}
+
+impl ops::BitOr for Cells {
+    type Output = Cells;
+
+    fn bitor(self, rhs: Cells) -> Cells {
+        Cells(self.0 | rhs.0)
+    }
+}
+
+impl ops::BitAnd for Cells {
+    type Output = Cells;
+
+    fn bitand(self, rhs: Cells) -> Cells {
+        Cells(self.0 & rhs.0)
+    }
+}
+
+impl ops::Sub for Cells {
+    type Output = Cells;
+
+    /// Gets the cells of this set that aren't in `rhs`.
+    fn sub(self, rhs: Cells) -> Cells {
+        Cells(self.0 & !rhs.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::tile::Tile;
+
+    use super::Cells;
+
+    #[test]
+    fn border_stays_on_the_board() {
+        // The border of a corner never wraps around to the other side
+        let corner = Cells::single(Tile::new(1, 'i'));
+        let border: Vec<_> = corner.border().iter().collect();
+        assert_eq!(border, [Tile::new(1, 'h'), Tile::new(2, 'i')]);
+
+        assert_eq!(Cells::ALL.border(), Cells::EMPTY);
+        assert_eq!(Cells::ALL.iter().count(), 108);
+        assert!(Cells::ALL.iter().all(|tile| Cells::ALL.contains(tile)));
+
+        let row: Cells = ('a'..='i').map(|col| Tile::new(12, col)).collect();
+        let within = row | Cells::single(Tile::new(11, 'a'));
+        assert_eq!(Cells::single(Tile::new(12, 'e')).flood(within), within);
+    }
+}
@@ -279,8 +279,8 @@ impl<'de, T: Deserialize<'de>> Deserialize<'de> for CompanyMap<T> {
             map.continental,
             map.imperial,
             map.american,
-            map.worldwide,
             map.festival,
+            map.worldwide,
             map.luxor,
             map.tower
         ] })
@@ -298,3 +298,20 @@ struct CompanyMapStruct<T> {
     luxor: T,
     tower: T,
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Company, CompanyMap};
+
+    #[test]
+    fn company_map_round_trips() {
+        let mut map = CompanyMap::new(&0);
+        for (i, (_, value)) in map.iter_mut().enumerate() {
+            *value = i;
+        }
+        let json = serde_json::to_string(&map).unwrap();
+        let read: CompanyMap<usize> = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, map);
+        assert_eq!(read[Company::Worldwide], map[Company::Worldwide]);
+    }
+}
@@ -0,0 +1,213 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+
+use super::messages::GameStart;
+use super::tile::{FullHand, Tile};
+
+/// The seed from which the tiles of a game are shuffled, when the server
+/// commits to the order of the tiles before dealing them. The server publishes
+/// a hash of the seed as the game starts and reveals the seed once it ends, so
+/// that players can check that the tiles were dealt as the seed dictates,
+/// rather than arranged as the game went on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DealSeed([u8; 32]);
+
+impl DealSeed {
+    pub fn random() -> Self {
+        Self(rand::random())
+    }
+
+    /// Gets the hash of the seed published at the start of the game. It is a
+    /// SHA-256 hash, so the players learn nothing of the seed from it, and
+    /// the server can't find another seed with the same hash to deal from.
+    pub fn commitment(&self) -> DealCommitment {
+        let mut hash = Sha256::new();
+        hash.update(b"acquire deal");
+        hash.update(self.0);
+        DealCommitment(hash.finalize().into())
+    }
+
+    /// Gets every tile, in the order in which they are dealt.
+    pub fn tiles(&self) -> Vec<Tile> {
+        let mut tiles: Vec<_> = Tile::boneyard().iter().copied().collect();
+        tiles.shuffle(&mut StdRng::from_seed(self.0));
+        tiles
+    }
+
+    /// Checks the deal of a game against this seed: the commitment, the
+    /// tiles that decided the order of play, and the opening hand of
+    /// `player` and the tiles they drew later on, if known. The server deals
+    /// a starting tile to each player in order of their names, and then a
+    /// hand to each in order of play. Unless the seats were drawn at random,
+    /// the order of play must also be that of the starting tiles.
+    ///
+    /// Every tile drawn after the opening hands is the next of the seed's, so
+    /// each of `draws` is given as the number of tiles anyone drew after the
+    /// opening hands before it, along with the tile drawn.
+    pub fn verify(&self, start: &GameStart, hand: Option<(&str, &FullHand)>, draws: &[(usize, Tile)])
+        -> Result<(), DealMismatch>
+    {
+        if start.deal_commitment != Some(self.commitment()) {
+            return Err(DealMismatch::Commitment);
+        }
+
+        let tiles = self.tiles();
+        let players = start.play_order.len();
+        let mut names = start.play_order.to_vec();
+        names.sort();
+        let mut starting: Vec<_> = names.into_iter().zip(tiles.iter().copied()).collect();
+        let mut dealt: Vec<_> = start.play_order.iter().cloned()
+            .zip(start.tiles_placed.iter().copied())
+            .collect();
+        match start.random_seating {
+            true => dealt.sort(),
+            false => starting.sort_by_key(|&(_, tile)| tile),
+        }
+        if dealt != starting {
+            return Err(DealMismatch::StartingTiles);
+        }
+
+        if let Some((player, hand)) = hand {
+            let seat = start.play_order.iter()
+                .position(|name| &**name == player)
+                .ok_or(DealMismatch::Hand)?;
+            let offset = players + seat * 6;
+            let mut expected = tiles.get(offset..offset + 6).ok_or(DealMismatch::Hand)?.to_vec();
+            let mut found: Vec<_> = hand.iter().copied().collect();
+            expected.sort();
+            found.sort();
+            if expected != found {
+                return Err(DealMismatch::Hand);
+            }
+        }
+
+        let opening = players * 7;
+        for &(drawn, tile) in draws {
+            if tiles.get(opening + drawn) != Some(&tile) {
+                return Err(DealMismatch::Draw(tile));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The hash of a [`DealSeed`], published as a game starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DealCommitment([u8; 32]);
+
+/// Writes and reads 32 bytes as 64 hexadecimal digits, as both the seed and
+/// its commitment are written.
+macro_rules! hex_bytes {
+    ($name:ident) => {
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let invalid = || format!("expected 64 hexadecimal digits, got '{s}'");
+                if s.len() != 64 || !s.is_ascii() {
+                    return Err(invalid());
+                }
+
+                let mut bytes = [0; 32];
+                for (i, byte) in bytes.iter_mut().enumerate() {
+                    *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).map_err(|_| invalid())?;
+                }
+                Ok(Self(bytes))
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                where S: Serializer
+            {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+                where D: Deserializer<'de>
+            {
+                let s = String::deserialize(deserializer)?;
+                s.parse().map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+hex_bytes!(DealSeed);
+hex_bytes!(DealCommitment);
+
+/// A way in which a game was not dealt as its [`DealSeed`] dictates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum DealMismatch {
+    #[error("the seed doesn't match the commitment made at the start of the game")]
+    Commitment,
+    #[error("the starting tiles aren't the ones the seed deals")]
+    StartingTiles,
+    #[error("your opening hand isn't the one the seed deals")]
+    Hand,
+    #[error("you drew {0}, which the seed doesn't deal you then")]
+    Draw(Tile),
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::messages::GameStart;
+    use crate::game::tile::{Boneyard, Hand};
+
+    use super::{DealMismatch, DealSeed};
+
+    #[test]
+    fn seed_verifies_its_own_deal() {
+        let seed = DealSeed::random();
+        assert_eq!(seed.to_string().parse(), Ok(seed));
+
+        // Deal as the server does, in order of the players' names
+        let mut boneyard = Boneyard::stacked(seed.tiles());
+        let mut starting = [("alice", boneyard.remove().unwrap()), ("bob", boneyard.remove().unwrap())];
+        starting.sort_by_key(|&(_, tile)| tile);
+        let hands: Vec<_> = starting.iter()
+            .map(|_| Hand::from_boneyard(&mut boneyard).unwrap())
+            .collect();
+
+        let mut start = GameStart {
+            tiles_placed: starting.iter().map(|&(_, tile)| tile).collect(),
+            play_order: starting.iter().map(|&(name, _)| name.into()).collect(),
+            deal_commitment: Some(seed.commitment()),
+            ..GameStart::two_player_test()
+        };
+        let first = &*start.play_order[0];
+        assert_eq!(seed.verify(&start, Some((first, &hands[0])), &[]), Ok(()));
+        assert_eq!(seed.verify(&start, Some((first, &hands[1])), &[]), Err(DealMismatch::Hand));
+
+        // Tiles drawn later come off the same boneyard, in order
+        let drawn = [boneyard.remove().unwrap(), boneyard.remove().unwrap()];
+        assert_eq!(seed.verify(&start, None, &[(1, drawn[1])]), Ok(()));
+        assert_eq!(seed.verify(&start, None, &[(0, drawn[1])]), Err(DealMismatch::Draw(drawn[1])));
+
+        // Seats drawn at random may be in any order, but the tiles must still
+        // be the ones each player drew
+        let mut shuffled = start.clone();
+        shuffled.random_seating = true;
+        shuffled.play_order.reverse();
+        shuffled.tiles_placed.reverse();
+        assert_eq!(seed.verify(&shuffled, None, &[]), Ok(()));
+        shuffled.tiles_placed.swap(0, 1);
+        assert_eq!(seed.verify(&shuffled, None, &[]), Err(DealMismatch::StartingTiles));
+
+        start.deal_commitment = Some(DealSeed::random().commitment());
+        assert_eq!(seed.verify(&start, None, &[]), Err(DealMismatch::Commitment));
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Identifies a game among every game hosted anywhere, so that its result can
+/// be told apart from others once submitted to a tracker. It is a random
+/// (version 4) UUID, written in its usual hyphenated form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GameId(u128);
+
+impl GameId {
+    /// Makes up an ID at random.
+    pub fn generate() -> Self {
+        let bits: u128 = rand::thread_rng().gen();
+        // Mark the UUID as version 4, of the RFC 4122 variant
+        let bits = bits & !(0xf << 76) | (0x4 << 76);
+        let bits = bits & !(0x3 << 62) | (0x2 << 62);
+        Self(bits)
+    }
+}
+
+impl fmt::Display for GameId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = format!("{:032x}", self.0);
+        write!(f, "{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+    }
+}
+
+/// Reasons a game ID could not be read.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("'{0}' is not a valid game ID")]
+pub struct InvalidGameId(String);
+
+impl FromStr for GameId {
+    type Err = InvalidGameId;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let groups: Vec<_> = s.split('-').collect();
+        let well_formed = groups.iter().map(|group| group.len()).eq([8, 4, 4, 4, 12])
+            && groups.iter().all(|group| group.chars().all(|c| c.is_ascii_hexdigit()));
+        match well_formed {
+            true => Ok(Self(u128::from_str_radix(&groups.concat(), 16).unwrap())),
+            false => Err(InvalidGameId(s.to_owned())),
+        }
+    }
+}
+
+impl Serialize for GameId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for GameId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id: Box<str> = Deserialize::deserialize(deserializer)?;
+        id.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::GameId;
+
+    #[test]
+    fn game_id_round_trips() {
+        let id = GameId::generate();
+        let text = id.to_string();
+        assert_eq!(text.len(), 36);
+        assert_eq!(text.as_bytes()[14], b'4');
+        assert_eq!(text.parse::<GameId>().unwrap(), id);
+        assert_ne!(id, GameId::generate());
+
+        assert!("not-a-game-id".parse::<GameId>().is_err());
+        assert!("0123456789abcdef0123456789abcdef".parse::<GameId>().is_err());
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::HashSet;
+
+use super::board::Board;
+use super::kernel::{Game, Ambiguous, GameDisambiguation, ResolvingMerge, BuyingStock, PlacingTile};
+use super::messages::*;
+use super::tile::Hand;
+use super::{Company, CompanyMap, Money};
+
+/// An action suggested to a player, along with a one-line rationale.
+#[derive(Debug, Clone)]
+pub struct Hint {
+    pub action: PlayerAction,
+    pub rationale: String,
+}
+
+/// Evaluates the game from the point of view of `player` and suggests an
+/// action. The evaluation is deliberately lightweight: it considers majority
+/// positions in each company, the growth potential of chains, and the cash
+/// the player has on hand. Returns [`None`] if no action is needed from the
+/// player, or if the player's hand is needed but not known.
+pub fn suggest(game: &Game<Ambiguous>, player: &str, hand: Option<&Hand>) -> Option<Hint> {
+    match game.clone().disambiguate() {
+        GameDisambiguation::PlacingTile(game) => {
+            if game.active_player() != player { return None; }
+            suggest_placement(&game, player, hand?)
+        },
+        GameDisambiguation::ResolvingMerge(game) => {
+            if game.resolving_player() != player { return None; }
+            Some(suggest_resolution(&game, player))
+        },
+        GameDisambiguation::BuyingStock(game) => {
+            if game.active_player() != player { return None; }
+            Some(suggest_purchase(&game, player))
+        },
+    }
+}
+
+/// The number of shares `player` holds in `company`, along with the most
+/// shares held by any other single player.
+fn majority_position<S: crate::game::kernel::State>(
+    game: &Game<S>,
+    player: &str,
+    company: Company
+) -> (u8, u8) {
+    let mine = game.players()[player].holdings[company];
+    let best_other = game.players().iter()
+        .filter(|(name, _)| &***name != player)
+        .map(|(_, data)| data.holdings[company])
+        .max()
+        .unwrap_or(0);
+    (mine, best_other)
+}
+
+fn suggest_placement(game: &Game<PlacingTile>, player: &str, hand: &Hand) -> Option<Hint> {
+    let board = game.board();
+
+    // Best placement so far, along with its score
+    let mut best: Option<(i64, Hint)> = None;
+
+    for &tile in hand.iter() {
+        let mut companies = HashSet::new();
+        let mut unaffiliated = false;
+        for neighbor in Board::neighbors(tile) {
+            match board[neighbor] {
+                Some(Some(company)) => { companies.insert(company); },
+                Some(None) => unaffiliated = true,
+                None => {},
+            }
+        }
+
+        let (implication, score, rationale) = match companies.len() {
+            0 if unaffiliated => {
+                // Prefer founding the most valuable company available
+                let company = match CompanyMap::new(&()).iter()
+                    .map(|(company, _)| company)
+                    .filter(|&company| !board.company_exists(company))
+                    .max_by_key(|&company| founding_price(company))
+                {
+                    Some(v) => v,
+                    None => continue,
+                };
+                (
+                    Some(TilePlacementImplication::FoundsCompany(company)),
+                    3000 + founding_price(company) as i64,
+                    format!("founding {company} earns a free share and an early lead in its majority"),
+                )
+            },
+            0 => (None, 0, format!("{tile} is a quiet move that gives nothing away")),
+            1 => {
+                let company = *companies.iter().next().unwrap();
+                let (mine, best_other) = majority_position(game, player, company);
+                let score = if mine >= best_other { mine as i64 * 100 } else { -(best_other as i64) * 50 };
+                (None, score, format!("grows {company}, in which you hold {mine} shares"))
+            },
+            _ => {
+                // The largest company survives; ties go to the company in
+                // which the player holds the most shares.
+                let into = *companies.iter()
+                    .max_by_key(|&&company| (
+                        board.company_sizes[company],
+                        game.players()[player].holdings[company]
+                    ))
+                    .unwrap();
+                let defunct: Vec<_> = companies.iter().copied()
+                    .filter(|&company| company != into)
+                    .collect();
+                let bonus: Money = defunct.iter()
+                    .map(|&company| game.projected_bonus(player, company))
+                    .sum();
+                let names = defunct.iter()
+                    .map(Company::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" and ");
+                (
+                    Some(TilePlacementImplication::MergesCompanies(Merge::new(&defunct, into))),
+                    2000 + bonus.dollars() as i64,
+                    format!("merging {names} into {into} pays you {bonus} in shareholder bonuses"),
+                )
+            },
+        };
+
+        let placement = TilePlacement { tile, implication };
+
+        // Skip any tile that cannot be legally played
+        if board.check_implication(placement).is_err() { continue; }
+
+        if best.as_ref().map(|(best_score, _)| score > *best_score).unwrap_or(true) {
+            best = Some((score, Hint {
+                action: PlayerAction::PlayTile { placement },
+                rationale: capitalize(rationale),
+            }));
+        }
+    }
+
+    best.map(|(_, hint)| hint)
+}
+
+/// Rough value of a company when founded, used to break ties between
+/// companies that could be founded.
+fn founding_price(company: Company) -> u32 {
+    match company {
+        Company::Continental | Company::Imperial => 3,
+        Company::American | Company::Festival | Company::Worldwide => 2,
+        Company::Luxor | Company::Tower => 1,
+    }
+}
+
+fn suggest_purchase(game: &Game<BuyingStock>, player: &str) -> Hint {
+    let mut money = game.players()[player].money;
+    let mut holdings = game.players()[player].holdings;
+    let mut bank = *game.stock_bank();
+    let mut stock = [None; 3];
+    let mut reason = None;
+
+    for share in &mut stock {
+        let choice = CompanyMap::new(&()).iter()
+            .map(|(company, _)| company)
+            .filter(|&company| game.board().company_exists(company))
+            .filter(|&company| !bank.sold_out(company))
+            .filter(|&company| game.board().stock_price(company) <= money)
+            .filter_map(|company| {
+                let (_, best_other) = majority_position(game, player, company);
+                let gap = best_other as i64 - holdings[company] as i64;
+
+                // Contested majorities are worth the most, followed by narrow
+                // leads that need protecting.
+                let position = match gap {
+                    0..=2 => 3,
+                    -2..=-1 => 2,
+                    3.. => 1,
+                    _ => return None,
+                };
+                let score = position * 1000 - game.board().stock_price(company).dollars() as i64 / 10;
+                Some((score, gap, company))
+            })
+            .max_by_key(|(score, _, _)| *score);
+
+        let (_, gap, company) = match choice {
+            Some(v) => v,
+            None => break,
+        };
+
+        if reason.is_none() {
+            reason = Some(match gap {
+                0..=2 => format!("Buying {company} contests its majority while it is within reach"),
+                3.. => format!("{company} is the best value on the board"),
+                _ => format!("Buying {company} protects your narrow majority"),
+            });
+        }
+
+        *share = Some(company);
+        money -= game.board().stock_price(company);
+        holdings[company] += 1;
+        bank.try_issue(company, 1).unwrap();
+    }
+
+    Hint {
+        action: PlayerAction::BuyStock { stock },
+        rationale: reason.unwrap_or_else(|| {
+            "No purchase improves your position, so keep your cash".to_owned()
+        }),
+    }
+}
+
+fn suggest_resolution(game: &Game<ResolvingMerge>, player: &str) -> Hint {
+    let (defunct, into) = game.current_merge();
+    let shares = game.players()[player].holdings[defunct];
+    let defunct_price = game.board().stock_price(defunct);
+    let into_price = game.board().stock_price(into);
+
+    // Trading in two defunct shares for one surviving share is only worth it
+    // when the surviving share is worth more. Whatever is not traded is sold.
+    let prefer_trading = into_price > defunct_price * 2;
+    let action = game.legal_resolutions().into_iter()
+        .max_by_key(|action| match *action {
+            PlayerAction::ResolveMergeStock { selling, trading, .. } if prefer_trading => {
+                (trading, selling)
+            },
+            PlayerAction::ResolveMergeStock { selling, trading, .. } => (selling, trading),
+            _ => (0, 0),
+        })
+        .unwrap_or(PlayerAction::ResolveMergeStock { selling: shares, trading: 0, keeping: 0 });
+
+    let rationale = match action {
+        PlayerAction::ResolveMergeStock { trading: 1.., .. } => format!(
+            "One {into} share ({into_price}) is worth more than two {defunct} shares ({})",
+            defunct_price * 2
+        ),
+        PlayerAction::ResolveMergeStock { selling: 1.., .. } => {
+            format!("Selling {defunct} at {defunct_price} a share turns dead stock into cash")
+        },
+        _ => format!("You have no {defunct} stock worth disposing of"),
+    };
+
+    Hint { action, rationale }
+}
+
+fn capitalize(mut string: String) -> String {
+    if let Some(first) = string.get_mut(0..1) {
+        first.make_ascii_uppercase();
+    }
+    string
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::kernel::Game;
+    use crate::game::messages::*;
+    use crate::game::tile::{Hand, Tile};
+
+    #[test]
+    fn suggests_founding() {
+        let game = Game::start(&GameStart::two_player_test());
+
+        let mut hand = Hand::default();
+        hand.insert_tile(Tile::new(5, 'e')).unwrap();
+        hand.insert_tile(Tile::new(1, 'b')).unwrap();
+
+        let hint = super::suggest(&game.clone().into(), "alice", Some(&hand)).unwrap();
+        match hint.action {
+            PlayerAction::PlayTile { placement } => {
+                assert_eq!(placement.tile, Tile::new(1, 'b'));
+                assert!(matches!(
+                    placement.implication,
+                    Some(TilePlacementImplication::FoundsCompany(_))
+                ));
+            },
+            _ => panic!("expected a tile placement"),
+        }
+
+        // No hint is given out of turn
+        assert!(super::suggest(&game.into(), "bob", Some(&hand)).is_none());
+    }
+}
@@ -1,17 +1,28 @@
 use crate::game::messages::*;
+use crate::game::tile::Hand;
 
-use super::{Game, place_tile::PlacingTile, resolve_merge::{ResolvingMerge, MaybeResolvingMerge}, buy_stock::BuyingStock, State, TryGameUpdateResult};
+use super::{AwaitingAction, Game, place_tile::PlacingTile, resolve_merge::{ResolvingMerge, MaybeResolvingMerge}, buy_stock::BuyingStock, GameUpdateResult, ReplayError, State, TryGameUpdateResult};
 
 /// A state that indicates that the game is in one of three states:
 /// [`PlacingTile`], [`ResolvingMerge`], or [`BuyingStock`].
 #[derive(Debug, Clone)]
 pub struct Ambiguous {
-    state: AmbiguousState,
+    pub(super) state: AmbiguousState,
 }
 impl State for Ambiguous {}
 
+impl AwaitingAction for Ambiguous {
+    fn needed_action(game: &Game<Self>) -> ActionRequest {
+        match &game.state.state {
+            AmbiguousState::PlacingTile(_) => ActionRequest::PlayTile,
+            AmbiguousState::ResolvingMerge(state) => state.request(game.players()),
+            AmbiguousState::BuyingStock(_) => ActionRequest::BuyStock,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
-enum AmbiguousState {
+pub(super) enum AmbiguousState {
     PlacingTile(PlacingTile),
     ResolvingMerge(ResolvingMerge),
     BuyingStock(BuyingStock),
@@ -21,23 +32,16 @@ impl State for GameOver {}
 
 impl Game<Ambiguous> {
 
-    /// Gets the action required of the active player in order to advance the
-    /// game.
-    #[inline]
-    pub fn needed_action(&self) -> ActionRequest {
-        match &self.state.state {
-            AmbiguousState::PlacingTile(_) => {
-                ActionRequest::PlayTile
-            },
-            AmbiguousState::ResolvingMerge(state) => {
-                ActionRequest::ResolveMergeStock {
-                    defunct: state.current_defunct(),
-                    into: state.company_into()
-                }
-            },
-            AmbiguousState::BuyingStock(_) => {
-                ActionRequest::BuyStock
+    /// Lists every action that would advance the game from its current state.
+    /// Placing a tile requires the `hand` of the active player, so if it is
+    /// unknown, no placements are listed.
+    pub fn legal_actions(&self, hand: Option<&Hand>) -> Vec<PlayerAction> {
+        match self.clone().disambiguate() {
+            GameDisambiguation::PlacingTile(game) => {
+                hand.map(|hand| game.legal_placements(hand)).unwrap_or_default()
             },
+            GameDisambiguation::ResolvingMerge(game) => game.legal_resolutions(),
+            GameDisambiguation::BuyingStock(game) => game.legal_purchases(),
         }
     }
 
@@ -104,17 +108,48 @@ impl Game<Ambiguous> {
         }
     }
 
-    pub fn speed_play<I: IntoIterator<Item = TaggedPlayerAction>>(self, moves: I)
-         -> TryGameUpdateResult<Ambiguous, Ambiguous>
+    /// Hands the seat of a player over to `player`, who takes on their money,
+    /// holdings, and place in the order of play.
+    pub fn substitute(&mut self, seat: &str, player: Box<str>)
+        -> Result<(), InvalidMessageReason>
     {
-        let moves = moves.into_iter();
+        let players = &mut self.data.kernel.players;
+        if players.contains_key(&player) {
+            return Err(InvalidMessageReason::AlreadyInGame);
+        }
+        let data = players.remove(seat).ok_or(InvalidMessageReason::NotInGame)?;
+        players.insert(player.clone(), data);
 
-        let mut game: Game<Ambiguous> = self.into();
+        for data in players.values_mut() {
+            if &*data.next_player == seat {
+                data.next_player = player.clone();
+            }
+        }
+        if &*self.data.player == seat {
+            self.data.player = player.clone();
+        }
+        if let AmbiguousState::ResolvingMerge(state) = &mut self.state.state {
+            state.substitute(seat, &player);
+        }
+        Ok(())
+    }
 
-        for mv in moves {
-            game = match game.try_advance_game(&mv)? {
-                Ok(game) => game,
-                Err(game_over) => return Ok(Err(game_over)),
+    /// Plays `moves` in order. Stops early if the game ends, and fails at the
+    /// first move that cannot be played, naming its position.
+    pub fn speed_play<I: IntoIterator<Item = TaggedPlayerAction>>(self, moves: I)
+         -> Result<GameUpdateResult<Ambiguous>, ReplayError>
+    {
+        let mut game: Game<Ambiguous> = self;
+
+        for (index, mv) in moves.into_iter().enumerate() {
+            game = match game.try_advance_game(&mv) {
+                Ok(Ok(game)) => game,
+                Ok(Err(game_over)) => return Ok(Err(game_over)),
+                Err((_, reason)) => return Err(ReplayError {
+                    index,
+                    player_name: mv.player_name,
+                    reason,
+                }),
             }
         }
 
@@ -1,6 +1,6 @@
-use crate::game::{messages::*, Company};
+use crate::game::{messages::*, Company, CompanyMap, Money};
 
-use super::{State, Game, place_tile::PlacingTile, ID_CHECK_FAIL};
+use super::{AwaitingAction, State, Game, place_tile::PlacingTile, StaleAdvancer};
 
 /// The end state of a [`Game`] turn in which it is waiting for a player to
 /// buy stock.
@@ -8,12 +8,18 @@ use super::{State, Game, place_tile::PlacingTile, ID_CHECK_FAIL};
 pub struct BuyingStock;
 impl State for BuyingStock {}
 
+impl AwaitingAction for BuyingStock {
+    fn needed_action(_: &Game<Self>) -> ActionRequest {
+        ActionRequest::BuyStock
+    }
+}
+
 /// Advances the game state from [`BuyingStock`] to the next player's
 /// [`PlacingTile`] phase.
 #[derive(Debug, PartialEq, Eq)]
 pub struct BuyingStockStateAdvance {
     stock: [Option<Company>; 3],
-    total_cost: u32,
+    total_cost: Money,
     game_id: usize,
 }
 
@@ -22,7 +28,7 @@ impl BuyingStockStateAdvance {
         self.stock
     }
 
-    pub fn total_cost(&self) -> u32 {
+    pub fn total_cost(&self) -> Money {
         self.total_cost
     }
 }
@@ -37,6 +43,8 @@ impl Game<BuyingStock> {
     pub fn check_player_action(&self, action: &TaggedPlayerAction)
         -> Result<BuyingStockStateAdvance, InvalidMessageReason>
     {
+        self.check_action_number(action)?;
+
         if &self.data.player != &action.player_name {
             return Err(InvalidMessageReason::OutOfTurn);
         }
@@ -56,7 +64,10 @@ impl Game<BuyingStock> {
         -> Result<BuyingStockStateAdvance, BuyStockError>
     {
         // Check if the player can afford it
-        let mut total_cost: u32 = 0;
+        let mut total_cost = Money::ZERO;
+        // The bank as it would be after the shares so far are bought, as
+        // several may be of the same company
+        let mut bank = *self.stock_bank();
                 
         for stock in stock.iter() {
             if let Some(company) = stock {
@@ -69,7 +80,7 @@ impl Game<BuyingStock> {
                 }
 
                 // Check if there's stock to buy
-                if self.stock_bank()[*company] == 25 {
+                if bank.try_issue(*company, 1).is_err() {
                     return Err(BuyStockError::OutOfStock);
                 }
 
@@ -80,7 +91,7 @@ impl Game<BuyingStock> {
         let player_obj = self.players().get(&self.data.player).unwrap();
 
         // Check if there are sufficient funds
-        if total_cost > player_obj.money {
+        if player_obj.money.checked_sub(total_cost).is_none() {
             return Err(BuyStockError::InsufficientFunds {
                 deficit: total_cost - player_obj.money
             });
@@ -93,6 +104,33 @@ impl Game<BuyingStock> {
         })
     }
 
+    /// Lists every [`PlayerAction::BuyStock`] that the active player could
+    /// legally take. Shares are bought in no particular order, so each
+    /// combination of shares is only listed once.
+    pub fn legal_purchases(&self) -> Vec<PlayerAction> {
+        let options: Vec<Option<Company>> = std::iter::once(None)
+            .chain(CompanyMap::new(&()).iter()
+                .map(|(company, _)| company)
+                .filter(|&company| self.board().company_exists(company))
+                .map(Some)
+            )
+            .collect();
+
+        let mut actions = Vec::new();
+        for i in 0..options.len() {
+            for j in i..options.len() {
+                for k in j..options.len() {
+                    let stock = [options[i], options[j], options[k]];
+                    if self.check_buy_stock(stock).is_ok() {
+                        actions.push(PlayerAction::BuyStock { stock });
+                    }
+                }
+            }
+        }
+
+        actions
+    }
+
     /// Advances the game forward. This concludes a turn, and the game will
     /// return to the state of [`PlacingTile`], but now for the next player. The
     /// `new_tile` argument conveys one of three things.
@@ -112,18 +150,23 @@ impl Game<BuyingStock> {
         self,
         advancer: BuyingStockStateAdvance,
     )  -> Result<Game<PlacingTile>, Game<GameOver>> {
-        assert_eq!(advancer.game_id, self.data.id, "{ID_CHECK_FAIL}");
+        assert_eq!(advancer.game_id, self.data.id, "{StaleAdvancer}");
 
         let mut data = self.data;
+        data.kernel.actions_played += 1;
+        data.kernel.turns_played += 1;
 
         // Buy the stock
         let player_data = data.kernel.players.get_mut(&data.player).unwrap();
         for share in advancer.stock {
             if let Some(share) = share {
                 player_data.holdings[share] += 1;
+                data.kernel.stock_bank.try_issue(share, 1)
+                    .expect("shares were checked to be in the bank");
             }
         }
-        player_data.money -= advancer.total_cost;
+        player_data.money = player_data.money.checked_sub(advancer.total_cost)
+            .expect("funds were checked to cover the purchase");
 
         // See if one company dominates
         let possible_dominator = data.kernel.board.company_sizes.iter()
@@ -136,8 +179,8 @@ impl Game<BuyingStock> {
 
         // Check if there is any stock left to buy
         let out_of_stock = data.kernel.stock_bank.iter()
-            .any(|(company, stock_count)| {
-                data.kernel.board.company_exists(company) && *stock_count >= 25
+            .any(|(company, _)| {
+                data.kernel.board.company_exists(company) && data.kernel.stock_bank.sold_out(company)
             });
         
         if out_of_stock {
@@ -150,4 +193,16 @@ impl Game<BuyingStock> {
 
         Ok(Game { data, state: PlacingTile })
     }
+
+    /// Advances the game as [`advance_game`](Self::advance_game) does, but
+    /// hands the game back with a [`StaleAdvancer`] error if the `advancer`
+    /// was not produced by this object.
+    pub fn try_advance_game(self, advancer: BuyingStockStateAdvance)
+        -> Result<Result<Game<PlacingTile>, Game<GameOver>>, (Self, StaleAdvancer)>
+    {
+        match self.check_advancer(advancer.game_id) {
+            Ok(()) => Ok(self.advance_game(advancer)),
+            Err(stale) => Err((self, stale)),
+        }
+    }
 }
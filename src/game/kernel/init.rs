@@ -54,16 +54,19 @@ impl Game<PlacingTile> {
             })
             .collect();
 
-        dbg!(Self {
+        Self {
             data: Box::new(GameImpl::new(
                 GameKernel {
                     board,
                     stock_bank: Default::default(),
-                    players
+                    players,
+                    tie_rule: game_start_info.tie_rule,
+                    actions_played: 0,
+                    turns_played: 0,
                 }, 
                 first.clone())
             ),
             state: PlacingTile,
-        })
+        }
     }
 }
@@ -1,82 +1,140 @@
 use std::collections::HashMap;
 
-use crate::game::{CompanyMap, Company, messages::*};
+use serde::{Serialize, Deserialize};
+
+use crate::game::{Company, Money, MoneyOverflow, messages::*};
 use crate::game::board::Board;
+use crate::game::tile::Tile;
 
-use super::PlayerData;
+use super::{PlayerData, StockBank};
 
 /// Holds the core components of a game. This is moved around.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameKernel {
     /// The board of the game.
     pub board: Board,
     /// Records the number of stocks that are purchased by players for each company
-    pub stock_bank: CompanyMap<u8>,
+    pub stock_bank: StockBank,
     /// All players and their data.
     pub players: HashMap<Box<str>, PlayerData>,
+    /// How principle shareholders that are tied are paid.
+    #[serde(default)]
+    pub tie_rule: TieRule,
+    /// The number of actions played so far.
+    #[serde(default)]
+    pub actions_played: usize,
+    /// The number of turns that have ended so far.
+    #[serde(default)]
+    pub turns_played: u32,
 }
 
 impl GameKernel {
     
     /// Computes and pays the principle bonuses for the company in the defunct
-    /// slot, and stores these bonuses into the state.
+    /// slot, and stores these bonuses into the state. If any player can't
+    /// hold their bonus, nobody is paid.
     pub fn pay_principle_bonuses(&mut self, defunct: Company)
-        -> Vec<PrincipleShareholderResult>
+        -> Result<Vec<PrincipleShareholderResult>, MoneyOverflow>
+    {
+        let results = self.principle_bonuses(defunct)?;
+        let paid = results.iter()
+            .map(|result| self.players[&result.player].money.checked_add(result.prize).ok_or(MoneyOverflow))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (result, money) in results.iter().zip(paid) {
+            self.players.get_mut(&result.player).unwrap().money = money;
+        }
+        Ok(results)
+    }
+
+    /// Checks that every player could be paid the principle bonuses for
+    /// `defunct`, on top of `proceeds` paid to one of them first.
+    pub fn check_principle_bonuses(&self, defunct: Company, proceeds: Option<(&str, Money)>)
+        -> Result<(), MoneyOverflow>
+    {
+        for result in self.principle_bonuses(defunct)? {
+            let mut money = self.players[&result.player].money;
+            if let Some((_, proceeds)) = proceeds.filter(|&(player, _)| *result.player == *player) {
+                money = money.checked_add(proceeds).ok_or(MoneyOverflow)?;
+            }
+            money.checked_add(result.prize).ok_or(MoneyOverflow)?;
+        }
+        Ok(())
+    }
+
+    /// Computes the principle bonuses that would be paid were `defunct` to go
+    /// defunct now, without paying them.
+    pub fn principle_bonuses(&self, defunct: Company)
+        -> Result<Vec<PrincipleShareholderResult>, MoneyOverflow>
     {
         // Order the players by who has the most stock, with the principle
-        // shareholder being first.
+        // shareholder being first. Ties are ordered by name, so that every
+        // copy of the game agrees on the order.
         let mut players: Vec<_> = self.players.iter().collect();
-        players.sort_by(|&(_name_a, data_a), &(_name_b, data_b)| {
+        players.sort_by(|&(name_a, data_a), &(name_b, data_b)| {
             data_b.holdings[defunct]
                 .cmp(&data_a.holdings[defunct])
+                .then_with(|| name_a.cmp(name_b))
         });
 
         let mut vec: Vec<PrincipleShareholderResult> = vec![];
-        let mut i = 1;
 
-        for (name, data) in players {
+        for (i, (name, data)) in players.into_iter().enumerate() {
             let position = match vec.last() {
-                Some(prev) => {
-                    if prev.shares == data.holdings[defunct] {
-                        prev.position
-                    } else {
-                        i
-                    }
-                }
-                None => 1,
+                Some(prev) if prev.shares == data.holdings[defunct] => prev.position,
+                _ => i as u8 + 1,
             };
 
-            let result = PrincipleShareholderResult {
+            vec.push(PrincipleShareholderResult {
                 player: name.clone(),
                 shares: data.holdings[defunct],
                 position,
-                prize: {
-                    let multiplier = match position {
-                        1 => 10,
-                        2 => 5,
-                        _ => 0,
-                    };
-                    self.board.stock_price(defunct) * multiplier
-                },
-            };
-            vec.push(result);
+                prize: Money::ZERO,
+            });
+        }
+
+        // Only players who hold stock are entitled to a bonus
+        let count = |position| vec.iter()
+            .filter(|result| result.position == position && result.shares > 0)
+            .count() as u32;
+        let (first_count, second_count) = (count(1), count(2));
+        let first_bonus = self.board.stock_price(defunct).checked_mul(10).ok_or(MoneyOverflow)?;
+        let second_bonus = self.board.stock_price(defunct).checked_mul(5).ok_or(MoneyOverflow)?;
+        let both_bonuses = first_bonus.checked_add(second_bonus).ok_or(MoneyOverflow)?;
 
-            i += 1;
+        let (first_prize, second_prize) = match self.tie_rule {
+            TieRule::Split => match (first_count, second_count) {
+                (0, _) => (Money::ZERO, Money::ZERO),
+                (1, 0) => (both_bonuses, Money::ZERO),
+                (1, _) => (first_bonus, split_bonus(second_bonus, second_count)?),
+                _ => (split_bonus(both_bonuses, first_count)?, Money::ZERO),
+            },
+            TieRule::FullBonus => (first_bonus, second_bonus),
+        };
+
+        for result in vec.iter_mut().filter(|result| result.shares > 0) {
+            result.prize = match result.position {
+                1 => first_prize,
+                2 => second_prize,
+                _ => Money::ZERO,
+            };
         }
 
-        // Pay out the prize
-        vec.into_iter()
-            .map(|result| {
-                let data = self.players.get_mut(&result.player).unwrap();
-                data.money += result.prize;
-                result
-            })
-            .collect()
+        Ok(vec)
     }
 
-    pub fn get_standings(&self) -> Vec<FinalResult> {
-        use std::cmp::Ordering;
+    /// Sells `shares` of `company` held by `player` back to the bank at its
+    /// current price, unless the proceeds would be more than they can hold.
+    pub fn sell_stock(&mut self, player: &str, company: Company, shares: u8) -> Result<(), MoneyOverflow> {
+        let proceeds = self.board.stock_price(company).checked_mul(shares as u32).ok_or(MoneyOverflow)?;
+        let data = self.players.get_mut(player).unwrap();
+        data.money = data.money.checked_add(proceeds).ok_or(MoneyOverflow)?;
+        data.holdings[company] -= shares;
+        Ok(())
+    }
 
+    /// Ranks the players from richest to poorest. A player who `forfeited`
+    /// places last, however much money they have.
+    pub fn get_standings(&self, forfeited: Option<&str>) -> Vec<FinalResult> {
         let mut final_standings: Vec<_> = self.players.iter()
             .map(|(name, data)| {
                 FinalResult {
@@ -88,27 +146,216 @@ impl GameKernel {
             })
             .collect();
 
-        final_standings.sort();
-
-        // Compute places
-        let mut prev_place = 1;
-        let mut iter = final_standings.iter_mut();
-        let mut prev_money = iter.next().unwrap().final_money;
-
-        for standing in iter {
-            match standing.final_money.cmp(&prev_money) {
-                Ordering::Greater => unreachable!(),
-                Ordering::Equal => {
-                    standing.place = prev_place;
-                },
-                Ordering::Less => {
-                    prev_place += 1;
-                    standing.place = prev_place;
-                },
+        // Order the players from richest to poorest
+        let forfeits = |standing: &FinalResult| Some(&*standing.player_name) == forfeited;
+        final_standings.sort_by(|a, b| forfeits(a).cmp(&forfeits(b)).then_with(|| b.cmp(a)));
+
+        // Compute places, with players who tie sharing a place, and the
+        // places they share being skipped by the next player
+        let mut place = 0;
+        let mut prev = None;
+        for (i, standing) in final_standings.iter_mut().enumerate() {
+            let key = (forfeits(standing), standing.final_money);
+            if prev != Some(key) {
+                place = i as u8 + 1;
+                prev = Some(key);
             }
-            prev_money = standing.final_money;
+            standing.place = place;
         }
 
         final_standings
     }
+
+    /// Computes a hash of the board, the stock bank, and the cash and holdings
+    /// of every player. The hash is the same on every platform, and does not
+    /// depend on the order in which players are stored, so the hashes of two
+    /// copies of a game can be compared to check that they agree.
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = Fnv1a::new();
+
+        for row in 1..=Tile::NUM_ROWS {
+            for col in 'a'..=Tile::LAST_COL {
+                hash.write(&[match self.board[Tile::new(row, col)] {
+                    None => 0,
+                    Some(None) => 1,
+                    Some(Some(company)) => 2 + company.id() as u8,
+                }]);
+            }
+        }
+
+        for (_, &count) in self.stock_bank.iter() {
+            hash.write(&[count]);
+        }
+
+        let mut players: Vec<_> = self.players.iter().collect();
+        players.sort_by_key(|&(name, _)| name);
+        for (name, data) in players {
+            // Names are separated by a byte that never appears in UTF-8
+            hash.write(name.as_bytes());
+            hash.write(&[0xff]);
+            hash.write(&data.money.dollars().to_le_bytes());
+            for (_, &shares) in data.holdings.iter() {
+                hash.write(&[shares]);
+            }
+        }
+
+        hash.0
+    }
+}
+
+/// Splits a bonus evenly among `shareholders`, rounding up to the nearest $100.
+fn split_bonus(bonus: Money, shareholders: u32) -> Result<Money, MoneyOverflow> {
+    let hundreds = bonus.dollars().div_ceil(shareholders).div_ceil(100);
+    Money::new(hundreds).checked_mul(100).ok_or(MoneyOverflow)
+}
+
+/// The 64-bit FNV-1a hash. Unlike the hashers in the standard library, its
+/// output is guaranteed not to change between releases of Rust.
+pub(crate) struct Fnv1a(u64);
+
+impl Fnv1a {
+    pub(crate) fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::game::board::Board;
+    use crate::game::messages::TieRule;
+    use crate::game::{Company, CompanyMap, Money, MoneyOverflow};
+
+    use super::{GameKernel, PlayerData};
+
+    /// Sets up a game in which Luxor is a company of two tiles, whose shares
+    /// are held as given by players with no money.
+    fn kernel(tie_rule: TieRule, shares: &[u8]) -> GameKernel {
+        let mut board = Board::default();
+        board.company_sizes[Company::Luxor] = 2;
+
+        let players = shares.iter().enumerate()
+            .map(|(i, &shares)| {
+                let mut holdings = CompanyMap::new(&0);
+                holdings[Company::Luxor] = shares;
+                let data = PlayerData {
+                    money: Money::ZERO,
+                    holdings,
+                    order: i,
+                    next_player: "".into(),
+                };
+                (format!("player{i}").into_boxed_str(), data)
+            })
+            .collect();
+
+        GameKernel {
+            board,
+            stock_bank: Default::default(),
+            players,
+            tie_rule,
+            actions_played: 0,
+            turns_played: 0,
+        }
+    }
+
+    /// Pays the bonuses for Luxor, whose shares are held as given, and returns
+    /// the prize of each player in order.
+    fn bonuses(tie_rule: TieRule, shares: &[u8]) -> Vec<u32> {
+        let mut kernel = kernel(tie_rule, shares);
+        kernel.pay_principle_bonuses(Company::Luxor).unwrap();
+
+        (0..shares.len())
+            .map(|i| kernel.players[&*format!("player{i}")].money.dollars())
+            .collect()
+    }
+
+    #[test]
+    fn split_ties() {
+        assert_eq!(bonuses(TieRule::Split, &[5, 3, 0]), [2000, 1000, 0]);
+        assert_eq!(bonuses(TieRule::Split, &[5, 0]), [3000, 0]);
+        assert_eq!(bonuses(TieRule::Split, &[5, 5, 3]), [1500, 1500, 0]);
+        assert_eq!(bonuses(TieRule::Split, &[5, 3, 3]), [2000, 500, 500]);
+        assert_eq!(bonuses(TieRule::Split, &[4, 4, 4, 1]), [1000, 1000, 1000, 0]);
+        assert_eq!(bonuses(TieRule::Split, &[2, 1, 1, 1]), [2000, 400, 400, 400]);
+        assert_eq!(bonuses(TieRule::Split, &[0, 0]), [0, 0]);
+    }
+
+    #[test]
+    fn full_bonus_ties() {
+        assert_eq!(bonuses(TieRule::FullBonus, &[5, 3, 0]), [2000, 1000, 0]);
+        assert_eq!(bonuses(TieRule::FullBonus, &[5, 0]), [2000, 0]);
+        assert_eq!(bonuses(TieRule::FullBonus, &[5, 5, 3]), [2000, 2000, 0]);
+        assert_eq!(bonuses(TieRule::FullBonus, &[5, 3, 3]), [2000, 1000, 1000]);
+    }
+
+    #[test]
+    fn bonuses_never_overflow() {
+        let mut kernel = kernel(TieRule::Split, &[5, 3]);
+        kernel.players.get_mut("player1").unwrap().money = Money::new(u32::MAX - 500);
+        assert_eq!(kernel.pay_principle_bonuses(Company::Luxor), Err(MoneyOverflow));
+        assert_eq!(kernel.players["player0"].money, Money::ZERO);
+        assert_eq!(kernel.sell_stock("player1", Company::Luxor, 3), Err(MoneyOverflow));
+        assert_eq!(kernel.players["player1"].holdings[Company::Luxor], 3);
+    }
+
+    #[test]
+    fn state_hash_ignores_player_order() {
+        let kernel = kernel(TieRule::Split, &[5, 3, 0, 2, 1, 4]);
+
+        // A map built anew hashes its keys with a seed of its own, so a few
+        // tries are enough to store the players in another order
+        let players = std::iter::repeat_with(|| kernel.players.clone().into_iter().collect::<HashMap<_, _>>())
+            .take(100)
+            .find(|players| !players.keys().eq(kernel.players.keys()))
+            .unwrap();
+        let mut copy = GameKernel { players, ..kernel.clone() };
+        assert_eq!(copy.state_hash(), kernel.state_hash());
+
+        copy.players.get_mut("player2").unwrap().money = Money::new(100);
+        assert_ne!(copy.state_hash(), kernel.state_hash());
+    }
+
+    #[test]
+    fn tied_players_share_places() {
+        let players = [("carol", 8000), ("alice", 8000), ("bob", 9000), ("dave", 5000)].into_iter()
+            .enumerate()
+            .map(|(i, (name, money))| (name.into(), PlayerData {
+                money: Money::new(money),
+                holdings: CompanyMap::new(&0),
+                order: i,
+                next_player: "".into(),
+            }))
+            .collect();
+        let kernel = GameKernel {
+            board: Board::default(),
+            stock_bank: Default::default(),
+            players,
+            tie_rule: TieRule::default(),
+            actions_played: 0,
+            turns_played: 0,
+        };
+        let places = |forfeited| kernel.get_standings(forfeited).into_iter()
+            .map(|result| (result.place, result.player_name))
+            .collect::<Vec<_>>();
+
+        // Players who tie are listed by name, and the next place is skipped
+        assert_eq!(places(None), [
+            (1, "bob".into()), (2, "alice".into()), (2, "carol".into()), (4, "dave".into()),
+        ]);
+        assert_eq!(places(Some("bob")), [
+            (1, "alice".into()), (1, "carol".into()), (3, "dave".into()), (4, "bob".into()),
+        ]);
+    }
 }
@@ -1,12 +1,14 @@
 use std::{collections::HashMap, sync::atomic::Ordering};
 use std::sync::atomic::AtomicUsize;
 
+use serde::{Serialize, Deserialize};
+
 use crate::game::messages::*;
 
 use self::kernel::GameKernel;
 
 use super::board::Board;
-use super::CompanyMap;
+use super::{Company, CompanyMap, Money, MoneyOverflow};
 
 mod kernel;
 /// Declares everything surrounding the state [`DrawingInitialHands`].
@@ -19,6 +21,15 @@ mod resolve_merge;
 mod buy_stock;
 /// Declares everything surrounding the state [`Ambiguous`].
 mod ambiguous;
+/// Declares [`GameSnapshot`], the serializable form of a [`Game`].
+mod snapshot;
+/// Declares [`StockBank`], which accounts for the shares of every company.
+mod stock_bank;
+/// Plays whole games without a server, for benchmarks and tools.
+pub mod sim;
+/// Plays random games to check the invariants of the kernel.
+#[cfg(test)]
+mod simulation;
 
 pub use {
     place_tile::{
@@ -28,6 +39,7 @@ pub use {
     resolve_merge::{
         ContinueMerging,
         DoneMerging,
+        Founding,
         MaybeResolvingMerge,
         MergeResolution,
         MergeStep,
@@ -40,8 +52,17 @@ pub use {
     ambiguous::{
         Ambiguous,
         GameDisambiguation
+    },
+    snapshot::{
+        GameSnapshot,
+        HiddenTiles
+    },
+    stock_bank::{
+        OutOfShares,
+        StockBank
     }
 };
+pub(crate) use kernel::Fnv1a;
 
 /// Controls a game. The game moves between six states, indicated by the type
 /// argument. The game is created in the [`DrawingInitialHands`] state, where
@@ -96,9 +117,9 @@ impl<S: State> Game<S> {
         &self.data.kernel.board
     }
 
-    /// Gets a reference to the number of stocks that have been bought from each
-    /// company.
-    pub fn stock_bank(&self) -> &CompanyMap<u8> {
+    /// Gets a reference to the bank, which counts the stocks that have been
+    /// bought from each company.
+    pub fn stock_bank(&self) -> &StockBank {
         &self.data.kernel.stock_bank
     }
 
@@ -112,6 +133,21 @@ impl<S: State> Game<S> {
         &self.data.player
     }
 
+    /// Gets the number the next action played will be given.
+    pub fn next_action_number(&self) -> ActionNumber {
+        ActionNumber {
+            turn: self.data.kernel.turns_played + 1,
+            index: self.data.kernel.actions_played,
+        }
+    }
+
+    /// Gets the names of the players in order of play.
+    pub fn play_order(&self) -> Vec<&str> {
+        let mut players: Vec<_> = self.players().iter().collect();
+        players.sort_by_key(|(_, data)| data.order);
+        players.into_iter().map(|(name, _)| &**name).collect()
+    }
+
     /// Immediately ends this game with the reason [`GameOver::EndedEarly`].
     pub fn end_early(self) -> Game<GameOver> {
         Game {
@@ -120,19 +156,97 @@ impl<S: State> Game<S> {
         }
     }
 
+    /// Computes a hash of the state of the game that can be compared with the
+    /// hash of another copy of the game. See [`GameKernel::state_hash`].
+    #[inline]
+    pub fn state_hash(&self) -> u64 {
+        self.data.kernel.state_hash()
+    }
+
+    /// Immediately ends this game with the reason [`GameOver::OutOfTime`],
+    /// forfeiting it for `player`.
+    pub fn forfeit(self, player: &str) -> Game<GameOver> {
+        Game {
+            data: self.data,
+            state: GameOver::OutOfTime { player: player.into() },
+        }
+    }
+
     /// Get the current standings of the game.
     #[inline]
     pub fn get_standings(&self) -> Vec<FinalResult> {
-        self.data.kernel.get_standings()
+        self.data.kernel.get_standings(None)
+    }
+
+    /// Gets the principle shareholder bonus `player` would be paid were
+    /// `company` to go defunct right now. A bonus too great to be paid is
+    /// counted as the most money that can be held.
+    pub fn projected_bonus(&self, player: &str, company: Company) -> Money {
+        if self.players()[player].holdings[company] == 0 { return Money::ZERO; }
+
+        match self.data.kernel.principle_bonuses(company) {
+            Ok(results) => results.into_iter()
+                .find(|result| &*result.player == player)
+                .map_or(Money::ZERO, |result| result.prize),
+            Err(MoneyOverflow) => Money::new(u32::MAX),
+        }
+    }
+
+    /// Gets what `player` is worth: their cash, plus the value of their stock
+    /// at current prices and the bonuses they would be paid were every
+    /// company to go defunct right now. It stops at the most money that can
+    /// be held.
+    pub fn net_worth(&self, player: &str) -> Money {
+        let data = &self.players()[player];
+        data.holdings.iter()
+            .map(|(company, &shares)| {
+                let value = self.board().stock_price(company).checked_mul(shares as u32);
+                value.unwrap_or(Money::new(u32::MAX))
+                    .saturating_add(self.projected_bonus(player, company))
+            })
+            .fold(data.money, Money::saturating_add)
+    }
+
+    /// Ranks the players by their [`net worth`](Self::net_worth), from the
+    /// richest to the poorest. Players worth the same are listed by name.
+    pub fn net_worth_standings(&self) -> Vec<(&str, Money)> {
+        let mut standings: Vec<_> = self.players().keys()
+            .map(|name| (&**name, self.net_worth(name)))
+            .collect();
+        standings.sort_by(|(name_a, worth_a), (name_b, worth_b)| {
+            worth_b.cmp(worth_a).then_with(|| name_a.cmp(name_b))
+        });
+        standings
+    }
+
+    /// Checks that the object used to advance the state, whose game ID is
+    /// `game_id`, was created by this game.
+    fn check_advancer(&self, game_id: usize) -> Result<(), StaleAdvancer> {
+        match game_id == self.data.id {
+            true => Ok(()),
+            false => Err(StaleAdvancer),
+        }
+    }
+
+    /// Checks that `action`, if it is numbered, is numbered for the point the
+    /// game is at.
+    fn check_action_number(&self, action: &TaggedPlayerAction) -> Result<(), InvalidMessageReason> {
+        let expected = self.next_action_number();
+        match action.number {
+            Some(found) if found != expected => {
+                Err(InvalidMessageReason::StaleAction { expected, found })
+            },
+            _ => Ok(()),
+        }
     }
 }
 
 
 
 /// Data about a specific player in the [`Game`].
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerData {
-    pub money: u32,
+    pub money: Money,
     pub holdings: CompanyMap<u8>,
     pub order: usize,
     pub next_player: Box<str>,
@@ -140,9 +254,13 @@ pub struct PlayerData {
 
 /// Creates the IDs of games when initialized.
 static GAME_ID: AtomicUsize = AtomicUsize::new(0);
-/// This is the panic message if the ID of the object used to advance the state
-/// was not equal to the ID of the [`Game`] whose state is being advanced.
-const ID_CHECK_FAIL: &str = "argument used to advance state was not created by this object";
+
+/// The object used to advance the state was not created by the [`Game`] whose
+/// state is being advanced, as happens when it was checked against a clone or
+/// an earlier state of the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("argument used to advance state was not created by this object")]
+pub struct StaleAdvancer;
 
 impl Game<GameOver> {
     /// Provides the reason that this game is over.
@@ -150,36 +268,41 @@ impl Game<GameOver> {
         &self.state
     }
 
-    /// Tallies up the final results of the game.
-    pub fn tally_results(self) -> GameResults {
+    /// Tallies up the final results of the game. Fails if a player would be
+    /// paid more money than can be held.
+    pub fn tally_results(self) -> Result<GameResults, MoneyOverflow> {
 
         let mut data = self.data;
 
-        let shareholder_results: CompanyMap<_> = data.kernel.board.company_sizes
-            .map(|company, _size| {
-                let results = data.kernel
-                    .pay_principle_bonuses(company)
-                    .into_boxed_slice();
-
-                // Sell every player's remaining stock
-                for (_player, player_data) in &mut data.kernel.players {
-                    player_data.money += data.kernel.board.stock_price(company) 
-                        * player_data.holdings[company] as u32;
-                }
-
-                if data.kernel.board.company_exists(company) {
-                    Some(results)
-                } else {
-                    None
-                }
-            });
+        let mut shareholder_results = CompanyMap::new(&None);
+        let companies = data.kernel.board.company_sizes;
+        for (company, _size) in companies.iter() {
+            let results = data.kernel
+                .pay_principle_bonuses(company)?
+                .into_boxed_slice();
+
+            // Sell every player's remaining stock
+            let players: Vec<_> = data.kernel.players.keys().cloned().collect();
+            for player in players {
+                let shares = data.kernel.players[&player].holdings[company];
+                data.kernel.sell_stock(&player, company, shares)?;
+            }
+
+            if data.kernel.board.company_exists(company) {
+                shareholder_results[company] = Some(results);
+            }
+        }
 
-        let final_standings = data.kernel.get_standings();
+        let forfeited = match &self.state {
+            GameOver::OutOfTime { player } => Some(&**player),
+            _ => None,
+        };
+        let final_standings = data.kernel.get_standings(forfeited);
 
-        GameResults {
+        Ok(GameResults {
             shareholder_results,
             final_standings: final_standings.into_boxed_slice(),
-        }
+        })
     }
 }
 
@@ -201,6 +324,16 @@ pub type GameUpdateResult<S> = Result<Game<S>, Game<GameOver>>;
 /// update was not possible.
 pub type TryGameUpdateResult<SNew, SOld> = Result<GameUpdateResult<SNew>, (Game<SOld>, InvalidMessageReason)>;
 
+/// An action that [`Game::speed_play`] could not play, and why.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("action {} by {player_name} was rejected ({reason})", .index + 1)]
+pub struct ReplayError {
+    /// The position of the action among those played, counting from 0.
+    pub index: usize,
+    pub player_name: Box<str>,
+    pub reason: InvalidMessageReason,
+}
+
 mod sealed {
     pub trait SealedState {}
     impl SealedState for super::PlacingTile {}
@@ -213,18 +346,47 @@ mod sealed {
 
 pub trait State: std::fmt::Debug + Clone + sealed::SealedState {}
 
+/// A state in which the game waits on an action from one of its players.
+pub trait AwaitingAction: State {
+    /// Gets the action required to advance `game` out of this state.
+    fn needed_action(game: &Game<Self>) -> ActionRequest;
+}
+
+impl<S: AwaitingAction> Game<S> {
+    /// Gets the action required of the active player in order to advance the
+    /// game, with everything the player needs to know to take it.
+    #[inline]
+    pub fn needed_action(&self) -> ActionRequest {
+        S::needed_action(self)
+    }
+
+    /// Checks that `player` may trade in a dead tile now, which they may only
+    /// do on their own turn, before they place a tile.
+    pub fn check_dead_tile_trade(&self, player: &str) -> Result<(), InvalidMessageReason> {
+        if self.active_player() != player || !matches!(self.needed_action(), ActionRequest::PlayTile) {
+            return Err(InvalidMessageReason::DeadTileOutOfTurn);
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::game::Company;
+    use crate::game::{Company, Money};
     use crate::game::messages::{
         TilePlacementImplication,
         BuyStockError,
         TilePlacement,
-        GameStart
+        GameStart,
+        PlayerAction,
+        TaggedPlayerAction,
+        ActionNumber,
+        ActionRequest,
+        InvalidMessageReason
     };
-    use crate::game::tile::Tile;
+    use crate::game::tile::{Hand, Tile};
 
-    use super::Game;
+    use super::{Ambiguous, Founding, Game, StaleAdvancer};
     
     #[test]
     pub fn client_side_game() {
@@ -235,9 +397,15 @@ mod test {
         ].into_boxed_slice();
 
         let game = Game::start(&GameStart {
-            starting_cash: 6000,
+            starting_cash: Money::new(6000),
             play_order,
-            tiles_placed: vec![Tile::new(3, 'b'), Tile::new(2, 'a')].into_boxed_slice()
+            tiles_placed: vec![Tile::new(3, 'b'), Tile::new(2, 'a')].into_boxed_slice(),
+            tie_rule: Default::default(),
+            dead_tiles_per_turn: None,
+            deal_commitment: None,
+            random_seating: false,
+            policy: Default::default(),
+            game_id: None,
         });
 
         assert_eq!(&*game.active_player(), "player1");
@@ -274,7 +442,221 @@ mod test {
         ).unwrap();
         let game = game.advance_game(advancer).unwrap();
 
-        assert_eq!(game.stock_bank()[Company::Continental], 1);
+        // The founder's share and the one bought
+        assert_eq!(game.stock_bank()[Company::Continental], 2);
+        // As the only shareholder of Continental, of three tiles at $500 a
+        // share, player2 would be paid both bonuses
+        assert_eq!(game.net_worth_standings(), [
+            ("player2", Money::new(6000 - 500 + 2 * 500 + 7500)),
+            ("player1", Money::new(6000)),
+        ]);
         assert_eq!(&*game.active_player(), "player1");
     }
+
+    #[test]
+    pub fn legal_actions() {
+        let game = Game::start(&GameStart {
+            starting_cash: Money::new(6000),
+            play_order: vec!["player1".into(), "player2".into()].into_boxed_slice(),
+            tiles_placed: vec![Tile::new(3, 'b'), Tile::new(2, 'a')].into_boxed_slice(),
+            tie_rule: Default::default(),
+            dead_tiles_per_turn: None,
+            deal_commitment: None,
+            random_seating: false,
+            policy: Default::default(),
+            game_id: None,
+        });
+
+        let mut hand = Hand::default();
+        hand.insert_tile(Tile::new(2, 'b')).unwrap();
+        hand.insert_tile(Tile::new(12, 'd')).unwrap();
+
+        // 2-b must found one of the seven companies, and 12-d founds nothing
+        let placements = game.legal_placements(&hand);
+        assert_eq!(placements.len(), 8);
+        for action in placements {
+            let PlayerAction::PlayTile { placement } = action else { panic!() };
+            assert!(game.check_tile(placement).is_ok());
+        }
+
+        let advancer = game.check_tile(TilePlacement {
+            tile: Tile::new(12, 'd'),
+            implication: None
+        }).unwrap();
+        let game = game.advance_game(advancer);
+        let decision = game.decide_merge().unwrap();
+        let game = game.skip_merge(decision);
+
+        // With no companies on the board, the only purchase is nothing
+        let purchases = game.legal_purchases();
+        assert!(matches!(purchases[..], [PlayerAction::BuyStock { stock: [None, None, None] }]));
+    }
+
+    #[test]
+    pub fn no_founder_share() {
+        let mut game = Game::start(&GameStart {
+            starting_cash: Money::new(6000),
+            play_order: vec!["player1".into(), "player2".into()].into_boxed_slice(),
+            tiles_placed: vec![Tile::new(3, 'b'), Tile::new(2, 'a')].into_boxed_slice(),
+            tie_rule: Default::default(),
+            dead_tiles_per_turn: None,
+            deal_commitment: None,
+            random_seating: false,
+            policy: Default::default(),
+            game_id: None,
+        });
+
+        // Every share of Luxor is held by a player, from a time when it was
+        // last on the board
+        game.data.kernel.stock_bank.try_issue(Company::Luxor, 25).unwrap();
+        game.data.kernel.players.get_mut("player2").unwrap().holdings[Company::Luxor] = 25;
+
+        let advancer = game.check_tile(TilePlacement {
+            tile: Tile::new(2, 'b'),
+            implication: Some(TilePlacementImplication::FoundsCompany(Company::Luxor)),
+        }).unwrap();
+        let game = game.advance_game(advancer);
+
+        assert_eq!(game.founding(), Some(Founding { company: Company::Luxor, founder_share: false }));
+        assert_eq!(game.players()["player1"].holdings[Company::Luxor], 0);
+        assert_eq!(game.stock_bank()[Company::Luxor], 25);
+    }
+
+    #[test]
+    pub fn stale_advancer() {
+        let game = Game::start(&GameStart {
+            starting_cash: Money::new(6000),
+            play_order: vec!["player1".into(), "player2".into()].into_boxed_slice(),
+            tiles_placed: vec![Tile::new(3, 'b'), Tile::new(2, 'a')].into_boxed_slice(),
+            tie_rule: Default::default(),
+            dead_tiles_per_turn: None,
+            deal_commitment: None,
+            random_seating: false,
+            policy: Default::default(),
+            game_id: None,
+        });
+        let placement = TilePlacement { tile: Tile::new(12, 'd'), implication: None };
+
+        // A clone is a game of its own, so what it checks cannot advance the
+        // original
+        let advancer = game.clone().check_tile(placement).unwrap();
+        let (game, error) = game.try_advance_game(advancer).err().unwrap();
+        assert_eq!(error, StaleAdvancer);
+
+        let advancer = game.check_tile(placement).unwrap();
+        assert!(game.try_advance_game(advancer).is_ok());
+    }
+
+    #[test]
+    pub fn numbered_actions() {
+        let game: Game<Ambiguous> = Game::start(&GameStart {
+            starting_cash: Money::new(6000),
+            play_order: vec!["player1".into(), "player2".into()].into_boxed_slice(),
+            tiles_placed: vec![Tile::new(3, 'b'), Tile::new(2, 'a')].into_boxed_slice(),
+            tie_rule: Default::default(),
+            dead_tiles_per_turn: None,
+            deal_commitment: None,
+            random_seating: false,
+            policy: Default::default(),
+            game_id: None,
+        }).into();
+        let first = ActionNumber { turn: 1, index: 0 };
+        assert_eq!(game.next_action_number(), first);
+
+        let place = TaggedPlayerAction {
+            player_name: "player1".into(),
+            action: PlayerAction::PlayTile {
+                placement: TilePlacement { tile: Tile::new(12, 'd'), implication: None },
+            },
+            number: Some(first),
+        };
+        let game = game.try_advance_game(&place).ok().unwrap().ok().unwrap();
+        let buy = TaggedPlayerAction {
+            player_name: "player1".into(),
+            action: PlayerAction::BuyStock { stock: [None; 3] },
+            number: Some(ActionNumber { turn: 1, index: 1 }),
+        };
+        let game = game.try_advance_game(&buy).ok().unwrap().ok().unwrap();
+        assert_eq!(game.next_action_number(), ActionNumber { turn: 2, index: 2 });
+
+        // An action numbered for a point the game has moved past is refused
+        let (_, reason) = game.try_advance_game(&place).err().unwrap();
+        assert!(matches!(
+            reason,
+            InvalidMessageReason::StaleAction { found, .. } if found == first
+        ));
+    }
+
+    #[test]
+    fn speed_play_names_failing_action() {
+        let game: Game<Ambiguous> = Game::start(&GameStart::two_player_test()).into();
+        let action = |player_name: &str, action| TaggedPlayerAction {
+            player_name: player_name.into(),
+            action,
+            number: None,
+        };
+        let play = |tile| PlayerAction::PlayTile {
+            placement: TilePlacement { tile, implication: None },
+        };
+
+        // Alice plays out of turn on the third action
+        let error = game.speed_play([
+            action("alice", play(Tile::new(5, 'e'))),
+            action("alice", PlayerAction::BuyStock { stock: [None; 3] }),
+            action("alice", play(Tile::new(6, 'f'))),
+        ]).err().unwrap();
+        assert_eq!((error.index, &*error.player_name), (2, "alice"));
+        assert!(matches!(error.reason, InvalidMessageReason::OutOfTurn));
+        assert!(error.to_string().starts_with("action 3 by alice was rejected"));
+    }
+
+    #[test]
+    fn dead_tiles_traded_before_placing() {
+        let game: Game<Ambiguous> = Game::start(&GameStart::two_player_test()).into();
+
+        // Only alice may trade one in, as it is her turn to place a tile
+        assert!(game.check_dead_tile_trade("alice").is_ok());
+        assert!(matches!(game.check_dead_tile_trade("bob"), Err(InvalidMessageReason::DeadTileOutOfTurn)));
+
+        // Once she has placed one, it is too late
+        let place = TaggedPlayerAction {
+            player_name: "alice".into(),
+            action: PlayerAction::PlayTile {
+                placement: TilePlacement { tile: Tile::new(12, 'd'), implication: None },
+            },
+            number: None,
+        };
+        let game = game.try_advance_game(&place).ok().unwrap().ok().unwrap();
+        assert!(matches!(game.needed_action(), ActionRequest::BuyStock));
+        assert!(matches!(game.check_dead_tile_trade("alice"), Err(InvalidMessageReason::DeadTileOutOfTurn)));
+    }
+
+    #[test]
+    fn net_worth_counts_projected_bonuses() {
+        let mut game = Game::start(&GameStart::two_player_test());
+        let kernel = &mut game.data.kernel;
+        kernel.board.company_sizes[Company::Luxor] = 2;
+        kernel.board.company_sizes[Company::American] = 2;
+        for (player, company, shares) in [
+            ("alice", Company::Luxor, 5),
+            ("bob", Company::Luxor, 3),
+            ("bob", Company::American, 1),
+        ] {
+            kernel.players.get_mut(player).unwrap().holdings[company] = shares;
+        }
+
+        // Alice would be paid the first bonus for Luxor and bob the second,
+        // while bob alone holds American and would be paid both of its
+        assert_eq!(game.projected_bonus("alice", Company::Luxor), Money::new(2000));
+        assert_eq!(game.projected_bonus("bob", Company::Luxor), Money::new(1000));
+        assert_eq!(game.projected_bonus("alice", Company::American), Money::ZERO);
+        assert_eq!(game.projected_bonus("bob", Company::American), Money::new(3000 + 1500));
+        assert_eq!(game.net_worth_standings(), [
+            ("bob", Money::new(6000 + 3 * 200 + 1000 + 300 + 4500)),
+            ("alice", Money::new(6000 + 5 * 200 + 2000)),
+        ]);
+
+        // Nothing is paid out
+        assert_eq!(game.players()["alice"].money, Money::new(6000));
+    }
 }
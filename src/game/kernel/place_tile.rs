@@ -1,6 +1,10 @@
-use crate::game::messages::*;
+use std::collections::HashSet;
 
-use super::{State, Game, MaybeResolvingMerge, ID_CHECK_FAIL};
+use crate::game::board::Board;
+use crate::game::{messages::*, CompanyMap};
+use crate::game::tile::Hand;
+
+use super::{AwaitingAction, State, Game, Founding, MaybeResolvingMerge, StaleAdvancer};
 
 /// The beginning state of a [`Game`] turn in which it is waiting for a player to
 /// place a tile.
@@ -8,6 +12,12 @@ use super::{State, Game, MaybeResolvingMerge, ID_CHECK_FAIL};
 pub struct PlacingTile;
 impl State for PlacingTile {}
 
+impl AwaitingAction for PlacingTile {
+    fn needed_action(_: &Game<Self>) -> ActionRequest {
+        ActionRequest::PlayTile
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct PlacingTileStateAdvance {
     game_id: usize,
@@ -33,6 +43,8 @@ impl Game<PlacingTile> {
     pub fn check_player_action(&self, action: &TaggedPlayerAction)
         -> Result<PlacingTileStateAdvance, InvalidMessageReason>
     {
+        self.check_action_number(action)?;
+
         if let PlayerAction::PlayTile { placement } = action.action {
 
             if self.active_player() != &*action.player_name {
@@ -40,6 +52,7 @@ impl Game<PlacingTile> {
             }
 
             self.board().check_implication(placement)?;
+            self.check_bonuses(placement)?;
 
             Ok(PlacingTileStateAdvance {
                 game_id: self.data.id,
@@ -65,6 +78,54 @@ impl Game<PlacingTile> {
         })
     }
     
+    /// Checks that the principle bonuses a merging `placement` pays as soon as
+    /// it is placed, for the first of the defunct companies, can be paid.
+    fn check_bonuses(&self, placement: TilePlacement) -> Result<(), InvalidMessageReason> {
+        let Some(TilePlacementImplication::MergesCompanies(mut merge)) = placement.implication else {
+            return Ok(());
+        };
+        let defunct = merge.pop_defunct().expect("merge with empty defunct");
+        self.data.kernel.check_principle_bonuses(defunct, None)
+            .map_err(|_| InvalidMessageReason::MoneyOverflow)
+    }
+
+    /// Lists every [`PlayerAction::PlayTile`] that the active player, holding
+    /// `hand`, could legally take.
+    pub fn legal_placements(&self, hand: &Hand) -> Vec<PlayerAction> {
+        let board = self.board();
+        let mut actions = Vec::new();
+
+        for &tile in hand.iter() {
+            let companies: HashSet<_> = Board::neighbors(tile)
+                .filter_map(|neighbor| board[neighbor].flatten())
+                .collect();
+
+            let mut implications = vec![None];
+            implications.extend(CompanyMap::new(&()).iter()
+                .map(|(company, _)| Some(TilePlacementImplication::FoundsCompany(company)))
+            );
+            if companies.len() > 1 {
+                for &into in &companies {
+                    let defunct: Vec<_> = companies.iter().copied()
+                        .filter(|&company| company != into)
+                        .collect();
+                    implications.push(Some(
+                        TilePlacementImplication::MergesCompanies(Merge::new(&defunct, into))
+                    ));
+                }
+            }
+
+            actions.extend(implications.into_iter()
+                .map(|implication| TilePlacement { tile, implication })
+                .filter(|&placement| board.check_implication(placement).is_ok())
+                .filter(|&placement| self.check_bonuses(placement).is_ok())
+                .map(|placement| PlayerAction::PlayTile { placement })
+            );
+        }
+
+        actions
+    }
+
     /// Advances the game to the next state, which is [`MaybeResolvingMerge`].
     /// 
     /// # Panics
@@ -73,31 +134,48 @@ impl Game<PlacingTile> {
     pub fn advance_game(self, advancer: PlacingTileStateAdvance)
         -> Game<MaybeResolvingMerge>
     {
-        assert_eq!(advancer.game_id, self.data.id, "{ID_CHECK_FAIL}");
+        assert_eq!(advancer.game_id, self.data.id, "{StaleAdvancer}");
 
         let mut data = self.data;
 
+        data.kernel.actions_played += 1;
         data.kernel.board.place_tile(*advancer.placement());
 
         // Perform extra behavior based on the implication
         let state = match advancer.placement().implication {
-            None => MaybeResolvingMerge { merge: None },
+            None => MaybeResolvingMerge { merge: None, founding: None },
             Some(TilePlacementImplication::FoundsCompany(company)) => {
 
-                // Give the player their free stock (if there's one to give).
-                if data.kernel.stock_bank[company] < 25 {
+                // Give the player their free stock, unless every share of the
+                // company is already held by the players.
+                let founder_share = data.kernel.stock_bank.try_issue(company, 1).is_ok();
+                if founder_share {
                     data.kernel.players.get_mut(&data.player).unwrap().holdings[company] += 1;
-                    data.kernel.stock_bank[company] += 1;
                 }
 
-                MaybeResolvingMerge { merge: None }
+                MaybeResolvingMerge {
+                    merge: None,
+                    founding: Some(Founding { company, founder_share }),
+                }
             },
             // Compute and pay out principle shareholder bonuses
             Some(TilePlacementImplication::MergesCompanies(merge)) => {
-                MaybeResolvingMerge { merge: Some(merge) }
+                MaybeResolvingMerge { merge: Some(merge), founding: None }
             },
         };
 
         Game { data, state }
     }
+
+    /// Advances the game as [`advance_game`](Self::advance_game) does, but
+    /// hands the game back with a [`StaleAdvancer`] error if the `advancer`
+    /// was not produced by this object.
+    pub fn try_advance_game(self, advancer: PlacingTileStateAdvance)
+        -> Result<Game<MaybeResolvingMerge>, (Self, StaleAdvancer)>
+    {
+        match self.check_advancer(advancer.game_id) {
+            Ok(()) => Ok(self.advance_game(advancer)),
+            Err(stale) => Err((self, stale)),
+        }
+    }
 }
\ No newline at end of file
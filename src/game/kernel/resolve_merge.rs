@@ -1,19 +1,33 @@
+use std::collections::HashMap;
+
+use serde::{Serialize, Deserialize};
+
 use crate::game::{messages::*, Company};
 
-use super::{Game, State, buy_stock::BuyingStock, ID_CHECK_FAIL, ambiguous::Ambiguous};
+use super::{AwaitingAction, Game, PlayerData, State, buy_stock::BuyingStock, StaleAdvancer, ambiguous::Ambiguous};
 
 /// Intermediate state in which the [`Game`] is deciding whether to enter
 /// [`ResolvingMerge`] or [`BuyingStock`].
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MaybeResolvingMerge {
     pub(super) merge: Option<Merge>,
+    pub(super) founding: Option<Founding>,
 }
 impl State for MaybeResolvingMerge {}
 
+/// A company founded by the tile that was just placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Founding {
+    pub company: Company,
+    /// Whether the founder received their free share. There is no share to
+    /// give if every share of the company is already held.
+    pub founder_share: bool,
+}
+
 /// The possible intermediate state of a [`Game`] turn in which it is
 /// facilitating the merge of one or more defunct companies into one large
 /// company.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ResolvingMerge {
     /// The merge that initiated this entire process. This does not change until
     /// the object is dropped.
@@ -29,7 +43,24 @@ pub struct ResolvingMerge {
 }
 impl State for ResolvingMerge {}
 
+impl AwaitingAction for ResolvingMerge {
+    fn needed_action(game: &Game<Self>) -> ActionRequest {
+        game.state.request(game.players())
+    }
+}
+
 impl ResolvingMerge {
+    /// Gets the request made of the shareholder resolving their stock, given
+    /// the `players` of the game.
+    pub(super) fn request(&self, players: &HashMap<Box<str>, PlayerData>) -> ActionRequest {
+        let shareholder = &self.shareholder_results[self.resolving_player].player;
+        ActionRequest::ResolveMergeStock {
+            defunct: self.current_defunct,
+            into: self.merge.into,
+            shares: players[shareholder].holdings[self.current_defunct],
+        }
+    }
+
     /// Gets the company whose defunct stock is currently being resolved.
     pub fn current_defunct(&self) -> Company {
         self.current_defunct
@@ -39,6 +70,15 @@ impl ResolvingMerge {
     pub fn company_into(&self) -> Company {
         self.merge.into
     }
+
+    /// Renames a shareholder whose seat was handed to another player.
+    pub(super) fn substitute(&mut self, seat: &str, player: &str) {
+        for result in &mut self.shareholder_results {
+            if &*result.player == seat {
+                result.player = player.into();
+            }
+        }
+    }
 }
 
 mod sealed {
@@ -112,6 +152,11 @@ impl<N: MergeResolution + Eq> MergeStep<N> {
 }
 
 impl Game<MaybeResolvingMerge> {
+    /// Gets the company founded by the tile that was just placed, if any.
+    pub fn founding(&self) -> Option<Founding> {
+        self.state.founding
+    }
+
     /// Decide whether or not this Game has a merge to resolve. Returns the
     /// object that advances the state either way.
     pub fn decide_merge(&self)
@@ -136,7 +181,7 @@ impl Game<MaybeResolvingMerge> {
     pub fn skip_merge(self, skipper: DoneMerging)
         -> Game<BuyingStock>
     {
-        assert_eq!(skipper.game_id, self.data.id, "{ID_CHECK_FAIL}");
+        assert_eq!(skipper.game_id, self.data.id, "{StaleAdvancer}");
 
         Game {
             data: self.data,
@@ -149,11 +194,13 @@ impl Game<MaybeResolvingMerge> {
     /// 
     /// # Panics
     /// 
-    /// This function panics if the `starter` was not produced by this object.
+    /// This function panics if the `starter` was not produced by this object,
+    /// or if the bonuses for the first defunct company can't be paid, which
+    /// checking the placement of the tile rules out.
     pub fn commence_merge(mut self, starter: ContinueMerging)
         -> Game<ResolvingMerge>
     {
-        assert_eq!(starter.game_id, self.data.id, "{ID_CHECK_FAIL}");
+        assert_eq!(starter.game_id, self.data.id, "{StaleAdvancer}");
 
         let mut merge = self.state.merge.unwrap();
         let current_defunct = merge.pop_defunct().expect("merge with empty defunct");
@@ -161,7 +208,8 @@ impl Game<MaybeResolvingMerge> {
             current_merge: merge,
             merge,
             current_defunct,
-            shareholder_results: self.data.kernel.pay_principle_bonuses(current_defunct),
+            shareholder_results: self.data.kernel.pay_principle_bonuses(current_defunct)
+                .expect("bonuses were checked along with the placement"),
             resolving_player: 0,
         };
 
@@ -170,6 +218,30 @@ impl Game<MaybeResolvingMerge> {
             state,
         }
     }
+
+    /// Skips the merge as [`skip_merge`](Self::skip_merge) does, but hands the
+    /// game back with a [`StaleAdvancer`] error if the `skipper` was not
+    /// produced by this object.
+    pub fn try_skip_merge(self, skipper: DoneMerging)
+        -> Result<Game<BuyingStock>, (Self, StaleAdvancer)>
+    {
+        match self.check_advancer(skipper.game_id) {
+            Ok(()) => Ok(self.skip_merge(skipper)),
+            Err(stale) => Err((self, stale)),
+        }
+    }
+
+    /// Begins the merge as [`commence_merge`](Self::commence_merge) does, but
+    /// hands the game back with a [`StaleAdvancer`] error if the `starter` was
+    /// not produced by this object.
+    pub fn try_commence_merge(self, starter: ContinueMerging)
+        -> Result<Game<ResolvingMerge>, (Self, StaleAdvancer)>
+    {
+        match self.check_advancer(starter.game_id) {
+            Ok(()) => Ok(self.commence_merge(starter)),
+            Err(stale) => Err((self, stale)),
+        }
+    }
 }
 
 impl Game<ResolvingMerge> {
@@ -186,6 +258,12 @@ impl Game<ResolvingMerge> {
         self.state.resolving_player == 0
     }
 
+    /// Gets the name of the shareholder who must resolve their defunct stock
+    /// next.
+    pub fn resolving_player(&self) -> &str {
+        &self.state.shareholder_results[self.state.resolving_player].player
+    }
+
     /// Gets the list of principle shareholders for the current merge.
     pub fn principle_shareholders(&self) -> &[PrincipleShareholderResult] {
         &self.state.shareholder_results[..]
@@ -200,6 +278,8 @@ impl Game<ResolvingMerge> {
             MergeStep<DoneMerging>
         >, InvalidMessageReason>
     {
+        self.check_action_number(action)?;
+
         let resolving_player = &self.state
             .shareholder_results[self.state.resolving_player].player;
 
@@ -242,17 +322,31 @@ impl Game<ResolvingMerge> {
         }
 
         // Ensure the number of things being traded is even
-        if keeping % 2 == 1 {
+        if trading % 2 == 1 {
             return Err(MergeResolveError::TradesInOddStock);
         }
 
         // Ensure there's enough stock in the new company to trade for
-        if keeping % 2 + self.stock_bank()[self.state.current_merge.into] >= 25 {
+        if self.stock_bank().available(self.state.current_merge.into) < trading / 2 {
             return Err(MergeResolveError::OutOfStock)
         }
 
+        // Ensure the player can be paid for what they sell and, if this is the
+        // last of the shareholders, that the bonuses for the next defunct
+        // company can be paid too
+        let proceeds = self.board().stock_price(self.state.current_defunct)
+            .checked_mul(selling as u32)
+            .filter(|&proceeds| player_obj.money.checked_add(proceeds).is_some())
+            .ok_or(MergeResolveError::MoneyOverflow)?;
+        let last = self.state.resolving_player + 1 == self.state.shareholder_results.len();
+        let mut rest = self.state.current_merge;
+        if let Some(next) = rest.pop_defunct().filter(|_| last) {
+            self.data.kernel.check_principle_bonuses(next, Some((resolving_player, proceeds)))
+                .map_err(|_| MergeResolveError::MoneyOverflow)?;
+        }
+
         // Are we done with this defunct company?
-        if self.state.resolving_player == self.state.shareholder_results.len() {
+        if last {
 
             // Are we done with the merge?
             if self.state.current_merge.defunct_is_empty() {
@@ -273,15 +367,45 @@ impl Game<ResolvingMerge> {
         }))
     }
 
+    /// Lists every [`PlayerAction::ResolveMergeStock`] that the resolving
+    /// player could legally take.
+    pub fn legal_resolutions(&self) -> Vec<PlayerAction> {
+        let shares = self.players()[self.resolving_player()]
+            .holdings[self.state.current_defunct];
+
+        let mut actions = Vec::new();
+        for trading in 0..=shares {
+            for selling in 0..=(shares - trading) {
+                let keeping = shares - trading - selling;
+                if self.check_merge_resolution(selling, keeping, trading).is_ok() {
+                    actions.push(PlayerAction::ResolveMergeStock { selling, trading, keeping });
+                }
+            }
+        }
+
+        actions
+    }
+
     /// Updates the kernel data to resolve the defunct stock of one player.
     fn apply_merge<N: MergeResolution + Eq>(&mut self, next_step: MergeStep<N>) {
-        assert_eq!(next_step.resolve.game_id(), self.data.id, "{ID_CHECK_FAIL}");
-
-        let stock_price = self.board().stock_price(self.state.current_defunct);
-        let player_obj = self.data.kernel.players.get_mut(&self.data.player).unwrap();
-        player_obj.holdings[self.state.current_defunct] += next_step.keeping();
-        player_obj.money += stock_price * next_step.selling() as u32;
-        player_obj.holdings[self.state.current_merge.into] += next_step.trading() / 2;
+        assert_eq!(next_step.resolve.game_id(), self.data.id, "{StaleAdvancer}");
+
+        let (defunct, into) = self.current_merge();
+        let resolving_player = self.resolving_player().to_owned();
+        self.data.kernel.sell_stock(&resolving_player, defunct, next_step.selling())
+            .expect("proceeds were checked when the step was made");
+        self.data.kernel.actions_played += 1;
+
+        let player_obj = self.data.kernel.players.get_mut(&*resolving_player).unwrap();
+        player_obj.holdings[defunct] = next_step.keeping();
+        player_obj.holdings[into] += next_step.trading() / 2;
+
+        // Shares sold or traded go back to the bank, which pays out the shares
+        // they are traded for
+        let stock_bank = &mut self.data.kernel.stock_bank;
+        stock_bank.return_shares(defunct, next_step.selling() + next_step.trading());
+        stock_bank.try_issue(into, next_step.trading() / 2)
+            .expect("shares traded for were checked to be in the bank");
     }
 
     /// Advances the merge along. If the merge resolution has to resolve the
@@ -302,7 +426,8 @@ impl Game<ResolvingMerge> {
         if self.state.resolving_player == self.state.shareholder_results.len() {
             let defunct = self.state.current_merge.pop_defunct().unwrap();
             self.state.shareholder_results = self.data.kernel
-                .pay_principle_bonuses(defunct);
+                .pay_principle_bonuses(defunct)
+                .expect("bonuses were checked when the step was made");
             self.state.current_defunct = defunct;
             self.state.resolving_player = 0;
 
@@ -328,8 +453,8 @@ impl Game<ResolvingMerge> {
 
         // Check if there is any stock left to buy
         let out_of_stock = game.stock_bank().iter()
-            .any(|(company, stock_count)| {
-                game.data.kernel.board.company_exists(company) && *stock_count >= 25
+            .any(|(company, _)| {
+                game.data.kernel.board.company_exists(company) && game.stock_bank().sold_out(company)
             });
         
         if out_of_stock {
@@ -363,4 +488,235 @@ impl Game<ResolvingMerge> {
             }
         }
     }
+
+    /// Advances the merge as [`continue_merge`](Self::continue_merge) does,
+    /// leaving the game untouched if the `step` was not produced by this
+    /// object.
+    pub fn try_continue_merge(&mut self, step: MergeStep<ContinueMerging>)
+        -> Result<Option<&[PrincipleShareholderResult]>, StaleAdvancer>
+    {
+        self.check_advancer(step.resolve.game_id)?;
+        Ok(self.continue_merge(step))
+    }
+
+    /// Finishes the merge as [`finish_merge`](Self::finish_merge) does, but
+    /// hands the game back with a [`StaleAdvancer`] error if the `step` was
+    /// not produced by this object.
+    pub fn try_finish_merge(self, step: MergeStep<DoneMerging>)
+        -> Result<Result<Game<BuyingStock>, Game<GameOver>>, (Self, StaleAdvancer)>
+    {
+        match self.check_advancer(step.resolve.game_id) {
+            Ok(()) => Ok(self.finish_merge(step)),
+            Err(stale) => Err((self, stale)),
+        }
+    }
+
+    /// Steps the merge forward as [`step_merge`](Self::step_merge) does, but
+    /// hands the game back with a [`StaleAdvancer`] error if the `step` was
+    /// not produced by this object.
+    pub fn try_step_merge(self,
+        step: Result<MergeStep<ContinueMerging>, MergeStep<DoneMerging>>
+    ) -> Result<Result<Game<Ambiguous>, Game<GameOver>>, (Self, StaleAdvancer)> {
+        let game_id = match &step {
+            Ok(not_done) => not_done.resolve.game_id,
+            Err(done) => done.resolve.game_id,
+        };
+        match self.check_advancer(game_id) {
+            Ok(()) => Ok(self.step_merge(step)),
+            Err(stale) => Err((self, stale)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::kernel::{Game, Ambiguous, GameDisambiguation};
+    use crate::game::messages::*;
+    use crate::game::tile::Tile;
+    use crate::game::{Company, Money};
+
+    /// Starts a game in which alice is about to place 2-b, merging Luxor and
+    /// Tower, each of two tiles, into American, of five tiles.
+    fn merging_game() -> Game<Ambiguous> {
+        let mut game = Game::start(&GameStart {
+            starting_cash: Money::new(6000),
+            play_order: vec!["alice".into(), "bob".into(), "carol".into()].into_boxed_slice(),
+            tiles_placed: vec![
+                Tile::new(2, 'c'), Tile::new(2, 'd'), Tile::new(2, 'e'), Tile::new(2, 'f'),
+                Tile::new(1, 'a'), Tile::new(3, 'b'),
+            ].into_boxed_slice(),
+            tie_rule: Default::default(),
+            dead_tiles_per_turn: None,
+            deal_commitment: None,
+            random_seating: false,
+            policy: Default::default(),
+            game_id: None,
+        });
+
+        let kernel = &mut game.data.kernel;
+        for (tile, company) in [
+            (Tile::new(2, 'g'), Company::American),
+            (Tile::new(1, 'b'), Company::Luxor),
+            (Tile::new(4, 'b'), Company::Tower),
+        ] {
+            kernel.board.place_tile(TilePlacement {
+                tile,
+                implication: Some(TilePlacementImplication::FoundsCompany(company)),
+            });
+        }
+
+        for (player, company, shares) in [
+            ("alice", Company::Tower, 4),
+            ("bob", Company::Tower, 4),
+            ("carol", Company::Tower, 1),
+            ("bob", Company::Luxor, 3),
+            ("carol", Company::Luxor, 5),
+            ("alice", Company::American, 2),
+        ] {
+            kernel.players.get_mut(player).unwrap().holdings[company] = shares;
+            kernel.stock_bank.try_issue(company, shares).unwrap();
+        }
+
+        game.into()
+    }
+
+    /// Plays `actions` in order, panicking if any are rejected.
+    fn play(game: Game<Ambiguous>, actions: &[(&str, PlayerAction)]) -> Game<Ambiguous> {
+        game.speed_play(actions.iter().map(|(player, action)| TaggedPlayerAction {
+            player_name: (*player).into(),
+            action: *action,
+            number: None,
+        })).unwrap().unwrap()
+    }
+
+    fn merge_tile() -> PlayerAction {
+        PlayerAction::PlayTile { placement: TilePlacement {
+            tile: Tile::new(2, 'b'),
+            implication: Some(TilePlacementImplication::MergesCompanies(
+                Merge::new(&[Company::Luxor, Company::Tower], Company::American)
+            )),
+        }}
+    }
+
+    fn resolve(selling: u8, trading: u8, keeping: u8) -> PlayerAction {
+        PlayerAction::ResolveMergeStock { selling, trading, keeping }
+    }
+
+    #[test]
+    fn merge_two_companies() {
+        let game = play(merging_game(), &[("alice", merge_tile())]);
+
+        // Tower is resolved first. Alice and bob tie for first, splitting both
+        // bonuses, and each shareholder resolves in order of their shares.
+        let GameDisambiguation::ResolvingMerge(merge) = game.clone().disambiguate() else {
+            panic!("the merge did not begin");
+        };
+        assert_eq!(merge.current_merge(), (Company::Tower, Company::American));
+        assert_eq!(merge.resolving_player(), "alice");
+        assert_eq!(game.players()["alice"].money, Money::new(7500));
+        assert_eq!(game.players()["bob"].money, Money::new(7500));
+
+        let game = play(game, &[
+            ("alice", resolve(1, 2, 1)),
+            ("bob", resolve(4, 0, 0)),
+            ("carol", resolve(0, 0, 1)),
+        ]);
+
+        // Luxor is resolved next, by its shareholders in their new order
+        let GameDisambiguation::ResolvingMerge(merge) = game.clone().disambiguate() else {
+            panic!("the second defunct company was skipped");
+        };
+        assert_eq!(merge.current_merge(), (Company::Luxor, Company::American));
+        assert_eq!(merge.resolving_player(), "carol");
+
+        let game = play(game, &[
+            ("carol", resolve(0, 4, 1)),
+            ("bob", resolve(3, 0, 0)),
+            ("alice", resolve(0, 0, 0)),
+        ]);
+        assert!(matches!(game.needed_action(), ActionRequest::BuyStock));
+        assert_eq!(game.active_player(), "alice");
+
+        // Bonuses plus $200 for each share sold
+        let players = game.players();
+        assert_eq!(players["alice"].money, Money::new(6000 + 1500 + 200));
+        assert_eq!(players["bob"].money, Money::new(6000 + 1500 + 800 + 1000 + 600));
+        assert_eq!(players["carol"].money, Money::new(6000 + 2000));
+
+        // Kept shares remain, and traded shares are exchanged two for one
+        assert_eq!(players["alice"].holdings[Company::Tower], 1);
+        assert_eq!(players["alice"].holdings[Company::American], 3);
+        assert_eq!(players["bob"].holdings[Company::Tower], 0);
+        assert_eq!(players["bob"].holdings[Company::Luxor], 0);
+        assert_eq!(players["carol"].holdings[Company::Tower], 1);
+        assert_eq!(players["carol"].holdings[Company::Luxor], 1);
+        assert_eq!(players["carol"].holdings[Company::American], 2);
+
+        // The bank takes back every share sold or traded
+        assert_eq!(game.stock_bank()[Company::Tower], 2);
+        assert_eq!(game.stock_bank()[Company::Luxor], 1);
+        assert_eq!(game.stock_bank()[Company::American], 5);
+
+        assert_eq!(game.board().company_sizes[Company::American], 10);
+        assert_eq!(game.board().company_sizes[Company::Tower], 0);
+        assert_eq!(game.board().company_sizes[Company::Luxor], 0);
+    }
+
+    #[test]
+    fn invalid_resolutions() {
+        let mut game = merging_game();
+        game.data.kernel.stock_bank.try_issue(Company::American, 24 - 2).unwrap();
+        let game = play(game, &[("alice", merge_tile())]);
+
+        let GameDisambiguation::ResolvingMerge(merge) = game.disambiguate() else {
+            panic!("the merge did not begin");
+        };
+        // Arguments are in the order of selling, keeping, and trading
+        assert!(matches!(
+            merge.check_merge_resolution(0, 1, 2),
+            Err(MergeResolveError::ResolvesNonexistentStock)
+        ));
+        assert!(matches!(
+            merge.check_merge_resolution(0, 1, 3),
+            Err(MergeResolveError::TradesInOddStock)
+        ));
+        assert!(matches!(
+            merge.check_merge_resolution(0, 0, 4),
+            Err(MergeResolveError::OutOfStock)
+        ));
+        assert!(merge.check_merge_resolution(2, 0, 2).is_ok());
+
+        // Only the resolving shareholder may resolve their stock
+        let action = TaggedPlayerAction {
+            player_name: "bob".into(),
+            action: resolve(4, 0, 0),
+            number: None,
+        };
+        assert!(matches!(merge.check_player_action(&action), Err(InvalidMessageReason::OutOfTurn)));
+    }
+
+    #[test]
+    fn merge_asks_for_shares_held() {
+        let game = merging_game();
+        assert!(matches!(game.needed_action(), ActionRequest::PlayTile));
+        let game = play(game, &[("alice", merge_tile())]);
+
+        // Each shareholder is told how many of the defunct shares they hold
+        let asked = |game: &Game<Ambiguous>| match game.needed_action() {
+            ActionRequest::ResolveMergeStock { defunct, into, shares } => (defunct, into, shares),
+            other => panic!("expected a merge to resolve, not {other:?}"),
+        };
+        assert_eq!(asked(&game), (Company::Tower, Company::American, 4));
+        let game = play(game, &[("alice", resolve(4, 0, 0))]);
+        assert_eq!(asked(&game), (Company::Tower, Company::American, 4));
+        let game = play(game, &[("bob", resolve(4, 0, 0))]);
+        assert_eq!(asked(&game), (Company::Tower, Company::American, 1));
+        let game = play(game, &[("carol", resolve(1, 0, 0))]);
+        assert_eq!(asked(&game), (Company::Luxor, Company::American, 5));
+        let game = play(game, &[("carol", resolve(5, 0, 0)), ("bob", resolve(3, 0, 0))]);
+        assert_eq!(asked(&game), (Company::Luxor, Company::American, 0));
+
+        let game = play(game, &[("alice", resolve(0, 0, 0))]);
+        assert!(matches!(game.needed_action(), ActionRequest::BuyStock));
+    }
 }
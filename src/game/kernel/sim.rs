@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::game::bot::Bot;
+use crate::game::messages::*;
+use crate::game::tile::{Hand, Tile};
+use crate::game::Money;
+
+use super::{Game, Ambiguous, GameDisambiguation, GameResults};
+
+/// The cash each player starts a simulated game with.
+const STARTING_CASH: Money = Money::new(6000);
+
+/// Plays a whole game without a server, each seat played by one of
+/// `strategies` in turn, and tallies its results. The players are named
+/// `player1`, `player2` and so on in order of play. The tiles are dealt from
+/// a shuffle seeded by `seed`, so that the same seed and strategies always
+/// play out the same game, unless a strategy chooses its actions at random.
+///
+/// A player whose strategy finds no action for them, as with a hand of
+/// unplayable tiles, forfeits the game, as they would on a server.
+///
+/// # Panics
+///
+/// This function panics if `strategies` is empty, if a strategy chooses an
+/// action the game does not allow, or if a player is owed more money than can
+/// be held, which the starting cash of a simulated game rules out.
+pub fn simulate(seed: u64, strategies: &[Bot]) -> GameResults {
+    assert!(!strategies.is_empty(), "a game needs at least one player");
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut draw_pile: Vec<_> = Tile::boneyard().iter().copied().collect();
+    draw_pile.shuffle(&mut rng);
+
+    let play_order: Box<[Box<str>]> = (1..=strategies.len())
+        .map(|i| format!("player{i}").into_boxed_str())
+        .collect();
+    let tiles_placed: Box<[Tile]> = draw_pile.split_off(draw_pile.len() - strategies.len()).into();
+    let mut hands: HashMap<_, _> = play_order.iter()
+        .map(|name| {
+            let mut hand = Hand::default();
+            for tile in draw_pile.split_off(draw_pile.len() - 6) {
+                hand.insert_tile(tile).unwrap();
+            }
+            (name.clone(), hand)
+        })
+        .collect();
+
+    let mut game: Game<Ambiguous> = Game::start(&GameStart {
+        starting_cash: STARTING_CASH,
+        play_order,
+        tiles_placed,
+        tie_rule: Default::default(),
+        dead_tiles_per_turn: None,
+        deal_commitment: None,
+        random_seating: false,
+        policy: Default::default(),
+        game_id: None,
+    }).into();
+
+    loop {
+        let player = awaited_player(&game);
+        let strategy = &strategies[game.players()[&player].order];
+        let Some(hint) = strategy.choose_action(&game, &player, &hands[&player]) else {
+            return game.forfeit(&player).tally_results().expect("too little cash to overflow");
+        };
+        let action = TaggedPlayerAction { player_name: player, action: hint.action, number: None };
+
+        game = match game.try_advance_game(&action) {
+            Ok(Ok(game)) => game,
+            Ok(Err(game_over)) => return game_over.tally_results().expect("too little cash to overflow"),
+            Err((_, reason)) => panic!("strategy chose {action:?}, which was rejected ({reason})"),
+        };
+
+        // Replenish the hand of the player who just moved
+        let hand = hands.get_mut(&action.player_name).unwrap();
+        match action.action {
+            PlayerAction::PlayTile { placement } => {
+                hand.remove_tile(placement.tile);
+            },
+            PlayerAction::BuyStock { .. } => {
+                if let Some(tile) = draw_pile.pop() {
+                    hand.insert_tile(tile).unwrap();
+                }
+            },
+            PlayerAction::ResolveMergeStock { .. } => {},
+        }
+    }
+}
+
+/// Gets the player from whom `game` needs an action, who is not the active
+/// player while a merge is being resolved.
+fn awaited_player(game: &Game<Ambiguous>) -> Box<str> {
+    match game.clone().disambiguate() {
+        GameDisambiguation::ResolvingMerge(game) => game.resolving_player().into(),
+        _ => game.active_player().into(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::bot::{Bot, Difficulty};
+
+    use super::simulate;
+
+    #[test]
+    fn same_seed_same_game() {
+        let strategies = vec![Bot::new(Difficulty::Easy); 3];
+        let first = simulate(7, &strategies);
+        let second = simulate(7, &strategies);
+
+        assert_eq!(first.final_standings.len(), 3);
+        assert_eq!(first.final_standings, second.final_standings);
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::game::board::Board;
+use crate::game::messages::*;
+use crate::game::tile::{Hand, Tile};
+use crate::game::{CompanyMap, Money};
+
+use super::{Game, Ambiguous, GameDisambiguation, State};
+
+/// The number of random games played by each test.
+const GAMES: u64 = 1000;
+
+/// A game played out by choosing uniformly among the legal actions, with
+/// tiles dealt from a seeded shuffle so that any failure can be reproduced
+/// from its seed.
+struct Simulation {
+    game: Game<Ambiguous>,
+    hands: HashMap<Box<str>, Hand>,
+    draw_pile: Vec<Tile>,
+    rng: StdRng,
+}
+
+impl Simulation {
+    fn new(seed: u64, players: usize) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut draw_pile: Vec<_> = (1..=Tile::NUM_ROWS)
+            .flat_map(|row| ('a'..=Tile::LAST_COL).map(move |col| Tile::new(row, col)))
+            .collect();
+        draw_pile.shuffle(&mut rng);
+
+        let play_order: Box<[Box<str>]> = (1..=players)
+            .map(|i| format!("player{i}").into_boxed_str())
+            .collect();
+        let tiles_placed: Box<[Tile]> = draw_pile.split_off(draw_pile.len() - players).into();
+
+        let hands = play_order.iter()
+            .map(|name| {
+                let mut hand = Hand::default();
+                for tile in draw_pile.split_off(draw_pile.len() - 6) {
+                    hand.insert_tile(tile).unwrap();
+                }
+                (name.clone(), hand)
+            })
+            .collect();
+
+        let game = Game::start(&GameStart {
+            starting_cash: Money::new(6000),
+            play_order,
+            tiles_placed,
+            tie_rule: Default::default(),
+            dead_tiles_per_turn: None,
+            deal_commitment: None,
+            random_seating: false,
+            policy: Default::default(),
+            game_id: None,
+        });
+
+        Self { game: game.into(), hands, draw_pile, rng }
+    }
+
+    /// Plays a random legal action, checking the invariants of the game
+    /// afterward. Returns [`None`] once the game is over, or once the active
+    /// player has no legal action.
+    fn step(mut self) -> Option<Self> {
+        let player = match self.game.needed_action() {
+            ActionRequest::ResolveMergeStock { .. } => match self.game.clone().disambiguate() {
+                GameDisambiguation::ResolvingMerge(game) => game.resolving_player().into(),
+                _ => unreachable!(),
+            },
+            _ => Box::<str>::from(self.game.active_player()),
+        };
+
+        let actions = self.game.legal_actions(self.hands.get(&player));
+        let action = TaggedPlayerAction {
+            player_name: player,
+            action: *actions.choose(&mut self.rng)?,
+            number: None,
+        };
+
+        let before = self.game.clone();
+        let after = match self.game.try_advance_game(&action) {
+            Ok(result) => result,
+            Err((_, reason)) => panic!("legal action {action:?} was rejected: {reason:?}"),
+        };
+
+        match &after {
+            Ok(game) => {
+                // Bonuses are paid as each defunct company begins to be
+                // resolved
+                let bonuses = match game.clone().disambiguate() {
+                    GameDisambiguation::ResolvingMerge(game) if game.beginning_of_new_merge() => {
+                        game.principle_shareholders().to_vec()
+                    },
+                    _ => vec![],
+                };
+                check_step(&before, game, &action, &bonuses);
+            },
+            Err(game_over) => check_step(&before, game_over, &action, &[]),
+        }
+
+        self.game = after.ok()?;
+        self.draw(&action);
+        Some(self)
+    }
+
+    /// Updates the hand of the player that took `action`.
+    fn draw(&mut self, action: &TaggedPlayerAction) {
+        let hand = self.hands.get_mut(&action.player_name).unwrap();
+        match action.action {
+            PlayerAction::PlayTile { placement } => {
+                assert!(hand.remove_tile(placement.tile));
+            },
+            PlayerAction::BuyStock { .. } => {
+                if let Some(tile) = self.draw_pile.pop() {
+                    hand.insert_tile(tile).unwrap();
+                }
+            },
+            PlayerAction::ResolveMergeStock { .. } => {},
+        }
+    }
+}
+
+/// Checks the invariants that must hold for any game.
+fn check_game<S: State>(game: &Game<S>) {
+    let board = game.board();
+
+    // No more than 25 shares of a company are in circulation, and the bank
+    // accounts for every one of them
+    for (company, &issued) in game.stock_bank().iter() {
+        let held: u32 = game.players().values()
+            .map(|data| data.holdings[company] as u32)
+            .sum();
+        assert!(held <= 25, "{held} shares of {company} are held");
+        assert_eq!(held, issued as u32, "the bank has issued {issued} shares of {company}");
+    }
+
+    // The size of each company matches the tiles it has on the board
+    let mut sizes = CompanyMap::new(&0);
+    for row in 1..=Tile::NUM_ROWS {
+        for col in 'a'..=Tile::LAST_COL {
+            let tile = Tile::new(row, col);
+            if let Some(Some(company)) = board[tile] {
+                sizes[company] += 1;
+
+                // Every tile next to a company is part of it, except for the
+                // tile of a merge still being resolved
+                for neighbor in Board::neighbors(tile) {
+                    if board.merger_tile() == Some(neighbor) { continue; }
+                    assert!(
+                        board[neighbor].is_none() || board[neighbor] == Some(Some(company)),
+                        "{neighbor} is not part of {company}, but is adjacent to {tile}"
+                    );
+                }
+            }
+        }
+    }
+    for (company, &size) in sizes.iter() {
+        assert_eq!(board.company_sizes[company] as u32, size, "size of {company}");
+        assert!(size == 0 || size >= 2, "{company} has only one tile");
+    }
+}
+
+/// Checks that `action` moved money and shares from `before` to `after`
+/// exactly as the rules say it should, given the `bonuses` paid out along the
+/// way.
+fn check_step<S: State>(
+    before: &Game<Ambiguous>,
+    after: &Game<S>,
+    action: &TaggedPlayerAction,
+    bonuses: &[PrincipleShareholderResult],
+) {
+    check_game(after);
+
+    // The change in cash expected for each player
+    let mut expected: HashMap<&str, i64> = before.players().keys()
+        .map(|name| (&**name, 0))
+        .collect();
+    let actor = &*action.player_name;
+
+    match action.action {
+        PlayerAction::PlayTile { .. } => {},
+        PlayerAction::BuyStock { stock } => {
+            let cost: Money = stock.iter()
+                .flatten()
+                .map(|&company| before.board().stock_price(company))
+                .sum();
+            *expected.get_mut(actor).unwrap() -= cost.dollars() as i64;
+
+            for company in stock.into_iter().flatten() {
+                let bought = stock.iter().filter(|&&c| c == Some(company)).count() as u8;
+                assert_eq!(
+                    after.players()[actor].holdings[company],
+                    before.players()[actor].holdings[company] + bought,
+                    "{actor} bought {bought} shares of {company}"
+                );
+            }
+        },
+        PlayerAction::ResolveMergeStock { selling, trading, keeping } => {
+            let (defunct, into) = match before.needed_action() {
+                ActionRequest::ResolveMergeStock { defunct, into, shares } => {
+                    assert_eq!(shares, selling + trading + keeping, "{actor} resolved every share of {defunct}");
+                    (defunct, into)
+                },
+                _ => unreachable!(),
+            };
+            *expected.get_mut(actor).unwrap() +=
+                (before.board().stock_price(defunct) * selling as u32).dollars() as i64;
+
+            let holdings = &after.players()[actor].holdings;
+            assert_eq!(holdings[defunct], keeping, "{actor} kept {keeping} shares of {defunct}");
+            assert_eq!(
+                holdings[into],
+                before.players()[actor].holdings[into] + trading / 2,
+                "{actor} traded {trading} shares of {defunct} into {into}"
+            );
+        },
+    }
+
+    for result in bonuses {
+        *expected.get_mut(&*result.player).unwrap() += result.prize.dollars() as i64;
+    }
+
+    for (name, delta) in expected {
+        let found = after.players()[name].money.dollars() as i64 - before.players()[name].money.dollars() as i64;
+        assert_eq!(found, delta, "change in the cash of {name} after {action:?}");
+    }
+}
+
+/// Plays `GAMES` random games with `players` players each.
+fn play_random_games(players: usize) {
+    for seed in 0..GAMES {
+        let result = std::panic::catch_unwind(|| {
+            let mut simulation = Simulation::new(seed, players);
+            check_game(&simulation.game);
+            while let Some(next) = simulation.step() {
+                simulation = next;
+            }
+        });
+
+        if let Err(panic) = result {
+            eprintln!("random game with {players} players failed with seed {seed}");
+            std::panic::resume_unwind(panic);
+        }
+    }
+}
+
+#[test]
+fn random_two_player_games() {
+    play_random_games(2);
+}
+
+#[test]
+fn random_six_player_games() {
+    play_random_games(6);
+}
+
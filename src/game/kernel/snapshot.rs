@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use serde::{Serialize, Deserialize};
+
+use crate::game::tile::{Boneyard, Hand, Tile};
+
+use super::ambiguous::AmbiguousState;
+use super::kernel::GameKernel;
+use super::{Game, GameImpl, Ambiguous, PlacingTile, ResolvingMerge, BuyingStock};
+
+/// The exact state of a [`Game`] in progress, in a form that can be
+/// serialized. Unlike a [`GameHistory`](crate::game::messages::GameHistory),
+/// restoring a snapshot does not require replaying every action.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameSnapshot {
+    kernel: GameKernel,
+    /// The player from which action is needed.
+    player: Box<str>,
+    state: SnapshotState,
+    /// The tiles that only the server knows about. This is [`None`] for
+    /// snapshots made by, or sent to, clients.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hidden: Option<HiddenTiles>,
+}
+
+/// The tiles that are hidden from the players of a game.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HiddenTiles {
+    pub boneyard: Boneyard<Tile>,
+    pub hands: HashMap<Box<str>, Hand>,
+}
+
+/// The serialized form of [`AmbiguousState`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "state")]
+enum SnapshotState {
+    PlacingTile,
+    ResolvingMerge(ResolvingMerge),
+    BuyingStock,
+}
+
+impl Game<Ambiguous> {
+    /// Takes a snapshot of this game, with no hidden tiles.
+    pub fn snapshot(&self) -> GameSnapshot {
+        let state = match &self.state.state {
+            AmbiguousState::PlacingTile(_) => SnapshotState::PlacingTile,
+            AmbiguousState::ResolvingMerge(state) => {
+                SnapshotState::ResolvingMerge(state.clone())
+            },
+            AmbiguousState::BuyingStock(_) => SnapshotState::BuyingStock,
+        };
+
+        GameSnapshot {
+            kernel: self.data.kernel.clone(),
+            player: self.data.player.clone(),
+            state,
+            hidden: None,
+        }
+    }
+}
+
+/// Restores a game from a snapshot. The restored game is given a new ID, so
+/// advancers produced by the original game cannot be used on it.
+impl From<GameSnapshot> for Game<Ambiguous> {
+    fn from(snapshot: GameSnapshot) -> Self {
+        let state = match snapshot.state {
+            SnapshotState::PlacingTile => AmbiguousState::PlacingTile(PlacingTile),
+            SnapshotState::ResolvingMerge(state) => AmbiguousState::ResolvingMerge(state),
+            SnapshotState::BuyingStock => AmbiguousState::BuyingStock(BuyingStock),
+        };
+
+        Game {
+            data: Box::new(GameImpl::new(snapshot.kernel, snapshot.player)),
+            state: Ambiguous { state },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::kernel::{Game, Ambiguous};
+    use crate::game::messages::*;
+    use crate::game::tile::Tile;
+    use crate::game::Company;
+
+    use super::GameSnapshot;
+
+    #[test]
+    fn snapshot_round_trip() {
+        let game = Game::start(&GameStart::two_player_test());
+        let game: Game<Ambiguous> = game.into();
+        let game = game
+            .speed_play([
+                TaggedPlayerAction {
+                    player_name: "alice".into(),
+                    action: PlayerAction::PlayTile { placement: TilePlacement {
+                        tile: Tile::new(1, 'b'),
+                        implication: Some(TilePlacementImplication::FoundsCompany(Company::Festival)),
+                    }},
+                    number: None,
+                },
+                TaggedPlayerAction {
+                    player_name: "alice".into(),
+                    action: PlayerAction::BuyStock {
+                        stock: [Some(Company::Festival), None, None],
+                    },
+                    number: None,
+                },
+            ])
+            .unwrap()
+            .unwrap();
+
+        let json = serde_json::to_string(&game.snapshot()).unwrap();
+        let snapshot: GameSnapshot = serde_json::from_str(&json).unwrap();
+        let restored: Game<_> = snapshot.into();
+
+        assert_eq!(restored.active_player(), "bob");
+        assert_eq!(restored.play_order(), ["alice", "bob"]);
+        assert_eq!(restored.board()[Tile::new(1, 'b')], Some(Some(Company::Festival)));
+        assert_eq!(restored.board()[Tile::new(5, 'e')], None);
+        assert_eq!(restored.board().company_sizes, game.board().company_sizes);
+        assert_eq!(restored.players()["alice"].holdings[Company::Festival], 2);
+        assert_eq!(restored.players()["alice"].money, game.players()["alice"].money);
+        assert!(matches!(restored.needed_action(), ActionRequest::PlayTile));
+        assert_eq!(restored.state_hash(), game.state_hash());
+    }
+}
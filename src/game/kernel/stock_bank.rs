@@ -0,0 +1,111 @@
+use std::ops;
+
+use serde::{Serialize, Deserialize};
+
+use crate::game::{Company, CompanyMap};
+
+/// The bank did not have as many shares of a company left as were asked of
+/// it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("only {available} shares of {company} are left in the bank")]
+pub struct OutOfShares {
+    pub company: Company,
+    pub available: u8,
+}
+
+/// Keeps count of the shares of each company that the bank has issued to
+/// players. Every share changes hands through here, so no more than
+/// [`StockBank::SHARES`] of a company are ever held at once.
+///
+/// Indexing the bank by a company gets the number of its shares that have
+/// been issued.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct StockBank {
+    issued: CompanyMap<u8>,
+}
+
+impl StockBank {
+    /// The number of shares each company has.
+    pub const SHARES: u8 = 25;
+
+    /// Gets the number of shares of `company` that are left to be issued.
+    pub fn available(&self, company: Company) -> u8 {
+        Self::SHARES - self.issued[company]
+    }
+
+    /// Determines if every share of `company` has been issued.
+    pub fn sold_out(&self, company: Company) -> bool {
+        self.available(company) == 0
+    }
+
+    /// Iterates over the number of shares of each company that have been
+    /// issued.
+    pub fn iter(&self) -> impl Iterator<Item = (Company, &u8)> {
+        self.issued.iter()
+    }
+
+    /// Issues `count` shares of `company`, if the bank has that many left.
+    /// Otherwise, nothing is issued.
+    pub fn try_issue(&mut self, company: Company, count: u8) -> Result<(), OutOfShares> {
+        let available = self.available(company);
+        if count > available {
+            return Err(OutOfShares { company, available });
+        }
+        self.issued[company] += count;
+        Ok(())
+    }
+
+    /// Takes back `count` shares of `company`, which become available to be
+    /// issued again.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if more shares are returned than were issued.
+    pub fn return_shares(&mut self, company: Company, count: u8) {
+        self.issued[company] = self.issued[company].checked_sub(count)
+            .unwrap_or_else(|| panic!("more shares of {company} were returned than were issued"));
+    }
+}
+
+impl ops::Index<Company> for StockBank {
+    type Output = u8;
+
+    fn index(&self, company: Company) -> &u8 {
+        &self.issued[company]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::Company;
+
+    use super::{OutOfShares, StockBank};
+
+    #[test]
+    fn issues_no_more_than_every_share() {
+        let mut bank = StockBank::default();
+        assert_eq!(bank.try_issue(Company::Luxor, 24), Ok(()));
+        assert_eq!(
+            bank.try_issue(Company::Luxor, 2),
+            Err(OutOfShares { company: Company::Luxor, available: 1 })
+        );
+        // A failed issue leaves the bank untouched
+        assert_eq!(bank[Company::Luxor], 24);
+
+        assert_eq!(bank.try_issue(Company::Luxor, 1), Ok(()));
+        assert!(bank.sold_out(Company::Luxor));
+        assert!(!bank.sold_out(Company::Tower));
+
+        bank.return_shares(Company::Luxor, 5);
+        assert_eq!(bank.available(Company::Luxor), 5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn takes_back_no_more_than_was_issued() {
+        let mut bank = StockBank::default();
+        bank.try_issue(Company::Tower, 2).unwrap();
+        bank.return_shares(Company::Tower, 3);
+    }
+}
@@ -1,9 +1,16 @@
+use std::collections::HashMap;
 use std::fmt;
 
 use crate::server::Handshake;
+use crate::server::capability::Capabilities;
 
-use super::{Company, CompanyMap};
-use super::tile::{Tile, FullHand};
+use super::deal::{DealCommitment, DealSeed};
+use super::kernel::GameSnapshot;
+
+use super::{Company, CompanyMap, Money};
+use super::game_id::GameId;
+use super::policy::RulePolicy;
+use super::tile::{Tile, FullHand, Hand};
 
 use serde::{Serialize, Deserialize};
 
@@ -14,6 +21,17 @@ pub enum ServerMessage {
     Chat {
         player_name: Box<str>,
         message: Box<str>,
+        /// When the server received the message, in seconds since the Unix
+        /// epoch.
+        #[serde(default)]
+        timestamp: u64,
+        /// The position of the message among every chat message the server
+        /// has relayed, starting from zero.
+        #[serde(default)]
+        sequence: u64,
+        /// Where the message was said.
+        #[serde(default)]
+        channel: ChatChannel,
     },
     Join {
         #[serde(flatten)]
@@ -22,16 +40,60 @@ pub enum ServerMessage {
     Quit {
         #[serde(flatten)]
         handshake: Handshake,
+        /// Why the connection left, if it said so before closing. [`None`]
+        /// if the connection was lost.
+        #[serde(default)]
+        reason: Option<LeaveReason>,
+    },
+    /// A connection has been seated at the table, or unseated to spectate.
+    /// The handshake carries whether the connection is now spectating.
+    Seated {
+        #[serde(flatten)]
+        handshake: Handshake,
+    },
+    /// What a connection may do as an admin has changed. The handshake carries
+    /// its capabilities now.
+    Trusted {
+        #[serde(flatten)]
+        handshake: Handshake,
+    },
+    /// A connection has changed the name it goes by.
+    Renamed {
+        old_name: Box<str>,
+        new_name: Box<str>,
+    },
+    /// A player has taken over the seat of a player who left the game in
+    /// progress.
+    Substituted {
+        #[serde(flatten)]
+        substitution: Substitution,
+        /// The hash of the game once the seat has changed hands.
+        state_hash: u64,
     },
     PlayerMove {
         #[serde(flatten)]
         action: TaggedPlayerAction,
+        /// The hash of the game once the action has been applied, which
+        /// clients compare against their own to detect a desync.
+        state_hash: u64,
+        /// The request ID the action was sent with, given only to the player
+        /// who sent it.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        request_id: Option<RequestId>,
     },
     /// A player drew, or had, a tile that cannot be played and is requesting a new one.
     DeadTile {
         player_name: Box<str>,
         dead_tile: Tile,
     },
+    /// The tiles the players drew to decide the order of play, sent just
+    /// before the game they start, in the order they were drawn.
+    StartingDraw {
+        draws: Box<[StartingDraw]>,
+        /// Whether the seats were then drawn at random, rather than decided by
+        /// the tiles.
+        random_seating: bool,
+    },
     /// A new game has begun. This message is personalized for each player.
     GameStart {
         #[serde(flatten)]
@@ -45,18 +107,41 @@ pub enum ServerMessage {
         defunct: Company,
         results: Box<[PrincipleShareholderResult]>,
     },
+    /// A player founded a company but received no free share for it, as every
+    /// share of the company is already held.
+    NoFounderShare {
+        player_name: Box<str>,
+        company: Company,
+    },
+    /// A shareholder is next to resolve their stock in a defunct company.
+    ResolvingNext {
+        player: Box<str>,
+        defunct: Company,
+        into: Company,
+    },
     /// The game is over
     GameOver {
         #[serde(flatten)]
         reason: GameOver,
         results: Box<[FinalResult]>,
+        /// The seed the tiles were dealt from, if the server committed to one
+        /// with [`GameStart::deal_commitment`].
+        #[serde(default)]
+        deal_seed: Option<DealSeed>,
+        /// The ID given with [`GameStart::game_id`].
+        #[serde(default)]
+        game_id: Option<GameId>,
     },
     /// The server is shutting down.
     Shutdown,
     /// Tells a player it's their turn, and requests a specific game action.
     YourTurn {
         #[serde(flatten)]
-        request: ActionRequest
+        request: ActionRequest,
+        /// The number the requested action will be given, which the player
+        /// may send it with.
+        #[serde(flatten)]
+        number: ActionNumber,
     },
     /// Informs the player of a tile that has been added to their hand.
     TileDraw {
@@ -65,16 +150,192 @@ pub enum ServerMessage {
     /// An invalid message was sent.
     Invalid {
         #[serde(flatten)]
-        reason: InvalidMessageReason
+        reason: InvalidMessageReason,
+        /// The request ID of the action that was refused, if the message was
+        /// an action sent with one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        request_id: Option<RequestId>,
+    },
+    /// The state of the game, sent to a player that requested a resync.
+    Resync {
+        /// [`None`] if there is no game in progress.
+        snapshot: Option<Box<GameSnapshot>>,
+        /// The player's hand, or [`None`] for spectators.
+        hand: Option<Hand>,
+        /// The time left to each player, if the game is played on the clock.
+        #[serde(default)]
+        clock: Option<ClockState>,
+    },
+    /// The clock has moved on to another player, in a game played on the
+    /// clock.
+    Clock {
+        #[serde(flatten)]
+        clock: ClockState,
+    },
+    /// The log of every action accepted in the latest game, sent to the admin
+    /// who asked for it.
+    ActionLog {
+        entries: Box<[LoggedAction]>,
+    },
+    /// A player has proposed ending the game early, and the other players are
+    /// asked to vote on it.
+    EndProposed {
+        player_name: Box<str>,
+    },
+    /// A player has voted on ending the game early. If the vote passes, the
+    /// game is then ended with [`GameOver::EndedEarly`].
+    EndVoted {
+        player_name: Box<str>,
+        end_game: bool,
+    },
+    /// Too few players agreed to end the game early, and it goes on.
+    EndRejected,
+    /// A player has handed their seat to the server for now, or taken it
+    /// back. While handed over, the seat is moved for as soon as it is its
+    /// turn.
+    Autopilot {
+        player_name: Box<str>,
+        autopilot: bool,
+    },
+    /// A player let their time run out on `missed_turns` turns in a row, and
+    /// the server plays their seat until they take it back, rather than the
+    /// game stalling on them. See [`ClientMessage::SetAutopilot`].
+    Away {
+        player_name: Box<str>,
+        missed_turns: usize,
+    },
+    /// An admin has scheduled the next game to start at `starts_at`, in
+    /// seconds since the Unix epoch, which is `seconds_left` from now. Sent
+    /// again as a reminder as the start draws near.
+    StartScheduled {
+        starts_at: u64,
+        seconds_left: u64,
+    },
+    /// A player has joined or left the queue for a game, which is started
+    /// once `table_size` players are waiting.
+    Queued {
+        player_name: Box<str>,
+        queued: bool,
+        waiting: usize,
+        table_size: usize,
+    },
+    /// A player has voted to kick `target` out of the server, proposing it if
+    /// nobody had. `target` is kicked once `needed` votes are cast.
+    KickVoted {
+        player_name: Box<str>,
+        target: Box<str>,
+        votes: usize,
+        needed: usize,
+    },
+    /// Too few players voted in time to kick `target`, who stays.
+    KickFailed {
+        target: Box<str>,
+    },
+    /// The player has been kicked out of the server, which then closes the
+    /// connection.
+    Kicked,
+    /// Answers a [`ClientMessage::Ping`], sent only to the client that sent
+    /// it.
+    Pong {
+        pong: u64,
+    },
+    /// The server failed while handling a message, through no fault of any
+    /// player, and has recovered as best it could. Unless the game was
+    /// unaffected, everyone is then sent a [`ServerMessage::Resync`].
+    ServerError {
+        /// What went wrong.
+        message: Box<str>,
+        #[serde(flatten)]
+        recovery: Recovery,
     },
 }
 
+/// What became of the game in progress when the server failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "recovery")]
+pub enum Recovery {
+    /// The game, if there was one, was left as it was.
+    Unaffected,
+    /// The game was carried on from its latest autosave, losing any actions
+    /// played since.
+    Restored {
+        after_actions: usize,
+    },
+    /// The game could not be carried on, and was abandoned.
+    Lost,
+}
+
 /// Information about the start of a game.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameStart {
-    pub starting_cash: u32,
+    pub starting_cash: Money,
     pub tiles_placed: Box<[Tile]>,
     pub play_order: Box<[Box<str>]>,
+    /// How principle shareholders that are tied are paid.
+    #[serde(default)]
+    pub tie_rule: TieRule,
+    /// The most dead tiles a player may trade in on one turn. If [`None`],
+    /// any number may be traded in.
+    #[serde(default)]
+    pub dead_tiles_per_turn: Option<u8>,
+    /// The hash of the [`DealSeed`] from which the tiles are dealt, if the
+    /// server has committed to their order.
+    #[serde(default)]
+    pub deal_commitment: Option<DealCommitment>,
+    /// If set, the order of play was drawn at random, rather than decided by
+    /// the starting tiles. Each player still has the starting tile at their
+    /// place in `tiles_placed`.
+    #[serde(default)]
+    pub random_seating: bool,
+    /// What the players may lean on besides their own play.
+    #[serde(default)]
+    pub policy: RulePolicy,
+    /// The ID of the game, which its result is submitted under.
+    #[serde(default)]
+    pub game_id: Option<GameId>,
+}
+
+#[cfg(test)]
+impl GameStart {
+    /// A game between alice and bob, with 1-A and 9-I placed and everything
+    /// else left at its default, for tests that need any game at all.
+    pub fn two_player_test() -> Self {
+        Self {
+            starting_cash: Money::new(6000),
+            tiles_placed: [Tile::new(1, 'a'), Tile::new(9, 'i')].into(),
+            play_order: ["alice".into(), "bob".into()].into(),
+            tie_rule: Default::default(),
+            dead_tiles_per_turn: None,
+            deal_commitment: None,
+            random_seating: false,
+            policy: Default::default(),
+            game_id: None,
+        }
+    }
+}
+
+/// A tile drawn by a player to decide the order of play, which is placed on
+/// the board as the game starts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StartingDraw {
+    pub player_name: Box<str>,
+    pub tile: Tile,
+}
+
+/// How principle shareholder bonuses are paid out to shareholders that hold
+/// the same number of shares.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TieRule {
+    /// The official rule. Shareholders tied for first split both bonuses, and
+    /// nobody places second. Shareholders tied for second split the second
+    /// bonus. Split bonuses are rounded up to the nearest $100. A lone
+    /// shareholder receives both bonuses.
+    #[default]
+    Split,
+    /// A house rule. Each tied shareholder receives the full bonus of their
+    /// place, and a lone shareholder receives only the first bonus.
+    FullBonus,
 }
 
 /// history of the entire game, i guess
@@ -82,13 +343,130 @@ pub struct GameStart {
 pub struct GameHistory {
     pub start: GameStart,
     pub actions: Box<[TaggedPlayerAction]>,
+    /// Every seat that changed hands, in the order they did.
+    #[serde(default)]
+    pub substitutions: Box<[Substitution]>,
+    /// Snapshots taken every so often as the game was played, in order, from
+    /// which any point in it can be reached without playing it from the start.
+    #[serde(default)]
+    pub checkpoints: Box<[Checkpoint]>,
+}
+
+impl GameHistory {
+    /// Strips everything that is not meant for every player's eyes from this
+    /// history.
+    pub fn redact(&self) -> PublicGameHistory {
+        // The history is destructured so that any field added to it has to be
+        // considered here before it can reach a client
+        let GameHistory { start, actions, substitutions, checkpoints: _ } = self;
+        PublicGameHistory {
+            start: start.clone(),
+            checkpoint: None,
+            actions: actions.clone(),
+            substitutions: substitutions.clone(),
+        }
+    }
+
+    /// Gets the history of the game up to just after its first `actions`
+    /// actions, starting from the latest checkpoint taken by then, so that
+    /// the game at that point is reached by playing as few actions as
+    /// possible.
+    pub fn seek(&self, actions: usize) -> PublicGameHistory {
+        let mut history = self.redact();
+        history.actions = history.actions[..actions.min(history.actions.len())].into();
+        history.substitutions = history.substitutions.into_vec().into_iter()
+            .filter(|substitution| substitution.after_actions <= actions)
+            .collect();
+        match self.checkpoints.iter().rev().find(|checkpoint| checkpoint.after_actions <= actions) {
+            Some(checkpoint) => history.since(checkpoint.clone()),
+            None => history,
+        }
+    }
+}
+
+/// The history of a game, holding only what anyone connected to the server may
+/// see. This is what is sent to those who join a game in progress.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublicGameHistory {
+    pub start: GameStart,
+    /// The state of the game partway through, if the history is sent from
+    /// there on. The actions and substitutions are then only those made after
+    /// it, so that a long game needn't be sent in full.
+    #[serde(default)]
+    pub checkpoint: Option<Checkpoint>,
+    pub actions: Box<[TaggedPlayerAction]>,
+    #[serde(default)]
+    pub substitutions: Box<[Substitution]>,
+}
+
+impl PublicGameHistory {
+    /// Drops everything that happened before `checkpoint` from this history,
+    /// which then starts from there.
+    pub fn since(self, checkpoint: Checkpoint) -> Self {
+        let skipped = checkpoint.after_actions - self.checkpoint.as_ref().map_or(0, |c| c.after_actions);
+        let actions = self.actions.into_vec().into_iter().skip(skipped).collect();
+        let substitutions = self.substitutions.into_vec().into_iter()
+            .filter(|substitution| substitution.after_actions >= checkpoint.after_actions)
+            .collect();
+        Self {
+            start: self.start,
+            checkpoint: Some(checkpoint),
+            actions,
+            substitutions,
+        }
+    }
+}
+
+/// A snapshot of a game taken after some number of its actions, from which the
+/// rest can be played.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The number of actions played before the snapshot was taken.
+    pub after_actions: usize,
+    /// The game as every player sees it, without the hidden tiles.
+    pub snapshot: Box<GameSnapshot>,
+}
+
+/// An action accepted by the server, as recorded in its action log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggedAction {
+    /// The number of actions accepted before this one in the game.
+    pub sequence: u64,
+    /// When the action was accepted, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub action: TaggedPlayerAction,
+    /// The hash of this entry chained onto the hash of the one before it, so
+    /// that no entry can be changed without changing every hash after it.
+    pub hash: u64,
+}
+
+/// The time each player has left in a game played on the clock.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClockState {
+    /// The milliseconds left to each player.
+    pub remaining_ms: HashMap<Box<str>, u64>,
+    /// The player whose clock is running, if any.
+    pub running: Option<Box<str>>,
+}
+
+/// A seat handed over partway through a game, from a player who left to one
+/// who took their place.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Substitution {
+    /// The player who held the seat.
+    pub seat: Box<str>,
+    /// The player who holds it now.
+    pub player_name: Box<str>,
+    /// The number of actions played before the seat changed hands.
+    pub after_actions: usize,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FinalResult {
     pub place: u8,
     pub player_name: Box<str>,
-    pub final_money: u32,
+    pub final_money: Money,
 }
 
 impl PartialOrd for FinalResult {
@@ -97,16 +475,21 @@ impl PartialOrd for FinalResult {
     }
 }
 
+/// Results are ordered from worst to best: by money, and then by name, the
+/// name earlier in the alphabet being the better, so that results with the
+/// same money are always listed in the same order.
 impl Ord for FinalResult {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.final_money.cmp(&other.final_money)
+            .then_with(|| other.player_name.cmp(&self.player_name))
+            .then_with(|| other.place.cmp(&self.place))
     }
 }
                     
 
 impl fmt::Display for FinalResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "[{}] {} with ${}",
+        write!(f, "[{}] {} with {}",
             self.place,
             self.player_name,
             self.final_money
@@ -125,20 +508,20 @@ pub struct PrincipleShareholderResult {
     /// Position in shareholding
     pub position: u8,
     /// Prize earned as a principal shareholding bonus, if any.
-    pub prize: u32,
+    pub prize: Money,
 }
 
 impl fmt::Display for PrincipleShareholderResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.position {
             1 => write!(f,
-                "{}, the principle shareholder with {} shares, receives ${}.",
+                "{}, the principle shareholder with {} shares, receives {}.",
                 self.player,
                 self.shares,
                 self.prize
             ),
             2 => write!(f,
-                "{}, the second-place shareholder with {} shares, receives ${}.",
+                "{}, the second-place shareholder with {} shares, receives {}.",
                 self.player,
                 self.shares,
                 self.prize
@@ -151,10 +534,28 @@ impl fmt::Display for PrincipleShareholderResult {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ClientMessage {
-    TakingTurn(PlayerAction),
-    /// A chat message, can be sent by anyone.
+    TakingTurn {
+        #[serde(flatten)]
+        action: PlayerAction,
+        /// Chosen by the client, and echoed back in the
+        /// [`ServerMessage::PlayerMove`] or [`ServerMessage::Invalid`] that
+        /// answers the action, so the client can tell which of the actions it
+        /// sent was played or refused.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        request_id: Option<RequestId>,
+        /// The number of the action, as given by the
+        /// [`ServerMessage::YourTurn`] it answers. An action sent with a
+        /// number the game has moved past is refused, rather than being taken
+        /// for a later action.
+        #[serde(flatten)]
+        number: Option<ActionNumber>,
+    },
+    /// A chat message, can be sent by anyone to the lobby, and by the
+    /// players seated in the game to its room.
     Chat {
-        message: Box<str>
+        message: Box<str>,
+        #[serde(default)]
+        channel: ChatChannel,
     },
     /// The client wishes to replace a dead tile.
     DeadTile {
@@ -162,14 +563,115 @@ pub enum ClientMessage {
     },
     /// Administrative commands that have restricted use.
     Admin(AdminCommand),
+    /// The player votes on ending the game early, without waiting for the
+    /// admin. The first vote in favor proposes it to the other players.
+    ProposeEnd {
+        end_game: bool,
+    },
+    /// Asks the server to answer with a [`ServerMessage::Pong`] carrying the
+    /// same number, so the client can time the round trip.
+    Ping {
+        ping: u64,
+    },
+    /// The client wishes to go by another name. Names can only change between
+    /// games.
+    Rename {
+        new_name: Box<str>,
+    },
+    /// The client joins or leaves the queue for a game, on a server that
+    /// starts games for the players waiting on one.
+    Queue {
+        queued: bool,
+    },
+    /// The player hands their seat to the server, which plays it until they
+    /// take it back, as when they step away from the game for a while.
+    SetAutopilot {
+        autopilot: bool,
+    },
+    /// The player votes to kick another out of the server, for when no admin
+    /// is around to. The first vote proposes it to the other players.
+    VoteKick {
+        votekick: Box<str>,
+    },
+    /// The client is about to close its connection on purpose, and says
+    /// why. A connection that closes without sending this is taken to have
+    /// been lost.
+    Leaving {
+        reason: LeaveReason,
+    },
+    /// The client's game has fallen out of step with the server's, and the
+    /// client is asking for the state of the game to be sent again.
+    RequestResync,
 }
 
+/// Why a client closed its connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaveReason {
+    /// The player chose to quit.
+    Quit,
+    /// The client has nothing more to read from the player, as when its input
+    /// is closed.
+    EndOfInput,
+    /// The player was kicked out of the server. Only ever given by the
+    /// server.
+    Kicked,
+}
+
+/// Where a chat message is said, which decides who hears it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatChannel {
+    /// Heard by everyone connected to the server, whether seated in the game
+    /// or not.
+    #[default]
+    Lobby,
+    /// Heard only by the players seated in the game.
+    Room,
+}
+
+impl fmt::Display for ChatChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChatChannel::Lobby => f.write_str("lobby"),
+            ChatChannel::Room => f.write_str("room"),
+        }
+    }
+}
+
+/// Identifies an action a client sent, among every action it has sent. The
+/// server only echoes it back, so any numbering the client likes will do.
+pub type RequestId = u64;
+
 /// An action sent from players to the server.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaggedPlayerAction {
     pub player_name: Box<str>,
     #[serde(flatten)]
     pub action: PlayerAction,
+    /// Where the action falls in the game. The server numbers every action it
+    /// accepts. An action with a number can only be played at that point in
+    /// the game.
+    #[serde(flatten)]
+    pub number: Option<ActionNumber>,
+}
+
+/// Where an action falls in a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ActionNumber {
+    /// The turn the action is part of, counting from 1. A turn runs from a
+    /// player placing a tile to their buying stock, and takes in the merges
+    /// in between.
+    pub turn: u32,
+    /// The position of the action among every action of the game, counting
+    /// from 0.
+    pub index: usize,
+}
+
+impl fmt::Display for ActionNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "action {} of turn {}", self.index, self.turn)
+    }
 }
 
 /// Writes this PlayerAction as it would appear in a chat
@@ -245,7 +747,33 @@ pub enum AdminCommand {
     Kick {
         player_name: Box<str>,
     },
-    SilenceChat,
+    /// Gives a spectator a seat at the table for the next game.
+    Seat {
+        player_name: Box<str>,
+    },
+    /// Takes a player's seat away, leaving them to spectate the next game.
+    Unseat {
+        player_name: Box<str>,
+    },
+    /// Hands the seat of a player who has left the game in progress to a
+    /// connected player who has no seat.
+    Substitute {
+        seat: Box<str>,
+        player_name: Box<str>,
+    },
+    /// Sends the admin the log of every action accepted in the latest game.
+    ExportLog,
+    /// Starts the next game at `at`, in seconds since the Unix epoch, with
+    /// everyone seated then. No more seats are given in the final minute.
+    ScheduleStart {
+        at: u64,
+    },
+    /// Sets what a player may do as an admin. An admin can only hand out
+    /// capabilities they have, to someone with none they lack.
+    Trust {
+        player_name: Box<str>,
+        capabilities: Capabilities,
+    },
 }
 
 /// An action requested from the player by the server.
@@ -258,13 +786,17 @@ pub enum ActionRequest {
     BuyStock,
     /// Expected response from the client is [`PlayerAction::ResolveMergeStock`].
     ResolveMergeStock {
-        defunct: Company, into: Company
+        defunct: Company, into: Company,
+        /// The number of shares in the defunct company that the shareholder
+        /// has to resolve.
+        #[serde(default)]
+        shares: u8,
     },
 }
 
 /// Indicates an action performed by a player that changes the state of the
 /// game.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "action")]
 pub enum PlayerAction {
     /// A player placed a tile. This is in response to [`ActionRequest::PlayTile`].
@@ -307,12 +839,11 @@ pub enum TilePlacementImplication {
 
 /// Indicates a merger in progress.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "MergeRepr")]
 pub struct Merge {
     /// The smaller companies that are being removed from the board by the
-    /// merging process. 
-    /// 
-    /// TODO: manually write the [`Deserialize`] implementation so that this is
-    /// never in an invalid state.
+    /// merging process. The companies are listed first, followed by any
+    /// [`None`] values.
     defunct: [Option<Company>; 3],
     /// The company into which the defunct company is merging.
     pub into: Company,
@@ -351,6 +882,36 @@ impl Merge {
     }
 }
 
+/// The serialized form of a [`Merge`], which may not be valid.
+#[derive(Deserialize)]
+struct MergeRepr {
+    defunct: [Option<Company>; 3],
+    into: Company,
+}
+
+impl TryFrom<MergeRepr> for Merge {
+    type Error = &'static str;
+
+    fn try_from(value: MergeRepr) -> Result<Self, Self::Error> {
+        let defunct: Vec<_> = value.defunct.iter().copied().flatten().collect();
+
+        if defunct.is_empty() {
+            return Err("merge has no defunct companies");
+        }
+        if value.defunct[..defunct.len()].iter().any(Option::is_none) {
+            return Err("defunct companies must be listed before any nulls");
+        }
+        if defunct.contains(&value.into) {
+            return Err("company cannot merge into itself");
+        }
+        if defunct.iter().enumerate().any(|(i, company)| defunct[..i].contains(company)) {
+            return Err("defunct company listed twice");
+        }
+
+        Ok(Merge::new(&defunct, value.into))
+    }
+}
+
 struct MergeDefunctIter<'a> {
     iter: std::slice::Iter<'a, Option<Company>>,
 }
@@ -367,7 +928,7 @@ impl<'a> Iterator for MergeDefunctIter<'a> {
 }
 
 /// Reason why the game ended.
-#[derive(Debug, Clone, Copy, thiserror::Error, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "reason")]
 pub enum GameOver {
     /// All the stock in the active companies have been bought out.
@@ -382,8 +943,13 @@ pub enum GameOver {
         company: Company
     },
     /// The game was manually ended early.
-    #[error("the game was ended by the host")]
+    #[error("the game was ended early")]
     EndedEarly,
+    /// A player ran out of time and forfeited, placing last.
+    #[error("{player} ran out of time and forfeited")]
+    OutOfTime {
+        player: Box<str>,
+    },
 }
 
 #[derive(Debug, Clone, thiserror::Error, Serialize, Deserialize)]
@@ -410,15 +976,96 @@ pub enum InvalidMessageReason {
     /// The client requested replacement of a tile that wasn't dead.
     #[error("cannot replace tile, as it is not a dead tile")]
     NotDeadTile,
+    /// The client requested replacement of a dead tile at any time other than
+    /// its own turn, before its tile is placed.
+    #[error("dead tiles can only be traded in on your turn, before you place a tile")]
+    DeadTileOutOfTurn,
+    /// The client requested replacement of more dead tiles than are allowed in
+    /// a turn.
+    #[error("no more dead tiles can be traded in this turn")]
+    DeadTileLimit,
     /// The tile implication is incorrect.
     #[error("incorrect tile implication")]
     IncorrectTileImplication(#[from] IncorrectImplication),
     /// The client does not have the permission needed to send an admin command.
     #[error("cannot send admin command")]
     PermissionDenied,
+    /// An admin command named a player that isn't connected.
+    #[error("no player by that name is connected")]
+    PlayerNotConnected,
+    /// A player tried to take a name that someone connected is using.
+    #[error("that name is already in use")]
+    NameTaken,
+    /// A spectator couldn't be seated, as every seat at the table is taken.
+    #[error("every seat at the table is taken")]
+    NoSeatFree,
+    /// A seat was to be handed over, but the player holding it is still
+    /// connected.
+    #[error("the player holding that seat is still connected")]
+    SeatOccupied,
+    /// A seat was to be handed over, but nobody by that name plays in the
+    /// game.
+    #[error("nobody by that name has a seat in the game")]
+    NotInGame,
+    /// A seat was to be handed to a player who already has one.
+    #[error("that player already has a seat in the game")]
+    AlreadyInGame,
+    /// A player voted against ending the game when nobody had proposed it.
+    #[error("nobody has proposed ending the game")]
+    NoEndProposed,
+    /// A spectator tried to chat in the room, which only the players seated
+    /// in the game are in.
+    #[error("only the players seated in the game can chat in its room")]
+    NotInRoom,
+    /// A player asked to queue for a game on a server that doesn't start
+    /// games from a queue.
+    #[error("this server doesn't make up games from a queue")]
+    NoMatchmaking,
+    /// A game was scheduled to start at a time that has already come.
+    #[error("that start time has already passed")]
+    StartTimePassed,
+    /// A seat was asked for in the final minute before a scheduled start.
+    #[error("no more seats are given just before a scheduled start")]
+    SeatsClosed,
+    /// A player who isn't seated at the table tried to vote to kick someone.
+    #[error("only players with a seat can vote to kick")]
+    NotSeated,
+    /// A vote to kick someone was proposed while a vote to kick another is
+    /// still open.
+    #[error("a vote to kick another player is still open")]
+    KickVoteOpen,
+    /// A vote to kick was proposed too soon after the proposer's last one,
+    /// or against a player who recently outlasted one.
+    #[error("too soon to propose another vote to kick")]
+    KickCooldown,
+    /// A vote to kick was proposed with too few players seated for it to be
+    /// fair to the target.
+    #[error("too few players are seated to vote to kick")]
+    TooFewToKick,
+    /// A vote to kick was proposed while an admin who can kick players is
+    /// connected to decide instead.
+    #[error("an admin is around to kick players")]
+    AdminPresent,
+    /// A tile was placed that would pay a principle shareholder more money
+    /// than can be held.
+    #[error("that would pay a player more money than can be held")]
+    MoneyOverflow,
+    /// An action was sent for a point in the game other than the one it is
+    /// at, as when it answers a request that another action already did.
+    #[error("the action was sent as {found}, but the game is at {expected}")]
+    StaleAction {
+        expected: ActionNumber,
+        found: ActionNumber,
+    },
     /// A message that was sent over JSON was invalid
     #[error("invalid JSON: {0}")]
     JsonParseErr(Box<str>),
+    /// The rule policy of the game in progress forbids the message.
+    #[error("the rules of this game don't allow that")]
+    ForbiddenByPolicy,
+    /// A message was valid JSON, but its contents could never be valid.
+    #[error("malformed message: {0}")]
+    MalformedMessage(Box<str>),
 }
 
 /// An illegal tile placement move.
@@ -438,6 +1085,8 @@ pub enum IncorrectImplication {
     IncorrectDefunct(Company),
     #[error("{0} bordered the merger tile and was ignored")]
     MissedDefunct(Company),
+    #[error("{0} doesn't border the merger tile but was listed as surviving")]
+    IncorrectSurvivor(Company),
     /// The tile merges two safe companies, and thus cannot be played.
     #[error("tile cannot be played")]
     DeadTile,
@@ -460,9 +1109,9 @@ pub enum BuyStockError {
     #[error("no more shares to purchase")]
     OutOfStock,
     /// The player has an insufficient amount of funds to cover a purchase.
-    #[error("lacking ${deficit}")]
+    #[error("lacking {deficit}")]
     InsufficientFunds {
-        deficit: u32,
+        deficit: Money,
     }
 }
 
@@ -482,6 +1131,10 @@ pub enum MergeResolveError {
     /// shares are available.
     #[error("no more shares to purchase")]
     OutOfStock,
+    /// The stock sold, or the bonuses paid once the player is done, would
+    /// give a player more money than can be held.
+    #[error("that would pay a player more money than can be held")]
+    MoneyOverflow,
 }
 
 #[cfg(test)]
@@ -493,38 +1146,54 @@ mod test {
         let actions = [
             ActionRequest::BuyStock,
             ActionRequest::PlayTile,
-            ActionRequest::ResolveMergeStock { defunct: Company::Festival, into: Company::Continental }
+            ActionRequest::ResolveMergeStock { defunct: Company::Festival, into: Company::Continental, shares: 4 }
         ];
         println!("{}", serde_json::to_string_pretty(&actions).unwrap());
 
         let commands = [
             AdminCommand::StartGame,
-            AdminCommand::SilenceChat,
             AdminCommand::Kick { player_name: "wallaby".to_owned().into_boxed_str() },
             AdminCommand::EndGame,
+            AdminCommand::Seat { player_name: "wallaby".to_owned().into_boxed_str() },
+            AdminCommand::Unseat { player_name: "wallaby".to_owned().into_boxed_str() },
+            AdminCommand::Substitute {
+                seat: "wallaby".to_owned().into_boxed_str(),
+                player_name: "wombat".to_owned().into_boxed_str(),
+            },
+            AdminCommand::ExportLog,
             AdminCommand::Shutdown,
         ];
         println!("{}", serde_json::to_string_pretty(&commands).unwrap());
 
         let errors = [
-            BuyStockError::InsufficientFunds { deficit: 42 },
+            BuyStockError::InsufficientFunds { deficit: Money::new(42) },
             BuyStockError::NonexistentCompany { company: Company::Luxor },
             BuyStockError::OutOfStock,
         ];
         println!("{}", serde_json::to_string_pretty(&errors).unwrap());
         let messages = [
             ClientMessage::Admin(AdminCommand::Kick { player_name: "wallaby".to_owned().into_boxed_str() }),
-            ClientMessage::Chat { message: "hello, world!".to_owned().into_boxed_str() },
-            ClientMessage::TakingTurn(PlayerAction::ResolveMergeStock { selling: 3, trading: 4, keeping: 6 }),
-            ClientMessage::TakingTurn(PlayerAction::PlayTile {
-                placement: TilePlacement {
-                    tile: Tile::new(3, 'f'), implication: Some(
-                        TilePlacementImplication::MergesCompanies(
-                            Merge::new(&[Company::Worldwide], Company::Continental)
+            ClientMessage::Chat { message: "hello, world!".to_owned().into_boxed_str(), channel: ChatChannel::Lobby },
+            ClientMessage::TakingTurn {
+                action: PlayerAction::ResolveMergeStock { selling: 3, trading: 4, keeping: 6 },
+                request_id: None,
+                number: None,
+            },
+            ClientMessage::TakingTurn {
+                action: PlayerAction::PlayTile {
+                    placement: TilePlacement {
+                        tile: Tile::new(3, 'f'), implication: Some(
+                            TilePlacementImplication::MergesCompanies(
+                                Merge::new(&[Company::Worldwide], Company::Continental)
+                            )
                         )
-                    )
-                }
-            }),
+                    }
+                },
+                request_id: Some(12),
+                number: None,
+            },
+            ClientMessage::Ping { ping: 7 },
+            ClientMessage::RequestResync,
         ];
         println!("{}", serde_json::to_string_pretty(&messages).unwrap());
         let gameovers = [
@@ -563,6 +1232,9 @@ mod test {
             ServerMessage::Chat {
                 player_name: "wallaby".to_owned().into_boxed_str(),
                 message: "hello".to_owned().into_boxed_str(),
+                timestamp: 1_700_000_000,
+                sequence: 0,
+                channel: ChatChannel::Room,
             },
             ServerMessage::Shutdown,
             ServerMessage::GameOver {
@@ -572,8 +1244,10 @@ mod test {
                 results: vec![FinalResult {
                     place: 1,
                     player_name: "wallaby".to_owned().into_boxed_str(),
-                    final_money: 42069,
+                    final_money: Money::new(42069),
                 }].into_boxed_slice(),
+                deal_seed: None,
+                game_id: Some(GameId::generate()),
             },
             ServerMessage::PlayerMove {
                 action: TaggedPlayerAction {
@@ -583,8 +1257,11 @@ mod test {
                             tile: Tile::new(6, 'i'),
                             implication: None
                         }
-                    }
-                }
+                    },
+                    number: None,
+                },
+                state_hash: 0xacc0_1e5e,
+                request_id: Some(12),
             }
         ];
         println!("{}", serde_json::to_string_pretty(&broadcasts).unwrap());
@@ -1,8 +1,19 @@
+pub mod analysis;
 pub mod board;
+pub mod cells;
+pub mod deal;
+pub mod game_id;
+pub mod bot;
 mod company;
+pub mod hint;
 pub mod messages;
+pub mod policy;
+pub mod replay;
+mod money;
+pub mod svg;
 pub mod kernel;
 pub mod tile;
 
 pub use company::Company;
 pub use company::CompanyMap;
+pub use money::{Money, MoneyOverflow};
@@ -0,0 +1,131 @@
+use std::fmt;
+use std::iter::Sum;
+use std::ops;
+
+use serde::{Serialize, Deserialize};
+
+/// An amount of money, in whole dollars. It never silently wraps around: the
+/// `checked_` methods give [`None`] for an amount that cannot be held, and the
+/// operators panic. Payouts to players go through the `checked_` methods, and
+/// fail with [`MoneyOverflow`] rather than pay a player the wrong amount.
+///
+/// Money is displayed with a dollar sign and with its thousands separated, as
+/// in `$6,000`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Money(u32);
+
+impl Money {
+    pub const ZERO: Self = Self(0);
+
+    pub const fn new(dollars: u32) -> Self {
+        Self(dollars)
+    }
+
+    /// Gets the amount in dollars.
+    pub const fn dollars(self) -> u32 {
+        self.0
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    /// Takes `other` away from this amount, giving [`None`] if this amount
+    /// does not cover it.
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    pub fn checked_mul(self, times: u32) -> Option<Self> {
+        self.0.checked_mul(times).map(Self)
+    }
+
+    /// Adds `other` to this amount, stopping at the most that can be held.
+    pub fn saturating_add(self, other: Self) -> Self {
+        Self(self.0.saturating_add(other.0))
+    }
+}
+
+/// A payout that would take a player past the most money that can be held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("the payout is more money than can be held")]
+pub struct MoneyOverflow;
+
+impl ops::Add for Money {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.checked_add(other).unwrap_or_else(|| panic!("{self} plus {other} is too much money to hold"))
+    }
+}
+
+impl ops::AddAssign for Money {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl ops::Sub for Money {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.checked_sub(other).unwrap_or_else(|| panic!("{self} does not cover {other}"))
+    }
+}
+
+impl ops::SubAssign for Money {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+impl ops::Mul<u32> for Money {
+    type Output = Self;
+
+    fn mul(self, times: u32) -> Self {
+        self.checked_mul(times).unwrap_or_else(|| panic!("{self} times {times} is too much money to hold"))
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, |total, amount| total + amount)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.0.to_string();
+        let mut text = String::with_capacity(digits.len() + digits.len() / 3 + 1);
+        text.push('$');
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                text.push(',');
+            }
+            text.push(digit);
+        }
+        f.pad(&text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Money;
+
+    #[test]
+    fn displayed_with_separators() {
+        assert_eq!(Money::new(0).to_string(), "$0");
+        assert_eq!(Money::new(600).to_string(), "$600");
+        assert_eq!(Money::new(6000).to_string(), "$6,000");
+        assert_eq!(Money::new(1_234_567).to_string(), "$1,234,567");
+        assert_eq!(format!("{:>8}", Money::new(6000)), "  $6,000");
+    }
+
+    #[test]
+    fn never_wraps_around() {
+        assert_eq!(Money::new(500).checked_sub(Money::new(600)), None);
+        assert_eq!(Money::new(u32::MAX).checked_add(Money::new(1)), None);
+        assert_eq!(Money::new(600).checked_mul(3), Some(Money::new(1800)));
+    }
+}
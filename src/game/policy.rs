@@ -0,0 +1,95 @@
+use std::str::FromStr;
+
+use serde::{Serialize, Deserialize};
+
+use super::messages::ClientMessage;
+
+/// What the players of a game may lean on besides their own play, as set by
+/// the server for a ranked game or a tournament. It is sent with the start of
+/// the game: clients keep to it, and the server refuses what it can see
+/// breaking it. Hints are worked out by each client on its own, so only a
+/// client that keeps to the policy can be held to them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RulePolicy {
+    /// If set, players may not ask their client for a hint.
+    #[serde(default)]
+    pub no_hints: bool,
+    /// If set, players may not hand their seat to the server. The server may
+    /// still play the seat of a player who lets their time run out too often.
+    #[serde(default)]
+    pub no_autopilot: bool,
+    /// Who may chat while the game is in progress.
+    #[serde(default)]
+    pub chat: ChatPolicy,
+}
+
+impl RulePolicy {
+    /// Checks if the policy forbids a message from a client, which is
+    /// `seated` if it has a seat in the game.
+    pub fn forbids(self, msg: &ClientMessage, seated: bool) -> bool {
+        match msg {
+            ClientMessage::SetAutopilot { autopilot } => self.no_autopilot && *autopilot,
+            ClientMessage::Chat { .. } => !self.chat.allows(seated),
+            _ => false,
+        }
+    }
+}
+
+/// Who may chat while a game is in progress. Between games, anyone may.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatPolicy {
+    /// Everyone may chat.
+    #[default]
+    Open,
+    /// Only the players seated in the game may chat, so that spectators can't
+    /// tell them what they see.
+    Seated,
+    /// Nobody may chat.
+    Closed,
+}
+
+impl ChatPolicy {
+    /// Checks if a client, which is `seated` if it has a seat in the game,
+    /// may chat.
+    pub fn allows(self, seated: bool) -> bool {
+        match self {
+            ChatPolicy::Open => true,
+            ChatPolicy::Seated => seated,
+            ChatPolicy::Closed => false,
+        }
+    }
+}
+
+impl FromStr for ChatPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(ChatPolicy::Open),
+            "seated" => Ok(ChatPolicy::Seated),
+            "closed" => Ok(ChatPolicy::Closed),
+            _ => Err(format!("unknown chat policy '{s}', expected open, seated, or closed")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::messages::{ChatChannel, ClientMessage};
+
+    use super::{ChatPolicy, RulePolicy};
+
+    #[test]
+    fn forbids_what_is_turned_off() {
+        let policy = RulePolicy { no_hints: true, no_autopilot: true, chat: ChatPolicy::Seated };
+        let chat = ClientMessage::Chat { message: "psst".into(), channel: ChatChannel::Lobby };
+        assert!(policy.forbids(&chat, false));
+        assert!(!policy.forbids(&chat, true));
+        assert!(policy.forbids(&ClientMessage::SetAutopilot { autopilot: true }, true));
+
+        // Taking a seat back is always allowed
+        assert!(!policy.forbids(&ClientMessage::SetAutopilot { autopilot: false }, true));
+        assert!(!RulePolicy::default().forbids(&chat, false));
+    }
+}
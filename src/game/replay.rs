@@ -0,0 +1,212 @@
+use std::io;
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use super::deal::{DealMismatch, DealSeed};
+use super::game_id::GameId;
+use super::kernel::{self, Game, GameUpdateResult, ReplayError};
+use super::messages::*;
+use super::MoneyOverflow;
+
+/// The extension replay files are saved with.
+pub const EXTENSION: &str = "acq";
+
+/// A finished game, kept in full so that it can be shared and played back.
+/// Anyone can check with [`Replay::verify`] that the recorded results are
+/// what the recorded moves lead to, and, if the server committed to its deal,
+/// that the tiles were dealt from the recorded seed.
+///
+/// Replays are saved as JSON in files ending in `.acq`. Each records the
+/// version of the format it was written in, and a replay written in a newer
+/// version than [`Replay::VERSION`] is refused rather than misread.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    /// The version of the format the replay was written in.
+    pub version: u32,
+    #[serde(default)]
+    pub game_id: Option<GameId>,
+    /// How the game was set up: its rules, the players in order of play and
+    /// the tiles they drew for it.
+    pub start: GameStart,
+    /// The seed the tiles were dealt from, if the server committed to one.
+    #[serde(default)]
+    pub deal_seed: Option<DealSeed>,
+    pub actions: Box<[TaggedPlayerAction]>,
+    /// Every seat that changed hands, in the order they did.
+    #[serde(default)]
+    pub substitutions: Box<[Substitution]>,
+    #[serde(flatten)]
+    pub reason: GameOver,
+    /// The final standings, with the winner first.
+    pub results: Box<[FinalResult]>,
+}
+
+/// Reasons a replay can't be trusted.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum VerifyError {
+    #[error("the replay was written in version {0} of the format, which this build can't read")]
+    UnsupportedVersion(u32),
+    #[error("the game ID doesn't match the one the game was started with")]
+    GameIdMismatch,
+    #[error("the tiles weren't dealt from the recorded seed, as {0}")]
+    Deal(#[from] DealMismatch),
+    #[error(transparent)]
+    Replay(#[from] ReplayError),
+    #[error("{player_name} couldn't take the seat of {seat} ({reason})")]
+    Substitution {
+        seat: Box<str>,
+        player_name: Box<str>,
+        reason: InvalidMessageReason,
+    },
+    #[error("the game was over after {played} of the {recorded} actions recorded")]
+    ActionsAfterEnd {
+        played: usize,
+        recorded: usize,
+    },
+    #[error("the game ended as {found}, but the replay says {recorded}")]
+    ReasonMismatch {
+        recorded: GameOver,
+        found: GameOver,
+    },
+    #[error("the recorded results aren't those the game ends with")]
+    ResultsMismatch,
+    #[error("the results can't be tallied, as {0}")]
+    MoneyOverflow(#[from] MoneyOverflow),
+}
+
+impl Replay {
+    /// The version of the format replays are written in by this build.
+    pub const VERSION: u32 = 1;
+
+    /// Reads a replay from a file.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    /// Writes the replay to a file.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+
+    /// Plays the game through from its start, checking that it ends as
+    /// recorded.
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        if self.version > Self::VERSION {
+            return Err(VerifyError::UnsupportedVersion(self.version));
+        }
+        if self.start.game_id.is_some_and(|game_id| Some(game_id) != self.game_id) {
+            return Err(VerifyError::GameIdMismatch);
+        }
+        if let Some(seed) = &self.deal_seed {
+            seed.verify(&self.start, None, &[])?;
+        }
+
+        let recorded = self.actions.len();
+        let game_over = match self.position(recorded)? {
+            Err(game_over) => game_over,
+            // The moves played out without ending the game, as they do when
+            // it is ended by other means
+            Ok(game) => match &self.reason {
+                GameOver::OutOfTime { player } => game.forfeit(player),
+                _ => game.end_early(),
+            },
+        };
+
+        let played = game_over.next_action_number().index;
+        if played != recorded {
+            return Err(VerifyError::ActionsAfterEnd { played, recorded });
+        }
+        if *game_over.reason() != self.reason {
+            return Err(VerifyError::ReasonMismatch {
+                recorded: self.reason.clone(),
+                found: game_over.reason().clone(),
+            });
+        }
+        if game_over.tally_results()?.final_standings != self.results {
+            return Err(VerifyError::ResultsMismatch);
+        }
+        Ok(())
+    }
+
+    /// Plays the first `played` of the recorded actions, handing over the
+    /// seats that changed hands along the way, to get the game as it stood
+    /// then. Stops early if the game ends first.
+    pub fn position(&self, played: usize)
+        -> Result<GameUpdateResult<kernel::Ambiguous>, VerifyError>
+    {
+        self.play_through(played, |_, _| {})
+    }
+
+    /// Plays the first `played` of the recorded actions as
+    /// [`position`](Self::position) does, showing each to `visit` along with
+    /// the game as it stood before it.
+    pub fn play_through<F>(&self, played: usize, mut visit: F)
+        -> Result<GameUpdateResult<kernel::Ambiguous>, VerifyError>
+        where F: FnMut(&Game<kernel::Ambiguous>, &TaggedPlayerAction)
+    {
+        let mut game: Game<kernel::Ambiguous> = Game::start(&self.start).into();
+        let mut substitutions = self.substitutions.iter().peekable();
+        let played = played.min(self.actions.len());
+        for index in 0..=played {
+            // Seats change hands between the actions they were made after
+            while let Some(substitution) = substitutions.next_if(|s| s.after_actions <= index) {
+                game.substitute(&substitution.seat, substitution.player_name.clone())
+                    .map_err(|reason| VerifyError::Substitution {
+                        seat: substitution.seat.clone(),
+                        player_name: substitution.player_name.clone(),
+                        reason,
+                    })?;
+            }
+            if index == played { break; }
+            let action = &self.actions[index];
+            visit(&game, action);
+            game = match game.try_advance_game(action) {
+                Ok(Ok(game)) => game,
+                Ok(Err(game_over)) => return Ok(Err(game_over)),
+                Err((_, reason)) => {
+                    let player_name = action.player_name.clone();
+                    return Err(ReplayError { index, player_name, reason }.into());
+                },
+            };
+        }
+        Ok(Ok(game))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::game_id::GameId;
+    use crate::game::kernel::Game;
+    use crate::game::messages::{GameOver, GameStart};
+
+    use super::{Replay, VerifyError};
+
+    #[test]
+    fn verifies_recorded_results() {
+        let game_id = GameId::generate();
+        let start = GameStart { game_id: Some(game_id), ..GameStart::two_player_test() };
+        let results = Game::start(&start).end_early().tally_results().unwrap().final_standings;
+        let replay = Replay {
+            version: Replay::VERSION,
+            game_id: Some(game_id),
+            start,
+            deal_seed: None,
+            actions: Box::new([]),
+            substitutions: Box::new([]),
+            reason: GameOver::EndedEarly,
+            results,
+        };
+        let json = serde_json::to_string(&replay).unwrap();
+        let replay: Replay = serde_json::from_str(&json).unwrap();
+        assert!(replay.verify().is_ok());
+
+        let mut tampered = replay.clone();
+        tampered.results.reverse();
+        assert!(matches!(tampered.verify(), Err(VerifyError::ResultsMismatch)));
+        let tampered = Replay { reason: GameOver::NoStock, ..replay.clone() };
+        assert!(matches!(tampered.verify(), Err(VerifyError::ReasonMismatch { .. })));
+        let newer = Replay { version: Replay::VERSION + 1, ..replay };
+        assert!(matches!(newer.verify(), Err(VerifyError::UnsupportedVersion(_))));
+    }
+}
@@ -0,0 +1,126 @@
+use std::fmt::Write;
+
+use super::board::Board;
+use super::messages::FinalResult;
+use super::tile::Tile;
+use super::Company;
+
+/// The width and height of a cell, in pixels.
+const CELL: u32 = 40;
+/// The space around the board, and between it and the standings.
+const MARGIN: u32 = 20;
+/// The height of a line of the standings.
+const LINE: u32 = 24;
+
+const EMPTY_FILL: &str = "#eeeeee";
+const UNINCORPORATED_FILL: &str = "#9e9e9e";
+
+/// Gets the color a company's tiles are filled with, after the colors it is
+/// drawn in on the terminal.
+fn fill(company: Company) -> &'static str {
+    match company {
+        Company::Continental => "#00acc1",
+        Company::Imperial => "#d81b60",
+        Company::American => "#1e88e5",
+        Company::Festival => "#43a047",
+        Company::Worldwide => "#f5f5f5",
+        Company::Luxor => "#e53935",
+        Company::Tower => "#fdd835",
+    }
+}
+
+/// Escapes text to be placed in an SVG document.
+fn escape(text: &str) -> String {
+    text.chars().fold(String::with_capacity(text.len()), |mut escaped, c| {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+/// Draws the board as an SVG document, laid out as on the terminal, with the
+/// rows of the board across and its columns down. Each tile is labelled, and
+/// each company's tiles also carry its initial.
+pub fn board(board: &Board) -> String {
+    final_position(board, &[])
+}
+
+/// Draws the board as [`board`] does, with the final standings listed below
+/// it.
+pub fn final_position(board: &Board, standings: &[FinalResult]) -> String {
+    let cols = Tile::NUM_ROWS as u32;
+    let rows = Tile::col_as_num(Tile::LAST_COL) as u32;
+    let width = cols * CELL + 2 * MARGIN;
+    let board_height = rows * CELL + 2 * MARGIN;
+    let height = match standings.len() as u32 {
+        0 => board_height,
+        lines => board_height + lines * LINE + MARGIN,
+    };
+
+    let mut svg = String::new();
+    // Writing to a String cannot fail
+    let _ = writeln!(svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}" font-family="sans-serif">"#);
+    let _ = writeln!(svg, r#"<rect width="{width}" height="{height}" fill="white"/>"#);
+
+    for col in 'a'..=Tile::LAST_COL {
+        for row in 1..=Tile::NUM_ROWS {
+            let tile = Tile::new(row, col);
+            let cell = board[tile];
+            let color = match cell {
+                Some(Some(company)) => fill(company),
+                Some(None) => UNINCORPORATED_FILL,
+                None => EMPTY_FILL,
+            };
+
+            let x = MARGIN + (row as u32 - 1) * CELL;
+            let y = MARGIN + (Tile::col_as_num(col) as u32 - 1) * CELL;
+            let _ = writeln!(svg,
+                r#"<rect x="{x}" y="{y}" width="{CELL}" height="{CELL}" fill="{color}" stroke="white" stroke-width="2"/>"#);
+            let _ = writeln!(svg,
+                r##"<text x="{}" y="{}" font-size="11" text-anchor="middle" fill="#424242">{tile}</text>"##,
+                x + CELL / 2, y + 14);
+            if let Some(Some(company)) = cell {
+                let _ = writeln!(svg,
+                    r#"<text x="{}" y="{}" font-size="16" font-weight="bold" text-anchor="middle">{}</text>"#,
+                    x + CELL / 2, y + 33, company.char());
+            }
+        }
+    }
+
+    for (i, result) in standings.iter().enumerate() {
+        let y = board_height + (i as u32 + 1) * LINE;
+        let _ = writeln!(svg, r#"<text x="{MARGIN}" y="{y}" font-size="16">{}</text>"#, escape(&result.to_string()));
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::board::Board;
+    use crate::game::messages::{FinalResult, TilePlacement};
+    use crate::game::tile::Tile;
+    use crate::game::Money;
+
+    use super::final_position;
+
+    #[test]
+    fn draws_every_cell_and_result() {
+        let mut board = Board::new();
+        board.place_tile(TilePlacement { tile: Tile::new(3, 'c'), implication: None });
+        let standings = [FinalResult { place: 1, player_name: "<alice>".into(), final_money: Money::new(9000) }];
+
+        let svg = final_position(&board, &standings);
+        assert!(svg.starts_with("<svg ") && svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<rect ").count(), 1 + 12 * 9);
+        assert!(svg.contains(r##"fill="#9e9e9e""##));
+        assert!(svg.contains("[1] &lt;alice&gt; with $9,000"));
+    }
+}
@@ -115,7 +115,7 @@ impl FromStr for Tile {
             return Err(InvalidRow(row));
         }
 
-        // The column will be the first byte
+        // The column is a single letter
         let mut col_chars = col_str.chars();
         let col = match col_chars.next() {
             Some(x) => match x {
@@ -125,6 +125,9 @@ impl FromStr for Tile {
             },
             None => return Err(InvalidColumn(col_str.to_owned())),
         };
+        if col_chars.next().is_some() {
+            return Err(InvalidColumn(col_str.to_owned()));
+        }
         
         Ok(Tile { row, col: col as u8 })
     }
@@ -144,7 +147,8 @@ impl<'de> serde::Deserialize<'de> for Tile {
     where
         D: serde::Deserializer<'de>
     {
-        let string: &str = Deserialize::deserialize(deserializer)?;
+        // Not borrowed, as the string may contain escapes
+        let string: String = Deserialize::deserialize(deserializer)?;
         string.parse().map_err(serde::de::Error::custom)
     }
 }
@@ -331,25 +335,36 @@ impl TryFrom<Hand> for FullHand {
 }
 
 /// A collection from which items are removed at random.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Boneyard<T> {
     boneyard: Vec<T>,
+    /// Whether items are removed at random. If not, they are removed in the
+    /// order in which the boneyard was stacked.
+    random: bool,
 }
 
 impl<T> Boneyard<T> {
     pub fn new(initial: Vec<T>) -> Self {
-        Self { boneyard: initial }
+        Self { boneyard: initial, random: true }
+    }
+
+    /// Creates a boneyard from which items are removed in the order given,
+    /// rather than at random.
+    pub fn stacked(mut order: Vec<T>) -> Self {
+        order.reverse();
+        Self { boneyard: order, random: false }
     }
 
     /// Takes a random value from the boneyard. Returns [`None`] if the boneyard
     /// is empty.
     pub fn remove(&mut self) -> Option<T> {
 
-        // Get a random index
-        let idx: usize = rand::random();
-        let idx = idx % self.boneyard.len();
-
         let last = self.boneyard.pop()?;
+        if !self.random { return Some(last); }
+
+        // Get a random index, including that of the value just popped
+        let idx: usize = rand::random();
+        let idx = idx % (self.boneyard.len() + 1);
 
         if idx == self.boneyard.len() {
             Some(last)
@@ -357,11 +372,55 @@ impl<T> Boneyard<T> {
             Some(std::mem::replace(&mut self.boneyard[idx], last))
         }
     }
+
+    /// Iterates over the items left in the boneyard, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.boneyard.iter()
+    }
+}
+
+/// A boneyard as it is written out, with its items in the order they are
+/// stored, so that a stacked boneyard is read back in the same order.
+#[derive(Serialize)]
+struct BoneyardRef<'a, T> {
+    items: &'a [T],
+    random: bool,
+}
+
+/// A boneyard as it is read back.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredBoneyard<T> {
+    Full { items: Vec<T>, random: bool },
+    /// The items alone, as boneyards were first written, which are removed
+    /// at random.
+    Items(Vec<T>),
+}
+
+impl<T: Serialize> Serialize for Boneyard<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer
+    {
+        BoneyardRef { items: &self.boneyard, random: self.random }.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Boneyard<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>
+    {
+        Ok(match StoredBoneyard::deserialize(deserializer)? {
+            StoredBoneyard::Full { items, random } => Self { boneyard: items, random },
+            StoredBoneyard::Items(items) => Self::new(items),
+        })
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{Tile, TileFromStrError};
+    use super::{Boneyard, Tile, TileFromStrError};
 
     #[test]
     fn tile_parsing() {
@@ -370,5 +429,27 @@ mod test {
         assert_eq!("17-i".parse::<Tile>(), Err(TileFromStrError::InvalidRow(17)));
         assert_eq!("i".parse::<Tile>(), Err(TileFromStrError::NoDash));
         assert_eq!("".parse::<Tile>(), Err(TileFromStrError::NoDash));
+        assert_eq!("1-ab".parse::<Tile>(), Err(TileFromStrError::InvalidColumn("ab".to_owned())));
+    }
+
+    #[test]
+    fn boneyard_runs_out() {
+        let mut boneyard = Boneyard::new(vec![1, 2, 3]);
+        let mut removed: Vec<_> = std::iter::from_fn(|| boneyard.remove()).collect();
+        removed.sort();
+        assert_eq!(removed, [1, 2, 3]);
+        assert_eq!(boneyard.remove(), None);
+    }
+
+    #[test]
+    fn stacked_boneyard_stays_stacked() {
+        let boneyard = Boneyard::stacked(vec![1, 2, 3]);
+        let json = serde_json::to_string(&boneyard).unwrap();
+        let mut boneyard: Boneyard<i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(std::iter::from_fn(|| boneyard.remove()).collect::<Vec<_>>(), [1, 2, 3]);
+
+        let boneyard: Boneyard<i32> = serde_json::from_str("[1, 2, 3]").unwrap();
+        assert!(boneyard.random);
+        assert_eq!(boneyard.iter().count(), 3);
     }
 }
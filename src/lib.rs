@@ -0,0 +1,4 @@
+pub mod client;
+pub mod game;
+pub mod net;
+pub mod server;
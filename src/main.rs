@@ -1,33 +1,168 @@
-use std::io;
+use std::io::{self, Write};
 use std::net::Ipv4Addr;
+use std::path::Path;
+use std::time::Duration;
 
-use clap::Parser;
-use server::{Server, Handshake};
+use acquire::client::{self, ClientConfig};
+use acquire::client::notifier::Notifier;
+use acquire::client::session::Session;
+use acquire::server::resume::ResumeToken;
+use acquire::game::policy::RulePolicy;
+use acquire::game::analysis::Report;
+use acquire::game::replay::{self, Replay};
+use acquire::net;
+use acquire::server::{Server, ServerOptions, Handshake};
+use acquire::server::autosave::AutosaveOptions;
+use acquire::server::capability::Capabilities;
+use acquire::server::feature::Features;
+use acquire::server::clock::TimeBank;
+use acquire::server::invite::InviteCode;
+use clap::{CommandFactory, Parser};
+use clap::error::ErrorKind;
 use tokio::net::{TcpListener, TcpStream};
 
 mod cli;
-mod client;
-mod game;
-mod net;
-mod server;
+
+/// Printed by the `rules` subcommand.
+const RULES: &str = include_str!("rules.txt");
 
 #[tokio::main]
 async fn main() {
 
     let cli = cli::Cli::parse();
 
+    if let cli::HostIntent::Rules = cli.intent {
+        print!("{RULES}");
+        return;
+    }
+    if let cli::HostIntent::Replay { command } = &cli.intent {
+        replay(command);
+        return;
+    }
+    if let cli::HostIntent::Analyze { dir } = &cli.intent {
+        analyze(dir);
+        return;
+    }
+    if let cli::HostIntent::Host { port, view_port: Some(view_port), .. } = cli.intent {
+        // Two ports of 0 are each picked apart, so only a fixed port clashes
+        if port == view_port && port != 0 {
+            cli::Cli::command()
+                .error(ErrorKind::ArgumentConflict, "the view port must differ from the port players join on")
+                .exit();
+        }
+    }
+    let Some(name) = cli.name else {
+        cli::Cli::command()
+            .error(ErrorKind::MissingRequiredArgument, "a --name is needed to join or host a game")
+            .exit();
+    };
+
     let host_handshake = Handshake {
-        player_name: cli.name.into_boxed_str(),
+        player_name: name.into_boxed_str(),
         spectating: cli.spectate,
-        admin: false,
+        capabilities: Capabilities::NONE,
+        features: Features::SUPPORTED,
     };
 
+    let mut config = ClientConfig {
+        tutorial: cli.tutorial,
+        hint_time_budget: Duration::from_millis(cli.hint_time),
+        lang: cli.lang,
+        export_dir: None,
+        console: None,
+        toasts: !cli.no_toasts,
+        notifier: match cli.sound_command {
+            Some(command) => Notifier::Command(command.into_boxed_str()),
+            None if cli.bell => Notifier::Bell,
+            None => Notifier::Off,
+        },
+        game_log: cli.log_game,
+    };
+
+    let screen_reader = cli.screen_reader;
     let result = match cli.intent {
-        cli::HostIntent::Join { address } => {
-            join(address, host_handshake).await
+        cli::HostIntent::Join { address, retry, socks5, code } => {
+            join(address, retry, socks5, code, host_handshake, config, screen_reader).await
+        },
+        cli::HostIntent::Host {
+            port,
+            queue_early_actions,
+            time_bank,
+            time_out_penalty,
+            afk_turns,
+            pacing,
+            end_vote_majority,
+            commit_deal,
+            random_seating,
+            no_hints,
+            no_autopilot,
+            chat,
+            server_name,
+            motd,
+            view_port,
+            webhook_url,
+            submit_results,
+            export_dir,
+            checkpoint_interval,
+            saves,
+            invite,
+            matchmaking,
+            headless,
+        } => {
+            let cli::SaveArgs { autosave_dir, autosave_actions, autosave_minutes, replay_dir } = *saves;
+
+            // A directory that can't be written to is better found now than
+            // in the middle of a game
+            if let Some(dir) = &autosave_dir {
+                if let Err(why) = std::fs::create_dir_all(dir) {
+                    eprintln!("Can't autosave to {}: {why}", dir.display());
+                    return;
+                }
+            }
+            if let Some(dir) = &replay_dir {
+                if let Err(why) = std::fs::create_dir_all(dir) {
+                    eprintln!("Can't save replays to {}: {why}", dir.display());
+                    return;
+                }
+            }
+            config.export_dir = export_dir;
+            let options = ServerOptions {
+                max_players: Some(8),
+                max_connections: Some(16),
+                queue_early_actions,
+                time_bank: time_bank.map(|secs| TimeBank {
+                    per_player: Duration::from_secs(secs),
+                    penalty: time_out_penalty,
+                    afk_turns,
+                }),
+                end_vote_majority,
+                commit_deal,
+                random_seating,
+                name: server_name.map(Into::into),
+                motd: motd.map(Into::into),
+                webhook: webhook_url,
+                results_url: submit_results,
+                checkpoint_interval,
+                autosave: autosave_dir.map(|dir| AutosaveOptions {
+                    dir,
+                    every_actions: Some(autosave_actions),
+                    every: autosave_minutes.map(|minutes| Duration::from_secs(minutes * 60)),
+                }),
+                replay_dir,
+                invite_code: invite.then(InviteCode::generate),
+                matchmaking: matchmaking.map(usize::from),
+                pacing,
+                policy: RulePolicy { no_hints, no_autopilot, chat },
+            };
+            let ui = match (headless, screen_reader) {
+                (true, _) => HostUi::Console,
+                (false, true) => HostUi::ScreenReader,
+                (false, false) => HostUi::Terminal,
+            };
+            host(port, view_port, options, host_handshake, config, ui).await
         },
-        cli::HostIntent::Host { port } => {
-            host(port, host_handshake).await
+        cli::HostIntent::Rules | cli::HostIntent::Replay { .. } | cli::HostIntent::Analyze { .. } => {
+            unreachable!("the rules and replays are dealt with before connecting")
         },
     };
 
@@ -36,41 +171,314 @@ async fn main() {
     }
 }
 
-/// Hosts a game
-async fn join(address: String, handshake: Handshake) -> io::Result<()> {
-    let socket = TcpStream::connect(address).await?;
-    println!("Connected to remote server.");
+/// Runs a `replay` subcommand, exiting with an error if the replay can't be
+/// read or trusted.
+fn replay(command: &cli::ReplayCommand) {
+    let (cli::ReplayCommand::Verify { file } | cli::ReplayCommand::Explore { file }) = command;
+    let replay = match Replay::load(file) {
+        Ok(replay) => replay,
+        Err(why) => {
+            eprintln!("Can't read {}: {why}", file.display());
+            std::process::exit(1);
+        },
+    };
+    match command {
+        cli::ReplayCommand::Verify { .. } => {
+            if let Err(why) = replay.verify() {
+                eprintln!("{} can't be trusted: {why}", file.display());
+                std::process::exit(1);
+            }
+            let game = replay.game_id.map_or_else(|| "The game".to_owned(), |id| format!("Game {id}"));
+            println!("{game} checks out: its {} moves lead to the results it records.", replay.actions.len());
+        },
+        cli::ReplayCommand::Explore { .. } => {
+            if let Err(why) = client::explore::run(&replay, io::stdin().lock(), io::stdout()) {
+                eprintln!("Exploring stopped: {why}");
+            }
+        },
+    }
+}
+
+/// Prints a report of the replays in `dir`, leaving out any that can't be
+/// read or trusted.
+fn analyze(dir: &Path) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(why) => {
+            eprintln!("Can't read {}: {why}", dir.display());
+            std::process::exit(1);
+        },
+    };
+    let mut files: Vec<_> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == replay::EXTENSION))
+        .collect();
+    files.sort();
 
-    let remote_connection = net::RemoteServer::new(socket)
-        .connect_player(handshake).await?;
+    let mut report = Report::default();
+    for file in files {
+        let added = Replay::load(&file)
+            .map_err(|why| why.to_string())
+            .and_then(|replay| report.add(&replay).map_err(|why| why.to_string()));
+        if let Err(why) = added {
+            eprintln!("Left out {}: {why}", file.display());
+        }
+    }
+    print!("{report}");
+}
+
+/// Joins a game hosted elsewhere
+async fn join(
+    mut address: String,
+    retry: bool,
+    mut proxy: Option<String>,
+    mut invite_code: Option<InviteCode>,
+    mut handshake: Handshake,
+    config: ClientConfig,
+    screen_reader: bool,
+) -> io::Result<()> {
+
+    // A session the client didn't leave cleanly may be taken up again,
+    // keeping the seat held for it
+    let resume_token = match Session::load() {
+        Some(session) if offer_resume(&session).await? => {
+            address = session.address.into();
+            proxy = session.proxy.map(Into::into);
+            invite_code = session.invite_code;
+            handshake.player_name = session.player_name;
+            handshake.spectating = session.spectating;
+            session.resume_token
+        },
+        _ => ResumeToken::generate(),
+    };
+
+    let remote_connection = loop {
+        let socket = open_socket(&address, retry, proxy.as_deref()).await?;
+        println!("Connected to remote server.");
+
+        let server = net::RemoteServer::new(socket);
+        match server.resume_player(handshake.clone(), invite_code.clone(), Some(resume_token.clone())).await {
+            Ok(connection) => break connection,
+            Err(err) => {
+                let suggested = net::Rejection::of(&err)
+                    .and_then(|rejection| rejection.suggested_name.clone());
+                match suggested {
+                    Some(name) if accept_suggested_name(&err, &name).await? => {
+                        handshake.player_name = name;
+                    },
+                    _ => return Err(err),
+                }
+            },
+        }
+    };
     println!("Successfully joined server! Starting client.");
 
-    client::robust::run_io(remote_connection).await?;
+    let session = Session {
+        address: address.into(),
+        proxy: proxy.map(Into::into),
+        player_name: remote_connection.handshake.player_name.clone(),
+        spectating: remote_connection.handshake.spectating,
+        invite_code,
+        resume_token,
+    };
+    if let Err(why) = session.save() {
+        println!("Couldn't keep the session to resume later: {why}");
+    }
+
+    if screen_reader {
+        client::primitive::run(remote_connection, config).await?;
+    } else {
+        client::robust::run_io(remote_connection, config).await?;
+    }
+
+    // The player left on purpose, or the server went away, so there is
+    // nothing to resume
+    Session::clear();
     Ok(())
 }
 
-async fn host(port: u16, mut handshake: Handshake) -> io::Result<()> {
+/// Offers to resume a session the client didn't leave cleanly. Returns
+/// whether the player took it. The address and name given on the command
+/// line are what the player asked for, so the session, which replaces them,
+/// is only resumed if the player says so.
+async fn offer_resume(session: &Session) -> io::Result<bool> {
+    print!("Your last session, as {} on {}, ended unexpectedly. Type y to resume it, or press Enter to start afresh: ",
+        session.player_name, session.address);
+    io::stdout().flush()?;
+    let answer = tokio::task::spawn_blocking(|| {
+        let mut line = String::new();
+        io::stdin().read_line(&mut line).map(|_| line)
+    }).await.map_err(io::Error::other)??;
+    Ok(matches!(answer.trim(), "y" | "Y"))
+}
 
-    // Set the handshake's admin to true, since the host is an administrator
-    handshake.admin = true;
+/// Connects to the server at `address`, retrying until it answers if `retry`
+/// is set.
+async fn open_socket(address: &str, retry: bool, proxy: Option<&str>) -> io::Result<TcpStream> {
+    match retry {
+        true => {
+            let socket = tokio::select! {
+                socket = dial_with_retry(address, proxy) => socket?,
+                _ = tokio::signal::ctrl_c() => {
+                    println!();
+                    return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+                },
+            };
+            // Listening for Ctrl-C stopped it from ending the process, so
+            // have it end it again as usual
+            tokio::spawn(async {
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    std::process::exit(130);
+                }
+            });
+            Ok(socket)
+        },
+        false => dial(address, proxy).await.map_err(io::Error::other),
+    }
+}
 
-    // Start the server
-    let (server, host_interface) = Server::start(
-        Some(8), Some(16), handshake
-    );
+/// Offers to join under the name the server suggested in place of one that is
+/// taken. Returns whether the player took it.
+async fn accept_suggested_name(err: &io::Error, name: &str) -> io::Result<bool> {
+    print!("Couldn't join: {err}. Press Enter to join as {name} instead, or type n to quit: ");
+    io::stdout().flush()?;
+    let answer = tokio::task::spawn_blocking(|| {
+        let mut line = String::new();
+        io::stdin().read_line(&mut line).map(|_| line)
+    }).await.map_err(io::Error::other)??;
+    Ok(matches!(answer.trim(), "" | "y" | "Y"))
+}
+
+/// Connects to a server, through a proxy if one is given.
+async fn dial(address: &str, proxy: Option<&str>) -> Result<TcpStream, net::dial::DialError> {
+    match proxy {
+        Some(proxy) => net::dial::connect_through(proxy, address).await,
+        None => net::dial::connect(address).await,
+    }
+}
+
+/// Connects to a server, trying again for as long as it doesn't answer, and
+/// keeping the player posted on a single status line.
+async fn dial_with_retry(address: &str, proxy: Option<&str>) -> io::Result<TcpStream> {
+    let mut backoff = net::dial::Backoff::new();
+    for attempt in 1.. {
+        match dial(address, proxy).await {
+            Ok(socket) => {
+                if attempt > 1 { println!(); }
+                return Ok(socket);
+            },
+            Err(err) if err.is_transient() => {
+                let delay = backoff.next_delay();
+                print!("\r\x1b[KNo answer from {address} (attempt {attempt}), trying again in {}s. Ctrl-C to cancel.",
+                    delay.as_secs());
+                io::stdout().flush()?;
+                tokio::time::sleep(delay).await;
+            },
+            Err(err) => {
+                if attempt > 1 { println!(); }
+                return Err(io::Error::other(err));
+            },
+        }
+    }
+    unreachable!()
+}
+
+/// What the host runs on the terminal alongside the server.
+enum HostUi {
+    /// The game client, drawn on the terminal
+    Terminal,
+    /// The game client, narrated as plain lines for a screen reader
+    ScreenReader,
+    /// No game client, just the console
+    Console,
+}
+
+/// Listens on `port` of this machine, or on any free port if it is 0. A port
+/// already in use is reported with what to do about it.
+async fn bind(port: u16) -> io::Result<TcpListener> {
+    TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await.map_err(|err| match err.kind() {
+        io::ErrorKind::AddrInUse => io::Error::new(err.kind(), format!(
+            "port {port} is already in use. Close whatever is using it, or \
+            host on port 0 to have a free one picked"
+        )),
+        _ => err,
+    })
+}
 
-    // Start the TCP listener
-    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await?;
+async fn host(
+    port: u16,
+    view_port: Option<u16>,
+    options: ServerOptions,
+    mut handshake: Handshake,
+    mut config: ClientConfig,
+    ui: HostUi,
+) -> io::Result<()> {
+
+    // The host may do anything as an admin
+    handshake.capabilities = Capabilities::ALL;
+    // A host without a game client can only watch
+    if let HostUi::Console = ui {
+        handshake.spectating = true;
+    }
+
+    // Bind the ports before starting anything, so that a port in use is
+    // reported before there is a server to shut down
+    let listener = bind(port).await?;
+    let viewers = match view_port {
+        Some(view_port) => Some(bind(view_port).await?),
+        None => None,
+    };
+
+    // Start the server
+    let invite_code = options.invite_code.clone();
+    let (server, host_interface) = Server::start(options, handshake);
     println!("Server started: listening at {}.", listener.local_addr()?);
-    let net_handle = tokio::spawn(net::listen_for_connections(server, listener));
+    if let Some(code) = invite_code {
+        println!("Players join with --code {code}.");
+    }
+    if let Some(viewers) = viewers {
+        println!("Viewers can watch at {}.", viewers.local_addr()?);
+        tokio::spawn(net::listen_for_viewers(server.clone(), viewers));
+    }
+    let console = server.console();
+    config.console = Some(console.clone());
+    tokio::spawn(net::listen_for_connections(server.clone(), listener));
+    server.ready().await;
 
     // Start the client
+    if let HostUi::Console = ui {
+        return client::console::run(host_interface, console).await;
+    }
     println!("Starting client");
-    std::thread::sleep(std::time::Duration::from_secs(1));
-    match client::robust::run(host_interface).await? {
-        Ok(()) => {},
-        Err(_) => {},
-    };
+    if let HostUi::ScreenReader = ui {
+        if let Err(never) = client::primitive::run(host_interface, config).await {
+            match never {}
+        }
+    } else {
+        match client::robust::run(host_interface, config).await? {
+            Ok(()) => {},
+            Err(_) => {},
+        };
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use super::bind;
+
+    #[tokio::test]
+    async fn port_in_use_explained() {
+        // Port 0 has a free port picked
+        let listener = bind(0).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert_ne!(port, 0);
+
+        let err = bind(port).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        assert!(err.to_string().contains(&format!("port {port} is already in use")));
+    }
+}
@@ -1,13 +1,33 @@
+use std::borrow::Cow;
 use std::io;
+use std::net::IpAddr;
 
-use tokio::io::{BufStream, AsyncBufReadExt, AsyncWriteExt};
+use tokio::io::{BufStream, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc;
 
+use crate::client::viewer;
 use crate::game::messages::*;
-use crate::server::{Server, Interface, Handshake, ServerState, NewConnection};
-
-const DELIM: u8 = '\n' as u8;
+use crate::server::{ConnectionReject, Server, Interface, Handshake, ServerState, NewConnection};
+use crate::server::capability::Capabilities;
+use crate::server::feature::Features;
+use crate::server::invite::InviteCode;
+use crate::server::resume::ResumeToken;
+
+/// Declares the parsing and validation of messages sent by clients.
+pub mod protocol;
+/// Separates the messages sent over a connection from one another.
+pub mod frame;
+/// Compresses the larger messages sent over a connection.
+pub mod compress;
+/// Finds and connects to the server behind an address typed in by a player.
+pub mod dial;
+/// Reaches a server through a SOCKS5 proxy, such as an SSH tunnel.
+pub mod socks5;
+
+use self::compress::Encoding;
+use self::frame::{Framing, MAX_FRAME_LEN, PREAMBLE, PROTOCOL_VERSION};
+use self::protocol::{HandshakeRequest, ProtocolError, MAX_MESSAGE_LEN};
 
 /// Listens for incoming TCP connections on the passed listener and connects
 /// them to a server.
@@ -19,7 +39,7 @@ pub async fn listen_for_connections(
     let server = server.clone();
 
     loop {
-        let (socket, _addr) = stream.accept().await?;
+        let (socket, addr) = stream.accept().await?;
         let mut socket = RemoteClient::new(socket);
 
         // Start the connection, but in a separate task to not block the accept loop
@@ -27,14 +47,14 @@ pub async fn listen_for_connections(
         tokio::spawn(async move {
 
             // Check if the handshake is valid
-            let connection = recv_and_connect(&server_clone, &mut socket).await;
+            let connection = recv_and_connect(&server_clone, &mut socket, addr.ip()).await;
 
             let interface = match connection {
                 Ok(Some(interface)) => interface,
                 Err(_) | Ok(None) => {
                     // Shut down the socket; ignore any errors, as we have no
                     // way to report them
-                    socket.0.shutdown().await.ok();
+                    socket.socket.shutdown().await.ok();
 
                     // Terminate this connection
                     return;
@@ -47,6 +67,74 @@ pub async fn listen_for_connections(
     }
 }
 
+/// Listens for viewers on the passed listener, each of whom is connected to
+/// the server as a spectator and sent the game as plain text by
+/// [`viewer::run`], so anyone can watch with `nc`. A server open only by
+/// invitation asks each viewer for its invite code first, checked as it is
+/// for players.
+pub async fn listen_for_viewers(
+    server: Server,
+    stream: TcpListener,
+) -> io::Result<()> {
+
+    loop {
+        let (mut socket, addr) = stream.accept().await?;
+
+        let server = server.clone();
+        tokio::spawn(async move {
+            let mut handshake = Handshake {
+                player_name: "viewer".into(),
+                spectating: true,
+                capabilities: Capabilities::NONE,
+                features: Features::NONE,
+            };
+            let connection = match recv_viewer_invite(&server, &mut socket, addr.ip()).await {
+                Ok(()) => loop {
+                    match server.connect_player(handshake.clone()).await {
+                        Err(ConnectionReject::NameTaken { suggestion }) => handshake.player_name = suggestion,
+                        result => break result,
+                    }
+                },
+                Err(reject) => Err(reject),
+            };
+
+            // Ignore any IO errors, as we have no way to report them
+            match connection {
+                Ok(connection) => match viewer::run(connection, &mut socket).await {
+                    Ok(()) => {},
+                    Err(never) => match never {},
+                },
+                Err(reject) => {
+                    socket.write_all(format!("Couldn't watch: {reject}.\n").as_bytes()).await.ok();
+                },
+            }
+            socket.shutdown().await.ok();
+        });
+    }
+}
+
+/// Asks a viewer connected through `socket` from `address` for the server's
+/// invite code, if it needs one, and checks the line sent back. A viewer that
+/// sends nothing, or more than a code could be, gives no code.
+async fn recv_viewer_invite(server: &Server, socket: &mut TcpStream, address: IpAddr)
+    -> Result<(), ConnectionReject>
+{
+    if !server.needs_invite() { return Ok(()) }
+
+    socket.write_all(b"Invite code: ").await.ok();
+    let mut line = String::new();
+    let mut reader = tokio::io::BufReader::new(socket.take(VIEWER_INVITE_LEN));
+    let code = match reader.read_line(&mut line).await {
+        Ok(_) => line.parse::<InviteCode>().ok(),
+        Err(_) => None,
+    };
+    server.check_invite(address, code.as_ref())
+}
+
+/// The most a viewer may send in answer to being asked for an invite code,
+/// which is more than any code is long.
+const VIEWER_INVITE_LEN: u64 = 64;
+
 /// Tries to receive a handshake through the socket and connect it to the
 /// provided [`Server`]. Responds to the sender with the proper response, be it
 /// a success message or an error message. If the handshake was invalid,
@@ -55,11 +143,36 @@ pub async fn listen_for_connections(
 async fn recv_and_connect(
     server: &Server,
     socket: &mut RemoteClient,
+    address: IpAddr,
 ) -> io::Result<Option<NewConnection<std::convert::Infallible>>> {
 
+    // A client that sends a preamble names the version of the protocol it
+    // speaks. Any other is from before there were versions.
+    let first_byte = socket.socket.fill_buf().await?.first().copied();
+    if first_byte == Some(PREAMBLE[0]) {
+        let mut preamble = [0; PREAMBLE.len() + 1];
+        socket.socket.read_exact(&mut preamble).await?;
+        let (magic, version) = preamble.split_at(PREAMBLE.len());
+        match Framing::for_version(version[0]).filter(|_| magic == PREAMBLE) {
+            Some(framing) => socket.framing = framing,
+            None => {
+                let reason = format!("unsupported protocol version {}", version[0]);
+                socket.reject(&reason, None).await?;
+                return Ok(None);
+            },
+        }
+
+        // From version 3, the client lists the encodings it can read
+        if version[0] >= 3 {
+            let accepts = socket.socket.read_u8().await?;
+            socket.encoded = true;
+            socket.compress = accepts & Encoding::Deflate.flag() != 0;
+        }
+    }
+
     // Get the handshake
     let mut handshake_buf = Vec::new();
-    let bytes_read = socket.0.read_until(DELIM, &mut handshake_buf).await?;
+    let bytes_read = socket.framing.read(&mut socket.socket, &mut handshake_buf).await?;
 
     if bytes_read == 0 {
         return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
@@ -67,37 +180,37 @@ async fn recv_and_connect(
         ));
     }
 
-    // Parse the handshake
-    let interface = serde_json::from_slice(&handshake_buf)
-        .map_err(|e| e.to_string())
-
-        // Reject any connections requesting admin privileges
-        .and_then(|handshake: Handshake| {
-            if handshake.admin {
-                Err("Cannot join as administrator".to_owned())
-            } else {
-                Ok(handshake)
-            }
-        })
-
-        // Try to connect the handshake
-        .and_then(|handshake: Handshake| {
-            server.connect_player(handshake)
-                .map_err(|e| e.to_string())
-        });
+    // Parse the handshake, rejecting any requesting admin privileges
+    let parsed = socket.open(&handshake_buf)
+        .map_err(ProtocolError::Encoding)
+        .and_then(|handshake| protocol::parse_handshake(&handshake));
+    let interface = match parsed {
+
+        // Try to connect the handshake once its invite code checks out,
+        // suggesting another name if the one given is taken
+        Ok(request) => match server.check_invite(address, request.invite_code.as_ref()) {
+            Ok(()) => server.resume_player(request.handshake, request.resume_token).await,
+            Err(e) => Err(e),
+        }
+            .map_err(|e| match e {
+                ConnectionReject::NameTaken { ref suggestion } => {
+                    let suggestion = Some(suggestion.clone());
+                    (e.to_string(), suggestion)
+                },
+                e => (e.to_string(), None),
+            }),
+        Err(e) => Err((e.to_string(), None)),
+    };
 
     // Write the response to the socket
     Ok(match interface {
         Ok(interface) => {
-            socket.0.write_all(HANDSHAKE_ACCEPT).await?;
-            socket.0.flush().await?;
+            socket.write(HANDSHAKE_ACCEPT).await?;
+            socket.socket.flush().await?;
             Some(interface)
         },
-        Err(reject_msg) => {
-            socket.0.write_all(HANDSHAKE_REJECT_NEEDLE).await?;
-            socket.0.write_all(reject_msg.as_bytes()).await?;
-            socket.0.write_all(&[DELIM]).await?;
-            socket.0.flush().await?;
+        Err((reject_msg, suggestion)) => {
+            socket.reject(&reject_msg, suggestion.as_deref()).await?;
             None
         },
     })
@@ -105,17 +218,83 @@ async fn recv_and_connect(
 
 /// Message sent to indicate that a handshake was accepted and the server has
 /// opened the connection.
-const HANDSHAKE_ACCEPT: &[u8] = b"Accepted\n";
+const HANDSHAKE_ACCEPT: &[u8] = b"Accepted";
 /// Beginning of a message sent to indicate that a handshake was rejected for
 /// some reason.
 const HANDSHAKE_REJECT_NEEDLE: &[u8] = b"Rejected: ";
+/// Beginning of the line following a rejection that suggests a name to join
+/// with instead. Only sent to clients that frame messages with their length,
+/// as this line would be taken for a message of its own in newline framing.
+const HANDSHAKE_SUGGEST_NEEDLE: &[u8] = b"\nSuggested name: ";
+
+/// A handshake the server turned down, as returned within an [`io::Error`] of
+/// kind [`io::ErrorKind::ConnectionRefused`] by [`RemoteServer::connect_player`].
+#[derive(Debug, thiserror::Error)]
+#[error("{reason}")]
+pub struct Rejection {
+    pub reason: String,
+    /// A name the server suggests joining with instead of one that is taken.
+    pub suggested_name: Option<Box<str>>,
+}
+
+impl Rejection {
+    /// Gets the rejection behind an error, if the error is one.
+    pub fn of(err: &io::Error) -> Option<&Self> {
+        err.get_ref()?.downcast_ref()
+    }
+}
 
 /// Remote proxy for a client.
-pub struct RemoteClient(BufStream<TcpStream>);
+pub struct RemoteClient {
+    socket: BufStream<TcpStream>,
+    /// The framing spoken by the client, known once it starts its handshake.
+    framing: Framing,
+    /// Whether each frame starts with its [`Encoding`], as from version 3.
+    encoded: bool,
+    /// Whether the client can read [`Encoding::Deflate`].
+    compress: bool,
+}
 
 impl RemoteClient {
     pub fn new(socket: TcpStream) -> Self {
-        Self(BufStream::new(socket))
+        Self {
+            socket: BufStream::new(socket),
+            framing: Framing::Lines,
+            encoded: false,
+            compress: false,
+        }
+    }
+
+    /// Writes a message in the framing and encoding the client speaks,
+    /// without flushing.
+    async fn write(&mut self, message: &[u8]) -> io::Result<()> {
+        match self.encoded {
+            true => {
+                let frame = compress::encode(message, self.compress);
+                self.framing.write(&mut self.socket, &frame).await
+            },
+            false => self.framing.write(&mut self.socket, message).await,
+        }
+    }
+
+    /// Gets the message in a frame read from the client.
+    fn open<'a>(&self, frame: &'a [u8]) -> io::Result<Cow<'a, [u8]>> {
+        match self.encoded {
+            true => compress::decode(frame, MAX_MESSAGE_LEN),
+            false => Ok(Cow::Borrowed(frame)),
+        }
+    }
+
+    /// Tells the client that its handshake was rejected, and why, along with
+    /// a name to try instead if there is one.
+    async fn reject(&mut self, reason: &str, suggestion: Option<&str>) -> io::Result<()> {
+        let mut message = [HANDSHAKE_REJECT_NEEDLE, reason.as_bytes()].concat();
+        if let Some(suggestion) = suggestion.filter(|_| self.framing == Framing::LengthPrefixed) {
+            message.extend_from_slice(HANDSHAKE_SUGGEST_NEEDLE);
+            message.extend_from_slice(suggestion.as_bytes());
+        }
+        self.write(&message).await?;
+        self.socket.flush().await
     }
 
     /// Connects any fallible interface. Upon receiving of an error, terminates
@@ -125,9 +304,8 @@ impl RemoteClient {
     {
         // Initiate the connection by sending the server's state
         let state = serde_json::to_vec(&connection.server_state).unwrap();
-        self.0.write_all(&state).await?;
-        self.0.write_all(&[DELIM]).await?;
-        self.0.flush().await?;
+        self.write(&state).await?;
+        self.socket.flush().await?;
 
         let mut incoming_msg_buffer = Vec::new();
         let mut interface = connection.interface;
@@ -149,26 +327,18 @@ impl RemoteClient {
                     }
                 },
                 // Deserialize incoming messages
-                bytes_res = self.0.read_until(DELIM, &mut incoming_msg_buffer) => {
+                bytes_res = self.framing.read(&mut self.socket, &mut incoming_msg_buffer) => {
                     let bytes = bytes_res?;
 
-                    // TODO: error on messages that are too long
-
                     // Shut down if we get an EOF
                     if bytes == 0 { break; }
 
-                    // Deserialize the message
-                    let msg_result = serde_json::from_slice(&incoming_msg_buffer);
-
-                    // Create the error message
-                    let msg_result = match msg_result {
-                        Err(e) => Err(ServerMessage::Invalid {
-                            reason: InvalidMessageReason::JsonParseErr(
-                                e.to_string().into_boxed_str()
-                            )
-                        }),
-                        Ok(v) => Ok(v),
-                    };
+                    // Deserialize the message, creating the error message if it
+                    // cannot be accepted
+                    let msg_result = self.open(&incoming_msg_buffer)
+                        .map_err(ProtocolError::Encoding)
+                        .and_then(|msg| protocol::parse_client_message(&msg))
+                        .map_err(|e| ServerMessage::Invalid { reason: e.into(), request_id: None });
 
                     // Clear the message buffer since we've gotten the whole message
                     incoming_msg_buffer.clear();
@@ -183,7 +353,7 @@ impl RemoteClient {
         }
 
         // Cleanly shut down
-        self.0.shutdown().await?;
+        self.socket.shutdown().await?;
         if let Err(e) = interface.close().await {
             return Ok(Err(e))
         }
@@ -195,49 +365,61 @@ impl RemoteClient {
         // There shouldn't be an error on serializing
         let msg_ser = serde_json::to_vec(&msg).unwrap();
 
-        self.0.write_all(&msg_ser).await?;
-        self.0.write_all(&[DELIM]).await?;
-        self.0.flush().await?;
+        self.write(&msg_ser).await?;
+        self.socket.flush().await?;
         Ok(())
     }
 }
 
-/// Remote proxy for a server. Capable of connecting one player.
+/// Remote proxy for a server. Capable of connecting one player. Speaks
+/// [`PROTOCOL_VERSION`], so messages are framed with their length, and the
+/// server may compress the larger ones.
 #[derive(Debug)]
 pub struct RemoteServer(BufStream<TcpStream>);
 
+/// The framing a [`RemoteServer`] speaks, that of [`PROTOCOL_VERSION`].
+const FRAMING: Framing = Framing::LengthPrefixed;
+
 impl RemoteServer {
 
     pub fn new(socket: TcpStream) -> Self {
         Self(BufStream::new(socket))
     }
 
+    /// Writes a message with its framing and encoding, without flushing.
+    /// Messages to the server are short, so they are never compressed.
+    async fn write(&mut self, message: &[u8]) -> io::Result<()> {
+        FRAMING.write(&mut self.0, &compress::encode(message, false)).await
+    }
+
     /// Tries to send a handshake, then waits for an acknowledgment from the
     /// server. Returns [`Ok`] with the state of the server if the handshake was
     /// accepted, and an error with the rejection message if the handshake was
     /// rejected.
-    async fn send_handshake(&mut self, handshake: &Handshake)
+    async fn send_handshake(&mut self, request: &HandshakeRequest)
         -> io::Result<ServerState>
     {
-        let handshake_msg = serde_json::to_vec(&handshake).unwrap();
-        self.0.write_all(&handshake_msg).await?;
-        self.0.write_all(&[DELIM]).await?;
+        let handshake_msg = serde_json::to_vec(request).unwrap();
+        self.0.write_all(PREAMBLE).await?;
+        self.0.write_all(&[PROTOCOL_VERSION, Encoding::Deflate.flag()]).await?;
+        self.write(&handshake_msg).await?;
         self.0.flush().await?;
 
         // Wait for a reply from the server.
         let mut reply_buf = vec![];
-        let bytes_read = self.0.read_until(DELIM, &mut reply_buf).await?;
+        let bytes_read = FRAMING.read(&mut self.0, &mut reply_buf).await?;
         if bytes_read == 0 {
             return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
                 "Unexpected EOF when sending handshake"
             ));
         };
+        let reply_buf = compress::decode(&reply_buf, MAX_FRAME_LEN)?;
 
-        if &reply_buf == HANDSHAKE_ACCEPT {
+        if *reply_buf == *HANDSHAKE_ACCEPT {
 
             // Wait for the server to send its state
             let mut reply_buf = vec![];
-            let bytes_read = self.0.read_until(DELIM, &mut reply_buf).await?;
+            let bytes_read = FRAMING.read(&mut self.0, &mut reply_buf).await?;
             if bytes_read == 0 {
                 return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
                     "Unexpected EOF when sending handshake"
@@ -245,6 +427,7 @@ impl RemoteServer {
             };
 
             // TODO: handle a bad message from the server
+            let reply_buf = compress::decode(&reply_buf, MAX_FRAME_LEN)?;
             let server_state: ServerState = serde_json::from_slice(&reply_buf).unwrap();
 
             return Ok(server_state);
@@ -252,12 +435,17 @@ impl RemoteServer {
 
         else if reply_buf.starts_with(HANDSHAKE_REJECT_NEEDLE) {
             
-            // Get the rejection message
+            // Get the rejection message, and any name suggested in its place
             let start = HANDSHAKE_REJECT_NEEDLE.len();
             let message = std::str::from_utf8(&reply_buf[start..])
-                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?
-                .to_owned();
-            return Err(io::Error::new(io::ErrorKind::ConnectionRefused, message));
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            let needle = std::str::from_utf8(HANDSHAKE_SUGGEST_NEEDLE).unwrap();
+            let (reason, suggested_name) = match message.split_once(needle) {
+                Some((reason, name)) => (reason, Some(name.into())),
+                None => (message, None),
+            };
+            let rejection = Rejection { reason: reason.to_owned(), suggested_name };
+            return Err(io::Error::new(io::ErrorKind::ConnectionRefused, rejection));
         }
 
         return Err(io::Error::new(io::ErrorKind::InvalidData,
@@ -270,18 +458,33 @@ impl RemoteServer {
 
         // There shouldn't be an error on serializing
         let msg_ser = serde_json::to_vec(&msg).unwrap();
-        self.0.write_all(&msg_ser).await?;
-        self.0.write_all(&[DELIM]).await?;
+        self.write(&msg_ser).await?;
         self.0.flush().await?;
         Ok(())
     }
     
-    /// Creates a player [`Interface`] that interfaces with this remote server.
-    pub async fn connect_player(mut self, handshake: Handshake)
+    /// Creates a player [`Interface`] that interfaces with this remote server,
+    /// giving it `invite_code` if it is only open by invitation.
+    pub async fn connect_player(self, handshake: Handshake, invite_code: Option<InviteCode>)
         -> io::Result<NewConnection<io::Error>>
     {
+        self.resume_player(handshake, invite_code, None).await
+    }
+
+    /// Creates a player [`Interface`] as [`connect_player`] does, giving the
+    /// server the token of this client, so that the seat it holds can be
+    /// taken back after a dropped connection.
+    ///
+    /// [`connect_player`]: RemoteServer::connect_player
+    pub async fn resume_player(mut self,
+        handshake: Handshake,
+        invite_code: Option<InviteCode>,
+        resume_token: Option<ResumeToken>,
+    ) -> io::Result<NewConnection<io::Error>> {
         // Try to connect to the server
-        let server_state = self.send_handshake(&handshake).await?;
+        let request = HandshakeRequest { handshake, invite_code, resume_token };
+        let server_state = self.send_handshake(&request).await?;
+        let handshake = request.handshake;
 
         let (
             interface_sender,
@@ -312,7 +515,7 @@ impl RemoteServer {
                         }
                     },
                     // Deserialize incoming messages
-                    bytes_res = self.0.read_until(DELIM, &mut incoming_msg_buffer) => {
+                    bytes_res = FRAMING.read(&mut self.0, &mut incoming_msg_buffer) => {
 
                         let bytes = match bytes_res {
                             Ok(v) => v,
@@ -323,14 +526,18 @@ impl RemoteServer {
                             },
                         };
 
-                        // TODO: error on messages that are too long
-
                         // Shut down if we get an EOF
                         if bytes == 0 { break; }
 
                         // Deserialize the message. Messages from the server
                         // should be valid JSON, so we can call unwrap.
-                        let msg = serde_json::from_slice(&incoming_msg_buffer).unwrap();
+                        let msg = match compress::decode(&incoming_msg_buffer, MAX_FRAME_LEN) {
+                            Ok(msg) => serde_json::from_slice(&msg).unwrap(),
+                            Err(e) => {
+                                outgoing_sender.send(Err(e)).await.ok();
+                                break;
+                            },
+                        };
 
                         // Clear the message buffer since we've gotten the whole message
                         incoming_msg_buffer.clear();
@@ -358,3 +565,268 @@ impl RemoteServer {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::time::Duration;
+
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use crate::game::messages::*;
+    use crate::server::{Server, ServerOptions, Handshake, NewConnection, Interface};
+    use crate::server::capability::Capabilities;
+    use crate::server::feature::Features;
+    use crate::server::invite::InviteCode;
+
+    use super::{Rejection, RemoteServer};
+
+    /// Starts a server hosted by "host" that listens on a loopback socket.
+    async fn start_server() -> (Server, Interface<std::convert::Infallible>, SocketAddr) {
+        let host = Handshake { capabilities: Capabilities::ALL, ..handshake("host") };
+        let (server, host) = Server::start(ServerOptions::default(), host);
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(super::listen_for_connections(server.clone(), listener));
+        (server, host.interface, address)
+    }
+
+    fn handshake(name: &str) -> Handshake {
+        Handshake { player_name: name.into(), spectating: false, capabilities: Capabilities::NONE, features: Features::NONE }
+    }
+
+    async fn join(address: SocketAddr, handshake: Handshake)
+        -> io::Result<NewConnection<io::Error>>
+    {
+        let socket = TcpStream::connect(address).await?;
+        RemoteServer::new(socket).connect_player(handshake, None).await
+    }
+
+    #[tokio::test]
+    async fn invite_only() {
+        let code: InviteCode = "ABCD".parse().unwrap();
+        let options = ServerOptions { invite_code: Some(code.clone()), ..Default::default() };
+        let host = Handshake { capabilities: Capabilities::ALL, ..handshake("host") };
+        let (server, _host) = Server::start(options, host);
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(super::listen_for_connections(server, listener));
+
+        let join_with = |name: &'static str, code: Option<&'static str>| async move {
+            let socket = TcpStream::connect(address).await.unwrap();
+            let code = code.map(|code| code.parse().unwrap());
+            RemoteServer::new(socket).connect_player(handshake(name), code).await
+        };
+        for wrong in [None, Some("ABCE")] {
+            let rejected = join_with("alice", wrong).await.unwrap_err();
+            assert_eq!(rejected.kind(), io::ErrorKind::ConnectionRefused);
+        }
+        // Codes are matched regardless of case
+        let alice = join_with("alice", Some("abcd")).await.unwrap();
+        assert_eq!(alice.server_state.info.unwrap().invite_code, None);
+
+        // Guessing one code after another soon shuts out even the right one
+        for _ in 0..4 {
+            join_with("bob", Some("WXYZ")).await.unwrap_err();
+        }
+        let refused = join_with("bob", Some("ABCD")).await.unwrap_err();
+        assert!(refused.to_string().contains("too many"), "{refused}");
+    }
+
+    /// Waits for a message that satisfies `predicate`, skipping any others.
+    async fn wait_for<E: std::fmt::Debug>(
+        interface: &mut Interface<E>,
+        predicate: impl Fn(&ServerMessage) -> bool,
+    ) -> ServerMessage {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let msg = interface.recv().await.expect("connection closed").unwrap();
+                if predicate(&msg) { return msg; }
+            }
+        }).await.expect("timed out waiting for message")
+    }
+
+    #[tokio::test]
+    async fn handshake_rejection() {
+        let (_server, _host, address) = start_server().await;
+
+        let taken = join(address, handshake("host")).await.unwrap_err();
+        assert_eq!(taken.kind(), io::ErrorKind::ConnectionRefused);
+        assert_eq!(taken.to_string().trim(), "name is already in use");
+        let suggested = Rejection::of(&taken).unwrap().suggested_name.clone().unwrap();
+        assert_eq!(&*suggested, "host_2");
+        join(address, handshake(&suggested)).await.unwrap();
+
+        let admin = Handshake { capabilities: Capabilities::ALL, ..handshake("mallory") };
+        let admin = join(address, admin).await.unwrap_err();
+        assert_eq!(admin.kind(), io::ErrorKind::ConnectionRefused);
+
+        // A client speaking a version of the protocol from the future is told
+        // so in the framing every version understands
+        let mut socket = BufStream::new(TcpStream::connect(address).await.unwrap());
+        socket.write_all(b"ACQ\x09").await.unwrap();
+        socket.flush().await.unwrap();
+        let mut line = String::new();
+        socket.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "Rejected: unsupported protocol version 9\n");
+
+        // A rejected connection doesn't stop the server from accepting others
+        let connection = join(address, handshake("alice")).await.unwrap();
+        assert!(connection.server_state.connections.get_handshake("host").is_some());
+    }
+
+    #[tokio::test]
+    async fn malformed_json() {
+        let (_server, _host, address) = start_server().await;
+        let mut socket = BufStream::new(TcpStream::connect(address).await.unwrap());
+        let mut line = String::new();
+
+        // A handshake that isn't JSON is rejected
+        socket.write_all(b"{\"player_name\": \n").await.unwrap();
+        socket.flush().await.unwrap();
+        socket.read_line(&mut line).await.unwrap();
+        assert!(line.starts_with("Rejected: "), "{line}");
+
+        // Once connected, malformed messages are reported without dropping
+        // the connection
+        let mut socket = BufStream::new(TcpStream::connect(address).await.unwrap());
+        socket.write_all(b"{\"player_name\":\"alice\",\"spectating\":false,\"admin\":false}\n")
+            .await.unwrap();
+        socket.flush().await.unwrap();
+        for _ in 0..2 {
+            line.clear();
+            socket.read_line(&mut line).await.unwrap();
+        }
+
+        socket.write_all(b"{\"action\": \"play_tile\", \"tile\": \"99-z\"}\n").await.unwrap();
+        socket.write_all(b"{\"message\": \"still here\"}\n").await.unwrap();
+        socket.flush().await.unwrap();
+
+        line.clear();
+        socket.read_line(&mut line).await.unwrap();
+        let msg: ServerMessage = serde_json::from_str(&line).unwrap();
+        assert!(matches!(
+            msg,
+            ServerMessage::Invalid { reason: InvalidMessageReason::JsonParseErr(_), .. }
+        ), "{line}");
+
+        line.clear();
+        socket.read_line(&mut line).await.unwrap();
+        let msg: ServerMessage = serde_json::from_str(&line).unwrap();
+        assert!(matches!(msg, ServerMessage::Chat { .. }), "{line}");
+    }
+
+    #[tokio::test]
+    async fn disconnect_mid_game() {
+        let (_server, mut host, address) = start_server().await;
+        let alice = join(address, handshake("alice")).await.unwrap();
+
+        host.sender().send(ClientMessage::Admin(AdminCommand::StartGame)).await.unwrap();
+        wait_for(&mut host, |msg| matches!(msg, ServerMessage::GameStart { .. })).await;
+
+        // Dropping the socket without a word is seen by everyone else as the
+        // player losing their connection
+        drop(alice);
+        let quit = wait_for(&mut host, |msg| matches!(msg, ServerMessage::Quit { .. })).await;
+        let ServerMessage::Quit { handshake: quitter, reason } = quit else { unreachable!() };
+        assert_eq!(&*quitter.player_name, "alice");
+        assert_eq!(reason, None);
+
+        // The player's name is free to be used again
+        let alice = join(address, handshake("alice")).await.unwrap();
+        assert!(alice.server_state.game_history.is_some());
+        assert!(alice.server_state.hand.is_some());
+        let info = alice.server_state.info.unwrap();
+        assert!(info.in_progress);
+        assert_eq!((&*info.name, info.players, info.spectators), ("host", 2, 0));
+
+        // Saying so before closing is seen as quitting
+        alice.interface.sender().send(ClientMessage::Leaving { reason: LeaveReason::Quit }).await.unwrap();
+        drop(alice.interface);
+        let quit = wait_for(&mut host, |msg| matches!(msg, ServerMessage::Quit { .. })).await;
+        let ServerMessage::Quit { reason, .. } = quit else { unreachable!() };
+        assert_eq!(reason, Some(LeaveReason::Quit));
+    }
+
+    #[tokio::test]
+    async fn eof() {
+        let (_server, host, address) = start_server().await;
+
+        // Closing the socket before finishing the handshake is harmless
+        let mut socket = TcpStream::connect(address).await.unwrap();
+        socket.write_all(b"{\"player_name\"").await.unwrap();
+        socket.shutdown().await.unwrap();
+        drop(socket);
+
+        // The server shuts down when the host quits, which closes the remote
+        // connection
+        let mut alice = join(address, handshake("alice")).await.unwrap();
+        host.sender().send(ClientMessage::Admin(AdminCommand::Shutdown)).await.unwrap();
+        wait_for(&mut alice.interface, |msg| matches!(msg, ServerMessage::Shutdown)).await;
+
+        let closed = tokio::time::timeout(Duration::from_secs(5), alice.interface.recv()).await;
+        assert!(matches!(closed, Ok(None)));
+    }
+
+    #[tokio::test]
+    async fn viewers_watch_as_plain_text() {
+        let (server, host, _) = start_server().await;
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(super::listen_for_viewers(server, listener));
+
+        async fn read_until(viewer: &mut BufStream<TcpStream>, needle: &str) {
+            let mut text = String::new();
+            tokio::time::timeout(Duration::from_secs(5), async {
+                while !text.contains(needle) {
+                    assert!(viewer.read_line(&mut text).await.unwrap() > 0, "{text}");
+                }
+            }).await.expect("timed out waiting for frame");
+        }
+        let mut viewer = BufStream::new(TcpStream::connect(address).await.unwrap());
+        read_until(&mut viewer, "Waiting for a game to start.").await;
+
+        // Each viewer is a spectator, named so as not to clash with another
+        let _second = TcpStream::connect(address).await.unwrap();
+        read_until(&mut viewer, "viewer_2 joined.").await;
+
+        host.sender().send(ClientMessage::Chat { message: "hello".into(), channel: ChatChannel::Lobby }).await.unwrap();
+        read_until(&mut viewer, "hello").await;
+    }
+
+    #[tokio::test]
+    async fn viewers_give_invite_code() {
+        let options = ServerOptions { invite_code: Some("ABCD".parse().unwrap()), ..Default::default() };
+        let (server, _host) = Server::start(options, handshake("host"));
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(super::listen_for_viewers(server, listener));
+
+        let watch_with = |code: &'static str| async move {
+            let mut viewer = TcpStream::connect(address).await.unwrap();
+            viewer.write_all(code.as_bytes()).await.unwrap();
+            let mut text = String::new();
+            tokio::time::timeout(Duration::from_secs(5), async {
+                while !text.contains("Couldn't watch") && !text.contains("Waiting for a game") {
+                    let mut buf = [0; 256];
+                    let read = viewer.read(&mut buf).await.unwrap();
+                    assert!(read > 0, "{text}");
+                    text.push_str(&String::from_utf8_lossy(&buf[..read]));
+                }
+            }).await.expect("timed out waiting for the viewer to be let in or turned away");
+            text
+        };
+        assert!(watch_with("abcd\n").await.contains("Waiting for a game"));
+
+        // Guessing one code after another soon shuts out even the right one
+        let wrong = watch_with("WXYZ\n").await;
+        assert!(wrong.contains("invite code is missing or wrong"), "{wrong}");
+        for _ in 0..3 {
+            watch_with("WXYZ\n").await;
+        }
+        let refused = watch_with("ABCD\n").await;
+        assert!(refused.contains("too many"), "{refused}");
+    }
+}
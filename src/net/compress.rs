@@ -0,0 +1,116 @@
+use std::borrow::Cow;
+use std::io::{self, Read, Write};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+/// Messages shorter than this, in bytes, are sent as they are. Most messages
+/// are a few dozen bytes, which compression would only make longer.
+pub const MIN_COMPRESSED_LEN: usize = 512;
+
+/// How the message in a frame is encoded. From protocol version 3, the first
+/// byte of every frame names its encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Encoding {
+    Plain = 0,
+    /// Compressed with DEFLATE by [`encode`].
+    Deflate = 1,
+}
+
+impl Encoding {
+    /// Gets the encoding named by the first byte of a frame.
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Encoding::Plain),
+            1 => Some(Encoding::Deflate),
+            _ => None,
+        }
+    }
+
+    /// Gets the flag with which a client says it can read this encoding, in
+    /// the byte that follows its preamble.
+    pub fn flag(self) -> u8 {
+        1 << (self as u8)
+    }
+}
+
+/// Tags a message with its encoding, compressing it first if `compress` is set
+/// and it is long enough to be worth it.
+pub fn encode(message: &[u8], compress: bool) -> Vec<u8> {
+    if compress && message.len() >= MIN_COMPRESSED_LEN {
+        let mut frame = vec![Encoding::Deflate as u8];
+        compress_into(message, &mut frame);
+        frame
+    } else {
+        [&[Encoding::Plain as u8], message].concat()
+    }
+}
+
+/// Gets the message from a frame made by [`encode`], which decompresses to no
+/// more than `max_len` bytes.
+pub fn decode(frame: &[u8], max_len: usize) -> io::Result<Cow<'_, [u8]>> {
+    let (&tag, message) = frame.split_first().ok_or_else(|| corrupt("empty frame"))?;
+    match Encoding::from_tag(tag) {
+        Some(Encoding::Plain) => Ok(Cow::Borrowed(message)),
+        Some(Encoding::Deflate) => decompress(message, max_len).map(Cow::Owned),
+        None => Err(corrupt("unknown encoding")),
+    }
+}
+
+/// Compresses `input` with DEFLATE, and appends the result to `out`.
+/// Serialized messages repeat the same keys and names over and over, which
+/// it shrinks well.
+fn compress_into(input: &[u8], out: &mut Vec<u8>) {
+    let mut encoder = DeflateEncoder::new(out, Compression::default());
+    // Writing to a Vec can't fail
+    encoder.write_all(input).expect("writing to memory");
+    encoder.finish().expect("writing to memory");
+}
+
+/// Reverses [`compress_into`], refusing any input that would decompress to
+/// more than `max_len` bytes.
+fn decompress(input: &[u8], max_len: usize) -> io::Result<Vec<u8>> {
+    let mut out = vec![];
+    DeflateDecoder::new(input)
+        .take(max_len as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|err| corrupt(&err.to_string()))?;
+
+    match out.len() <= max_len {
+        true => Ok(out),
+        false => Err(corrupt("decompresses to too many bytes")),
+    }
+}
+
+fn corrupt(why: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("corrupt compressed message: {why}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, encode, Encoding};
+
+    #[test]
+    fn compressed_messages_round_trip() {
+        let actions = r#"{"action":"play_tile","tile":"1-a","implication":null},"#.repeat(40);
+        let noise: Vec<u8> = (0..2000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8).collect();
+
+        for message in [actions.as_bytes(), &noise, "ab".repeat(600).as_bytes(), b""] {
+            let frame = encode(message, true);
+            assert_eq!(&*decode(&frame, message.len()).unwrap(), message);
+        }
+
+        let frame = encode(actions.as_bytes(), true);
+        assert_eq!(frame[0], Encoding::Deflate as u8);
+        assert!(frame.len() < actions.len() / 10, "{} bytes", frame.len());
+        assert_eq!(encode(b"short", true)[0], Encoding::Plain as u8);
+
+        // Corrupt or oversized input is rejected rather than trusted
+        assert!(decode(&frame, actions.len() - 1).is_err());
+        assert!(decode(&frame[..frame.len() - 1], actions.len()).is_err());
+        assert!(decode(&[Encoding::Deflate as u8, 0xff, 0xff, 0xff], 100).is_err());
+        assert!(decode(&[7], 100).is_err());
+    }
+}
@@ -0,0 +1,279 @@
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+
+use super::socks5::{self, Reply, Socks5Error};
+
+/// The port joined when the address names none, and hosted on by default.
+pub const DEFAULT_PORT: u16 = 7070;
+/// How long a single address is given to accept the connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long an attempt is waited on before the next address is tried
+/// alongside it.
+const STAGGER: Duration = Duration::from_millis(250);
+
+/// Reasons an address could not be joined.
+#[derive(Debug, thiserror::Error)]
+pub enum DialError {
+    #[error("'{0}' is not a valid address")]
+    InvalidAddress(String),
+    #[error("couldn't look up '{host}': {source}")]
+    Lookup {
+        host: String,
+        source: io::Error,
+    },
+    #[error("'{0}' has no addresses to connect to")]
+    NoAddresses(String),
+    #[error("couldn't connect to {}", describe_failures(.0))]
+    Unreachable(Vec<(SocketAddr, Failure)>),
+    #[error(transparent)]
+    Proxy(#[from] Socks5Error),
+}
+
+impl DialError {
+    /// Checks if joining again later may succeed, as when the server is still
+    /// starting up.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            DialError::Unreachable(failures) => failures.iter()
+                .all(|(_, failure)| matches!(failure, Failure::Refused | Failure::TimedOut)),
+            DialError::Proxy(Socks5Error::Refused(reply)) => matches!(reply,
+                Reply::ConnectionRefused | Reply::HostUnreachable | Reply::TtlExpired),
+            _ => false,
+        }
+    }
+}
+
+/// Spaces out the attempts to join a server that isn't answering, doubling
+/// the wait after each.
+#[derive(Debug)]
+pub struct Backoff {
+    delay: Duration,
+}
+
+impl Backoff {
+    const FIRST: Duration = Duration::from_secs(1);
+    const MAX: Duration = Duration::from_secs(30);
+
+    pub fn new() -> Self {
+        Self { delay: Self::FIRST }
+    }
+
+    /// Gets how long to wait before the next attempt.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.delay;
+        self.delay = (self.delay * 2).min(Self::MAX);
+        delay
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Why a connection to one address failed.
+#[derive(Debug)]
+pub enum Failure {
+    /// Nothing is listening at the address, or a firewall turned us away.
+    Refused,
+    TimedOut,
+    Other(io::Error),
+}
+
+impl From<io::Error> for Failure {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::ConnectionRefused => Failure::Refused,
+            io::ErrorKind::TimedOut => Failure::TimedOut,
+            _ => Failure::Other(err),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Refused => write!(f, "the connection was refused"),
+            Failure::TimedOut => write!(f, "no answer after {} seconds", CONNECT_TIMEOUT.as_secs()),
+            Failure::Other(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+fn describe_failures(failures: &[(SocketAddr, Failure)]) -> String {
+    let failures: Vec<_> = failures.iter()
+        .map(|(addr, failure)| format!("{addr} ({failure})"))
+        .collect();
+    failures.join(", ")
+}
+
+/// Splits an address into its host and port, using [`DEFAULT_PORT`] if it
+/// names none. IPv6 addresses with a port must be in brackets, as in
+/// `[::1]:7070`.
+pub fn split_address(address: &str) -> Result<(&str, u16), DialError> {
+    split_with_default(address, DEFAULT_PORT)
+}
+
+/// Splits an address into its host and port, using `default_port` if it
+/// names none.
+pub(crate) fn split_with_default(address: &str, default_port: u16) -> Result<(&str, u16), DialError> {
+    let invalid = || DialError::InvalidAddress(address.to_owned());
+    let address = address.trim();
+
+    // A bare IPv6 address has colons of its own
+    if address.parse::<IpAddr>().is_ok() {
+        return Ok((address, default_port));
+    }
+
+    let (host, port) = match address.strip_prefix('[') {
+        Some(rest) => {
+            let (host, rest) = rest.split_once(']').ok_or_else(invalid)?;
+            match rest {
+                "" => (host, None),
+                _ => (host, Some(rest.strip_prefix(':').ok_or_else(invalid)?)),
+            }
+        },
+        None => match address.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (address, None),
+        },
+    };
+
+    let port = match port {
+        Some(port) => port.parse().map_err(|_| invalid())?,
+        None => default_port,
+    };
+    match host.is_empty() || host.contains(':') && host.parse::<IpAddr>().is_err() {
+        true => Err(invalid()),
+        false => Ok((host, port)),
+    }
+}
+
+/// Looks up an address and connects to it. Where a name has several
+/// addresses, they are tried in turn, alternating between IPv6 and IPv4, with
+/// each given a head start of [`STAGGER`] before the next joins it. The first
+/// to connect is used.
+pub async fn connect(address: &str) -> Result<TcpStream, DialError> {
+    let (host, port) = split_address(address)?;
+    connect_host(host, port).await
+}
+
+/// Connects to `address` through the SOCKS5 proxy at `proxy`, whose port is
+/// [`socks5::DEFAULT_PORT`] if not given. The proxy looks up the address
+/// itself, so a name known only on its side of the network can be joined.
+pub async fn connect_through(proxy: &str, address: &str) -> Result<TcpStream, DialError> {
+    let (host, port) = split_address(address)?;
+    let (proxy_host, proxy_port) = split_with_default(proxy, socks5::DEFAULT_PORT)?;
+    let mut stream = connect_host(proxy_host, proxy_port).await?;
+    socks5::connect(&mut stream, host, port).await?;
+    Ok(stream)
+}
+
+async fn connect_host(host: &str, port: u16) -> Result<TcpStream, DialError> {
+    let addrs: Vec<_> = tokio::net::lookup_host((host, port)).await
+        .map_err(|source| DialError::Lookup { host: host.to_owned(), source })?
+        .collect();
+    if addrs.is_empty() {
+        return Err(DialError::NoAddresses(host.to_owned()));
+    }
+
+    let mut pending = interleave(addrs).into_iter().peekable();
+    let mut attempts = JoinSet::new();
+    let mut failures = Vec::new();
+    loop {
+        if let Some(addr) = pending.next() {
+            attempts.spawn(async move {
+                let result = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(addr)).await;
+                (addr, result)
+            });
+        }
+        if attempts.is_empty() { break; }
+
+        tokio::select! {
+            Some(attempt) = attempts.join_next() => {
+                match attempt.expect("connection attempt panicked") {
+                    (_, Ok(Ok(stream))) => return Ok(stream),
+                    (addr, Ok(Err(err))) => failures.push((addr, err.into())),
+                    (addr, Err(_)) => failures.push((addr, Failure::TimedOut)),
+                }
+            },
+            _ = tokio::time::sleep(STAGGER), if pending.peek().is_some() => {},
+        }
+    }
+
+    Err(DialError::Unreachable(failures))
+}
+
+/// Orders addresses to alternate between IPv6 and IPv4, starting with the
+/// family of the first, so that a broken route for one family costs little.
+fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first_v6 = addrs[0].is_ipv6();
+    let (mut first, mut second): (Vec<_>, Vec<_>) = addrs.into_iter()
+        .partition(|addr| addr.is_ipv6() == first_v6);
+    first.reverse();
+    second.reverse();
+
+    let mut ordered = Vec::with_capacity(first.len() + second.len());
+    while let Some(addr) = first.pop() {
+        ordered.push(addr);
+        ordered.extend(second.pop());
+    }
+    ordered.extend(second.into_iter().rev());
+    ordered
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use tokio::net::TcpListener;
+
+    use super::{connect, interleave, split_address, Backoff, DialError, Failure, DEFAULT_PORT};
+
+    #[test]
+    fn addresses_split() {
+        assert_eq!(split_address("example.com").unwrap(), ("example.com", DEFAULT_PORT));
+        assert_eq!(split_address("example.com:9000").unwrap(), ("example.com", 9000));
+        assert_eq!(split_address("10.0.0.2:80").unwrap(), ("10.0.0.2", 80));
+        assert_eq!(split_address("::1").unwrap(), ("::1", DEFAULT_PORT));
+        assert_eq!(split_address("[::1]").unwrap(), ("::1", DEFAULT_PORT));
+        assert_eq!(split_address("[fe80::1]:9000").unwrap(), ("fe80::1", 9000));
+
+        for invalid in ["", ":80", "host:port", "[::1", "[::1]80", "1:2:x:80"] {
+            assert!(matches!(split_address(invalid), Err(DialError::InvalidAddress(_))), "{invalid}");
+        }
+
+        let v4 = |n| SocketAddr::from((Ipv4Addr::new(10, 0, 0, n), 1));
+        let v6 = |n| SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, n], 1));
+        assert_eq!(interleave(vec![v6(1), v6(2), v6(3), v4(1)]), [v6(1), v4(1), v6(2), v6(3)]);
+        assert_eq!(interleave(vec![v4(1), v4(2), v6(1), v6(2)]), [v4(1), v6(1), v4(2), v6(2)]);
+    }
+
+    #[tokio::test]
+    async fn connects_or_explains() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(connect(&format!("127.0.0.1:{port}")).await.is_ok());
+
+        drop(listener);
+        match connect(&format!("127.0.0.1:{port}")).await {
+            Err(err @ DialError::Unreachable(_)) => {
+                let DialError::Unreachable(failures) = &err else { unreachable!() };
+                assert!(matches!(failures[..], [(_, Failure::Refused)]), "{failures:?}");
+                assert!(err.is_transient());
+            },
+            other => panic!("expected a refusal, got {other:?}"),
+        }
+        assert!(!split_address("[::1").unwrap_err().is_transient());
+
+        let mut backoff = Backoff::new();
+        let delays: Vec<_> = (0..7).map(|_| backoff.next_delay().as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 30, 30]);
+    }
+}
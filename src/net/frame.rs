@@ -0,0 +1,169 @@
+use std::io;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Sent by a client before its handshake to ask for [`Framing::LengthPrefixed`],
+/// followed by the [`PROTOCOL_VERSION`] it speaks. No JSON handshake can start
+/// with these bytes, so a server can tell such a client from one that sends
+/// its handshake straight away.
+pub const PREAMBLE: &[u8; 3] = b"ACQ";
+/// The version of the protocol spoken by this build. Version 1, which has no
+/// preamble, delimits messages with newlines. Version 2 frames them with their
+/// length, and version 3 adds the [`Encoding`](super::compress::Encoding) of
+/// each frame.
+pub const PROTOCOL_VERSION: u8 = 3;
+/// The longest frame, in bytes, that will be read. Longer frames are taken to
+/// be corrupt, rather than allocated for.
+pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+const DELIM: u8 = b'\n';
+
+/// How the messages sent over a connection are told apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// Each message is followed by a newline, as in protocol version 1.
+    Lines,
+    /// Each message is preceded by its length in bytes, as a 4-byte big-endian
+    /// integer.
+    LengthPrefixed,
+}
+
+impl Framing {
+    /// Gets the framing used by a version of the protocol, if it is one this
+    /// build speaks.
+    pub fn for_version(version: u8) -> Option<Self> {
+        match version {
+            1 => Some(Framing::Lines),
+            2 | 3 => Some(Framing::LengthPrefixed),
+            _ => None,
+        }
+    }
+
+    /// Reads the next message into `buf`, without its framing. Returns the
+    /// number of bytes read, which is zero at the end of the stream.
+    ///
+    /// This is cancel safe, like [`AsyncBufReadExt::read_until`]: bytes read
+    /// before a cancellation are kept in `buf`, and the message is finished by
+    /// calling this again with the same `buf`. Once a message has been handled,
+    /// `buf` must be cleared before reading the next.
+    pub async fn read<R>(self, reader: &mut R, buf: &mut Vec<u8>) -> io::Result<usize>
+        where R: AsyncBufRead + Unpin
+    {
+        match self {
+            Framing::Lines => {
+                let bytes = reader.read_until(DELIM, buf).await?;
+                if buf.last() == Some(&DELIM) {
+                    buf.pop();
+                }
+                Ok(bytes)
+            },
+            Framing::LengthPrefixed => {
+                let mut bytes = 0;
+                loop {
+                    let wanted = match frame_len(buf)? {
+                        Some(len) if buf.len() == 4 + len => {
+                            buf.drain(..4);
+                            return Ok(bytes);
+                        },
+                        Some(len) => 4 + len - buf.len(),
+                        None => 4 - buf.len(),
+                    };
+
+                    let available = reader.fill_buf().await?;
+                    if available.is_empty() {
+                        return match buf.is_empty() {
+                            true => Ok(0),
+                            false => Err(io::ErrorKind::UnexpectedEof.into()),
+                        };
+                    }
+                    let taken = wanted.min(available.len());
+                    buf.extend_from_slice(&available[..taken]);
+                    reader.consume(taken);
+                    bytes += taken;
+                }
+            },
+        }
+    }
+
+    /// Writes `message` with its framing, without flushing.
+    pub async fn write<W>(self, writer: &mut W, message: &[u8]) -> io::Result<()>
+        where W: AsyncWrite + Unpin
+    {
+        match self {
+            Framing::Lines => {
+                writer.write_all(message).await?;
+                writer.write_all(&[DELIM]).await
+            },
+            Framing::LengthPrefixed => {
+                let len = u32::try_from(message.len())
+                    .ok()
+                    .filter(|&len| len as usize <= MAX_FRAME_LEN)
+                    .ok_or_else(|| too_long(message.len()))?;
+                writer.write_all(&len.to_be_bytes()).await?;
+                writer.write_all(message).await
+            },
+        }
+    }
+}
+
+/// Gets the length of the frame whose header starts `buf`, once all four bytes
+/// of the header are there.
+fn frame_len(buf: &[u8]) -> io::Result<Option<usize>> {
+    let Some(header) = buf.get(..4) else { return Ok(None) };
+    let len = u32::from_be_bytes(header.try_into().unwrap()) as usize;
+    match len <= MAX_FRAME_LEN {
+        true => Ok(Some(len)),
+        false => Err(too_long(len)),
+    }
+}
+
+fn too_long(len: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("frame of {len} bytes is longer than {MAX_FRAME_LEN}"),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use tokio::io::BufReader;
+
+    use super::{Framing, MAX_FRAME_LEN};
+
+    #[tokio::test]
+    async fn frames_round_trip() {
+        for framing in [Framing::Lines, Framing::LengthPrefixed] {
+            let mut wire = Vec::new();
+            framing.write(&mut wire, b"{\"message\":\"hi\"}").await.unwrap();
+            framing.write(&mut wire, b"").await.unwrap();
+            framing.write(&mut wire, b"last").await.unwrap();
+
+            // Read a byte at a time, to split every frame across reads
+            let mut reader = BufReader::with_capacity(1, &wire[..]);
+            let mut buf = Vec::new();
+            let mut messages = Vec::new();
+            while framing.read(&mut reader, &mut buf).await.unwrap() > 0 {
+                messages.push(String::from_utf8(std::mem::take(&mut buf)).unwrap());
+            }
+            assert_eq!(messages, ["{\"message\":\"hi\"}", "", "last"], "{framing:?}");
+        }
+
+        // Only a length prefix can carry a newline
+        let mut wire = Vec::new();
+        Framing::LengthPrefixed.write(&mut wire, b"two\nlines").await.unwrap();
+        let mut buf = Vec::new();
+        Framing::LengthPrefixed.read(&mut &wire[..], &mut buf).await.unwrap();
+        assert_eq!(buf, b"two\nlines");
+
+        // A frame cut off, or too long to be real, is an error
+        let mut buf = Vec::new();
+        let cut = Framing::LengthPrefixed.read(&mut &wire[..6], &mut buf).await;
+        assert_eq!(cut.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        let huge = (MAX_FRAME_LEN as u32 + 1).to_be_bytes();
+        let mut buf = Vec::new();
+        let huge = Framing::LengthPrefixed.read(&mut &huge[..], &mut buf).await;
+        assert_eq!(huge.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,144 @@
+use serde::{Serialize, Deserialize};
+
+use crate::game::messages::*;
+use crate::server::Handshake;
+use crate::server::invite::InviteCode;
+use crate::server::resume::ResumeToken;
+
+/// The longest message, in bytes, that will be parsed.
+pub const MAX_MESSAGE_LEN: usize = 4096;
+/// The longest name, in characters, that a player may join with.
+pub const MAX_NAME_LEN: usize = 32;
+
+/// Reasons a message received over the wire cannot be accepted.
+#[derive(Debug, thiserror::Error)]
+pub enum ProtocolError {
+    #[error("message is longer than {MAX_MESSAGE_LEN} bytes")]
+    TooLong,
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    #[error("{0}")]
+    Encoding(std::io::Error),
+    #[error("cannot join with admin capabilities")]
+    Admin,
+    #[error("name must be between 1 and {MAX_NAME_LEN} characters long")]
+    NameLength,
+    #[error("name cannot contain control characters")]
+    NameCharacters,
+    /// No player can hold more than the 25 shares of a company.
+    #[error("more shares resolved than exist")]
+    TooManyShares,
+}
+
+impl From<ProtocolError> for InvalidMessageReason {
+    fn from(value: ProtocolError) -> Self {
+        let message = value.to_string().into_boxed_str();
+        match value {
+            ProtocolError::Json(_) => InvalidMessageReason::JsonParseErr(message),
+            _ => InvalidMessageReason::MalformedMessage(message),
+        }
+    }
+}
+
+/// A handshake as sent by a client joining over the network, along with the
+/// invite code the player was given and the token of the client, if any.
+/// Neither goes further than the checks made of them, so that they are never
+/// passed on to other players.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HandshakeRequest {
+    #[serde(flatten)]
+    pub handshake: Handshake,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invite_code: Option<InviteCode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume_token: Option<ResumeToken>,
+}
+
+/// Parses the handshake sent by a client that is joining over the network,
+/// rejecting any handshake that asks for admin capabilities.
+pub fn parse_handshake(bytes: &[u8]) -> Result<HandshakeRequest, ProtocolError> {
+    if bytes.len() > MAX_MESSAGE_LEN { return Err(ProtocolError::TooLong); }
+
+    let request: HandshakeRequest = serde_json::from_slice(bytes)?;
+
+    if !request.handshake.capabilities.is_empty() {
+        return Err(ProtocolError::Admin);
+    }
+    check_name(&request.handshake.player_name)?;
+
+    Ok(request)
+}
+
+/// Checks that a name is one a player may go by.
+pub fn check_name(name: &str) -> Result<(), ProtocolError> {
+    if !(1..=MAX_NAME_LEN).contains(&name.chars().count()) {
+        return Err(ProtocolError::NameLength);
+    }
+    if name.chars().any(char::is_control) {
+        return Err(ProtocolError::NameCharacters);
+    }
+    Ok(())
+}
+
+/// Parses a message sent by a client. Messages that could never be valid are
+/// rejected here, rather than left for the server to make sense of.
+pub fn parse_client_message(bytes: &[u8]) -> Result<ClientMessage, ProtocolError> {
+    if bytes.len() > MAX_MESSAGE_LEN { return Err(ProtocolError::TooLong); }
+
+    let message: ClientMessage = serde_json::from_slice(bytes)?;
+
+    match &message {
+        ClientMessage::TakingTurn { action: PlayerAction::ResolveMergeStock {
+            selling, trading, keeping
+        }, .. } if *selling as u32 + *trading as u32 + *keeping as u32 > 25 => {
+            return Err(ProtocolError::TooManyShares);
+        },
+        ClientMessage::Rename { new_name } => check_name(new_name)?,
+        _ => {},
+    }
+
+    Ok(message)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_client_message, parse_handshake, ProtocolError};
+
+    #[test]
+    fn rejects_crafted_messages() {
+        let parse = |json: &str| parse_client_message(json.as_bytes());
+
+        assert!(parse(r#"{"action":"play_tile","tile":"1-a","implication":null}"#).is_ok());
+        assert!(matches!(
+            parse(r#"{"action":"play_tile","tile":"13-a","implication":null}"#),
+            Err(ProtocolError::Json(_))
+        ));
+        assert!(matches!(
+            parse(r#"{"action":"resolve_merge_stock","selling":200,"trading":100,"keeping":0}"#),
+            Err(ProtocolError::TooManyShares)
+        ));
+
+        // Merges must list each defunct company once, and never the survivor
+        for defunct in [
+            r#"[null, null, null]"#,
+            r#"[null, "Luxor", null]"#,
+            r#"["Luxor", "Luxor", null]"#,
+            r#"["Tower", null, null]"#,
+        ] {
+            let json = format!(
+                r#"{{"action":"play_tile","tile":"1-a","implication":
+                    {{"merges_companies":{{"defunct":{defunct},"into":"Tower"}}}}}}"#
+            );
+            assert!(matches!(parse(&json), Err(ProtocolError::Json(_))), "{defunct}");
+        }
+
+        let long = format!(r#"{{"message":"{}"}}"#, "a".repeat(super::MAX_MESSAGE_LEN));
+        assert!(matches!(parse(&long), Err(ProtocolError::TooLong)));
+
+        assert!(matches!(
+            parse_handshake(br#"{"player_name":"a\nb","spectating":false,"admin":false}"#),
+            Err(ProtocolError::NameCharacters)
+        ));
+        assert!(matches!(parse(r#"{"new_name":""}"#), Err(ProtocolError::NameLength)));
+    }
+}
@@ -0,0 +1,153 @@
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// The port a proxy is reached on when its address names none.
+pub const DEFAULT_PORT: u16 = 1080;
+
+const VERSION: u8 = 5;
+const NO_AUTHENTICATION: u8 = 0;
+const NO_ACCEPTABLE_METHOD: u8 = 0xff;
+const CONNECT: u8 = 1;
+const ADDRESS_IPV4: u8 = 1;
+const ADDRESS_DOMAIN: u8 = 3;
+const ADDRESS_IPV6: u8 = 4;
+
+/// Reasons a SOCKS5 proxy would not connect us on.
+#[derive(Debug, thiserror::Error)]
+pub enum Socks5Error {
+    #[error("couldn't talk to the proxy: {0}")]
+    Io(#[from] io::Error),
+    #[error("the proxy asks for a login, which isn't supported")]
+    NoAcceptableMethod,
+    #[error("the proxy doesn't speak SOCKS5")]
+    NotSocks5,
+    #[error("'{0}' is too long a name to send to the proxy")]
+    HostTooLong(String),
+    #[error("the proxy couldn't connect: {0}")]
+    Refused(Reply),
+}
+
+/// Why a proxy didn't make the connection asked of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum Reply {
+    #[error("general failure")]
+    GeneralFailure,
+    #[error("not allowed by its rules")]
+    NotAllowed,
+    #[error("network unreachable")]
+    NetworkUnreachable,
+    #[error("host unreachable")]
+    HostUnreachable,
+    #[error("connection refused")]
+    ConnectionRefused,
+    #[error("time to live expired")]
+    TtlExpired,
+    #[error("unsupported command")]
+    CommandNotSupported,
+    #[error("unsupported kind of address")]
+    AddressTypeNotSupported,
+    #[error("unknown reply {0}")]
+    Other(u8),
+}
+
+impl Reply {
+    fn from_code(code: u8) -> Self {
+        match code {
+            1 => Reply::GeneralFailure,
+            2 => Reply::NotAllowed,
+            3 => Reply::NetworkUnreachable,
+            4 => Reply::HostUnreachable,
+            5 => Reply::ConnectionRefused,
+            6 => Reply::TtlExpired,
+            7 => Reply::CommandNotSupported,
+            8 => Reply::AddressTypeNotSupported,
+            _ => Reply::Other(code),
+        }
+    }
+}
+
+/// Asks the proxy at the other end of `stream` to connect it on to `host` and
+/// `port`. The host is sent by name, for the proxy to look up, so that names
+/// only known on its side of the network can be joined. Once this returns,
+/// the stream carries the connection to the host.
+pub async fn connect<S>(stream: &mut S, host: &str, port: u16) -> Result<(), Socks5Error>
+    where S: AsyncRead + AsyncWrite + Unpin
+{
+    let name_len = u8::try_from(host.len())
+        .map_err(|_| Socks5Error::HostTooLong(host.to_owned()))?;
+
+    stream.write_all(&[VERSION, 1, NO_AUTHENTICATION]).await?;
+    stream.flush().await?;
+    let mut choice = [0; 2];
+    stream.read_exact(&mut choice).await?;
+    match choice {
+        [VERSION, NO_AUTHENTICATION] => {},
+        [VERSION, NO_ACCEPTABLE_METHOD] => return Err(Socks5Error::NoAcceptableMethod),
+        _ => return Err(Socks5Error::NotSocks5),
+    }
+
+    let mut request = vec![VERSION, CONNECT, 0, ADDRESS_DOMAIN, name_len];
+    request.extend_from_slice(host.as_bytes());
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+    stream.flush().await?;
+
+    let mut reply = [0; 4];
+    stream.read_exact(&mut reply).await?;
+    match reply {
+        [VERSION, 0, _, _] => {},
+        [VERSION, code, _, _] => return Err(Socks5Error::Refused(Reply::from_code(code))),
+        _ => return Err(Socks5Error::NotSocks5),
+    }
+
+    // The address the proxy connected from is of no use to us, but must be
+    // read past to reach the host's first bytes
+    let address_len = match reply[3] {
+        ADDRESS_IPV4 => 4,
+        ADDRESS_IPV6 => 16,
+        ADDRESS_DOMAIN => stream.read_u8().await? as usize,
+        _ => return Err(Socks5Error::NotSocks5),
+    };
+    let mut bound = vec![0; address_len + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::{connect, Reply, Socks5Error};
+
+    #[tokio::test]
+    async fn connects_through_proxy() {
+        let (mut client, mut proxy) = tokio::io::duplex(256);
+        let proxy = tokio::spawn(async move {
+            let mut greeting = [0; 3];
+            proxy.read_exact(&mut greeting).await.unwrap();
+            assert_eq!(greeting, [5, 1, 0]);
+            proxy.write_all(&[5, 0]).await.unwrap();
+
+            let mut request = [0; 5 + 11 + 2];
+            proxy.read_exact(&mut request).await.unwrap();
+            assert_eq!(&request[..5], [5, 1, 0, 3, 11]);
+            assert_eq!(&request[5..16], b"example.com");
+            assert_eq!(request[16..], 7070u16.to_be_bytes());
+            proxy.write_all(&[5, 0, 0, 1, 10, 0, 0, 1, 0x1f, 0x90]).await.unwrap();
+            proxy.write_all(b"game").await.unwrap();
+        });
+
+        connect(&mut client, "example.com", 7070).await.unwrap();
+        let mut first = [0; 4];
+        client.read_exact(&mut first).await.unwrap();
+        assert_eq!(&first, b"game");
+        proxy.await.unwrap();
+
+        // A refusal is passed on with its reason
+        let (mut client, mut proxy) = tokio::io::duplex(256);
+        proxy.write_all(&[5, 0, 5, 5, 0, 1]).await.unwrap();
+        let refused = connect(&mut client, "example.com", 7070).await;
+        assert!(matches!(refused, Err(Socks5Error::Refused(Reply::ConnectionRefused))), "{refused:?}");
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::game::kernel::Fnv1a;
+use crate::game::messages::{LoggedAction, TaggedPlayerAction};
+
+/// An append-only log of the actions accepted in a game. Each entry carries a
+/// hash chained onto the one before it, so that a log exported during a
+/// dispute can be checked with [`verify`] for entries altered, removed, or
+/// slipped in after the fact.
+#[derive(Debug, Clone, Default)]
+pub struct ActionLog {
+    entries: Vec<LoggedAction>,
+}
+
+impl ActionLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an action accepted now.
+    pub fn append(&mut self, action: TaggedPlayerAction) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let sequence = self.entries.len() as u64;
+        let previous = self.entries.last().map_or(0, |entry| entry.hash);
+        let hash = chain(previous, sequence, timestamp, &action);
+        self.entries.push(LoggedAction { sequence, timestamp, action, hash });
+    }
+
+    /// Forgets every entry after the first `len`, as when the game is carried
+    /// on from a save made before them.
+    pub fn truncate(&mut self, len: usize) {
+        self.entries.truncate(len);
+    }
+
+    pub fn entries(&self) -> &[LoggedAction] {
+        &self.entries
+    }
+}
+
+/// Hashes an entry of the log, given the hash of the entry before it.
+fn chain(previous: u64, sequence: u64, timestamp: u64, action: &TaggedPlayerAction) -> u64 {
+    let mut hash = Fnv1a::new();
+    hash.write(&previous.to_le_bytes());
+    hash.write(&sequence.to_le_bytes());
+    hash.write(&timestamp.to_le_bytes());
+    hash.write(serde_json::to_string(action).unwrap().as_bytes());
+    hash.finish()
+}
+
+/// Checks that an exported log is exactly as the server recorded it. Returns
+/// the position of the first entry that doesn't match its hash or sequence
+/// number if it isn't.
+pub fn verify(entries: &[LoggedAction]) -> Result<(), usize> {
+    let mut previous = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        let hash = chain(previous, entry.sequence, entry.timestamp, &entry.action);
+        if entry.sequence != i as u64 || entry.hash != hash {
+            return Err(i);
+        }
+        previous = hash;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::messages::{PlayerAction, TaggedPlayerAction};
+
+    use super::{verify, ActionLog};
+
+    #[test]
+    fn tampering_breaks_the_chain() {
+        let mut log = ActionLog::new();
+        for player_name in ["alice", "bob", "alice"] {
+            log.append(TaggedPlayerAction {
+                player_name: player_name.into(),
+                action: PlayerAction::BuyStock { stock: [None; 3] },
+                number: None,
+            });
+        }
+        let mut entries = log.entries().to_vec();
+        assert_eq!(verify(&entries), Ok(()));
+
+        entries[1].action.player_name = "carol".into();
+        assert_eq!(verify(&entries), Err(1));
+
+        entries.remove(1);
+        assert_eq!(verify(&entries), Err(1));
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use crate::game::tile::Tile;
+
+/// Where a tile can be found during a game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TileLocation {
+    /// Not yet drawn.
+    Boneyard,
+    /// In the hand of the named player.
+    Hand(Box<str>),
+    /// Played onto the board.
+    Board,
+    /// Swapped out of a hand for being dead, and so removed from the game.
+    Dead,
+}
+
+/// Reasons the tiles of a game cannot be accounted for.
+#[derive(Debug, thiserror::Error)]
+pub enum TileAuditError {
+    #[error("{tile} was found in more than one place: {locations:?}")]
+    Duplicated { tile: Tile, locations: Vec<TileLocation> },
+    #[error("{tile} is nowhere to be found")]
+    Lost { tile: Tile },
+}
+
+/// A tally of where every tile of a game is. Each tile the game began with must
+/// be recorded in exactly one place for the audit to pass.
+#[derive(Debug, Default)]
+pub struct TileAudit {
+    locations: HashMap<Tile, Vec<TileLocation>>,
+}
+
+impl TileAudit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that each of `tiles` was found at `location`.
+    pub fn record(&mut self, tiles: impl IntoIterator<Item = Tile>, location: TileLocation) {
+        for tile in tiles {
+            self.locations.entry(tile).or_default().push(location.clone());
+        }
+    }
+
+    /// Checks that every one of `tiles` was recorded exactly once, reporting
+    /// the first that was not. Tiles recorded but not listed are ignored.
+    pub fn check(mut self, tiles: &[Tile]) -> Result<(), TileAuditError> {
+        for &tile in tiles {
+            match self.locations.remove(&tile) {
+                None => return Err(TileAuditError::Lost { tile }),
+                Some(locations) if locations.len() > 1 => {
+                    return Err(TileAuditError::Duplicated { tile, locations });
+                },
+                Some(_) => {},
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::tile::Tile;
+
+    use super::{TileAudit, TileAuditError, TileLocation};
+
+    #[test]
+    fn finds_duplicated_and_lost_tiles() {
+        let tiles: Vec<_> = Tile::boneyard().iter().copied().collect();
+
+        let mut audit = TileAudit::new();
+        audit.record(Tile::boneyard().iter().copied(), TileLocation::Boneyard);
+        assert!(audit.check(&tiles).is_ok());
+
+        let mut audit = TileAudit::new();
+        audit.record(Tile::boneyard().iter().copied(), TileLocation::Boneyard);
+        audit.record([Tile::new(4, 'c')], TileLocation::Hand("alice".into()));
+        assert!(matches!(
+            audit.check(&tiles),
+            Err(TileAuditError::Duplicated { tile, .. }) if tile == Tile::new(4, 'c')
+        ));
+
+        let mut audit = TileAudit::new();
+        audit.record(
+            Tile::boneyard().iter().copied().filter(|&tile| tile != Tile::new(1, 'a')),
+            TileLocation::Boneyard,
+        );
+        assert!(matches!(
+            audit.check(&tiles),
+            Err(TileAuditError::Lost { tile }) if tile == Tile::new(1, 'a')
+        ));
+    }
+}
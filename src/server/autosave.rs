@@ -0,0 +1,240 @@
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use crate::game::deal::DealSeed;
+use crate::game::kernel::GameSnapshot;
+use crate::game::messages::{ClockState, GameHistory};
+use crate::game::tile::Tile;
+
+use super::game::ServerGame;
+
+/// The number of files saved to in turn, so that the latest save is never
+/// the only one, should the server die while writing it.
+const SLOTS: usize = 3;
+
+/// When, and where, the game in progress is saved.
+#[derive(Debug, Clone)]
+pub struct AutosaveOptions {
+    /// The directory the saves are written to, as `autosave-0.json` and so
+    /// on.
+    pub dir: PathBuf,
+    /// If set, the game is saved once this many actions have been played
+    /// since the last save.
+    pub every_actions: Option<NonZeroUsize>,
+    /// If set, the game is saved once it has gone this long with actions
+    /// unsaved.
+    pub every: Option<Duration>,
+}
+
+/// A game in progress as saved to disk, with everything the server knows of
+/// it, hidden tiles and all.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedGame {
+    /// When the game was saved, in seconds since the Unix epoch.
+    pub saved_at: u64,
+    /// The game as it stands, including the boneyard and every hand.
+    pub snapshot: GameSnapshot,
+    pub history: GameHistory,
+    /// The tiles traded in for being dead, which are out of the game.
+    pub dead_tiles: Box<[Tile]>,
+    /// The seed the tiles were shuffled from, if the server committed to
+    /// their order, to be revealed once the game is over.
+    #[serde(default)]
+    pub deal_seed: Option<DealSeed>,
+    /// The time each player had left, if the game is played on the clock.
+    /// Every clock is stopped in the save.
+    #[serde(default)]
+    pub clock: Option<ClockState>,
+}
+
+/// Saves the game in progress as often as the host asked. The game is also
+/// saved as soon as it starts, so that its deal is never lost.
+#[derive(Debug)]
+pub(super) struct Autosaver {
+    options: AutosaveOptions,
+    /// The slot the next save is written to.
+    next_slot: usize,
+    /// The file the game in progress was last saved to.
+    latest: Option<PathBuf>,
+    /// The number of actions played in the game in progress when it was last
+    /// saved, or [`None`] if it has not been saved yet.
+    saved_actions: Option<usize>,
+    /// When an action was first played after the last save.
+    unsaved_since: Option<Instant>,
+}
+
+impl Autosaver {
+    pub fn new(options: AutosaveOptions) -> Self {
+        Self {
+            options,
+            next_slot: 0,
+            latest: None,
+            saved_actions: None,
+            unsaved_since: None,
+        }
+    }
+
+    /// Gets when the game is next due to be saved for having gone unsaved too
+    /// long, if it is at all.
+    pub fn deadline(&self) -> Option<Instant> {
+        Some(self.unsaved_since? + self.options.every?)
+    }
+
+    /// Saves the game if it is due to be saved. A save that fails is given up
+    /// on until the next is due, as the game goes on regardless.
+    pub fn save_if_due(&mut self, game: &ServerGame) {
+        let Some(actions) = game.actions_played() else {
+            // The next game is saved from its start
+            self.latest = None;
+            self.saved_actions = None;
+            self.unsaved_since = None;
+            return;
+        };
+        let Some(saved_actions) = self.saved_actions else {
+            self.save(game, actions);
+            return;
+        };
+        if actions == saved_actions { return; }
+
+        let unsaved_since = *self.unsaved_since.get_or_insert_with(Instant::now);
+        let enough_actions = self.options.every_actions
+            .is_some_and(|every| actions - saved_actions >= every.get());
+        let long_enough = self.options.every
+            .is_some_and(|every| unsaved_since.elapsed() >= every);
+        if enough_actions || long_enough {
+            self.save(game, actions);
+        }
+    }
+
+    fn save(&mut self, game: &ServerGame, actions: usize) {
+        let Some(saved) = game.save() else { return };
+        let path = self.options.dir.join(format!("autosave-{}.json", self.next_slot));
+        if write(&path, &saved).is_ok() {
+            self.next_slot = (self.next_slot + 1) % SLOTS;
+            self.latest = Some(path);
+        }
+        self.saved_actions = Some(actions);
+        self.unsaved_since = None;
+    }
+
+    /// Reads back the latest save of the game in progress, if it has one.
+    pub fn latest(&self) -> Option<SavedGame> {
+        load(self.latest.as_deref()?).ok()
+    }
+}
+
+/// Reads a save written by an autosave.
+pub fn load(path: &Path) -> io::Result<SavedGame> {
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+/// Writes a save in full beside `path` before moving it into place, so that
+/// the file at `path` is never left half written.
+pub(super) fn write(path: &Path, saved: &SavedGame) -> io::Result<()> {
+    let partial = path.with_extension("json.partial");
+    std::fs::write(&partial, serde_json::to_vec(saved)?)?;
+    std::fs::rename(&partial, path)
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroUsize;
+    use std::time::Duration;
+
+    use crate::game::deal::DealSeed;
+    use crate::game::messages::{AdminCommand, ClientMessage};
+    use crate::game::Money;
+    use crate::server::{Handshake, Server, ServerOptions};
+    use crate::server::capability::Capabilities;
+    use crate::server::clock::TimeBank;
+    use crate::server::fanout::Broadcaster;
+    use crate::server::feature::Features;
+    use crate::server::game::ServerGame;
+
+    use super::{load, AutosaveOptions, SavedGame};
+
+    #[tokio::test]
+    async fn saves_a_game_as_it_starts() {
+        let dir = std::env::temp_dir().join(format!("acquire-autosave-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let options = ServerOptions {
+            autosave: Some(AutosaveOptions {
+                dir: dir.clone(),
+                every_actions: NonZeroUsize::new(1),
+                every: None,
+            }),
+            ..Default::default()
+        };
+        let (server, host) = Server::start(options, Handshake {
+            player_name: "alice".into(),
+            spectating: false,
+            capabilities: Capabilities::ALL,
+            features: Features::NONE,
+        });
+        let _bob = server.connect_player(Handshake {
+            player_name: "bob".into(),
+            spectating: false,
+            capabilities: Capabilities::NONE,
+            features: Features::NONE,
+        }).await.unwrap();
+        host.interface.sender().send(ClientMessage::Admin(AdminCommand::StartGame)).await.unwrap();
+
+        let path = dir.join("autosave-0.json");
+        let mut waited = Duration::ZERO;
+        while !path.exists() {
+            assert!(waited < Duration::from_secs(5), "the game was never saved");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            waited += Duration::from_millis(10);
+        }
+        let saved = load(&path).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert!(saved.history.actions.is_empty());
+        assert_eq!(saved.snapshot.hidden.unwrap().hands.len(), 2);
+    }
+
+    #[test]
+    fn restores_the_deal_and_clock() {
+        let options = ServerOptions {
+            time_bank: Some(TimeBank {
+                per_player: Duration::from_secs(60),
+                penalty: Default::default(),
+                afk_turns: None,
+            }),
+            ..Default::default()
+        };
+        let broadcaster = Broadcaster::new();
+        let _subscriptions: Vec<_> = ["alice", "bob"].into_iter()
+            .map(|name| broadcaster.subscribe(&Handshake {
+                player_name: name.into(),
+                spectating: false,
+                capabilities: Capabilities::NONE,
+                features: Features::NONE,
+            }))
+            .collect();
+        let seed = DealSeed::random();
+        let mut game = ServerGame::new(broadcaster.clone(), &options);
+        game.seed_deal(seed);
+        game.start(Money::new(6000), ["alice".into(), "bob".into()], "alice".into()).unwrap();
+        game.sync_clock().unwrap();
+
+        let saved = game.save().unwrap();
+        let saved: SavedGame = serde_json::from_str(&serde_json::to_string(&saved).unwrap()).unwrap();
+        let clock = saved.clock.clone().unwrap();
+        let mut restored = ServerGame::new(broadcaster, &options);
+        assert!(restored.restore(saved));
+        let resaved = restored.save().unwrap();
+
+        // Two players drew a starting tile and six more each, so the seed's
+        // fifteenth tile is drawn next
+        assert_eq!(resaved.deal_seed, Some(seed));
+        let mut boneyard = resaved.snapshot.hidden.unwrap().boneyard;
+        assert_eq!(boneyard.remove(), Some(seed.tiles()[14]));
+        assert_eq!(resaved.clock.unwrap().remaining_ms, clock.remaining_ms);
+    }
+}
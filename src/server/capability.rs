@@ -0,0 +1,170 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::messages::AdminCommand;
+
+/// Something an admin may be trusted to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Starting a game.
+    Start,
+    /// Ending the game in progress.
+    End,
+    /// Seating, unseating and substituting players.
+    Seats,
+    /// Disconnecting players.
+    Kick,
+    /// Keeping players from rejoining.
+    Ban,
+    /// Adding and removing bots.
+    Bots,
+    /// Silencing the chat.
+    Chat,
+    /// Exporting the log of the latest game.
+    Log,
+    /// Changing what other admins may do, within what this admin may do.
+    Trust,
+    /// Shutting the server down.
+    Shutdown,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 10] = [
+        Capability::Start, Capability::End, Capability::Seats, Capability::Kick,
+        Capability::Ban, Capability::Bots, Capability::Chat, Capability::Log,
+        Capability::Trust, Capability::Shutdown,
+    ];
+
+    /// Gets the name the capability is typed and shown as.
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Start => "start",
+            Capability::End => "end",
+            Capability::Seats => "seats",
+            Capability::Kick => "kick",
+            Capability::Ban => "ban",
+            Capability::Bots => "bots",
+            Capability::Chat => "chat",
+            Capability::Log => "log",
+            Capability::Trust => "trust",
+            Capability::Shutdown => "shutdown",
+        }
+    }
+
+    /// Gets the capability an admin needs to give a command.
+    pub fn required_by(command: &AdminCommand) -> Self {
+        match command {
+            AdminCommand::Shutdown => Capability::Shutdown,
+            AdminCommand::StartGame | AdminCommand::ScheduleStart { .. } => Capability::Start,
+            AdminCommand::EndGame => Capability::End,
+            AdminCommand::Kick { .. } => Capability::Kick,
+            AdminCommand::Seat { .. }
+                | AdminCommand::Unseat { .. }
+                | AdminCommand::Substitute { .. } => Capability::Seats,
+            AdminCommand::ExportLog => Capability::Log,
+            AdminCommand::Trust { .. } => Capability::Trust,
+        }
+    }
+
+    fn bit(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+/// Everything a connection may do as an admin. A connection with no
+/// capabilities is no admin at all, and the host has every one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Vec<Capability>", into = "Vec<Capability>")]
+pub struct Capabilities(u16);
+
+impl Capabilities {
+    pub const NONE: Capabilities = Capabilities(0);
+    pub const ALL: Capabilities = Capabilities((1 << Capability::ALL.len()) - 1);
+
+    pub fn contains(self, capability: Capability) -> bool {
+        self.0 & capability.bit() != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Checks that every capability in `self` is also in `other`.
+    pub fn is_subset(self, other: Capabilities) -> bool {
+        self.0 & !other.0 == 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Capability> {
+        Capability::ALL.into_iter().filter(move |capability| self.contains(*capability))
+    }
+}
+
+impl FromIterator<Capability> for Capabilities {
+    fn from_iter<I: IntoIterator<Item = Capability>>(iter: I) -> Self {
+        Self(iter.into_iter().fold(0, |bits, capability| bits | capability.bit()))
+    }
+}
+
+impl From<Vec<Capability>> for Capabilities {
+    fn from(capabilities: Vec<Capability>) -> Self {
+        capabilities.into_iter().collect()
+    }
+}
+
+impl From<Capabilities> for Vec<Capability> {
+    fn from(capabilities: Capabilities) -> Self {
+        capabilities.iter().collect()
+    }
+}
+
+/// Shows the capabilities as typed: `none`, or their names separated by
+/// commas, as in `start,end`.
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "none");
+        }
+        let names: Vec<_> = self.iter().map(Capability::name).collect();
+        write!(f, "{}", names.join(","))
+    }
+}
+
+/// A capability named that doesn't exist.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("\"{0}\" is not a capability; try none, all, or any of {}", Capabilities::ALL)]
+pub struct UnknownCapability(pub String);
+
+impl FromStr for Capabilities {
+    type Err = UnknownCapability;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Capabilities::NONE),
+            "all" => Ok(Capabilities::ALL),
+            _ => s.split(',')
+                .map(|name| Capability::ALL.into_iter()
+                    .find(|capability| capability.name() == name)
+                    .ok_or_else(|| UnknownCapability(name.to_owned())))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Capabilities, Capability};
+
+    #[test]
+    fn capabilities_parsed_and_shown() {
+        let trusted: Capabilities = "start,end".parse().unwrap();
+        assert!(trusted.contains(Capability::Start) && !trusted.contains(Capability::Shutdown));
+        assert!(trusted.is_subset(Capabilities::ALL) && !Capabilities::ALL.is_subset(trusted));
+        assert_eq!(trusted.to_string(), "start,end");
+        assert_eq!(serde_json::to_string(&trusted).unwrap(), r#"["start","end"]"#);
+        assert_eq!("none".parse::<Capabilities>().unwrap(), Capabilities::NONE);
+        assert!("start,launch".parse::<Capabilities>().is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::game::messages::ClockState;
+
+/// A time bank given to every player at the start of a game, from which the
+/// time they spend on their moves is taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeBank {
+    /// The time each player has for the whole game.
+    pub per_player: Duration,
+    /// What happens to a player whose time runs out.
+    pub penalty: TimeOutPenalty,
+    /// If set, a player who lets their time run out on this many turns in a
+    /// row is taken to be away from the game, and the server plays their seat
+    /// for them.
+    pub afk_turns: Option<NonZeroUsize>,
+}
+
+/// What happens to a player whose time bank runs out.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TimeOutPenalty {
+    /// The server makes each of the player's moves for them, as soon as it is
+    /// their turn, for the rest of the game.
+    #[default]
+    AutoMove,
+    /// The game ends at once, with the player placing last.
+    Forfeit,
+}
+
+impl FromStr for TimeOutPenalty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto-move" => Ok(TimeOutPenalty::AutoMove),
+            "forfeit" => Ok(TimeOutPenalty::Forfeit),
+            _ => Err(format!("unknown penalty '{s}', expected auto-move or forfeit")),
+        }
+    }
+}
+
+/// Keeps the time each player has left, chess clock style. Only the clock of
+/// the player the game is waiting on runs.
+#[derive(Debug, Clone)]
+pub struct GameClock {
+    remaining: HashMap<Box<str>, Duration>,
+    /// The player whose clock is running, and when it was last started.
+    running: Option<(Box<str>, Instant)>,
+}
+
+impl GameClock {
+    /// Creates a clock that gives each player `per_player`, with no clock
+    /// running.
+    pub fn new<'a>(players: impl IntoIterator<Item = &'a str>, per_player: Duration) -> Self {
+        Self {
+            remaining: players.into_iter().map(|name| (name.into(), per_player)).collect(),
+            running: None,
+        }
+    }
+
+    /// Creates a clock that gives each player the time they had left in
+    /// `state`, with no clock running.
+    pub fn resume(state: &ClockState) -> Self {
+        Self {
+            remaining: state.remaining_ms.iter()
+                .map(|(player, &ms)| (player.clone(), Duration::from_millis(ms)))
+                .collect(),
+            running: None,
+        }
+    }
+
+    /// Gets the player whose clock is running.
+    pub fn running(&self) -> Option<&str> {
+        self.running.as_ref().map(|(player, _)| &**player)
+    }
+
+    /// Stops the running clock, charging its player for the time they spent,
+    /// and starts the clock of `player`.
+    pub fn start(&mut self, player: &str, now: Instant) {
+        if let Some((running, started)) = self.running.take() {
+            let remaining = self.remaining.entry(running).or_default();
+            *remaining = remaining.saturating_sub(now.saturating_duration_since(started));
+        }
+        self.running = Some((player.into(), now));
+    }
+
+    /// Gets the time a player has left as of `now`.
+    pub fn remaining(&self, player: &str, now: Instant) -> Duration {
+        let remaining = self.remaining.get(player).copied().unwrap_or_default();
+        match &self.running {
+            Some((running, started)) if &**running == player => {
+                remaining.saturating_sub(now.saturating_duration_since(*started))
+            },
+            _ => remaining,
+        }
+    }
+
+    /// Gets the moment the running clock runs out, if a clock is running.
+    pub fn deadline(&self) -> Option<Instant> {
+        let (player, started) = self.running.as_ref()?;
+        Some(*started + self.remaining.get(player).copied().unwrap_or_default())
+    }
+
+    /// Hands the time left to a player over to the one taking their seat.
+    pub fn substitute(&mut self, seat: &str, player: &str) {
+        if let Some(remaining) = self.remaining.remove(seat) {
+            self.remaining.insert(player.into(), remaining);
+        }
+        if let Some((running, _)) = &mut self.running {
+            if &**running == seat {
+                *running = player.into();
+            }
+        }
+    }
+
+    /// Gets the state of the clock as of `now`, to be sent to the clients.
+    pub fn state(&self, now: Instant) -> ClockState {
+        ClockState {
+            remaining_ms: self.remaining.keys()
+                .map(|player| (player.clone(), self.remaining(player, now).as_millis() as u64))
+                .collect(),
+            running: self.running().map(Into::into),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::GameClock;
+
+    #[test]
+    fn only_the_running_clock_runs() {
+        let minute = Duration::from_secs(60);
+        let start = Instant::now();
+        let mut clock = GameClock::new(["alice", "bob"], 5 * minute);
+        assert_eq!(clock.deadline(), None);
+
+        clock.start("alice", start);
+        assert_eq!(clock.remaining("alice", start + minute), 4 * minute);
+        assert_eq!(clock.remaining("bob", start + minute), 5 * minute);
+
+        clock.start("bob", start + 2 * minute);
+        assert_eq!(clock.remaining("alice", start + 10 * minute), 3 * minute);
+        assert_eq!(clock.deadline(), Some(start + 7 * minute));
+
+        // A clock that has run out stays at zero
+        assert_eq!(clock.remaining("bob", start + 60 * minute), Duration::ZERO);
+
+        clock.substitute("bob", "carol");
+        assert_eq!(clock.running(), Some("carol"));
+        assert_eq!(clock.state(start + 3 * minute).remaining_ms["carol"], 4 * 60_000);
+    }
+}
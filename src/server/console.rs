@@ -0,0 +1,145 @@
+use std::io;
+use std::path::PathBuf;
+
+use tokio::sync::{mpsc, oneshot};
+
+use crate::game::deal::DealSeed;
+
+use super::autosave;
+use super::game::ServerGame;
+use super::{ConnectionManager, ServerCommand};
+
+/// A command only the host can give, as it reaches past the game to the
+/// machine the server runs on, or to the deal itself.
+#[derive(Debug, Clone)]
+pub enum ConsoleCommand {
+    /// Saves the game in progress to a file, as an autosave would.
+    Save(PathBuf),
+    /// Carries on a game from a file written by [`Save`](Self::Save) or an
+    /// autosave, in place of any game in progress.
+    Load(PathBuf),
+    /// Deals the next game from this seed.
+    Seed(DealSeed),
+}
+
+/// Reasons a console command could not be carried out.
+#[derive(Debug, thiserror::Error)]
+pub enum ConsoleError {
+    #[error("no game is in progress")]
+    NoGame,
+    #[error("couldn't write {}: {source}", .path.display())]
+    Save { path: PathBuf, source: io::Error },
+    #[error("couldn't read {}: {source}", .path.display())]
+    Load { path: PathBuf, source: io::Error },
+    #[error("{} holds no hidden tiles to carry on with", .0.display())]
+    NotASave(PathBuf),
+    #[error("the server has shut down")]
+    ShuttingDown,
+}
+
+/// Gives console commands to a running server. Only the process hosting the
+/// server can get one, through [`Server::console`](super::Server::console).
+#[derive(Debug, Clone)]
+pub struct Console {
+    commands: mpsc::Sender<ServerCommand>,
+}
+
+impl Console {
+    pub(super) fn new(commands: mpsc::Sender<ServerCommand>) -> Self {
+        Self { commands }
+    }
+
+    /// Runs a command, returning a description of what it did.
+    pub async fn run(&self, command: ConsoleCommand) -> Result<Box<str>, ConsoleError> {
+        let (reply, response) = oneshot::channel();
+        self.commands.send(ServerCommand::Console { command, reply }).await
+            .map_err(|_| ConsoleError::ShuttingDown)?;
+        response.await.map_err(|_| ConsoleError::ShuttingDown)?
+    }
+}
+
+/// Carries out a console command on behalf of the task that owns the game.
+pub(super) fn run(
+    command: ConsoleCommand,
+    game: &mut ServerGame,
+    players: &ConnectionManager,
+) -> Result<Box<str>, ConsoleError> {
+    let done = match command {
+        ConsoleCommand::Save(path) => {
+            let saved = game.save().ok_or(ConsoleError::NoGame)?;
+            let actions = saved.history.actions.len();
+            if let Err(source) = autosave::write(&path, &saved) {
+                return Err(ConsoleError::Save { path, source });
+            }
+            format!("Saved the game after {actions} actions to {}.", path.display())
+        },
+        ConsoleCommand::Load(path) => {
+            let saved = match autosave::load(&path) {
+                Ok(saved) => saved,
+                Err(source) => return Err(ConsoleError::Load { path, source }),
+            };
+            let actions = saved.history.actions.len();
+            if !game.restore(saved) {
+                return Err(ConsoleError::NotASave(path));
+            }
+            // Everyone's game has changed under them. Should nobody be left
+            // to tell, the server finds out on its next broadcast.
+            for handshake in players.handshakes() {
+                if game.resync(handshake.player_name).is_err() { break; }
+            }
+            format!("Loaded the game after {actions} actions from {}.", path.display())
+        },
+        ConsoleCommand::Seed(seed) => {
+            game.seed_deal(seed);
+            format!("The next game will be dealt from seed {seed}.")
+        },
+    };
+    Ok(done.into_boxed_str())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::deal::DealSeed;
+    use crate::game::messages::{AdminCommand, ClientMessage, ServerMessage};
+    use crate::server::{Handshake, Server, ServerOptions};
+    use crate::server::capability::Capabilities;
+    use crate::server::feature::Features;
+
+    use super::{ConsoleCommand, ConsoleError};
+
+    #[tokio::test]
+    async fn saves_loads_and_seeds() {
+        let (server, mut host) = Server::start(ServerOptions::default(), Handshake {
+            player_name: "alice".into(),
+            spectating: false,
+            capabilities: Capabilities::ALL,
+            features: Features::NONE,
+        });
+        let _bob = server.connect_player(Handshake {
+            player_name: "bob".into(),
+            spectating: false,
+            capabilities: Capabilities::NONE,
+            features: Features::NONE,
+        }).await.unwrap();
+        let console = server.console();
+        let path = std::env::temp_dir().join(format!("acquire-console-{}.json", std::process::id()));
+
+        assert!(matches!(console.run(ConsoleCommand::Save(path.clone())).await, Err(ConsoleError::NoGame)));
+
+        let seed = DealSeed::random();
+        console.run(ConsoleCommand::Seed(seed)).await.unwrap();
+        host.interface.sender().send(ClientMessage::Admin(AdminCommand::StartGame)).await.unwrap();
+        let commitment = loop {
+            match host.interface.recv().await.unwrap().unwrap() {
+                ServerMessage::GameStart { info, .. } => break info.deal_commitment,
+                _ => continue,
+            }
+        };
+        assert_eq!(commitment, Some(seed.commitment()));
+
+        console.run(ConsoleCommand::Save(path.clone())).await.unwrap();
+        let loaded = console.run(ConsoleCommand::Load(path.clone())).await;
+        std::fs::remove_file(&path).ok();
+        assert_eq!(&*loaded.unwrap(), format!("Loaded the game after 0 actions from {}.", path.display()));
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{broadcast, mpsc};
+
+use crate::game::messages::ChatChannel;
+
+use super::{Handshake, NoListeners, ServerBroadcast};
+
+/// The number of broadcasts a spectator may leave unread before they are
+/// dropped from the server. Players are never dropped, as the game cannot go
+/// on without them.
+pub const SPECTATOR_MAX_LAG: usize = 256;
+
+/// The number of broadcasts held for one of the server's own sinks, such as
+/// the webhook, before the oldest are dropped to make room for the newest.
+pub const SINK_CAPACITY: usize = 256;
+
+/// Distributes broadcasts to every connection through a queue of its own, so
+/// that a connection that reads slowly holds up nobody but itself.
+///
+/// The registry of queues is behind a lock, but the lock is only ever held
+/// while messages are queued, never while one is awaited.
+#[derive(Debug, Clone, Default)]
+pub struct Broadcaster {
+    subscribers: Arc<Mutex<HashMap<Box<str>, Subscriber>>>,
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    queue: Queue,
+    spectating: bool,
+    /// Whether the subscriber is a connection, rather than something the
+    /// server runs itself. Broadcasts fail once no connection remains.
+    connection: bool,
+    lag: Arc<Lag>,
+}
+
+/// The sending end of a subscriber's queue.
+#[derive(Debug)]
+enum Queue {
+    /// Holds every broadcast until it is read.
+    Unbounded(mpsc::UnboundedSender<ServerBroadcast>),
+    /// Holds the latest [`SINK_CAPACITY`] broadcasts that `wants` accepts.
+    Sink {
+        queue: broadcast::Sender<ServerBroadcast>,
+        wants: fn(&ServerBroadcast) -> bool,
+    },
+}
+
+/// Tracks how far behind a connection is in reading its broadcasts.
+#[derive(Debug, Default)]
+struct Lag {
+    /// Broadcasts queued but not yet read.
+    queued: AtomicUsize,
+    /// The most broadcasts that have ever been queued at once.
+    peak: AtomicUsize,
+}
+
+/// A snapshot of how far behind a connection is in reading its broadcasts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LagMetrics {
+    /// Broadcasts queued but not yet read.
+    pub queued: usize,
+    /// The most broadcasts that have ever been queued at once.
+    pub peak: usize,
+}
+
+/// The receiving end of a connection's queue.
+#[derive(Debug)]
+pub struct Subscription {
+    queue: Receiver,
+    lag: Arc<Lag>,
+}
+
+#[derive(Debug)]
+enum Receiver {
+    Unbounded(mpsc::UnboundedReceiver<ServerBroadcast>),
+    Sink(broadcast::Receiver<ServerBroadcast>),
+}
+
+impl Broadcaster {
+    /// Creates a broadcaster with no subscribers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribes a connection to every broadcast sent from now on, replacing
+    /// any previous subscription under the same name.
+    pub fn subscribe(&self, handshake: &Handshake) -> Subscription {
+        let (queue, receiver) = mpsc::unbounded_channel();
+        let lag = Arc::new(Lag::default());
+
+        self.subscribers.lock().unwrap().insert(handshake.player_name.clone(), Subscriber {
+            queue: Queue::Unbounded(queue),
+            spectating: handshake.spectating,
+            connection: true,
+            lag: lag.clone(),
+        });
+
+        Subscription { queue: Receiver::Unbounded(receiver), lag }
+    }
+
+    /// Subscribes an observer to every broadcast sent from now on. Like a
+    /// spectator, an observer is dropped for falling behind, but it doesn't
+    /// count as anyone left to receive broadcasts.
+    #[cfg(feature = "observer")]
+    pub fn subscribe_observer(&self, name: Box<str>) -> Subscription {
+        let (queue, receiver) = mpsc::unbounded_channel();
+        let lag = Arc::new(Lag::default());
+
+        self.subscribers.lock().unwrap().insert(name, Subscriber {
+            queue: Queue::Unbounded(queue),
+            spectating: true,
+            connection: false,
+            lag: lag.clone(),
+        });
+
+        Subscription { queue: Receiver::Unbounded(receiver), lag }
+    }
+
+    /// Subscribes one of the server's own sinks, such as the webhook, to the
+    /// broadcasts that `wants` accepts. A sink is never dropped for falling
+    /// behind, but loses the oldest of its broadcasts instead, and doesn't
+    /// count as anyone left to receive broadcasts.
+    pub fn subscribe_sink(&self, name: Box<str>, wants: fn(&ServerBroadcast) -> bool) -> Subscription {
+        let (queue, receiver) = broadcast::channel(SINK_CAPACITY);
+
+        self.subscribers.lock().unwrap().insert(name, Subscriber {
+            queue: Queue::Sink { queue, wants },
+            spectating: false,
+            connection: false,
+            lag: Arc::default(),
+        });
+
+        Subscription { queue: Receiver::Sink(receiver), lag: Arc::default() }
+    }
+
+    /// Queues a broadcast for every subscriber, or, for a
+    /// [`ServerBroadcast::Private`] message, for the targeted player alone.
+    /// Chat in the room of the game is queued only for the subscribers seated
+    /// in it, as they are its members. Subscribers that have closed their
+    /// subscription are forgotten, as are spectators that have fallen more
+    /// than [`SPECTATOR_MAX_LAG`] broadcasts behind. Fails if no connection
+    /// remains to receive broadcasts.
+    pub fn send(&self, message: ServerBroadcast) -> Result<(), NoListeners> {
+        let mut subscribers = self.subscribers.lock().unwrap();
+
+        match &message {
+            ServerBroadcast::Private { target_player, .. } => {
+                let delivered = subscribers.get(target_player)
+                    .map(|subscriber| subscriber.queue(message.clone()));
+                if delivered == Some(false) {
+                    subscribers.remove(target_player);
+                }
+            },
+            ServerBroadcast::Chat { channel: ChatChannel::Room, .. } => {
+                subscribers.retain(|_, subscriber| {
+                    subscriber.spectating || subscriber.queue(message.clone())
+                });
+            },
+            _ => subscribers.retain(|_, subscriber| subscriber.queue(message.clone())),
+        }
+
+        match subscribers.values().any(|subscriber| subscriber.connection) {
+            true => Ok(()),
+            false => Err(NoListeners),
+        }
+    }
+
+    /// Marks whether a subscriber is spectating, and so whether it may be
+    /// dropped for falling behind.
+    pub fn set_spectating(&self, name: &str, spectating: bool) {
+        if let Some(subscriber) = self.subscribers.lock().unwrap().get_mut(name) {
+            subscriber.spectating = spectating;
+        }
+    }
+
+    /// Moves a subscriber to a new name, so that private broadcasts to that
+    /// name reach it.
+    pub fn rename(&self, old_name: &str, new_name: Box<str>) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if let Some(subscriber) = subscribers.remove(old_name) {
+            subscribers.insert(new_name, subscriber);
+        }
+    }
+
+    /// Drops a subscriber, as when they are kicked. Their connection closes
+    /// once it has read every broadcast already queued for it.
+    pub fn unsubscribe(&self, name: &str) {
+        self.subscribers.lock().unwrap().remove(name);
+    }
+
+    /// Gets how far behind each subscriber is in reading its broadcasts. The
+    /// server's own sinks are left out, as they never fall far behind.
+    pub fn lag(&self) -> HashMap<Box<str>, LagMetrics> {
+        self.subscribers.lock().unwrap()
+            .iter()
+            .filter(|(_, subscriber)| matches!(subscriber.queue, Queue::Unbounded(_)))
+            .map(|(name, subscriber)| (name.clone(), LagMetrics {
+                queued: subscriber.lag.queued.load(Ordering::Relaxed),
+                peak: subscriber.lag.peak.load(Ordering::Relaxed),
+            }))
+            .collect()
+    }
+}
+
+impl Subscriber {
+    /// Queues a message. Returns `false` if the subscriber is to be forgotten.
+    fn queue(&self, message: ServerBroadcast) -> bool {
+        let queue = match &self.queue {
+            Queue::Unbounded(queue) => queue,
+            // A full sink drops its oldest broadcast on its own
+            Queue::Sink { queue, wants } => {
+                return queue.receiver_count() > 0 && (!wants(&message) || queue.send(message).is_ok());
+            },
+        };
+
+        let queued = self.lag.queued.load(Ordering::Relaxed);
+        if self.spectating && queued >= SPECTATOR_MAX_LAG {
+            return false;
+        }
+
+        // The count is raised before the message can be read, so that it
+        // never drops below zero
+        self.lag.queued.fetch_add(1, Ordering::Relaxed);
+        if queue.send(message).is_err() {
+            return false;
+        }
+        self.lag.peak.fetch_max(queued + 1, Ordering::Relaxed);
+        true
+    }
+}
+
+impl Subscription {
+    /// Receives the next broadcast. Returns [`None`] once the connection has
+    /// been dropped from the server, or once the server has shut down.
+    pub async fn recv(&mut self) -> Option<ServerBroadcast> {
+        match &mut self.queue {
+            Receiver::Unbounded(queue) => {
+                let message = queue.recv().await?;
+                self.lag.queued.fetch_sub(1, Ordering::Relaxed);
+                Some(message)
+            },
+            // Whatever a sink missed for falling behind is gone, so it reads
+            // on from the oldest broadcast it still holds
+            Receiver::Sink(queue) => loop {
+                match queue.recv().await {
+                    Ok(message) => return Some(message),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::messages::ChatChannel;
+    use crate::game::tile::Tile;
+    use crate::server::{Handshake, PrivateBroadcast, ServerBroadcast};
+    use crate::server::capability::Capabilities;
+    use crate::server::feature::Features;
+
+    use super::{Broadcaster, SINK_CAPACITY, SPECTATOR_MAX_LAG};
+
+    fn handshake(name: &str, spectating: bool) -> Handshake {
+        Handshake {
+            player_name: name.into(),
+            spectating,
+            capabilities: Capabilities::NONE,
+            features: Features::NONE,
+        }
+    }
+
+    #[tokio::test]
+    async fn drops_lagging_spectators() {
+        let broadcaster = Broadcaster::new();
+        let mut player = broadcaster.subscribe(&handshake("player", false));
+        let mut spectator = broadcaster.subscribe(&handshake("spectator", true));
+
+        for _ in 0..=SPECTATOR_MAX_LAG {
+            broadcaster.send(ServerBroadcast::Shutdown).unwrap();
+        }
+
+        let lag = broadcaster.lag();
+        assert_eq!(lag["player"].queued, SPECTATOR_MAX_LAG + 1);
+        assert!(!lag.contains_key("spectator"));
+
+        // The spectator can read what was queued before it was dropped, but
+        // nothing after
+        for _ in 0..SPECTATOR_MAX_LAG {
+            assert!(spectator.recv().await.is_some());
+        }
+        assert!(spectator.recv().await.is_none());
+
+        assert!(player.recv().await.is_some());
+        assert_eq!(broadcaster.lag()["player"].queued, SPECTATOR_MAX_LAG);
+        assert_eq!(broadcaster.lag()["player"].peak, SPECTATOR_MAX_LAG + 1);
+
+        drop(player);
+        assert!(broadcaster.send(ServerBroadcast::Shutdown).is_err());
+    }
+
+    #[tokio::test]
+    async fn sinks_drop_oldest_and_keep_nobody_listening() {
+        let broadcaster = Broadcaster::new();
+        let player = broadcaster.subscribe(&handshake("player", false));
+        let mut sink = broadcaster.subscribe_sink("\0sink".into(), |broadcast| {
+            matches!(broadcast, ServerBroadcast::Chat { .. })
+        });
+
+        let chat = |sequence| ServerBroadcast::Chat {
+            player_name: "player".into(),
+            message: "hello".into(),
+            timestamp: 0,
+            sequence,
+            channel: ChatChannel::Lobby,
+        };
+        for sequence in 0..=SINK_CAPACITY as u64 {
+            broadcaster.send(ServerBroadcast::Shutdown).unwrap();
+            broadcaster.send(chat(sequence)).unwrap();
+        }
+        assert!(!broadcaster.lag().contains_key("\0sink"));
+
+        // The first broadcast the sink wanted made room for the last, and
+        // those it didn't want were never queued
+        for expected in 1..=SINK_CAPACITY as u64 {
+            let Some(ServerBroadcast::Chat { sequence, .. }) = sink.recv().await else {
+                panic!("expected a chat message");
+            };
+            assert_eq!(sequence, expected);
+        }
+
+        drop(player);
+        assert!(broadcaster.send(ServerBroadcast::Shutdown).is_err());
+    }
+
+    #[cfg(feature = "observer")]
+    #[test]
+    fn observers_keep_nobody_listening() {
+        let broadcaster = Broadcaster::new();
+        let player = broadcaster.subscribe(&handshake("player", false));
+        let _observer = broadcaster.subscribe_observer("\0observer".into());
+        broadcaster.send(ServerBroadcast::Shutdown).unwrap();
+
+        drop(player);
+        assert!(broadcaster.send(ServerBroadcast::Shutdown).is_err());
+    }
+
+    #[test]
+    fn routes_private_messages() {
+        let broadcaster = Broadcaster::new();
+        let _alice = broadcaster.subscribe(&handshake("alice", false));
+        let _bob = broadcaster.subscribe(&handshake("bob", false));
+
+        broadcaster.send(ServerBroadcast::Private {
+            target_player: "alice".into(),
+            message: PrivateBroadcast::TileDraw { tile: Tile::new(1, 'a') },
+        }).unwrap();
+
+        let lag = broadcaster.lag();
+        assert_eq!(lag["alice"].queued, 1);
+        assert_eq!(lag["bob"].queued, 0);
+    }
+}
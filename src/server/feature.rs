@@ -0,0 +1,112 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Something a client can handle that not every client can, which the server
+/// tailors what it sends to. Clients from before a feature was added never
+/// claim it, and so are sent nothing they can't read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// Reading messages in a binary encoding rather than JSON.
+    Binary,
+    /// Reading frames compressed with [`Encoding::Deflate`].
+    ///
+    /// [`Encoding::Deflate`]: crate::net::compress::Encoding::Deflate
+    Compression,
+    /// Taking a whole new copy of the game the server sends unasked, as after
+    /// the server recovers from an error.
+    Resync,
+    /// Watching a game some moves behind the players.
+    SpectatorDelay,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 4] = [
+        Feature::Binary, Feature::Compression, Feature::Resync, Feature::SpectatorDelay,
+    ];
+
+    /// Gets the name the feature is sent and shown as.
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Binary => "binary",
+            Feature::Compression => "compression",
+            Feature::Resync => "resync",
+            Feature::SpectatorDelay => "spectator_delay",
+        }
+    }
+
+    const fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Every feature a client can handle, sent in its handshake as a list of
+/// names. Names a server doesn't know, as from a client newer than it, are
+/// left out rather than refused.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(into = "Vec<Feature>")]
+pub struct Features(u8);
+
+impl Features {
+    pub const NONE: Features = Features(0);
+    /// The features the clients of this build handle.
+    pub const SUPPORTED: Features = Features(Feature::Compression.bit() | Feature::Resync.bit());
+
+    pub fn contains(self, feature: Feature) -> bool {
+        self.0 & feature.bit() != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Feature> {
+        Feature::ALL.into_iter().filter(move |feature| self.contains(*feature))
+    }
+}
+
+impl FromIterator<Feature> for Features {
+    fn from_iter<I: IntoIterator<Item = Feature>>(iter: I) -> Self {
+        Self(iter.into_iter().fold(0, |bits, feature| bits | feature.bit()))
+    }
+}
+
+impl From<Features> for Vec<Feature> {
+    fn from(features: Features) -> Self {
+        features.iter().collect()
+    }
+}
+
+impl<'de> Deserialize<'de> for Features {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let names: Vec<Box<str>> = Deserialize::deserialize(deserializer)?;
+        Ok(names.iter()
+            .filter_map(|name| Feature::ALL.into_iter().find(|feature| feature.name() == &**name))
+            .collect())
+    }
+}
+
+/// Shows the features as sent: `none`, or their names separated by commas.
+impl fmt::Display for Features {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "none");
+        }
+        let names: Vec<_> = self.iter().map(Feature::name).collect();
+        write!(f, "{}", names.join(","))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Feature, Features};
+
+    #[test]
+    fn unknown_features_left_out() {
+        let features: Features = serde_json::from_str(r#"["resync","telepathy"]"#).unwrap();
+        assert!(features.contains(Feature::Resync) && !features.contains(Feature::Binary));
+        assert_eq!(serde_json::to_string(&features).unwrap(), r#"["resync"]"#);
+        assert_eq!(Features::SUPPORTED.to_string(), "compression,resync");
+    }
+}
@@ -1,18 +1,99 @@
-use std::collections::HashMap;
-
-use tokio::sync::broadcast;
-
-use crate::game::kernel::{self, Game, GameDisambiguation, GameUpdateResult};
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::seq::SliceRandom;
+use tokio::time::Instant;
+
+use crate::game::bot::{Bot, Difficulty};
+use crate::game::deal::DealSeed;
+use crate::game::game_id::GameId;
+use crate::game::replay::{self, Replay};
+use crate::game::policy::RulePolicy;
+use crate::game::kernel::{self, Founding, Game, GameDisambiguation, GameUpdateResult, GameSnapshot, HiddenTiles};
 use crate::game::tile::{Tile, Boneyard, Hand};
 use crate::game::messages::*;
+use crate::game::{CompanyMap, Money};
+
+use super::action_log::ActionLog;
+use super::audit::{TileAudit, TileAuditError, TileLocation};
+use super::autosave::SavedGame;
+use super::clock::{GameClock, TimeBank, TimeOutPenalty};
+use super::fanout::Broadcaster;
+use super::matchmaking::Queue;
+use super::schedule::Countdown;
+use super::vote::{Majority, Vote};
+use super::{NoListeners, PrivateBroadcast, ServerBroadcast, ServerOptions, DEFAULT_CHECKPOINT_INTERVAL};
+
+/// Gets the hash of a game after it was updated, whether or not it ended.
+fn state_hash<S: kernel::State>(result: &GameUpdateResult<S>) -> u64 {
+    match result {
+        Ok(game) => game.state_hash(),
+        Err(game_over) => game_over.state_hash(),
+    }
+}
 
-use super::{PrivateBroadcast, ServerBroadcast};
+/// Gets the name of the player whose action the game is waiting on. This is the
+/// active player, except during a merge, when it is the shareholder resolving
+/// their stock.
+fn awaited_player(game: &Game<kernel::Ambiguous>) -> Box<str> {
+    match game.clone().disambiguate() {
+        GameDisambiguation::ResolvingMerge(game) => game.resolving_player().into(),
+        _ => game.active_player().into(),
+    }
+}
+
+/// Checks if `action` is the kind of action that `request` asks for.
+fn answers(action: &PlayerAction, request: &ActionRequest) -> bool {
+    matches!(
+        (action, request),
+        (PlayerAction::PlayTile { .. }, ActionRequest::PlayTile)
+            | (PlayerAction::BuyStock { .. }, ActionRequest::BuyStock)
+            | (PlayerAction::ResolveMergeStock { .. }, ActionRequest::ResolveMergeStock { .. })
+    )
+}
 
-/// Handles the server side of a game.
+/// Handles the server side of a game. Any of its methods that broadcast fail
+/// with [`NoListeners`] once every connection has closed, possibly partway
+/// through an update. The game should be discarded when that happens.
 #[derive(Debug)]
 pub struct ServerGame {
-    broadcaster: broadcast::Sender<ServerBroadcast>,
+    broadcaster: Broadcaster,
     _impl: Option<ServerGameImpl>,
+    /// The order in which the tiles of the next game are to be dealt. If
+    /// [`None`], the tiles are dealt at random.
+    stacked_tiles: Option<Vec<Tile>>,
+    /// Whether to panic on the next action. See [`ServerGame::fail_next_action`].
+    #[cfg(test)]
+    fail_next_action: bool,
+    /// The seed the host chose to deal the next game from.
+    next_seed: Option<DealSeed>,
+    /// See [`ServerOptions::queue_early_actions`](super::ServerOptions).
+    queue_early_actions: bool,
+    /// See [`ServerOptions::time_bank`](super::ServerOptions).
+    time_bank: Option<TimeBank>,
+    /// See [`ServerOptions::end_vote_majority`](super::ServerOptions).
+    end_vote_majority: Majority,
+    /// See [`ServerOptions::commit_deal`](super::ServerOptions).
+    commit_deal: bool,
+    /// See [`ServerOptions::random_seating`](super::ServerOptions).
+    random_seating: bool,
+    /// See [`ServerOptions::checkpoint_interval`](super::ServerOptions).
+    checkpoint_interval: NonZeroUsize,
+    /// The rule policy each game is started with.
+    policy: RulePolicy,
+    /// The directory each game is saved to as a replay once it is over, if
+    /// any.
+    replay_dir: Option<PathBuf>,
+    /// Every action accepted in the game in progress, or in the last game
+    /// played if none is.
+    action_log: ActionLog,
+    /// The players waiting for a game, if games are made up from a queue.
+    matchmaking: Option<Queue>,
+    /// The countdown to the start of the next game, if an admin has
+    /// scheduled one.
+    countdown: Option<Countdown>,
 }
 
 #[derive(Debug)]
@@ -20,14 +101,196 @@ struct ServerGameImpl {
     boneyard: Boneyard<Tile>,
     game: Game<kernel::Ambiguous>,
     player_tiles: HashMap<Box<str>, Hand>,
+    /// Every tile in the boneyard when the game began.
+    tiles: Box<[Tile]>,
+    /// Tiles swapped out of a hand for being dead, which are out of the game.
+    dead_tiles: Vec<Tile>,
+    /// The number of dead tiles the active player has traded in this turn.
+    dead_tiles_traded: u8,
+    /// The action each player sent before the game was ready for it, if the
+    /// server holds such actions rather than rejecting them, with the request
+    /// ID each was sent with.
+    queued_actions: HashMap<Box<str>, (TaggedPlayerAction, Option<RequestId>)>,
     start: GameStart,
     action_history: Vec<TaggedPlayerAction>,
+    /// Every seat that has changed hands during the game.
+    substitutions: Vec<Substitution>,
+    /// The time left to each player, if the game is played on the clock.
+    clock: Option<GameClock>,
+    /// The vote on ending the game early, if one has been proposed.
+    end_vote: Option<Vote>,
+    /// The seed the tiles were shuffled from, if the server committed to
+    /// their order. It is revealed once the game is over.
+    deal_seed: Option<DealSeed>,
+    /// The snapshots of the game taken so far, to catch up those who join it
+    /// and to seek through its history.
+    checkpoints: Vec<Checkpoint>,
+    /// The seats the server plays, moving for them as soon as it is their
+    /// turn.
+    autopilot: HashSet<Box<str>>,
+    /// The turns each player has let run out of time in a row.
+    missed_turns: HashMap<Box<str>, MissedTurns>,
+}
+
+/// A run of turns a player has let run out of time.
+#[derive(Debug, Clone, Copy)]
+struct MissedTurns {
+    /// The latest turn missed.
+    turn: u32,
+    /// The number of turns missed in a row.
+    count: usize,
+    /// The length of the action history once the server last moved for the
+    /// player. Any action of the player's own after it breaks the run.
+    moved_at: usize,
+}
+
+impl ServerGameImpl {
+    /// Counts the turn the game is waiting on `player` for as missed, giving
+    /// the number of turns they have now missed in a row. A turn counts once,
+    /// however many of its actions run out of time.
+    fn miss_turn(&mut self, player: &str) -> usize {
+        let turn = self.game.next_action_number().turn;
+        let acted = |since: usize| self.action_history[since..].iter()
+            .any(|action| *action.player_name == *player);
+        let count = match self.missed_turns.get(player) {
+            Some(missed) if acted(missed.moved_at) => 1,
+            Some(missed) if missed.turn == turn => missed.count,
+            Some(missed) => missed.count + 1,
+            None => 1,
+        };
+        self.missed_turns.insert(player.into(), MissedTurns { turn, count, moved_at: self.action_history.len() });
+        count
+    }
+
+    /// Checks if an action is one the player has already had applied, sent a
+    /// second time, as from both the action panel and the command line. A
+    /// numbered action is compared with the action played under its number.
+    /// Otherwise, only the actions the player has played since another player
+    /// last acted are compared.
+    fn is_duplicate(&self, action: &TaggedPlayerAction) -> bool {
+        let same = |applied: &TaggedPlayerAction| {
+            applied.player_name == action.player_name && applied.action == action.action
+        };
+        match action.number {
+            Some(number) => self.action_history.get(number.index).is_some_and(same),
+            None => self.action_history.iter().rev()
+                .take_while(|applied| applied.player_name == action.player_name)
+                .any(same),
+        }
+    }
+
+    /// Checks that every tile is in exactly one place: the boneyard, a hand,
+    /// the board, or the dead tiles.
+    fn audit_tiles(&self) -> Result<(), TileAuditError> {
+        let mut audit = TileAudit::new();
+        audit.record(self.boneyard.iter().copied(), TileLocation::Boneyard);
+        for (player, hand) in &self.player_tiles {
+            audit.record(hand.iter().copied(), TileLocation::Hand(player.clone()));
+        }
+        audit.record(self.game.board().played().iter(), TileLocation::Board);
+        audit.record(self.dead_tiles.iter().copied(), TileLocation::Dead);
+        audit.check(&self.tiles)
+    }
+
+    /// Takes a new checkpoint once `interval` actions have been played since
+    /// the last.
+    fn checkpoint_if_due(&mut self, interval: NonZeroUsize) {
+        let last = self.checkpoints.last().map_or(0, |checkpoint| checkpoint.after_actions);
+        if self.action_history.len() >= last + interval.get() {
+            self.checkpoints.push(Checkpoint {
+                after_actions: self.action_history.len(),
+                snapshot: Box::new(self.game.snapshot()),
+            });
+        }
+    }
+
+    /// Panics if a tile has been duplicated or lost, but only in debug builds.
+    fn debug_audit_tiles(&self) {
+        if cfg!(debug_assertions) {
+            if let Err(err) = self.audit_tiles() {
+                panic!("tile audit failed after {} actions: {err}", self.action_history.len());
+            }
+        }
+    }
 }
 
 impl ServerGame {
-    /// Creates a new [`ServerGame`] with no game in progress.
-    pub fn new(broadcaster: broadcast::Sender<ServerBroadcast>) -> Self {
-        Self { broadcaster, _impl: None }
+    /// Creates a new [`ServerGame`] with no game in progress, which plays its
+    /// games as the host chose in `options`. The options that call for more
+    /// than a flag are set afterwards, as with [`ServerGame::set_policy`].
+    pub fn new(broadcaster: Broadcaster, options: &ServerOptions) -> Self {
+        Self {
+            broadcaster,
+            _impl: None,
+            stacked_tiles: None,
+            #[cfg(test)]
+            fail_next_action: false,
+            next_seed: None,
+            queue_early_actions: options.queue_early_actions,
+            time_bank: options.time_bank,
+            end_vote_majority: options.end_vote_majority,
+            commit_deal: options.commit_deal,
+            random_seating: options.random_seating,
+            checkpoint_interval: options.checkpoint_interval.unwrap_or(DEFAULT_CHECKPOINT_INTERVAL),
+            policy: RulePolicy::default(),
+            replay_dir: None,
+            action_log: ActionLog::new(),
+            matchmaking: None,
+            countdown: None,
+        }
+    }
+
+    /// Starts each game from now on with `policy`. The game in progress, if
+    /// any, keeps the policy it was started with.
+    pub fn set_policy(&mut self, policy: RulePolicy) {
+        self.policy = policy;
+    }
+
+    /// Saves each game to `dir` as a replay once it is over.
+    pub fn record_replays(&mut self, dir: PathBuf) {
+        self.replay_dir = Some(dir);
+    }
+
+    /// Checks if the rule policy of the game in progress forbids a message
+    /// from `player_name`. With no game in progress, nothing is forbidden.
+    pub fn forbids(&self, player_name: &str, msg: &ClientMessage) -> bool {
+        self._impl.as_ref().is_some_and(|game_impl| {
+            let seated = game_impl.player_tiles.contains_key(player_name);
+            game_impl.start.policy.forbids(msg, seated)
+        })
+    }
+
+    /// Lets players queue for a game, which is made up once `table_size` of
+    /// them are waiting. See [`ServerGame::take_table`].
+    pub fn make_matches(&mut self, table_size: usize) {
+        self.matchmaking = Some(Queue::new(table_size));
+    }
+
+    /// Deals the tiles of the next game in the order given, rather than at
+    /// random. The players draw their starting tiles in order of their names,
+    /// and then draw their hands in order of play.
+    #[cfg(test)]
+    pub fn stack_tiles(&mut self, tiles: Vec<Tile>) {
+        self.stacked_tiles = Some(tiles);
+    }
+
+    /// Panics on the next action a player sends, before the game is touched,
+    /// as a stand-in for a bug in handling it.
+    #[cfg(test)]
+    pub fn fail_next_action(&mut self) {
+        self.fail_next_action = true;
+    }
+
+    /// Deals the next game from `seed`, committing to it as if the deal were
+    /// committed to, so that the players can check the deal once the game is
+    /// over.
+    pub fn seed_deal(&mut self, seed: DealSeed) {
+        self.next_seed = Some(seed);
+    }
+
+    /// Checks whether a game is in progress.
+    pub fn in_progress(&self) -> bool {
+        self._impl.is_some()
     }
 
     /// Makes a copy of this game's message history and returns it if there is a
@@ -36,45 +299,255 @@ impl ServerGame {
         self._impl.as_ref().map(|i| GameHistory {
             start: i.start.clone(),
             actions: i.action_history.to_owned().into_boxed_slice(),
+            substitutions: i.substitutions.to_owned().into_boxed_slice(),
+            checkpoints: i.checkpoints.to_owned().into_boxed_slice(),
         })
     }
 
-    /// Broadcasts and records any successful player actions. This function
-    /// assumes that a game is in progress and panics otherwise.
-    fn broadcast_player_action(&mut self, history: &mut Vec<TaggedPlayerAction>, action: TaggedPlayerAction) {
+    /// Gets the history of the game in progress to send to someone joining it,
+    /// starting from the latest checkpoint.
+    pub fn public_history(&self) -> Option<PublicGameHistory> {
+        let history = self.history()?;
+        Some(history.seek(history.actions.len()))
+    }
+
+    /// Gets the hand of a player in the game in progress.
+    pub fn hand(&self, player_name: &str) -> Option<Hand> {
+        self._impl.as_ref()?.player_tiles.get(player_name).copied()
+    }
+
+    /// Hands the seat of a player who left to `player_name`, who takes on
+    /// their hand along with everything else. Returns the substitution to be
+    /// announced, along with the hash of the game once it has been made.
+    pub fn substitute(&mut self, seat: &str, player_name: Box<str>)
+        -> Result<(Substitution, u64), InvalidMessageReason>
+    {
+        let game_impl = self._impl.as_mut().ok_or(InvalidMessageReason::NoGameStarted)?;
+        game_impl.game.substitute(seat, player_name.clone())?;
+
+        if let Some(hand) = game_impl.player_tiles.remove(seat) {
+            game_impl.player_tiles.insert(player_name.clone(), hand);
+        }
+        // An action queued by the player who left is not the new player's
+        game_impl.queued_actions.remove(seat);
+        if let Some(clock) = game_impl.clock.as_mut() {
+            clock.substitute(seat, &player_name);
+        }
+        if let Some(vote) = game_impl.end_vote.as_mut() {
+            vote.withdraw(seat);
+        }
+        // The new player plays the seat themselves, with a clean record
+        game_impl.autopilot.remove(seat);
+        game_impl.missed_turns.remove(seat);
+
+        let substitution = Substitution {
+            seat: seat.into(),
+            player_name,
+            after_actions: game_impl.action_history.len(),
+        };
+        game_impl.substitutions.push(substitution.clone());
+        game_impl.debug_audit_tiles();
+
+        Ok((substitution, game_impl.game.state_hash()))
+    }
+
+    /// Sends the state of the game privately to a player who has fallen out of
+    /// step with it. If it is that player's turn, their action is requested
+    /// again, as the resync discards any request they were answering.
+    pub fn resync(&mut self, player_name: Box<str>) -> Result<(), NoListeners> {
+
+        // Only the player's own hand is sent along with the snapshot
+        let mut snapshot = self.snapshot();
+        let hand = snapshot.as_mut()
+            .and_then(|snapshot| snapshot.hidden.take())
+            .and_then(|hidden| hidden.hands.get(&player_name).copied());
+
+        let clock = self._impl.as_ref()
+            .and_then(|game_impl| game_impl.clock.as_ref())
+            .map(|clock| clock.state(Instant::now()));
+
+        self.broadcast(ServerBroadcast::Private {
+            target_player: player_name.clone(),
+            message: PrivateBroadcast::Resync {
+                snapshot: snapshot.map(Box::new),
+                hand,
+                clock,
+            }
+        })?;
+
+        if let Some(game_impl) = self._impl.as_ref() {
+            if awaited_player(&game_impl.game) == player_name {
+                self.broadcast(ServerBroadcast::Private {
+                    target_player: player_name,
+                    message: PrivateBroadcast::YourTurn {
+                        request: game_impl.game.needed_action(),
+                        number: game_impl.game.next_action_number(),
+                    }
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Takes a snapshot of the game in progress, including the tiles hidden
+    /// from the players.
+    pub fn snapshot(&self) -> Option<GameSnapshot> {
+        self._impl.as_ref().map(|i| {
+            let mut snapshot = i.game.snapshot();
+            snapshot.hidden = Some(HiddenTiles {
+                boneyard: i.boneyard.clone(),
+                hands: i.player_tiles.clone(),
+            });
+            snapshot
+        })
+    }
+
+    /// Gets everything needed to carry on the game in progress, hidden tiles
+    /// and all, if there is one.
+    pub fn save(&self) -> Option<SavedGame> {
+        let saved_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let game_impl = self._impl.as_ref()?;
+        Some(SavedGame {
+            saved_at,
+            snapshot: self.snapshot()?,
+            history: self.history()?,
+            dead_tiles: game_impl.dead_tiles.clone().into_boxed_slice(),
+            deal_seed: game_impl.deal_seed,
+            clock: game_impl.clock.as_ref().map(|clock| clock.state(Instant::now())),
+        })
+    }
+
+    /// Carries on the game from a save, in place of any game in progress. Any
+    /// vote on ending the game is not saved, so the game goes on without one,
+    /// and the clock is started anew for whoever the game waits on. Returns
+    /// `false`, changing nothing, if the save holds no hidden tiles to carry
+    /// on with.
+    pub fn restore(&mut self, saved: SavedGame) -> bool {
+        let SavedGame { snapshot, history, dead_tiles, deal_seed, clock, .. } = saved;
+        let Some(hidden) = snapshot.hidden.clone() else { return false };
+        let game: Game<kernel::Ambiguous> = snapshot.into();
+
+        let mut tiles: Vec<_> = hidden.boneyard.iter().copied().collect();
+        tiles.extend(hidden.hands.values().flat_map(|hand| hand.iter().copied()));
+        tiles.extend(game.board().played().iter());
+        tiles.extend(dead_tiles.iter().copied());
+
+        self.action_log.truncate(history.actions.len());
+        self._impl = Some(ServerGameImpl {
+            boneyard: hidden.boneyard,
+            game,
+            start: history.start,
+            player_tiles: hidden.hands,
+            tiles: tiles.into_boxed_slice(),
+            dead_tiles: dead_tiles.into_vec(),
+            dead_tiles_traded: 0,
+            queued_actions: HashMap::new(),
+            action_history: history.actions.into_vec(),
+            substitutions: history.substitutions.into_vec(),
+            clock: clock.as_ref().map(GameClock::resume),
+            end_vote: None,
+            deal_seed,
+            checkpoints: history.checkpoints.into_vec(),
+            autopilot: HashSet::new(),
+            missed_turns: HashMap::new(),
+        });
+        self._impl.as_ref().unwrap().debug_audit_tiles();
+        true
+    }
+
+    /// Drops the game in progress without a word to anyone.
+    pub fn abandon(&mut self) {
+        self._impl = None;
+    }
+
+    /// Checks that the game in progress still accounts for every tile, as it
+    /// may not if the server failed partway through updating it.
+    pub fn is_intact(&self) -> bool {
+        self._impl.as_ref().is_some_and(|game_impl| game_impl.audit_tiles().is_ok())
+    }
+
+    /// Gets the number of actions played in the game in progress, if there is
+    /// one.
+    pub fn actions_played(&self) -> Option<usize> {
+        Some(self._impl.as_ref()?.action_history.len())
+    }
+
+    /// Broadcasts and records any successful player actions, along with the
+    /// hash of the game after the action was applied and the request ID the
+    /// action was sent with. This function assumes that a game is in progress
+    /// and panics otherwise.
+    fn broadcast_player_action(&mut self,
+        history: &mut Vec<TaggedPlayerAction>,
+        action: TaggedPlayerAction,
+        number: ActionNumber,
+        state_hash: u64,
+        request_id: Option<RequestId>,
+    ) -> Result<(), NoListeners> {
+        let action = TaggedPlayerAction { number: Some(number), ..action };
         history.push(action.clone());
-        self.broadcaster.send(ServerBroadcast::PlayerMove { action }).unwrap();
+        self.action_log.append(action.clone());
+        self.broadcast(ServerBroadcast::PlayerMove { action, state_hash, request_id })
     }
 
-    /// Starts the game with the specified starting cash and players. This
-    /// function only succeeds if no game is in progress, in which case the
-    /// function will return `true`. If there is a game in progress, this
-    /// function is a no-op and returns `false`. Broadcasts any messages that are
-    /// needed to facilitate the game.
+    /// Starts the game with the specified starting cash and players. If there
+    /// is a game in progress, the admin is told so and nothing else happens.
+    /// Broadcasts any messages that are needed to facilitate the game.
     pub fn start(
         &mut self,
-        starting_cash: u32,
+        starting_cash: Money,
         player_names: impl IntoIterator<Item = Box<str>>,
         admin_name: Box<str>,
-    ) {
+    ) -> Result<(), NoListeners> {
         if self._impl.is_some() {
-            self.broadcaster.send(ServerBroadcast::Private {
+            self.broadcast(ServerBroadcast::Private {
                 target_player: admin_name,
                 message: PrivateBroadcast::Invalid {
-                    reason: InvalidMessageReason::GameAlreadyStarted
+                    reason: InvalidMessageReason::GameAlreadyStarted,
+                    request_id: None,
                 }
-            }).unwrap();
-            return;
+            })?;
+            return Ok(());
         }
+        self.countdown = None;
+
+        // A stacked boneyard is dealt as stacked, with no commitment made
+        let stacked = self.stacked_tiles.take();
+        let deal_seed = self.next_seed.take()
+            .or_else(|| (self.commit_deal && stacked.is_none()).then(DealSeed::random));
+        let mut boneyard = match (stacked, deal_seed) {
+            (Some(tiles), _) => Boneyard::stacked(tiles),
+            (None, Some(seed)) => Boneyard::stacked(seed.tiles()),
+            (None, None) => Tile::boneyard(),
+        };
+        let all_tiles = boneyard.iter().copied().collect();
 
-        let mut boneyard = Tile::boneyard();
+        // Deal in a fixed order, so that a stacked boneyard is dealt the same
+        // way every time
+        let mut player_names: Vec<_> = player_names.into_iter().collect();
+        player_names.sort();
 
-        // Get random starting tiles for each player.
+        // Get random starting tiles for each player, and show everyone who
+        // drew what
         let mut players_and_tiles: Vec<_> = player_names.into_iter()
             .map(|name| (name, boneyard.remove().unwrap()))
             .collect();
-        // Determine player start order
-        players_and_tiles.sort_by(|(_, tile1), (_, tile2)| tile1.cmp(&tile2));
+        let draws = players_and_tiles.iter()
+            .map(|(player_name, tile)| StartingDraw { player_name: player_name.clone(), tile: *tile })
+            .collect();
+        self.broadcast(ServerBroadcast::StartingDraw {
+            draws,
+            random_seating: self.random_seating,
+        })?;
+
+        // Determine player start order, by the tiles unless the seats are
+        // drawn at random
+        match self.random_seating {
+            true => players_and_tiles.shuffle(&mut rand::thread_rng()),
+            false => players_and_tiles.sort_by_key(|(_, tile)| *tile),
+        }
 
         // Separate the players and tiles for the game start info
         let (players, tiles): (Vec<_>, Vec<_>) = players_and_tiles.clone().into_iter().unzip();
@@ -98,53 +571,88 @@ impl ServerGame {
             starting_cash, 
             play_order: players.into_boxed_slice(),
             tiles_placed: tiles.into_boxed_slice(),
+            tie_rule: Default::default(),
+            dead_tiles_per_turn: None,
+            deal_commitment: deal_seed.map(|seed| seed.commitment()),
+            random_seating: self.random_seating,
+            policy: self.policy,
+            game_id: Some(GameId::generate()),
         };
     
         let game = Game::start(&game_start_info);
+        self.action_log = ActionLog::new();
+        let clock = self.time_bank.map(|bank| {
+            GameClock::new(game_start_info.play_order.iter().map(|name| &**name), bank.per_player)
+        });
 
         // Broadcast the game start message
-        self.broadcaster.send(
+        self.broadcast(
             ServerBroadcast::GameStart {
                 info: game_start_info.clone(),
                 initial_hands
             }
-        ).unwrap();
+        )?;
 
         // Send the first YourTurn
-        self.broadcaster.send(ServerBroadcast::Private {
+        self.broadcast(ServerBroadcast::Private {
             target_player: game.active_player().to_owned().into_boxed_str(),
             message: PrivateBroadcast::YourTurn {
-                request: ActionRequest::PlayTile
+                request: ActionRequest::PlayTile,
+                number: game.next_action_number(),
             }
-        }).unwrap();
+        })?;
 
         self._impl = Some(ServerGameImpl {
             boneyard,
             game: game.into(),
             start: game_start_info,
             player_tiles,
+            tiles: all_tiles,
+            dead_tiles: Vec::new(),
+            dead_tiles_traded: 0,
+            queued_actions: HashMap::new(),
             action_history: Vec::new(),
+            substitutions: Vec::new(),
+            clock,
+            end_vote: None,
+            deal_seed,
+            checkpoints: Vec::new(),
+            autopilot: HashSet::new(),
+            missed_turns: HashMap::new(),
         });
+        self._impl.as_ref().unwrap().debug_audit_tiles();
+
+        Ok(())
     }
 
     /// Updates this game and broadcasts all the messages needed to facilitate
-    /// the game.
-    pub fn update(&mut self, action: TaggedPlayerAction) {
+    /// the game. The request ID the action was sent with, if any, is echoed
+    /// to its player in the move or the refusal that answers it.
+    pub fn update(&mut self, action: TaggedPlayerAction, request_id: Option<RequestId>)
+        -> Result<(), NoListeners>
+    {
+        #[cfg(test)]
+        if std::mem::take(&mut self.fail_next_action) {
+            panic!("failed on purpose");
+        }
+
         // Take the game, sending a message if there is no game
         let mut game_impl = match self._impl.take() {
             Some(v) => v,
             None => {
-                self.broadcaster.send(ServerBroadcast::Private {
+                self.broadcast(ServerBroadcast::Private {
                     target_player: action.player_name,
                     message: PrivateBroadcast::Invalid {
-                        reason: InvalidMessageReason::NoGameStarted
+                        reason: InvalidMessageReason::NoGameStarted,
+                        request_id,
                     }
-                }).unwrap();
-                return;
+                })?;
+                return Ok(());
             },
         };
         let history = &mut game_impl.action_history;
         let game = game_impl.game;
+        let number = game.next_action_number();
 
         let big_result = match game.disambiguate() {
             GameDisambiguation::PlacingTile(game) => {
@@ -161,27 +669,41 @@ impl ServerGame {
                         }
     
                         else {
-
-                            // Send the message
-                            self.broadcast_player_action(history, action.clone());
+                            // Dead tiles can no longer be traded in once the
+                            // tile is placed
+                            game_impl.dead_tiles_traded = 0;
 
                             // Decide whether to resolve the merge
                             let game = game.advance_game(advance);
+                            let founding = game.founding();
                             match game.decide_merge() {
                                 Ok(no_merge) => {
-                                    Ok(Ok(game.skip_merge(no_merge).into()))
+                                    let game = game.skip_merge(no_merge);
+                                    self.broadcast_player_action(history, action.clone(), number, game.state_hash(), request_id)?;
+
+                                    // Tell the founder why they were not given
+                                    // a share
+                                    if let Some(Founding { company, founder_share: false }) = founding {
+                                        self.broadcast(ServerBroadcast::NoFounderShare {
+                                            player_name: action.player_name.clone(),
+                                            company,
+                                        })?;
+                                    }
+
+                                    Ok(Ok(game.into()))
                                 },
                                 Err(merge) => {
         
                                     let game = game.commence_merge(merge);
+                                    self.broadcast_player_action(history, action.clone(), number, game.state_hash(), request_id)?;
         
                                     // Send the defunct company message
-                                    self.broadcaster.send(ServerBroadcast::CompanyDefunct {
+                                    self.broadcast(ServerBroadcast::CompanyDefunct {
                                         defunct: game.current_merge().0,
                                         results: game.principle_shareholders()
                                             .to_vec()
                                             .into_boxed_slice()
-                                    }).unwrap();
+                                    })?;
         
                                     Ok(Ok(game.into()))
                                 },
@@ -196,26 +718,27 @@ impl ServerGame {
             GameDisambiguation::ResolvingMerge(game) => {
                 match game.check_player_action(&action) {
                     Ok(advance) => {
-                        self.broadcast_player_action(history, action.clone());
-    
                         match advance {
                             Ok(merge) => {
                                 let mut game = game;
-                                let another_defunct = game.continue_merge(merge);
+                                let another_defunct = game.continue_merge(merge).is_some();
+                                self.broadcast_player_action(history, action.clone(), number, game.state_hash(), request_id)?;
     
-                                if let Some(next_merge) = another_defunct {
-                                    self.broadcaster.send(ServerBroadcast::CompanyDefunct {
+                                if another_defunct {
+                                    self.broadcast(ServerBroadcast::CompanyDefunct {
                                         defunct: game.current_merge().0,
                                         results: game.principle_shareholders()
                                             .to_vec()
                                             .into_boxed_slice()
-                                    }).unwrap();
+                                    })?;
                                 };
     
                                 Ok(Ok(game.into()))
                             },
                             Err(merge_done) => {
-                                Ok(game.finish_merge(merge_done).map(|g| g.into()))
+                                let result = game.finish_merge(merge_done);
+                                self.broadcast_player_action(history, action.clone(), number, state_hash(&result), request_id)?;
+                                Ok(result.map(|g| g.into()))
                             },
                         }
                     },
@@ -227,19 +750,20 @@ impl ServerGame {
             GameDisambiguation::BuyingStock(game) => {
                 match game.check_player_action(&action) {
                     Ok(advance) => {
-                        self.broadcast_player_action(history, action.clone());
+                        let result = game.advance_game(advance);
+                        self.broadcast_player_action(history, action.clone(), number, state_hash(&result), request_id)?;
     
                         // Draw and send the new tile
                         let new_tile = game_impl.boneyard.remove().unwrap();
                         game_impl.player_tiles.get_mut(&action.player_name).unwrap()
                             .insert_tile(new_tile)
                             .unwrap();
-                        self.broadcaster.send(ServerBroadcast::Private {
+                        self.broadcast(ServerBroadcast::Private {
                             target_player: action.player_name.clone(),
                             message: PrivateBroadcast::TileDraw { tile: new_tile }
-                        }).unwrap();
+                        })?;
     
-                        Ok(game.advance_game(advance).map(|g| g.into()))
+                        Ok(result.map(|g| g.into()))
                     },
                     Err(invalid) => {
                         Err((game.into(), invalid))
@@ -252,147 +776,650 @@ impl ServerGame {
         let game: GameUpdateResult<kernel::Ambiguous> = match big_result {
             Ok(v) => v,
             Err((game, invalid)) => {
-
-                // Put the game and impl back in place
                 game_impl.game = game;
+
+                // Drop an action sent twice without a word, rather than
+                // refuse it as if the first had not been played
+                if game_impl.is_duplicate(&action) {
+                    self._impl = Some(game_impl);
+                    return Ok(());
+                }
+
+                // Hold actions sent early by players until the game asks for
+                // them. Spectators have nothing to hold.
+                let early = matches!(invalid, InvalidMessageReason::OutOfTurn)
+                    && game_impl.player_tiles.contains_key(&action.player_name);
+                if self.queue_early_actions && early {
+                    // It will be played later than it was numbered for
+                    let action = TaggedPlayerAction { number: None, ..action };
+                    game_impl.queued_actions.insert(action.player_name.clone(), (action, request_id));
+                    self._impl = Some(game_impl);
+                    return Ok(());
+                }
+
+                // Put the impl back in place
                 self._impl = Some(game_impl);
 
-                self.broadcaster.send(ServerBroadcast::Private {
+                self.broadcast(ServerBroadcast::Private {
                     target_player: action.player_name,
-                    message: PrivateBroadcast::Invalid { reason: invalid }
-                }).unwrap();
-                return;
+                    message: PrivateBroadcast::Invalid { reason: invalid, request_id }
+                })?;
+                return Ok(());
             },
         };
 
         match game {
             Ok(game) => {
+                let request = game.needed_action();
+
+                // Let everyone follow along as each shareholder resolves
+                // their stock
+                if let ActionRequest::ResolveMergeStock { defunct, into, .. } = request {
+                    self.broadcast(ServerBroadcast::ResolvingNext {
+                        player: awaited_player(&game),
+                        defunct,
+                        into,
+                    })?;
+                }
+
+                // Apply the action the awaited player sent early, if it is
+                // the one the game now asks for, rather than asking for it
+                let awaited = awaited_player(&game);
+                let queued = game_impl.queued_actions.get(&awaited)
+                    .is_some_and(|(queued, _)| answers(&queued.action, &request))
+                    .then(|| game_impl.queued_actions.remove(&awaited).unwrap());
+
                 // Send the action request
-                self.broadcaster.send(ServerBroadcast::Private {
-                    target_player: game.active_player().to_owned().into_boxed_str(),
-                    message: PrivateBroadcast::YourTurn { request: game.needed_action() }
-                }).unwrap();
+                if queued.is_none() {
+                    self.broadcast(ServerBroadcast::Private {
+                        target_player: awaited,
+                        message: PrivateBroadcast::YourTurn {
+                            request,
+                            number: game.next_action_number(),
+                        }
+                    })?;
+                }
 
                 // Put the game and impl back in place
                 game_impl.game = game;
+                game_impl.debug_audit_tiles();
+                game_impl.checkpoint_if_due(self.checkpoint_interval);
                 self._impl = Some(game_impl);
+
+                if let Some((queued, request_id)) = queued {
+                    return self.update(queued, request_id);
+                }
             },
             // Handle a game over
-            Err(game_over) => {
-
-                let reason = game_over.reason().clone();
-                let results = game_over.tally_results();
-
-                // Send messages for the final companies
-                results.shareholder_results.into_iter()
-                    .filter_map(|(cmp, r)| r.map(|r| (cmp, r)))
-                    .for_each(|(defunct, results)| {
-                        self.broadcaster.send(ServerBroadcast::CompanyDefunct {
-                            defunct,
-                            results
-                        }).unwrap();
-                    });
-
-                // Send the game over message
-                self.broadcaster.send(ServerBroadcast::GameOver { 
-                    reason,
-                    results: results.final_standings
-                }).unwrap();
-            },
+            Err(game_over) => self.broadcast_game_over(
+                game_over,
+                &game_impl.start,
+                &game_impl.action_history,
+                &game_impl.substitutions,
+                game_impl.deal_seed,
+            )?,
         };
+
+        Ok(())
     }
     
-    /// Attempts to swap a dead tile out of the player's hand. If the player
-    /// does not have the tile in question, this function will notify the player
-    /// as necessary.
-    pub fn swap_dead_tile(&mut self, player_name: Box<str>, tile: Tile) {
-        
-        let game_impl = match self._impl.as_mut() {
-            Some(v) => v,
-            None => {
-                self.broadcaster.send(ServerBroadcast::Private {
-                    target_player: player_name,
-                    message: PrivateBroadcast::Invalid {
-                        reason: InvalidMessageReason::NoGameStarted
-                    }
-                }).unwrap();
-                return;
-            },
+    /// Attempts to swap a dead tile out of the player's hand. A dead tile may
+    /// only be traded in on the player's own turn, before they place a tile,
+    /// and no more than [`GameStart::dead_tiles_per_turn`] may be traded in on
+    /// one turn. If the trade breaks any of these rules, or the player does not
+    /// have the tile in question, this function will notify the player as
+    /// necessary.
+    pub fn swap_dead_tile(&mut self, player_name: Box<str>, tile: Tile)
+        -> Result<(), NoListeners>
+    {
+        let Some(game_impl) = self._impl.as_mut() else {
+            return self.reject(player_name, InvalidMessageReason::NoGameStarted);
         };
 
+        // Check that the player is about to place a tile
+        let game = &game_impl.game;
+        if let Err(reason) = game.check_dead_tile_trade(&player_name) {
+            return self.reject(player_name, reason);
+        }
+
+        // Check that the player hasn't traded in too many tiles already
+        if game_impl.start.dead_tiles_per_turn.is_some_and(|limit| game_impl.dead_tiles_traded >= limit) {
+            return self.reject(player_name, InvalidMessageReason::DeadTileLimit);
+        }
+
         // Check if the tile is dead
-        if !game_impl.game.board().dead_tile(tile) {
-            self.broadcaster.send(ServerBroadcast::Private {
-                target_player: player_name,
-                message: PrivateBroadcast::Invalid {
-                    reason: InvalidMessageReason::NotDeadTile
-                }
-            }).unwrap();
-            return;
+        if !game.board().dead_tile(tile) {
+            return self.reject(player_name, InvalidMessageReason::NotDeadTile);
         }
 
         // We can't use `get_hand_mut` because we need a disjoint borrow
         let player_hand = game_impl.player_tiles.get_mut(&player_name).unwrap();
 
         // First attempt remove the dead tile from the hand to see if it's present
-        let success = player_hand.remove_tile(tile);
-
-        if !success {
-            self.broadcaster.send(ServerBroadcast::Private {
-                target_player: player_name,
-                message: PrivateBroadcast::Invalid {
-                    reason: InvalidMessageReason::TileNotFound
-                }
-            }).unwrap();
-            return;
+        if !player_hand.remove_tile(tile) {
+            return self.reject(player_name, InvalidMessageReason::TileNotFound);
         }
 
-        // Then reinsert. Since we just removed a tile, this is guaranteed to succeed.
-        let new_tile = game_impl.boneyard.remove().unwrap();
-        player_hand.insert_tile(new_tile).unwrap();
+        // Then reinsert, unless there are no tiles left to draw. Since we just
+        // removed a tile, this is guaranteed to succeed.
+        let new_tile = game_impl.boneyard.remove();
+        if let Some(new_tile) = new_tile {
+            player_hand.insert_tile(new_tile).unwrap();
+        }
+        game_impl.dead_tiles.push(tile);
+        game_impl.dead_tiles_traded += 1;
+        game_impl.debug_audit_tiles();
 
         // Notify the players that the dead tile switch occurred
-        self.broadcaster.send(ServerBroadcast::DeadTile {
-            player_name,
+        self.broadcast(ServerBroadcast::DeadTile {
+            player_name: player_name.clone(),
             dead_tile: tile
-        }).unwrap();
+        })?;
+
+        match new_tile {
+            Some(tile) => self.broadcast(ServerBroadcast::Private {
+                target_player: player_name,
+                message: PrivateBroadcast::TileDraw { tile }
+            }),
+            None => Ok(()),
+        }
     }
 
     /// Forcibly ends the game. Returns `false` if there is no game to end.
-    pub fn end(&mut self, admin_name: Box<str>) {
+    pub fn end(&mut self, admin_name: Box<str>) -> Result<(), NoListeners> {
         let game_impl = match self._impl.take() {
             Some(v) => v,
             None => {
-                self.broadcaster.send(ServerBroadcast::Private {
+                self.broadcast(ServerBroadcast::Private {
                     target_player: admin_name,
                     message: PrivateBroadcast::Invalid {
-                        reason: InvalidMessageReason::NoGameStarted
+                        reason: InvalidMessageReason::NoGameStarted,
+                        request_id: None,
                     }
-                }).unwrap();
-                return
+                })?;
+                return Ok(())
+            },
+        };
+
+        self.broadcast_game_over(
+            game_impl.game.end_early(),
+            &game_impl.start,
+            &game_impl.action_history,
+            &game_impl.substitutions,
+            game_impl.deal_seed,
+        )
+    }
+
+    /// Starts the clock of the player the game is waiting on, if it isn't
+    /// already running, and tells everyone. Does nothing unless a game is in
+    /// progress on the clock.
+    pub fn sync_clock(&mut self) -> Result<(), NoListeners> {
+        let Some(game_impl) = self._impl.as_mut() else { return Ok(()) };
+        let Some(clock) = game_impl.clock.as_mut() else { return Ok(()) };
+
+        let awaited = awaited_player(&game_impl.game);
+        if clock.running() == Some(&*awaited) {
+            return Ok(());
+        }
+        let now = Instant::now();
+        clock.start(&awaited, now);
+        let clock = clock.state(now);
+        self.broadcast(ServerBroadcast::Clock { clock })
+    }
+
+    /// Gets the moment the player the game is waiting on runs out of time, if
+    /// the game is played on the clock. A seat the server plays is out of
+    /// time at once.
+    pub fn clock_deadline(&self) -> Option<Instant> {
+        let game_impl = self._impl.as_ref()?;
+        if !game_impl.autopilot.is_empty() && game_impl.autopilot.contains(&awaited_player(&game_impl.game)) {
+            return Some(Instant::now());
+        }
+        game_impl.clock.as_ref()?.deadline()
+    }
+
+    /// Penalizes the player whose time has run out, either by moving for them
+    /// or by ending the game with them forfeiting it. A player who has let
+    /// their time run out on [`TimeBank::afk_turns`] turns in a row is taken
+    /// to be away, and the server plays their seat from then on. The server
+    /// also moves for a seat it already plays.
+    pub fn time_out(&mut self) -> Result<(), NoListeners> {
+        let Some(game_impl) = self._impl.as_mut() else { return Ok(()) };
+        let player = awaited_player(&game_impl.game);
+        let autopilot = game_impl.autopilot.contains(&player);
+        let (penalty, afk_turns) = match self.time_bank {
+            _ if autopilot => (TimeOutPenalty::AutoMove, None),
+            Some(bank) => (bank.penalty, bank.afk_turns),
+            None => return Ok(()),
+        };
+        let missed = afk_turns.map(|_| game_impl.miss_turn(&player));
+
+        // A player left without a move, as with a hand of unplayable tiles,
+        // can only forfeit
+        let auto_move = match penalty {
+            TimeOutPenalty::AutoMove => {
+                let hand = game_impl.player_tiles.get(&player).copied().unwrap_or_default();
+                Bot::new(Difficulty::Easy).choose_action(&game_impl.game, &player, &hand)
             },
+            TimeOutPenalty::Forfeit => None,
+        };
+
+        let Some(hint) = auto_move else {
+            let game_impl = self._impl.take().unwrap();
+            return self.broadcast_game_over(
+                game_impl.game.forfeit(&player),
+                &game_impl.start,
+                &game_impl.action_history,
+                &game_impl.substitutions,
+                game_impl.deal_seed,
+            );
+        };
+        self.update(TaggedPlayerAction {
+            player_name: player.clone(),
+            action: hint.action,
+            number: None,
+        }, None)?;
+
+        // The game may have ended with the move
+        let Some(game_impl) = self._impl.as_mut() else { return Ok(()) };
+        if let Some(missed) = game_impl.missed_turns.get_mut(&player) {
+            missed.moved_at = game_impl.action_history.len();
+        }
+        match (missed, afk_turns) {
+            (Some(missed), Some(afk_turns)) if missed >= afk_turns.get() => {
+                game_impl.autopilot.insert(player.clone());
+                self.broadcast(ServerBroadcast::Away { player_name: player, missed_turns: missed })
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Sends the log of the actions accepted in the latest game to the admin
+    /// who asked for it.
+    pub fn export_log(&self, admin_name: Box<str>) -> Result<(), NoListeners> {
+        self.broadcast(ServerBroadcast::Private {
+            target_player: admin_name,
+            message: PrivateBroadcast::ActionLog {
+                entries: self.action_log.entries().into(),
+            }
+        })
+    }
+
+    /// Hands a player's own seat to the server, which moves for it as soon as
+    /// it is its turn, or takes it back, and tells everyone. A player can
+    /// only ever hand over or take back the seat they hold.
+    pub fn set_autopilot(&mut self, player_name: Box<str>, autopilot: bool)
+        -> Result<(), NoListeners>
+    {
+        let Some(game_impl) = self._impl.as_mut() else {
+            return self.reject(player_name, InvalidMessageReason::NoGameStarted);
+        };
+        if !game_impl.player_tiles.contains_key(&player_name) {
+            return self.reject(player_name, InvalidMessageReason::NotInGame);
+        }
+
+        let changed = match autopilot {
+            true => game_impl.autopilot.insert(player_name.clone()),
+            false => game_impl.autopilot.remove(&player_name),
+        };
+        // A player back at their seat starts afresh
+        game_impl.missed_turns.remove(&player_name);
+        match changed {
+            true => self.broadcast(ServerBroadcast::Autopilot { player_name, autopilot }),
+            false => Ok(()),
+        }
+    }
+
+    /// Records a player's vote on ending the game early, the first vote in
+    /// favor proposing it. Once enough players have voted to decide it, the
+    /// game either ends or goes on as before.
+    pub fn vote_end(&mut self, player_name: Box<str>, end_game: bool)
+        -> Result<(), NoListeners>
+    {
+        let Some(game_impl) = self._impl.as_mut() else {
+            return self.reject(player_name, InvalidMessageReason::NoGameStarted);
+        };
+        if !game_impl.player_tiles.contains_key(&player_name) {
+            return self.reject(player_name, InvalidMessageReason::NotInGame);
+        }
+
+        let proposing = game_impl.end_vote.is_none();
+        if proposing && !end_game {
+            return self.reject(player_name, InvalidMessageReason::NoEndProposed);
+        }
+        let vote = game_impl.end_vote.get_or_insert_with(Vote::default);
+        vote.cast(&player_name, end_game);
+        let outcome = vote.outcome(self.end_vote_majority, game_impl.player_tiles.len());
+        if outcome.is_some() {
+            game_impl.end_vote = None;
+        }
+
+        self.broadcast(match proposing {
+            true => ServerBroadcast::EndProposed { player_name },
+            false => ServerBroadcast::EndVoted { player_name, end_game },
+        })?;
+
+        match outcome {
+            Some(true) => {
+                let game_impl = self._impl.take().unwrap();
+                self.broadcast_game_over(
+                    game_impl.game.end_early(),
+                    &game_impl.start,
+                    &game_impl.action_history,
+                    &game_impl.substitutions,
+                    game_impl.deal_seed,
+                )
+            },
+            Some(false) => self.broadcast(ServerBroadcast::EndRejected),
+            None => Ok(()),
+        }
+    }
+
+    /// Puts a player in the queue for a game, or takes them out of it, and
+    /// tells everyone. Players may queue during a game for the one after it.
+    pub fn queue(&mut self, player_name: Box<str>, queued: bool) -> Result<(), NoListeners> {
+        let Some(queue) = &mut self.matchmaking else {
+            return self.reject(player_name, InvalidMessageReason::NoMatchmaking);
+        };
+        let changed = match queued {
+            true => queue.join(&player_name),
+            false => queue.leave(&player_name),
+        };
+        if !changed {
+            return Ok(());
+        }
+        let (waiting, table_size) = (queue.waiting(), queue.table_size());
+        self.broadcast(ServerBroadcast::Queued { player_name, queued, waiting, table_size })
+    }
+
+    /// Takes a player who has left the server out of the queue, without
+    /// telling anyone, as everyone is told that they left.
+    pub fn leave_queue(&mut self, player_name: &str) {
+        if let Some(queue) = &mut self.matchmaking {
+            queue.leave(player_name);
+        }
+    }
+
+    /// Keeps the place in the queue of a player who has changed names.
+    pub fn rename_queued(&mut self, old_name: &str, new_name: Box<str>) {
+        if let Some(queue) = &mut self.matchmaking {
+            queue.rename(old_name, new_name);
+        }
+    }
+
+    /// Takes the players for the next game from the front of the queue, once
+    /// enough are waiting and no game is in progress. They are to be seated
+    /// and the game started with them.
+    pub fn take_table(&mut self) -> Option<Vec<Box<str>>> {
+        if self.in_progress() {
+            return None;
+        }
+        self.matchmaking.as_mut()?.take_table()
+    }
+
+    /// Schedules the next game to start at `starts_at`, in seconds since the
+    /// Unix epoch, and tells everyone. A game already scheduled is moved to
+    /// the new time.
+    pub fn schedule_start(&mut self, admin_name: Box<str>, starts_at: u64) -> Result<(), NoListeners> {
+        if self.in_progress() {
+            return self.reject(admin_name, InvalidMessageReason::GameAlreadyStarted);
+        }
+        let now = Instant::now();
+        let Some(countdown) = Countdown::new(admin_name.clone(), starts_at, now) else {
+            return self.reject(admin_name, InvalidMessageReason::StartTimePassed);
         };
+        let seconds_left = countdown.seconds_left(now);
+        self.countdown = Some(countdown);
+        self.broadcast(ServerBroadcast::StartScheduled { starts_at, seconds_left })
+    }
+
+    /// Gets the moment the countdown to a scheduled start next needs
+    /// attention, to remind everyone of it or to start the game.
+    pub fn countdown_deadline(&self) -> Option<Instant> {
+        Some(self.countdown.as_ref()?.next_tick(Instant::now()))
+    }
 
-        let game_over = game_impl.game.end_early();
+    /// Checks if a game is about to start on schedule, such that no more
+    /// seats are given.
+    pub fn seats_closed(&self) -> bool {
+        self.countdown.as_ref().is_some_and(|countdown| countdown.seats_closed(Instant::now()))
+    }
 
+    /// Reminds everyone of the scheduled start, or starts the game with
+    /// `player_names` if its time has come.
+    pub fn count_down(&mut self, player_names: Vec<Box<str>>) -> Result<(), NoListeners> {
+        let Some(countdown) = &self.countdown else { return Ok(()) };
+        match countdown.seconds_left(Instant::now()) {
+            0 => {
+                let admin_name = countdown.admin_name().into();
+                self.start(Money::new(6000), player_names, admin_name)
+            },
+            seconds_left => {
+                let starts_at = countdown.starts_at();
+                self.broadcast(ServerBroadcast::StartScheduled { starts_at, seconds_left })
+            },
+        }
+    }
+
+    /// Tallies the results of a finished game, and broadcasts the final
+    /// shareholder bonuses and standings under the game's ID, revealing the
+    /// seed of the deal if there is one. The game is saved as a replay if
+    /// the host asked for replays.
+    fn broadcast_game_over(
+        &self,
+        game_over: Game<GameOver>,
+        start: &GameStart,
+        actions: &[TaggedPlayerAction],
+        substitutions: &[Substitution],
+        deal_seed: Option<DealSeed>,
+    ) -> Result<(), NoListeners> {
         let reason = game_over.reason().clone();
-        let results = game_over.tally_results();
+        // Should anyone be owed more than can be paid, nobody is paid, and
+        // the players are ranked by the money they hold
+        let standings = game_over.get_standings();
+        let results = game_over.tally_results().unwrap_or_else(|_| kernel::GameResults {
+            shareholder_results: CompanyMap::new(&None),
+            final_standings: standings.into_boxed_slice(),
+        });
 
         // Send messages for the final companies
-        results.shareholder_results.into_iter()
-            .filter_map(|(cmp, r)| r.map(|r| (cmp, r)))
-            .for_each(|(defunct, results)| {
-                self.broadcaster.send(ServerBroadcast::CompanyDefunct {
-                    defunct,
-                    results
-                }).unwrap();
-            });
+        let final_companies = results.shareholder_results.into_iter()
+            .filter_map(|(cmp, r)| r.map(|r| (cmp, r)));
+        for (defunct, results) in final_companies {
+            self.broadcast(ServerBroadcast::CompanyDefunct {
+                defunct,
+                results
+            })?;
+        }
+
+        if let Some(dir) = &self.replay_dir {
+            let replay = Replay {
+                version: Replay::VERSION,
+                game_id: start.game_id,
+                start: start.clone(),
+                deal_seed,
+                actions: actions.into(),
+                substitutions: substitutions.into(),
+                reason: reason.clone(),
+                results: results.final_standings.clone(),
+            };
+            // Games from before IDs were given are told apart by when they
+            // ended
+            let name = match start.game_id {
+                Some(game_id) => game_id.to_string(),
+                None => {
+                    let ended_at = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |since| since.as_secs());
+                    format!("game-{ended_at}")
+                },
+            };
+            let path = dir.join(name).with_extension(replay::EXTENSION);
+            replay.save(&path).ok();
+        }
 
         // Send the game over message
-        self.broadcaster.send(ServerBroadcast::GameOver { 
+        self.broadcast(ServerBroadcast::GameOver { 
             reason,
-            results: results.final_standings
-        }).unwrap();
+            results: results.final_standings,
+            deal_seed,
+            game_id: start.game_id,
+        })
+    }
+
+    /// Tells a player that the message they sent was invalid.
+    fn reject(&self, player_name: Box<str>, reason: InvalidMessageReason)
+        -> Result<(), NoListeners>
+    {
+        self.broadcast(ServerBroadcast::Private {
+            target_player: player_name,
+            message: PrivateBroadcast::Invalid { reason, request_id: None }
+        })
+    }
+
+    /// Sends a message to every connection. Fails if no connections remain to
+    /// receive it, which means that the server is shutting down.
+    fn broadcast(&self, message: ServerBroadcast) -> Result<(), NoListeners> {
+        self.broadcaster.send(message)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroUsize;
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use crate::game::messages::{PlayerAction, TaggedPlayerAction, TilePlacement, TilePlacementImplication};
+    use crate::game::{Company, Money};
+    use crate::server::capability::Capabilities;
+    use crate::server::clock::{TimeBank, TimeOutPenalty};
+    use crate::server::fanout::Broadcaster;
+    use crate::server::feature::Features;
+    use crate::server::{Handshake, NoListeners, ServerBroadcast, ServerOptions};
+
+    use super::ServerGame;
+
+    #[test]
+    fn start_fails_without_listeners() {
+        let mut game = ServerGame::new(Broadcaster::new(), &ServerOptions::default());
+        let started = game.start(Money::new(6000), ["alice".into(), "bob".into()], "alice".into());
+        assert!(matches!(started, Err(NoListeners)));
+        assert!(!game.in_progress());
+    }
+
+    #[test]
+    fn checkpoints_taken_at_interval() {
+        let broadcaster = Broadcaster::new();
+        let _subscriptions: Vec<_> = ["alice", "bob"].into_iter()
+            .map(|name| broadcaster.subscribe(&Handshake {
+                player_name: name.into(),
+                spectating: false,
+                capabilities: Capabilities::NONE,
+                features: Features::NONE,
+            }))
+            .collect();
+        let options = ServerOptions {
+            checkpoint_interval: NonZeroUsize::new(2),
+            ..Default::default()
+        };
+        let mut game = ServerGame::new(broadcaster, &options);
+        let tiles = [
+            "1-a", "12-i",
+            "1-b", "5-e", "6-f", "7-g", "8-h", "9-c",
+            "10-c", "11-e", "3-g", "5-a", "7-c", "9-g",
+            "3-e", "4-i",
+        ];
+        game.stack_tiles(tiles.iter().map(|tile| tile.parse().unwrap()).collect());
+        game.start(Money::new(6000), ["alice".into(), "bob".into()], "alice".into()).unwrap();
+
+        let play = |player_name: &str, tile: &str, implication| TaggedPlayerAction {
+            player_name: player_name.into(),
+            action: PlayerAction::PlayTile {
+                placement: TilePlacement { tile: tile.parse().unwrap(), implication },
+            },
+            number: None,
+        };
+        let buy = |player_name: &str| TaggedPlayerAction {
+            player_name: player_name.into(),
+            action: PlayerAction::BuyStock { stock: [None; 3] },
+            number: None,
+        };
+        let found = Some(TilePlacementImplication::FoundsCompany(Company::Continental));
+        let actions = [
+            play("alice", "1-b", found), buy("alice"),
+            play("bob", "10-c", None), buy("bob"),
+            play("alice", "5-e", None),
+        ];
+        for action in actions {
+            game.update(action, None).unwrap();
+        }
+
+        let history = game.history().unwrap();
+        assert_eq!(history.actions.len(), 5);
+        let taken: Vec<_> = history.checkpoints.iter().map(|checkpoint| checkpoint.after_actions).collect();
+        assert_eq!(taken, [2, 4]);
+
+        // Those who join are sent the game from the latest checkpoint
+        let public = game.public_history().unwrap();
+        assert_eq!(public.checkpoint.map(|checkpoint| checkpoint.after_actions), Some(4));
+        assert_eq!(public.actions.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn seat_played_after_missed_turns() {
+        let broadcaster = Broadcaster::new();
+        let mut subscription = broadcaster.subscribe(&Handshake {
+            player_name: "bob".into(),
+            spectating: false,
+            capabilities: Capabilities::NONE,
+            features: Features::NONE,
+        });
+        let options = ServerOptions {
+            time_bank: Some(TimeBank {
+                per_player: Duration::from_secs(60),
+                penalty: TimeOutPenalty::AutoMove,
+                afk_turns: NonZeroUsize::new(2),
+            }),
+            ..Default::default()
+        };
+        let mut game = ServerGame::new(broadcaster, &options);
+        let tiles = [
+            "1-a", "12-i",
+            "1-b", "5-e", "6-f", "7-g", "8-h", "9-c",
+            "10-c", "11-e", "3-g", "5-a", "7-c", "9-g",
+            "3-e", "4-i",
+        ];
+        game.stack_tiles(tiles.iter().map(|tile| tile.parse().unwrap()).collect());
+        game.start(Money::new(6000), ["alice".into(), "bob".into()], "alice".into()).unwrap();
+
+        // Both of alice's actions on her first turn time out, which misses
+        // only the one turn
+        game.time_out().unwrap();
+        game.time_out().unwrap();
+        for action in [
+            PlayerAction::PlayTile {
+                placement: TilePlacement { tile: "3-g".parse().unwrap(), implication: None },
+            },
+            PlayerAction::BuyStock { stock: [None; 3] },
+        ] {
+            game.update(TaggedPlayerAction { player_name: "bob".into(), action, number: None }, None).unwrap();
+        }
+
+        // Missing a second turn in a row has the server play her seat
+        game.time_out().unwrap();
+        let deadline = game.clock_deadline().unwrap();
+        assert!(deadline <= Instant::now());
+
+        drop(game);
+        let mut away = Vec::new();
+        while let Some(broadcast) = subscription.recv().await {
+            if let ServerBroadcast::Away { player_name, missed_turns } = broadcast {
+                away.push((player_name, missed_turns));
+            }
+        }
+        assert_eq!(away, [("alice".into(), 2)]);
     }
 }
 
@@ -874,7 +1901,7 @@ impl ServerGame {
 //     /// half of this channel will tell the game to cleanly end early.
 //     receiver: mpsc::Receiver<PlayerAction>,
 //     /// Emits messages to tell the players what to do.
-//     broadcaster: broadcast::Sender<ServerBroadcast>,
+//     broadcaster: Broadcaster,
 //     /// Records the number of stocks that are purchased by players for each company
 //     stock_bank: CompanyMap<u8>,
 //     /// The tiles that have not been drawn
@@ -0,0 +1,254 @@
+use crate::game::messages::ServerMessage;
+
+use super::{Handshake, ServerBroadcast};
+
+/// Who may be sent the hidden tiles a broadcast carries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Audience {
+    /// Anyone connected. The broadcast must carry no hidden tiles.
+    Everyone,
+    /// The one player the broadcast is addressed to.
+    Player(Box<str>),
+    /// Each player, who is sent only their own part of the broadcast.
+    EachPlayer,
+}
+
+impl Audience {
+    /// Finds who may be sent the hidden tiles of a broadcast. Every kind of
+    /// broadcast is listed by name, so none can be added without deciding who
+    /// it is for.
+    pub fn of(broadcast: &ServerBroadcast) -> Self {
+        match broadcast {
+            ServerBroadcast::Private { target_player, .. } => Audience::Player(target_player.clone()),
+            ServerBroadcast::GameStart { .. } => Audience::EachPlayer,
+            ServerBroadcast::Chat { .. }
+                | ServerBroadcast::StartingDraw { .. }
+                | ServerBroadcast::Join { .. }
+                | ServerBroadcast::Quit { .. }
+                | ServerBroadcast::Seated { .. }
+                | ServerBroadcast::Trusted { .. }
+                | ServerBroadcast::Renamed { .. }
+                | ServerBroadcast::Substituted { .. }
+                | ServerBroadcast::PlayerMove { .. }
+                | ServerBroadcast::DeadTile { .. }
+                | ServerBroadcast::CompanyDefunct { .. }
+                | ServerBroadcast::NoFounderShare { .. }
+                | ServerBroadcast::ResolvingNext { .. }
+                | ServerBroadcast::GameOver { .. }
+                | ServerBroadcast::Clock { .. }
+                | ServerBroadcast::EndProposed { .. }
+                | ServerBroadcast::EndVoted { .. }
+                | ServerBroadcast::EndRejected
+                | ServerBroadcast::Away { .. }
+                | ServerBroadcast::Autopilot { .. }
+                | ServerBroadcast::Queued { .. }
+                | ServerBroadcast::StartScheduled { .. }
+                | ServerBroadcast::KickVoted { .. }
+                | ServerBroadcast::KickFailed { .. }
+                | ServerBroadcast::ServerError { .. }
+                | ServerBroadcast::Shutdown => Audience::Everyone,
+        }
+    }
+}
+
+/// The hidden tiles carried by a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Secret {
+    None,
+    /// Tiles in the hand of whoever the message is for, such as a tile they
+    /// drew.
+    OwnHand,
+    /// Tiles hidden from every player, such as the boneyard.
+    Everything,
+}
+
+impl Secret {
+    pub fn of(message: &ServerMessage) -> Self {
+        match message {
+            ServerMessage::Resync { snapshot: Some(snapshot), .. } if snapshot.hidden.is_some() => {
+                Secret::Everything
+            },
+            ServerMessage::Resync { hand: Some(_), .. }
+                | ServerMessage::GameStart { initial_hand: Some(_), .. }
+                | ServerMessage::TileDraw { .. } => Secret::OwnHand,
+            ServerMessage::Resync { .. }
+                | ServerMessage::GameStart { .. }
+                | ServerMessage::Chat { .. }
+                | ServerMessage::StartingDraw { .. }
+                | ServerMessage::Join { .. }
+                | ServerMessage::Quit { .. }
+                | ServerMessage::Seated { .. }
+                | ServerMessage::Trusted { .. }
+                | ServerMessage::Renamed { .. }
+                | ServerMessage::Substituted { .. }
+                | ServerMessage::PlayerMove { .. }
+                | ServerMessage::DeadTile { .. }
+                | ServerMessage::CompanyDefunct { .. }
+                | ServerMessage::NoFounderShare { .. }
+                | ServerMessage::ResolvingNext { .. }
+                | ServerMessage::GameOver { .. }
+                | ServerMessage::Shutdown
+                | ServerMessage::YourTurn { .. }
+                | ServerMessage::Invalid { .. }
+                | ServerMessage::Clock { .. }
+                | ServerMessage::ActionLog { .. }
+                | ServerMessage::EndProposed { .. }
+                | ServerMessage::EndVoted { .. }
+                | ServerMessage::EndRejected
+                | ServerMessage::Away { .. }
+                | ServerMessage::Autopilot { .. }
+                | ServerMessage::Queued { .. }
+                | ServerMessage::StartScheduled { .. }
+                | ServerMessage::KickVoted { .. }
+                | ServerMessage::KickFailed { .. }
+                | ServerMessage::Kicked
+                | ServerMessage::ServerError { .. }
+                | ServerMessage::Pong { .. } => Secret::None,
+        }
+    }
+}
+
+/// A message that would have shown a connection tiles hidden from it.
+#[derive(Debug, thiserror::Error)]
+#[error("a message meant for {audience:?} would have shown {recipient} hidden tiles")]
+pub struct Leak {
+    pub recipient: Box<str>,
+    pub audience: Audience,
+}
+
+/// Checks that `message`, made from a broadcast meant for `audience`, shows
+/// `recipient` no tiles but their own.
+pub fn check(recipient: &Handshake, audience: &Audience, message: &ServerMessage)
+    -> Result<(), Leak>
+{
+    let allowed = match Secret::of(message) {
+        Secret::None => true,
+        Secret::OwnHand => match audience {
+            Audience::Everyone => false,
+            Audience::Player(target) => *target == recipient.player_name,
+            Audience::EachPlayer => true,
+        },
+        Secret::Everything => false,
+    };
+
+    match allowed {
+        true => Ok(()),
+        false => Err(Leak {
+            recipient: recipient.player_name.clone(),
+            audience: audience.clone(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::game::kernel::{Ambiguous, Game, GameSnapshot, HiddenTiles};
+    use crate::game::messages::{GameStart, ServerMessage};
+    use crate::game::tile::{Boneyard, FullHand, Hand, Tile};
+    use crate::game::Money;
+    use crate::server::fanout::Broadcaster;
+    use crate::server::capability::Capabilities;
+    use crate::server::feature::Features;
+    use crate::server::{personalize, Handshake, PrivateBroadcast, ServerBroadcast};
+
+    use super::{check, Audience, Secret};
+
+    fn handshake(name: &str, spectating: bool) -> Handshake {
+        Handshake {
+            player_name: name.into(),
+            spectating,
+            capabilities: Capabilities::NONE,
+            features: Features::NONE,
+        }
+    }
+
+    /// Deals a hand of the first six tiles in a row.
+    fn hand(row: u8) -> FullHand {
+        let mut boneyard = Boneyard::stacked(('a'..='f').map(|col| Tile::new(row, col)).collect());
+        Hand::from_boneyard(&mut boneyard).unwrap()
+    }
+
+    fn snapshot(start: &GameStart) -> GameSnapshot {
+        Game::<Ambiguous>::from(Game::start(start)).snapshot()
+    }
+
+    /// Sends the broadcasts that carry hidden tiles through the same queues
+    /// and conversion as the server, and checks that only the players they
+    /// belong to ever receive them.
+    #[tokio::test]
+    async fn hidden_tiles_reach_only_their_owners() {
+        let start = GameStart {
+            starting_cash: Money::new(6000),
+            play_order: ["alice".into(), "bob".into()].into(),
+            tiles_placed: [Tile::new(1, 'a'), Tile::new(12, 'i')].into(),
+            tie_rule: Default::default(),
+            dead_tiles_per_turn: None,
+            deal_commitment: None,
+            random_seating: false,
+            policy: Default::default(),
+            game_id: None,
+        };
+        let broadcasts = [
+            ServerBroadcast::GameStart {
+                info: start.clone(),
+                initial_hands: HashMap::from([("alice".into(), hand(3)), ("bob".into(), hand(5))]),
+            },
+            ServerBroadcast::Private {
+                target_player: "alice".into(),
+                message: PrivateBroadcast::TileDraw { tile: Tile::new(9, 'a') },
+            },
+            ServerBroadcast::Private {
+                target_player: "bob".into(),
+                message: PrivateBroadcast::Resync {
+                    snapshot: Some(Box::new(snapshot(&start))),
+                    hand: Some(hand(5).into()),
+                    clock: None,
+                },
+            },
+            ServerBroadcast::EndRejected,
+        ];
+
+        let broadcaster = Broadcaster::new();
+        let connections = [handshake("alice", false), handshake("bob", false), handshake("eve", true)];
+        let mut subscriptions: Vec<_> = connections.iter()
+            .map(|handshake| broadcaster.subscribe(handshake))
+            .collect();
+        for broadcast in broadcasts {
+            broadcaster.send(broadcast).unwrap();
+        }
+        broadcaster.send(ServerBroadcast::Shutdown).unwrap();
+
+        let mut secrets = HashMap::new();
+        for (handshake, subscription) in connections.iter().zip(&mut subscriptions) {
+            let mut received = 0;
+            while let Some(broadcast) = subscription.recv().await {
+                let audience = Audience::of(&broadcast);
+                let message = personalize(broadcast, handshake);
+                check(handshake, &audience, &message).unwrap();
+                if Secret::of(&message) != Secret::None {
+                    received += 1;
+                }
+                if matches!(message, ServerMessage::Shutdown) { break; }
+            }
+            secrets.insert(&*handshake.player_name, received);
+        }
+        assert_eq!(secrets, HashMap::from([("alice", 2), ("bob", 2), ("eve", 0)]));
+
+        // A message queued for the wrong connection is caught
+        let draw = ServerMessage::TileDraw { tile: Tile::new(9, 'a') };
+        let for_alice = Audience::Player("alice".into());
+        assert!(check(&connections[1], &for_alice, &draw).is_err());
+        assert!(check(&connections[0], &Audience::Everyone, &draw).is_err());
+
+        // As is a snapshot that still holds every hand
+        let mut snapshot = snapshot(&start);
+        snapshot.hidden = Some(HiddenTiles {
+            boneyard: Boneyard::stacked(vec![]),
+            hands: HashMap::new(),
+        });
+        let resync = ServerMessage::Resync { snapshot: Some(Box::new(snapshot)), hand: None, clock: None };
+        assert!(check(&connections[0], &for_alice, &resync).is_err());
+    }
+}
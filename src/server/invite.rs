@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use rand::seq::SliceRandom;
+use serde::{Serialize, Deserialize};
+use tokio::time::Instant;
+
+/// The letters a code is made up of when one is generated. Those easily
+/// mistaken for a digit, I and O, are left out, as codes are read out loud.
+const ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
+/// The length of a generated code.
+const GENERATED_LEN: usize = 4;
+/// The longest code that is accepted, in characters.
+const MAX_LEN: usize = 16;
+/// The wrong codes an address may give before it must wait to try again.
+const FREE_ATTEMPTS: u32 = 3;
+/// How long an address waits after the first wrong code past the free ones.
+/// The wait doubles with each wrong code after that.
+const FIRST_WAIT: Duration = Duration::from_secs(2);
+/// The longest an address is made to wait.
+const LONGEST_WAIT: Duration = Duration::from_secs(10 * 60);
+/// How long after its wait is over an address's wrong codes are forgotten.
+const FORGET_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// Reasons a typed invite code could not be used.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum InviteCodeError {
+    #[error("an invite code must be between 1 and {MAX_LEN} characters long")]
+    Length,
+    #[error("an invite code is made up of letters and digits only")]
+    Characters,
+}
+
+/// A short code the host hands out to those invited to a game, which must be
+/// given to join it. Codes are matched regardless of case.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Box<str>", into = "Box<str>")]
+pub struct InviteCode(Box<str>);
+
+impl InviteCode {
+    /// Makes up a code at random.
+    pub fn generate() -> Self {
+        let mut rng = rand::thread_rng();
+        let code: String = (0..GENERATED_LEN)
+            .map(|_| *ALPHABET.choose(&mut rng).unwrap() as char)
+            .collect();
+        Self(code.into())
+    }
+}
+
+impl From<Box<str>> for InviteCode {
+    fn from(code: Box<str>) -> Self {
+        Self(code.to_ascii_uppercase().into())
+    }
+}
+
+impl From<InviteCode> for Box<str> {
+    fn from(code: InviteCode) -> Self {
+        code.0
+    }
+}
+
+impl FromStr for InviteCode {
+    type Err = InviteCodeError;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let code = code.trim();
+        if !(1..=MAX_LEN).contains(&code.chars().count()) {
+            return Err(InviteCodeError::Length);
+        }
+        if !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(InviteCodeError::Characters);
+        }
+        Ok(Box::<str>::from(code).into())
+    }
+}
+
+impl fmt::Display for InviteCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The wrong codes given from each address. A generated code is short enough
+/// to be guessed by trying one after another, so once an address has given a
+/// few wrong codes, it must wait longer and longer before each next try.
+#[derive(Debug, Default)]
+pub struct Attempts {
+    /// The number of wrong codes given from each address, and when it may try
+    /// again.
+    failures: HashMap<IpAddr, (u32, Instant)>,
+}
+
+impl Attempts {
+    /// Checks whether `address` may try a code at `now`.
+    pub fn may_try(&self, address: IpAddr, now: Instant) -> bool {
+        self.failures.get(&address).is_none_or(|&(_, until)| now >= until)
+    }
+
+    /// Counts a wrong code given from `address` at `now`.
+    pub fn failed(&mut self, address: IpAddr, now: Instant) {
+        self.failures.retain(|_, &mut (_, until)| now < until + FORGET_AFTER);
+        let (count, until) = self.failures.entry(address).or_insert((0, now));
+        *count += 1;
+        *until = now + match count.checked_sub(FREE_ATTEMPTS + 1) {
+            Some(doublings) => FIRST_WAIT.saturating_mul(1 << doublings.min(16)).min(LONGEST_WAIT),
+            None => Duration::ZERO,
+        };
+    }
+
+    /// Forgets the wrong codes given from `address`, once it gave the right
+    /// one.
+    pub fn succeeded(&mut self, address: IpAddr) {
+        self.failures.remove(&address);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::{Attempts, InviteCode, FIRST_WAIT, FREE_ATTEMPTS};
+
+    #[test]
+    fn matched_regardless_of_case() {
+        let code: InviteCode = "abcd".parse().unwrap();
+        assert_eq!(code, "ABCD".parse().unwrap());
+        assert_eq!(code.to_string(), "ABCD");
+        assert_eq!(serde_json::from_str::<InviteCode>("\"aBcD\"").unwrap(), code);
+
+        assert!("AB CD".parse::<InviteCode>().is_err());
+        assert!("".parse::<InviteCode>().is_err());
+        assert_eq!(InviteCode::generate().to_string().len(), 4);
+    }
+
+    #[test]
+    fn guessing_backs_off() {
+        let guesser = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let other = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let now = Instant::now();
+        let mut attempts = Attempts::default();
+        for _ in 0..FREE_ATTEMPTS {
+            assert!(attempts.may_try(guesser, now));
+            attempts.failed(guesser, now);
+        }
+        attempts.failed(guesser, now);
+        assert!(!attempts.may_try(guesser, now));
+        assert!(attempts.may_try(other, now));
+
+        // Each wrong code past the free ones doubles the wait
+        let now = now + FIRST_WAIT;
+        assert!(attempts.may_try(guesser, now));
+        attempts.failed(guesser, now);
+        assert!(!attempts.may_try(guesser, now + FIRST_WAIT));
+        assert!(attempts.may_try(guesser, now + FIRST_WAIT * 2));
+
+        attempts.succeeded(guesser);
+        assert!(attempts.may_try(guesser, now + Duration::from_millis(1)));
+    }
+}
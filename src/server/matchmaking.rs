@@ -0,0 +1,79 @@
+/// The players waiting for a game to be made up for them, in the order they
+/// joined the queue. Once as many are waiting as a table seats, the first of
+/// them are taken to play.
+#[derive(Debug, Clone)]
+pub struct Queue {
+    table_size: usize,
+    waiting: Vec<Box<str>>,
+}
+
+impl Queue {
+    /// Creates an empty queue that fills tables of `table_size` players.
+    pub fn new(table_size: usize) -> Self {
+        Self { table_size, waiting: Vec::new() }
+    }
+
+    /// The number of players seated at each table.
+    pub fn table_size(&self) -> usize {
+        self.table_size
+    }
+
+    /// The number of players waiting.
+    pub fn waiting(&self) -> usize {
+        self.waiting.len()
+    }
+
+    /// Puts a player at the back of the queue. Returns `false` if they were
+    /// already waiting, in which case they keep their place.
+    pub fn join(&mut self, player_name: &str) -> bool {
+        if self.waiting.iter().any(|name| **name == *player_name) {
+            return false;
+        }
+        self.waiting.push(player_name.into());
+        true
+    }
+
+    /// Takes a player out of the queue. Returns `false` if they weren't
+    /// waiting.
+    pub fn leave(&mut self, player_name: &str) -> bool {
+        let before = self.waiting.len();
+        self.waiting.retain(|name| **name != *player_name);
+        self.waiting.len() != before
+    }
+
+    /// Keeps the place of a player who has changed names.
+    pub fn rename(&mut self, old_name: &str, new_name: Box<str>) {
+        if let Some(name) = self.waiting.iter_mut().find(|name| ***name == *old_name) {
+            *name = new_name;
+        }
+    }
+
+    /// Takes the players for a table from the front of the queue, if enough
+    /// are waiting.
+    pub fn take_table(&mut self) -> Option<Vec<Box<str>>> {
+        (self.waiting.len() >= self.table_size)
+            .then(|| self.waiting.drain(..self.table_size).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Queue;
+
+    #[test]
+    fn fills_tables_in_order() {
+        let mut queue = Queue::new(2);
+        assert!(queue.join("alice"));
+        assert!(!queue.join("alice"));
+        assert_eq!(queue.take_table(), None);
+
+        assert!(queue.join("bob"));
+        assert!(queue.join("carol"));
+        queue.rename("alice", "alicia".into());
+        assert!(queue.leave("bob"));
+        assert!(!queue.leave("bob"));
+
+        assert_eq!(queue.take_table(), Some(vec!["alicia".into(), "carol".into()]));
+        assert_eq!(queue.waiting(), 0);
+    }
+}
@@ -1,16 +1,79 @@
+use std::any::Any;
 use std::borrow::Borrow;
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use serde::{Serialize, Deserialize, Serializer};
-use tokio::sync::{mpsc, broadcast, Notify};
-
-use crate::game::tile::{Tile, FullHand};
-use crate::game::{messages::*, Company};
-
+use tokio::sync::{mpsc, oneshot, watch, Notify};
+
+use crate::game::tile::{Tile, FullHand, Hand};
+use crate::game::{messages::*, Company, Money};
+use crate::game::deal::DealSeed;
+use crate::game::kernel::GameSnapshot;
+use crate::game::game_id::GameId;
+use crate::game::policy::RulePolicy;
+use crate::net::protocol::MAX_NAME_LEN;
+
+use self::capability::{Capabilities, Capability};
+use self::feature::{Feature, Features};
+use self::fanout::{Broadcaster, Subscription};
 use self::game::ServerGame;
-
+use self::resume::{ResumeToken, ResumeTokens};
+use self::votekick::{KickTally, KickVotes};
+
+/// Declares the append-only log of the actions accepted in a game.
+pub mod action_log;
+/// Declares the checks that every tile of a game is accounted for.
+mod audit;
+/// Declares the saves of the game in progress written as it is played.
+pub mod autosave;
+/// Declares the capabilities that make up what an admin may do.
+pub mod capability;
+/// Declares the time banks that limit how long players may take to move.
+pub mod clock;
+/// Declares the features a client tells the server it can handle.
+pub mod feature;
+/// Declares the console through which the host gives commands that reach past
+/// the game, which no remote admin can.
+pub mod console;
+/// Declares the codes that must be given to join a game by invitation.
+pub mod invite;
+/// Declares the queue from which games are made up without an admin.
+pub mod matchmaking;
+/// Declares the tokens by which a player takes back their seat after a
+/// dropped connection.
+pub mod resume;
+/// Declares the countdown to a game scheduled to start at a set time.
+pub mod schedule;
+/// Declares the votes by which the players decide matters among themselves.
+pub mod vote;
+/// Declares the votes by which the players kick someone out without an admin.
+pub mod votekick;
+/// Declares the queues through which broadcasts reach each connection.
+pub mod fanout;
+/// Declares how quickly the steps of a cascade reach each connection.
+pub mod pacing;
 mod game;
+/// Declares the typed stream of events through which tools in the same
+/// process can follow a server.
+#[cfg(feature = "observer")]
+pub mod observer;
+/// Declares the notifier that posts summaries of each game to a webhook.
+pub mod webhook;
+/// Declares the submission of each game's result to a tracker.
+mod tracker;
+/// Declares the guard that keeps each player's tiles from reaching anyone
+/// else.
+pub mod guard;
+/// Declares the harness that plays scripted scenarios against a [`Server`].
+#[cfg(test)]
+mod scenario;
 
 /// Spawns the tasks that manage the server. This function returns the channels
 /// that the host will use to interface with the game. Closing either the sender
@@ -26,18 +89,139 @@ mod game;
 /// the server is closed, the returned receiver will close.
 // pub fn run() -> Server
 
+/// The number of actions between the checkpoints of a game when the host
+/// chooses no other. Those joining a game are sent about this many actions at
+/// most to play through.
+pub const DEFAULT_CHECKPOINT_INTERVAL: NonZeroUsize = NonZeroUsize::new(32).unwrap();
+
+/// Settings chosen by the host when starting a server.
+#[derive(Debug, Clone, Default)]
+pub struct ServerOptions {
+    /// See [`ConnectionManager::new`].
+    pub max_players: Option<usize>,
+    /// See [`ConnectionManager::new`].
+    pub max_connections: Option<usize>,
+    /// If set, an action a player sends before the game is ready for it, such
+    /// as a purchase of stock sent while a merge is still being resolved, is
+    /// held and applied once the game asks the player for it, rather than
+    /// rejected as out of turn.
+    pub queue_early_actions: bool,
+    /// If set, each player is given a time bank for the whole game, which
+    /// runs down whenever the game is waiting on them.
+    pub time_bank: Option<clock::TimeBank>,
+    /// The share of the players that must agree to end a game early, without
+    /// the admin.
+    pub end_vote_majority: vote::Majority,
+    /// If set, the tiles are shuffled from a [`DealSeed`] whose hash is sent
+    /// with the start of the game, and the seed itself with its end.
+    pub commit_deal: bool,
+    /// If set, the order of play is drawn at random, rather than decided by
+    /// the tiles the players draw.
+    pub random_seating: bool,
+    /// The name the server goes by. If not set, the server is named after the
+    /// host.
+    pub name: Option<Box<str>>,
+    /// A message of the day shown to each player as they join.
+    pub motd: Option<Box<str>>,
+    /// If set, only those who give this code may join over the network.
+    pub invite_code: Option<invite::InviteCode>,
+    /// If set, players may queue for a game, which is started with them
+    /// seated, and with no admin needed, once this many are waiting.
+    pub matchmaking: Option<usize>,
+    /// If set, the start and end of each game, and each merger in it, are
+    /// posted to this URL.
+    pub webhook: Option<webhook::WebhookUrl>,
+    /// If set, the final standings of each game are submitted to this URL
+    /// under the game's ID, as for a community leaderboard.
+    pub results_url: Option<webhook::WebhookUrl>,
+    /// The number of actions between the snapshots kept in the history of each
+    /// game. If not set, one is taken every [`DEFAULT_CHECKPOINT_INTERVAL`]
+    /// actions.
+    pub checkpoint_interval: Option<NonZeroUsize>,
+    /// If set, the game in progress is saved to disk as it is played.
+    pub autosave: Option<autosave::AutosaveOptions>,
+    /// If set, each game is saved to this directory as a replay once it is
+    /// over.
+    pub replay_dir: Option<PathBuf>,
+    /// How quickly the steps of a cascade, such as a merger, reach each
+    /// connection.
+    pub pacing: pacing::Pacing,
+    /// What the players of each game may lean on besides their own play.
+    pub policy: RulePolicy,
+}
+
 /// Copyable handle to a running server.
 #[derive(Debug, Clone)]
 pub struct Server {
-    /// Map of all the connections to the server.
-    connections: Arc<Mutex<ConnectionManager>>,
-    game: Arc<Mutex<ServerGame>>,
     /// Broadcaster that distributes messages from the server to where they need
     /// to go.
-    broadcaster: broadcast::Sender<ServerBroadcast>,
-    /// Channel used to send messages from the players to the server to be
-    /// processed.
-    client_sender: mpsc::Sender<TaggedClientMessage>,
+    broadcaster: Broadcaster,
+    /// Channel used to send commands to the task that owns the state of the
+    /// server.
+    commands: mpsc::Sender<ServerCommand>,
+    /// Becomes true once the task that owns the state of the server has begun
+    /// processing commands.
+    ready: watch::Receiver<bool>,
+    /// The code that must be given to join, if any.
+    invite_code: Option<invite::InviteCode>,
+    /// The wrong codes given from each address.
+    invite_attempts: Arc<Mutex<invite::Attempts>>,
+    /// How quickly the steps of a cascade reach each connection.
+    pacing: pacing::Pacing,
+}
+
+/// Commands processed, one at a time, by the task that owns the connections
+/// and the game.
+#[derive(Debug)]
+enum ServerCommand {
+    /// Connects a player, replying with the state of the server and the
+    /// receiver through which the player will hear everything that happens
+    /// after that state.
+    Connect {
+        handshake: Handshake,
+        name: SharedName,
+        resume_token: Option<ResumeToken>,
+        reply: oneshot::Sender<Result<
+            (ServerState, Subscription),
+            ConnectionReject
+        >>,
+    },
+    /// Disconnects a player, announcing that they quit.
+    Disconnect {
+        handshake: Handshake,
+    },
+    /// Processes a message sent by a player.
+    Message(TaggedClientMessage),
+    /// Runs a command given at the host's console, replying with what it did.
+    Console {
+        command: console::ConsoleCommand,
+        reply: oneshot::Sender<Result<Box<str>, console::ConsoleError>>,
+    },
+    /// See [`Server::stack_tiles`].
+    #[cfg(test)]
+    StackTiles(Vec<Tile>),
+    /// See [`Server::fail_next_action`].
+    #[cfg(test)]
+    FailNextAction,
+}
+
+/// The name a connection goes by, shared by the tasks that serve it, so that
+/// a rename takes effect for all of them at once.
+#[derive(Debug, Clone)]
+struct SharedName(Arc<Mutex<Box<str>>>);
+
+impl SharedName {
+    fn new(name: Box<str>) -> Self {
+        Self(Arc::new(Mutex::new(name)))
+    }
+
+    fn get(&self) -> Box<str> {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn set(&self, name: Box<str>) {
+        *self.0.lock().unwrap() = name;
+    }
 }
 
 impl Server {
@@ -45,46 +229,73 @@ impl Server {
     /// handle to the server. Second, the interface used by the host. If the
     /// host interface is closed, the server will gracefully shut down.
     pub fn start(
-        max_players: Option<usize>,
-        max_connections: Option<usize>,
+        options: ServerOptions,
         host_handshake: Handshake,
     ) -> (Self, NewConnection<std::convert::Infallible>) {
 
-        let (broadcaster, _) = broadcast::channel(16);
+        let broadcaster = Broadcaster::new();
 
         let mut connection_manager = ConnectionManager::new(
-            max_players,
-            max_connections
+            options.max_players,
+            options.max_connections
         );
 
         // Register the host as a player
         connection_manager.connect(host_handshake.clone()).unwrap();
 
         let interface_cm = connection_manager.clone();
+        let host_receiver = broadcaster.subscribe(&host_handshake);
 
-        let connections = Arc::new(Mutex::new(connection_manager));
-        let game = Arc::new(Mutex::new(ServerGame::new(broadcaster.clone())));
+        let name = options.name.clone().unwrap_or_else(|| host_handshake.player_name.clone());
+        let host_info = ServerInfo {
+            invite_code: options.invite_code.clone(),
+            ..ServerInfo::new(&name, options.motd.as_deref(), &connection_manager, false)
+        };
+        let host_name = SharedName::new(host_handshake.player_name.clone());
 
-        let client_sender = Self::listen_for_requests(
+        let mut game = ServerGame::new(broadcaster.clone(), &options);
+        game.set_policy(options.policy);
+        if let Some(dir) = options.replay_dir {
+            game.record_replays(dir);
+        }
+        if let Some(table_size) = options.matchmaking {
+            game.make_matches(table_size);
+        }
+
+        let (commands, ready) = Self::spawn_state_task(
             broadcaster.clone(),
-            connections.clone(),
-            game.clone(),
+            connection_manager,
+            HashMap::from([(host_handshake.player_name.clone(), host_name.clone())]),
+            name,
+            options.motd,
+            game,
+            options.autosave.map(autosave::Autosaver::new),
         );
 
+        if let Some(url) = options.webhook {
+            // The control character keeps it clear of every player's name
+            let subscription = broadcaster.subscribe_sink("\0webhook".into(), webhook::wants);
+            tokio::spawn(webhook::notify(url, subscription));
+        }
+        if let Some(url) = options.results_url {
+            let subscription = broadcaster.subscribe_sink("\0tracker".into(), tracker::wants);
+            tokio::spawn(tracker::submit_results(url, subscription));
+        }
+
         let server = Self {
-            connections,
-            game,
             broadcaster,
-            client_sender,
+            commands,
+            ready,
+            invite_code: options.invite_code,
+            invite_attempts: Default::default(),
+            pacing: options.pacing,
         };    
 
         // Create the host interface
         let shutdown = Arc::new(Notify::new());
-        let host_sender = server.player_to_server(
-            host_handshake.clone(), shutdown.clone()
-        );
+        let host_sender = server.player_to_server(host_name, shutdown.clone());
         let host_recv = server.server_to_player(
-            host_handshake.clone(), shutdown.clone()
+            host_handshake.clone(), host_receiver, shutdown.clone()
         );
 
         // Broadcast a shutdown if the host quits
@@ -98,7 +309,9 @@ impl Server {
             handshake: host_handshake,
             server_state: ServerState {
                 game_history: None,
+                hand: None,
                 connections: interface_cm,
+                info: Some(host_info),
             },
             interface: Interface::new(host_sender, host_recv),
         };
@@ -106,9 +319,45 @@ impl Server {
         (server, host_connection)
     }
 
+    /// Waits until the server has begun processing commands, such that a
+    /// client started after this returns is answered without delay. Returns
+    /// straight away if the server has already shut down.
+    pub async fn ready(&self) {
+        let mut ready = self.ready.clone();
+        while !*ready.borrow_and_update() {
+            if ready.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Whether those who join must give the server's invite code.
+    pub fn needs_invite(&self) -> bool {
+        self.invite_code.is_some()
+    }
+
+    /// Checks that `code`, given from `address`, is the one needed to join,
+    /// if the server needs any. An address that has given too many wrong
+    /// codes is refused for a while, whatever code it gives.
+    pub fn check_invite(&self, address: IpAddr, code: Option<&invite::InviteCode>) -> Result<(), ConnectionReject> {
+        let Some(needed) = &self.invite_code else { return Ok(()) };
+        let mut attempts = self.invite_attempts.lock().unwrap();
+        let now = tokio::time::Instant::now();
+        if !attempts.may_try(address, now) {
+            return Err(ConnectionReject::TooManyInviteAttempts);
+        }
+        if code != Some(needed) {
+            attempts.failed(address, now);
+            return Err(ConnectionReject::WrongInviteCode);
+        }
+        attempts.succeeded(address);
+        Ok(())
+    }
+
     /// Connects a player to the server, starting a process that transfer
-    /// messages between the player and the server. Returns [`None`] if the
-    /// player was not connected because the passed name was taken. 
+    /// messages between the player and the server. Returns an error if the
+    /// player was not connected, for example because the passed name was
+    /// taken.
     /// 
     /// # Disconnecting
     /// 
@@ -118,70 +367,106 @@ impl Server {
     /// gives the server time to process the disconnection before the client
     /// exits. It is advised that the player not drop or close the returned
     /// receiver until it automatically closes.
-    pub fn connect_player(&self, handshake: Handshake)
+    pub async fn connect_player(&self, handshake: Handshake)
         -> Result<NewConnection<std::convert::Infallible>, ConnectionReject>
     {
-        let mut connections_unlocked = self.connections.lock().unwrap();
-
-        // Validate the connection
-        connections_unlocked.connect(handshake.clone())?;
+        self.resume_player(handshake, None).await
+    }
 
-        // Broadcast a join message
-        self.broadcaster.send(ServerBroadcast::Join {
-            handshake: handshake.clone()
-        }).unwrap();
+    /// Connects a player as [`connect_player`] does, giving the token of the
+    /// client they join from, if it has one. A seat in the game in progress
+    /// held by a player who gave a token is only given back for the same
+    /// token.
+    ///
+    /// [`connect_player`]: Server::connect_player
+    pub async fn resume_player(&self, handshake: Handshake, resume_token: Option<ResumeToken>)
+        -> Result<NewConnection<std::convert::Infallible>, ConnectionReject>
+    {
+        let (reply, response) = oneshot::channel();
+        let name = SharedName::new(handshake.player_name.clone());
+        self.commands.send(ServerCommand::Connect {
+            handshake: handshake.clone(),
+            name: name.clone(),
+            resume_token,
+            reply,
+        }).await.map_err(|_| ConnectionReject::ShuttingDown)?;
+        let (server_state, broadcast_receiver) = response.await
+            .map_err(|_| ConnectionReject::ShuttingDown)??;
 
         let shutdown = Arc::new(Notify::new());
 
         // Send messages from the client to the server
-        let client_send = self.player_to_server(handshake.clone(), shutdown.clone());
+        let client_send = self.player_to_server(name.clone(), shutdown.clone());
 
         // Send messages from the server to the client
-        let client_recv = self.server_to_player(handshake.clone(), shutdown.clone());
+        let client_recv = self.server_to_player(
+            handshake.clone(), broadcast_receiver, shutdown.clone()
+        );
 
-        let broadcaster = self.broadcaster.clone();
-        let shutdown = shutdown.clone();
-        let connections = self.connections.clone();
+        let commands = self.commands.clone();
         let clone = handshake.clone();
-        let name = handshake.player_name.clone();
-        let disconnect_message = ServerBroadcast::Quit {
-            handshake: clone
-        };
 
         tokio::spawn(async move {
 
             // Task triggered on disconnect
             shutdown.notified().await;
 
-            // Disconnect the player
-            connections.lock().unwrap().disconnect(&name);
-
-            // Send a disconnect message. Ignore any SendErrors, as an error
-            // means that this is the last player to leave and the server will
+            // Disconnect the player, under whatever name they go by now.
+            // Ignore any errors, as an error means that the server has already
             // shut down.
-            broadcaster.send(disconnect_message).ok();
+            let handshake = Handshake { player_name: name.get(), ..clone };
+            commands.send(ServerCommand::Disconnect { handshake }).await.ok();
         });
 
         Ok(NewConnection {
             handshake,
-            server_state: ServerState {
-                game_history: self.game.lock().unwrap().history(),
-                connections: connections_unlocked.clone(),
-            },
+            server_state,
             interface: Interface::new(client_send, client_recv),
         })
     }
 
+    /// Subscribes to the events of the server from now on, as a spectator
+    /// would see them, without connecting as one.
+    #[cfg(feature = "observer")]
+    pub fn events(&self) -> observer::EventStream {
+        observer::EventStream::new(&self.broadcaster)
+    }
+
+    /// Gets a console through which the host can give commands that no
+    /// remote admin can, such as saving the game to a file.
+    pub fn console(&self) -> console::Console {
+        console::Console::new(self.commands.clone())
+    }
+
+    /// Gets how far behind each connection is in reading the messages sent to
+    /// it.
+    pub fn lag(&self) -> HashMap<Box<str>, fanout::LagMetrics> {
+        self.broadcaster.lag()
+    }
+
+    /// Deals the tiles of the next game in the order given, rather than at
+    /// random.
+    #[cfg(test)]
+    pub(crate) async fn stack_tiles(&self, tiles: Vec<Tile>) {
+        self.commands.send(ServerCommand::StackTiles(tiles)).await.unwrap();
+    }
+
+    /// Panics on the next action a player sends, to test recovering from it.
+    #[cfg(test)]
+    pub(crate) async fn fail_next_action(&self) {
+        self.commands.send(ServerCommand::FailNextAction).await.unwrap();
+    }
+
     /// Starts one half of a player connection: forwards messages from the
     /// player to the server to be processed. Returns a sender to be part of an
     /// [`Interface`], as well as a shutdown listener to be used internally.
     fn player_to_server(&self,
-        handshake: Handshake,
+        name: SharedName,
         shutdown: Arc<Notify>
     ) -> mpsc::Sender<ClientMessage> {
 
         // Clone necessary server parts
-        let player_server_send = self.client_sender.clone();
+        let player_server_send = self.commands.clone();
 
         // Create the channels for this process
         let (client_send, mut client_player_recv) = mpsc::channel(1);
@@ -197,11 +482,15 @@ impl Server {
                     },
                 };
                 if let Some(msg) = msg {
-                    player_server_send.send(TaggedClientMessage {
-                        player_name: handshake.player_name.clone(),
-                        kind: msg,
-                    // Unwrap is ok because the server owns this receiving end
-                    }).await.unwrap();
+                    let sent = player_server_send.send(ServerCommand::Message(
+                        TaggedClientMessage {
+                            player_name: name.get(),
+                            kind: msg,
+                        }
+                    )).await;
+
+                    // The server has shut down
+                    if sent.is_err() { break; }
                 } else {
                     dbg!("receiver close");
                     break;
@@ -216,30 +505,29 @@ impl Server {
     }
 
     /// Starts one half of a player connection: provides messages from the
-    /// server to the player. Returns a receiver to be part of an [`Interface`],
-    /// and accepts a shutdown listener. The spawned task will notify the
+    /// server, received through `broadcast_receiver`, to the player. Returns a
+    /// receiver to be part of an [`Interface`], and accepts a shutdown
+    /// listener. The spawned task will notify the
     /// shutdown object if the receiver is closed or if it receives a shutdown
     /// message, and the task will shut down if it receives a notification.
     fn server_to_player(&self,
-        handshake: Handshake,
+        mut handshake: Handshake,
+        mut broadcast_receiver: Subscription,
         shutdown: Arc<Notify>,
     ) -> mpsc::Receiver<Result<ServerMessage, std::convert::Infallible>> {
-        let mut broadcast_receiver = self.broadcaster.subscribe();
 
         // Receiver to be sent to the client
         let (player_client_send, client_recv) = mpsc::channel(1);
+        let pacing = self.pacing;
 
         tokio::spawn(async move {
             loop {
                 let broadcast = tokio::select! {
                     msg_r = broadcast_receiver.recv() => match msg_r {
-                        Ok(msg) => msg,
-                        Err(why) => match why {
-                            broadcast::error::RecvError::Closed => {
-                                dbg!("close");
-                                break
-                            },
-                            broadcast::error::RecvError::Lagged(_) => todo!(),
+                        Some(msg) => msg,
+                        None => {
+                            dbg!("close");
+                            break
                         },
                     },
                     _ = shutdown.notified() => {
@@ -248,95 +536,49 @@ impl Server {
                     }
                 };
                 
-                let result = match broadcast {
-
-                    // Handle the new tile of buying stock
-                    ServerBroadcast::PlayerMove { action } => {
-                        player_client_send.send(
-                            Ok(ServerMessage::PlayerMove { action })
-                        ).await
-                    }
-
-                    // Only forward the private message if it pertains to the
-                    // player.
-                    ServerBroadcast::Private {
-                        target_player,
-                        message
-                    } => {
-                        if &target_player == &handshake.player_name {
-                            let msg = match message {
-                                PrivateBroadcast::YourTurn { request } => {
-                                    ServerMessage::YourTurn { request }
-                                },
-                                PrivateBroadcast::TileDraw { tile } => {
-                                    ServerMessage::TileDraw { tile }
-                                }
-                                PrivateBroadcast::Invalid { reason } => {
-                                    ServerMessage::Invalid { reason }
-                                },
-                            };
-                            player_client_send.send(Ok(msg)).await
-                        } else {
-                            Ok(())
-                        }
-                    },
-
-                    // If it receives a shutdown message, forward the message and exit
-                    ServerBroadcast::Shutdown => {
-                        // Ignore the send error, as we're shutting down anyway.
-                        player_client_send.send(Ok(ServerMessage::Shutdown)).await.ok();
-                        dbg!("shutdown");
-                        break;
-                    },
-
-                    // Send only the part of the initial hand that pertains to the player
-                    ServerBroadcast::GameStart { info, initial_hands } => {
-                        let initial_hand = initial_hands
-                            .get(&handshake.player_name)
-                            .copied();
-                        player_client_send.send(Ok(ServerMessage::GameStart {
-                            info, initial_hand
-                        })).await
+                // Forward the shutdown message, then exit
+                let shutting_down = matches!(broadcast, ServerBroadcast::Shutdown);
+
+                // Go by a new name from the point the rename was broadcast,
+                // as every broadcast queued before it was meant for the old
+                if let ServerBroadcast::Renamed { old_name, new_name } = &broadcast {
+                    if *old_name == handshake.player_name {
+                        handshake.player_name = new_name.clone();
                     }
+                }
 
-                    ServerBroadcast::DeadTile { player_name, dead_tile } => {
-                        player_client_send.send(
-                            Ok(ServerMessage::DeadTile { player_name, dead_tile })
-                        ).await
+                // Never deliver a message carrying tiles hidden from this
+                // connection, however it came to be queued for it
+                let audience = guard::Audience::of(&broadcast);
+                let pause = pacing.pause_after(&broadcast);
+                let message = personalize(broadcast, &handshake);
+                if let Err(leak) = guard::check(&handshake, &audience, &message) {
+                    if cfg!(debug_assertions) {
+                        panic!("{leak}");
                     }
+                    continue;
+                }
 
-                    ServerBroadcast::Chat { player_name, message } => {
-                        player_client_send.send(
-                            Ok(ServerMessage::Chat { player_name, message })
-                        ).await
-                    },
-                    ServerBroadcast::Join { handshake } => {
-                        player_client_send.send(
-                            Ok(ServerMessage::Join { handshake })
-                        ).await
-                    },
-                    ServerBroadcast::CompanyDefunct { defunct, results } => {
-                        player_client_send.send(
-                            Ok(ServerMessage::CompanyDefunct { defunct, results })
-                        ).await
-                    },
-                    ServerBroadcast::GameOver { reason, results } => {
-                        player_client_send.send(
-                            Ok(ServerMessage::GameOver { reason, results })
-                        ).await
-                    },
-                    ServerBroadcast::Quit { handshake } => {
-                        player_client_send.send(
-                            Ok(ServerMessage::Quit { handshake })
-                        ).await
-                    }
-                };
+                let result = player_client_send.send(Ok(message)).await;
+                if shutting_down {
+                    // Ignore the send error, as we're shutting down anyway.
+                    dbg!("shutdown");
+                    break;
+                }
 
                 // Stop the listener if the returned receiver was closed
                 if result.is_err() {
                     dbg!("senderror");
                     break;
                 }
+
+                // Give the client time to show this step before the next
+                if let Some(pause) = pause {
+                    tokio::select! {
+                        _ = tokio::time::sleep(pause) => {},
+                        _ = shutdown.notified() => break,
+                    }
+                }
             }
         
             dbg!("Server to player closed");
@@ -346,94 +588,718 @@ impl Server {
         client_recv
     }
 
-    /// Starts the process that responds to requests from clients. This involves
-    /// controlling the game task and forwarding messages to it as necessary.
-    /// Returns the sender used to send requests. This cannot be made into a
-    /// function that takes `Self` as an argument, as the return value of this
-    /// function is needed to construct a running [`Server`] object.
-    fn listen_for_requests(
-        broadcaster: broadcast::Sender<ServerBroadcast>,
-        players: Arc<Mutex<ConnectionManager>>,
-        game: Arc<Mutex<ServerGame>>,
-    ) -> mpsc::Sender<TaggedClientMessage> {
-
-        let (sender, mut receiver) = mpsc::channel::<TaggedClientMessage>(1);
+    /// Spawns the task that owns the connections and the game, and processes
+    /// every command sent to the server in the order received. Returns the
+    /// sender used to send commands, and a receiver that becomes true once the
+    /// task is running. This cannot be made into a function that
+    /// takes `Self` as an argument, as the return value of this function is
+    /// needed to construct a running [`Server`] object.
+    fn spawn_state_task(
+        broadcaster: Broadcaster,
+        mut players: ConnectionManager,
+        mut names: HashMap<Box<str>, SharedName>,
+        name: Box<str>,
+        motd: Option<Box<str>>,
+        mut game: ServerGame,
+        mut autosaver: Option<autosave::Autosaver>,
+    ) -> (mpsc::Sender<ServerCommand>, watch::Receiver<bool>) {
+
+        let (sender, mut receiver) = mpsc::channel::<ServerCommand>(1);
+        let (ready, ready_receiver) = watch::channel(false);
 
         tokio::spawn(async move {
 
-            while let Some(message) = receiver.recv().await {
-                let mut game = game.lock().unwrap();
-                match message.kind {
-                    ClientMessage::TakingTurn(action) => {
+            // The number of chat messages relayed so far
+            let mut chat_sequence: u64 = 0;
+            // Why each connection that has said it is leaving is leaving, to
+            // be announced once it closes
+            let mut leaving: HashMap<Box<str>, LeaveReason> = HashMap::new();
+            let mut kick_votes = KickVotes::default();
+            let mut resume_tokens = ResumeTokens::default();
+
+            ready.send_replace(true);
+
+            loop {
 
-                        let action = TaggedPlayerAction {
-                            player_name: message.player_name.clone(),
-                            action
+                if let Some(autosaver) = &mut autosaver {
+                    autosaver.save_if_due(&game);
+                }
+
+                // Run the clock of whoever the game now waits on, and wait on
+                // the next command only for as long as their time lasts
+                if game.sync_clock().is_err() { break; }
+                let deadline = game.clock_deadline();
+                let autosave_deadline = autosaver.as_ref().and_then(|autosaver| autosaver.deadline());
+                let countdown_deadline = game.countdown_deadline();
+                let kick_vote_deadline = kick_votes.deadline();
+                let command = tokio::select! {
+                    command = receiver.recv() => match command {
+                        Some(command) => command,
+                        None => break,
+                    },
+                    _ = sleep_until(deadline) => {
+                        let was_in_progress = game.in_progress();
+                        let result = panic::catch_unwind(AssertUnwindSafe(|| game.time_out()))
+                            .unwrap_or_else(|panic| recover(
+                                panic, was_in_progress, &mut game, autosaver.as_ref(), &players, &broadcaster,
+                            ));
+                        if result.is_err() { break; }
+                        continue;
+                    },
+                    _ = sleep_until(countdown_deadline) => {
+                        let seated = players.players().map(Into::into).collect();
+                        let was_in_progress = game.in_progress();
+                        let result = panic::catch_unwind(AssertUnwindSafe(|| game.count_down(seated)))
+                            .unwrap_or_else(|panic| recover(
+                                panic, was_in_progress, &mut game, autosaver.as_ref(), &players, &broadcaster,
+                            ));
+                        if result.is_err() { break; }
+                        continue;
+                    },
+                    _ = sleep_until(kick_vote_deadline) => {
+                        if let Some(target) = kick_votes.expire(tokio::time::Instant::now()) {
+                            if broadcaster.send(ServerBroadcast::KickFailed { target }).is_err() { break; }
+                        }
+                        continue;
+                    },
+                    // The save itself is made at the top of the loop
+                    _ = sleep_until(autosave_deadline) => continue,
+                };
+
+                let message = match command {
+                    ServerCommand::Connect { handshake, name: shared_name, resume_token, reply } => {
+                        let seated = game.hand(&handshake.player_name).is_some();
+                        let result = match !handshake.spectating && game.seats_closed() {
+                            true => Err(ConnectionReject::SeatsClosed),
+                            false => resume_tokens
+                                .check(&handshake.player_name, resume_token.as_ref(), seated)
+                                .and_then(|()| players.connect(handshake.clone())),
                         };
+                        let result = result.map(|_| {
+                            names.insert(handshake.player_name.clone(), shared_name);
+                            resume_tokens.hold(&handshake.player_name, resume_token);
+
+                            // Broadcast a join message. This fails only if
+                            // nobody else is connected, in which case nobody
+                            // needs to know.
+                            broadcaster.send(ServerBroadcast::Join {
+                                handshake: handshake.clone()
+                            }).ok();
+
+                            // Subscribing here, rather than once the player
+                            // has the reply, ensures that the player hears
+                            // every broadcast made after this state.
+                            let hand = match handshake.spectating {
+                                true => None,
+                                false => game.hand(&handshake.player_name),
+                            };
+                            let info = ServerInfo::new(
+                                &name, motd.as_deref(), &players, game.in_progress(),
+                            );
+                            let state = ServerState {
+                                game_history: game.public_history(),
+                                hand,
+                                connections: players.clone(),
+                                info: Some(info),
+                            };
+                            (state, broadcaster.subscribe(&handshake))
+                        });
 
-                        game.update(action);
+                        // The player may have given up on connecting, in which
+                        // case their disconnection is processed as usual.
+                        reply.send(result).ok();
+                        continue;
                     },
-                    ClientMessage::Chat { message: chat_msg } => {
-                        broadcaster.send(
-                            ServerBroadcast::Chat {
-                                player_name: message.player_name,
-                                message: chat_msg,
-                            }
-                        ).unwrap();
+                    ServerCommand::Disconnect { handshake } => {
+                        players.disconnect(&handshake.player_name);
+                        names.remove(&handshake.player_name);
+                        game.leave_queue(&handshake.player_name);
+                        kick_votes.withdraw(&handshake.player_name);
+
+                        // Send a disconnect message. Ignore any SendErrors, as
+                        // an error means that this is the last player to leave
+                        // and the server will shut down.
+                        let reason = leaving.remove(&handshake.player_name);
+                        broadcaster.send(ServerBroadcast::Quit { handshake, reason }).ok();
+                        continue;
+                    },
+                    ServerCommand::Console { command, reply } => {
+                        reply.send(console::run(command, &mut game, &players)).ok();
+                        continue;
                     },
-                    ClientMessage::DeadTile { dead_tile } => {
-                        game.swap_dead_tile(message.player_name, dead_tile);
+                    #[cfg(test)]
+                    ServerCommand::StackTiles(tiles) => {
+                        game.stack_tiles(tiles);
+                        continue;
                     },
-                    ClientMessage::Admin(cmd) => {
+                    #[cfg(test)]
+                    ServerCommand::FailNextAction => {
+                        game.fail_next_action();
+                        continue;
+                    },
+                    ServerCommand::Message(message) => message,
+                };
 
-                        // Check if the sender is an admin
-                        let players = players.lock().unwrap();
-                        let player = players.get_handshake(&message.player_name).unwrap();
-                        if !player.admin {
+                // A panic in handling the message is recovered from, rather
+                // than taking every connection down with the server
+                let was_in_progress = game.in_progress();
+                let handled = panic::catch_unwind(AssertUnwindSafe(|| {
+                    let result = match message.kind {
+                        kind if game.forbids(&message.player_name, &kind) => {
                             broadcaster.send(ServerBroadcast::Private {
                                 target_player: message.player_name,
                                 message: PrivateBroadcast::Invalid {
-                                    reason: InvalidMessageReason::PermissionDenied
-                                }
-                            }).unwrap();
-                            continue;
-                        }
+                                    reason: InvalidMessageReason::ForbiddenByPolicy,
+                                    request_id: None,
+                                },
+                            })
+                        },
+                        ClientMessage::TakingTurn { action, request_id, number } => {
 
-                        match cmd {
-                            AdminCommand::Shutdown => break,
-                            AdminCommand::StartGame => {
-
-                                // Determine which players aren't spectators.
-                                let players: Vec<_> = players.players()
-                                    .map(|s| s.to_owned().into_boxed_str())
-                                    .collect();
-
-                                game.start(6000, players, message.player_name);
-                            },
-                            AdminCommand::EndGame => {
-                                game.end(message.player_name);
-                            },
-                            AdminCommand::Kick { player_name } => todo!(),
-                            AdminCommand::SilenceChat => todo!(),
-                        }
+                            let action = TaggedPlayerAction {
+                                player_name: message.player_name.clone(),
+                                action,
+                                number,
+                            };
+
+                            game.update(action, request_id)
+                        },
+                        ClientMessage::Chat { message: chat_msg, channel } => {
+
+                            // Only the players seated in the game are in its
+                            // room
+                            let player = players.get_handshake(&message.player_name).unwrap();
+                            if channel == ChatChannel::Room && player.spectating {
+                                broadcaster.send(ServerBroadcast::Private {
+                                    target_player: message.player_name,
+                                    message: PrivateBroadcast::Invalid {
+                                        reason: InvalidMessageReason::NotInRoom,
+                                        request_id: None,
+                                    }
+                                })
+                            } else {
+                                let timestamp = SystemTime::now()
+                                    .duration_since(UNIX_EPOCH)
+                                    .map_or(0, |since| since.as_secs());
+                                chat_sequence += 1;
+
+                                broadcaster.send(
+                                    ServerBroadcast::Chat {
+                                        player_name: message.player_name,
+                                        message: chat_msg,
+                                        timestamp,
+                                        sequence: chat_sequence - 1,
+                                        channel,
+                                    }
+                                )
+                            }
+                        },
+                        ClientMessage::DeadTile { dead_tile } => {
+                            game.swap_dead_tile(message.player_name, dead_tile)
+                        },
+                        ClientMessage::RequestResync => {
+                            game.resync(message.player_name)
+                        },
+                        ClientMessage::Leaving { reason } => {
+                            leaving.insert(message.player_name, reason);
+                            Ok(())
+                        },
+                        ClientMessage::Queue { queued } => {
+                            game.queue(message.player_name, queued)
+                        },
+                        ClientMessage::ProposeEnd { end_game } => {
+                            game.vote_end(message.player_name, end_game)
+                        },
+                        ClientMessage::SetAutopilot { autopilot } => {
+                            game.set_autopilot(message.player_name, autopilot)
+                        },
+                        ClientMessage::VoteKick { votekick } => vote_kick(
+                            &players, &broadcaster, &mut kick_votes, &mut leaving,
+                            message.player_name, votekick,
+                        ),
+                        ClientMessage::Rename { new_name } => rename(
+                            &mut players, &mut names, &broadcaster, &mut game,
+                            message.player_name, new_name,
+                        ),
+                        ClientMessage::Ping { ping } => {
+                            broadcaster.send(ServerBroadcast::Private {
+                                target_player: message.player_name,
+                                message: PrivateBroadcast::Pong { pong: ping },
+                            })
+                        },
+                        ClientMessage::Admin(cmd) => {
+
+                            // Check that the sender may give the command
+                            let player = players.get_handshake(&message.player_name).unwrap();
+                            if !player.capabilities.contains(Capability::required_by(&cmd)) {
+                                broadcaster.send(ServerBroadcast::Private {
+                                    target_player: message.player_name,
+                                    message: PrivateBroadcast::Invalid {
+                                        reason: InvalidMessageReason::PermissionDenied,
+                                        request_id: None,
+                                    }
+                                })
+                            } else {
+                                match cmd {
+                                    AdminCommand::Shutdown => return ControlFlow::Break(()),
+                                    AdminCommand::StartGame => {
+
+                                        // Determine which players aren't spectators.
+                                        let players: Vec<_> = players.players()
+                                            .map(|s| s.to_owned().into_boxed_str())
+                                            .collect();
+
+                                        game.start(Money::new(6000), players, message.player_name)
+                                    },
+                                    AdminCommand::EndGame => {
+                                        game.end(message.player_name)
+                                    },
+                                    AdminCommand::Kick { player_name } => {
+                                        match players.get_handshake(&player_name) {
+                                            Some(_) => kick(&broadcaster, &mut leaving, player_name),
+                                            None => broadcaster.send(ServerBroadcast::Private {
+                                                target_player: message.player_name,
+                                                message: PrivateBroadcast::Invalid {
+                                                    reason: InvalidMessageReason::PlayerNotConnected,
+                                                    request_id: None,
+                                                },
+                                            }),
+                                        }
+                                    },
+                                    AdminCommand::Seat { player_name } => seat(
+                                        &mut players, &broadcaster, &game,
+                                        message.player_name, player_name, false,
+                                    ),
+                                    AdminCommand::Unseat { player_name } => seat(
+                                        &mut players, &broadcaster, &game,
+                                        message.player_name, player_name, true,
+                                    ),
+                                    AdminCommand::Substitute { seat, player_name } => substitute(
+                                        &mut players, &broadcaster, &mut game,
+                                        message.player_name, seat, player_name,
+                                    ),
+                                    AdminCommand::ExportLog => {
+                                        game.export_log(message.player_name)
+                                    },
+                                    AdminCommand::ScheduleStart { at } => {
+                                        game.schedule_start(message.player_name, at)
+                                    },
+                                    AdminCommand::Trust { player_name, capabilities } => trust(
+                                        &mut players, &broadcaster,
+                                        message.player_name, player_name, capabilities,
+                                    ),
+                                }
+                            }
+                        },
+                    };
+
+                    // Start a game for the players waiting on one, once enough
+                    // are
+                    let result = result.and_then(|()| match game.take_table() {
+                        Some(table) => start_table(&mut players, &broadcaster, &mut game, table),
+                        None => Ok(()),
+                    });
+
+                    // Nobody is left to play, so there is nothing left to serve
+                    match result {
+                        Ok(()) => ControlFlow::Continue(()),
+                        Err(NoListeners) => ControlFlow::Break(()),
+                    }
+                }));
+                match handled {
+                    Ok(ControlFlow::Continue(())) => {},
+                    Ok(ControlFlow::Break(())) => break,
+                    Err(panic) => {
+                        let recovered = recover(
+                            panic, was_in_progress, &mut game, autosaver.as_ref(), &players, &broadcaster,
+                        );
+                        if recovered.is_err() { break; }
                     },
-                };
+                }
+            }
+
+            // Send a shutdown message. Ignore any error, as it means that
+            // everyone has already left.
+            broadcaster.send(ServerBroadcast::Shutdown).ok();
+        });
+
+        (sender, ready_receiver)
+    }
+}
+
+/// Recovers from a panic while handling a command. A game left unfit to go on
+/// is carried on from its latest autosave, or abandoned if it has none. Either
+/// way, everyone is told what went wrong and, if their game has changed,
+/// resynced. Fails only if nobody is left to tell.
+fn recover(
+    panic: Box<dyn Any + Send>,
+    was_in_progress: bool,
+    game: &mut ServerGame,
+    autosaver: Option<&autosave::Autosaver>,
+    players: &ConnectionManager,
+    broadcaster: &Broadcaster,
+) -> Result<(), NoListeners> {
+    let message: Box<str> = match panic.downcast::<String>() {
+        Ok(message) => (*message).into(),
+        Err(panic) => panic.downcast_ref::<&str>().copied().unwrap_or("an unknown error").into(),
+    };
+
+    let recovery = match game.in_progress() {
+        true if game.is_intact() => Recovery::Unaffected,
+        false if !was_in_progress => Recovery::Unaffected,
+        _ => {
+            let saved = autosaver.and_then(autosave::Autosaver::latest);
+            let after_actions = saved.as_ref().map_or(0, |saved| saved.history.actions.len());
+            match saved.is_some_and(|saved| game.restore(saved)) {
+                true => Recovery::Restored { after_actions },
+                false => {
+                    game.abandon();
+                    Recovery::Lost
+                },
             }
+        },
+    };
+
+    broadcaster.send(ServerBroadcast::ServerError { message, recovery })?;
+    if recovery != Recovery::Unaffected {
+        // Clients that can't take a resync unasked are left to ask for one
+        let resyncable = players.handshakes()
+            .filter(|handshake| handshake.features.contains(Feature::Resync));
+        for handshake in resyncable {
+            game.resync(handshake.player_name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Waits until `deadline`, or forever if there is none.
+async fn sleep_until(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Turns a broadcast into the message sent to one connection. A message meant
+/// for each player separately, such as the start of a game, is given only the
+/// part that pertains to the connection.
+fn personalize(broadcast: ServerBroadcast, recipient: &Handshake) -> ServerMessage {
+    match broadcast {
+        ServerBroadcast::PlayerMove { action, state_hash, request_id } => {
+            let request_id = request_id.filter(|_| action.player_name == recipient.player_name);
+            ServerMessage::PlayerMove { action, state_hash, request_id }
+        },
+
+        // Private messages are only ever queued for the player they pertain
+        // to.
+        ServerBroadcast::Private { message, .. } => match message {
+            PrivateBroadcast::YourTurn { request, number } => ServerMessage::YourTurn { request, number },
+            PrivateBroadcast::TileDraw { tile } => ServerMessage::TileDraw { tile },
+            PrivateBroadcast::Invalid { reason, request_id } => {
+                ServerMessage::Invalid { reason, request_id }
+            },
+            PrivateBroadcast::Resync { snapshot, hand, clock } => {
+                ServerMessage::Resync { snapshot, hand, clock }
+            },
+            PrivateBroadcast::ActionLog { entries } => ServerMessage::ActionLog { entries },
+            PrivateBroadcast::Pong { pong } => ServerMessage::Pong { pong },
+            PrivateBroadcast::Kicked => ServerMessage::Kicked,
+        },
+        ServerBroadcast::Shutdown => ServerMessage::Shutdown,
+
+        // Send only the part of the initial hand that pertains to the player
+        ServerBroadcast::StartingDraw { draws, random_seating } => {
+            ServerMessage::StartingDraw { draws, random_seating }
+        },
+        ServerBroadcast::GameStart { info, initial_hands } => {
+            let initial_hand = initial_hands.get(&recipient.player_name).copied();
+            ServerMessage::GameStart { info, initial_hand }
+        },
+        ServerBroadcast::DeadTile { player_name, dead_tile } => {
+            ServerMessage::DeadTile { player_name, dead_tile }
+        },
+        ServerBroadcast::Chat { player_name, message, timestamp, sequence, channel } => {
+            ServerMessage::Chat { player_name, message, timestamp, sequence, channel }
+        },
+        ServerBroadcast::Join { handshake } => ServerMessage::Join { handshake },
+        ServerBroadcast::CompanyDefunct { defunct, results } => {
+            ServerMessage::CompanyDefunct { defunct, results }
+        },
+        ServerBroadcast::NoFounderShare { player_name, company } => {
+            ServerMessage::NoFounderShare { player_name, company }
+        },
+        ServerBroadcast::ResolvingNext { player, defunct, into } => {
+            ServerMessage::ResolvingNext { player, defunct, into }
+        },
+        ServerBroadcast::GameOver { reason, results, deal_seed, game_id } => {
+            ServerMessage::GameOver { reason, results, deal_seed, game_id }
+        },
+        ServerBroadcast::Quit { handshake, reason } => ServerMessage::Quit { handshake, reason },
+        ServerBroadcast::Seated { handshake } => ServerMessage::Seated { handshake },
+        ServerBroadcast::Trusted { handshake } => ServerMessage::Trusted { handshake },
+        ServerBroadcast::Renamed { old_name, new_name } => {
+            ServerMessage::Renamed { old_name, new_name }
+        },
+        ServerBroadcast::Substituted { substitution, state_hash } => {
+            ServerMessage::Substituted { substitution, state_hash }
+        },
+        ServerBroadcast::Clock { clock } => ServerMessage::Clock { clock },
+        ServerBroadcast::EndProposed { player_name } => ServerMessage::EndProposed { player_name },
+        ServerBroadcast::EndVoted { player_name, end_game } => {
+            ServerMessage::EndVoted { player_name, end_game }
+        },
+        ServerBroadcast::EndRejected => ServerMessage::EndRejected,
+        ServerBroadcast::Autopilot { player_name, autopilot } => {
+            ServerMessage::Autopilot { player_name, autopilot }
+        },
+        ServerBroadcast::Away { player_name, missed_turns } => {
+            ServerMessage::Away { player_name, missed_turns }
+        },
+        ServerBroadcast::StartScheduled { starts_at, seconds_left } => {
+            ServerMessage::StartScheduled { starts_at, seconds_left }
+        },
+        ServerBroadcast::Queued { player_name, queued, waiting, table_size } => {
+            ServerMessage::Queued { player_name, queued, waiting, table_size }
+        },
+        ServerBroadcast::KickVoted { player_name, target, votes, needed } => {
+            ServerMessage::KickVoted { player_name, target, votes, needed }
+        },
+        ServerBroadcast::KickFailed { target } => ServerMessage::KickFailed { target },
+        ServerBroadcast::ServerError { message, recovery } => {
+            ServerMessage::ServerError { message, recovery }
+        },
+    }
+}
 
-            // Send a shutdown message
-            broadcaster.send(ServerBroadcast::Shutdown).unwrap();
+/// Seats a spectator at the table, or unseats a player to spectate, and tells
+/// everyone. Seats only change between games; any problem is reported to the
+/// admin who asked.
+fn seat(
+    players: &mut ConnectionManager,
+    broadcaster: &Broadcaster,
+    game: &ServerGame,
+    admin_name: Box<str>,
+    player_name: Box<str>,
+    spectating: bool,
+) -> Result<(), NoListeners> {
+    let result = if game.in_progress() {
+        Err(InvalidMessageReason::GameAlreadyStarted)
+    } else if !spectating && game.seats_closed() {
+        Err(InvalidMessageReason::SeatsClosed)
+    } else {
+        players.set_spectating(&player_name, spectating)
+    };
+
+    match result {
+        Ok(handshake) => {
+            broadcaster.set_spectating(&player_name, spectating);
+            broadcaster.send(ServerBroadcast::Seated { handshake })
+        },
+        Err(reason) => broadcaster.send(ServerBroadcast::Private {
+            target_player: admin_name,
+            message: PrivateBroadcast::Invalid { reason, request_id: None },
+        }),
+    }
+}
+
+/// Sets what a player may do as an admin, and tells everyone. The admin who
+/// asked must have every capability the player has and is given, so that no
+/// admin can raise anyone above themselves or strip anyone who is above them.
+fn trust(
+    players: &mut ConnectionManager,
+    broadcaster: &Broadcaster,
+    admin_name: Box<str>,
+    player_name: Box<str>,
+    capabilities: Capabilities,
+) -> Result<(), NoListeners> {
+    let admin = players.get_handshake(&admin_name).map_or(Capabilities::NONE, |admin| admin.capabilities);
+    let result = match players.get_handshake(&player_name) {
+        None => Err(InvalidMessageReason::PlayerNotConnected),
+        Some(player) if !player.capabilities.is_subset(admin) || !capabilities.is_subset(admin) => {
+            Err(InvalidMessageReason::PermissionDenied)
+        },
+        Some(_) => players.set_capabilities(&player_name, capabilities),
+    };
+
+    match result {
+        Ok(handshake) => broadcaster.send(ServerBroadcast::Trusted { handshake }),
+        Err(reason) => broadcaster.send(ServerBroadcast::Private {
+            target_player: admin_name,
+            message: PrivateBroadcast::Invalid { reason, request_id: None },
+        }),
+    }
+}
+
+/// Changes the name a connection goes by, and tells everyone. Names only change
+/// between games; any problem is reported to the player who asked.
+fn rename(
+    players: &mut ConnectionManager,
+    names: &mut HashMap<Box<str>, SharedName>,
+    broadcaster: &Broadcaster,
+    game: &mut ServerGame,
+    old_name: Box<str>,
+    new_name: Box<str>,
+) -> Result<(), NoListeners> {
+    let result = if game.in_progress() {
+        Err(InvalidMessageReason::GameAlreadyStarted)
+    } else {
+        players.rename(&old_name, new_name.clone())
+    };
+    if let Err(reason) = result {
+        return broadcaster.send(ServerBroadcast::Private {
+            target_player: old_name,
+            message: PrivateBroadcast::Invalid { reason, request_id: None },
         });
+    }
 
-        sender
+    if let Some(shared_name) = names.remove(&old_name) {
+        shared_name.set(new_name.clone());
+        names.insert(new_name.clone(), shared_name);
     }
+    game.rename_queued(&old_name, new_name.clone());
+    broadcaster.rename(&old_name, new_name.clone());
+    broadcaster.send(ServerBroadcast::Renamed { old_name, new_name })
+}
+
+/// Kicks a player out of the server. They are told so, and their connection
+/// is closed once they have read it, upon which everyone is told they were
+/// kicked.
+fn kick(
+    broadcaster: &Broadcaster,
+    leaving: &mut HashMap<Box<str>, LeaveReason>,
+    player_name: Box<str>,
+) -> Result<(), NoListeners> {
+    leaving.insert(player_name.clone(), LeaveReason::Kicked);
+    let result = broadcaster.send(ServerBroadcast::Private {
+        target_player: player_name.clone(),
+        message: PrivateBroadcast::Kicked,
+    });
+    broadcaster.unsubscribe(&player_name);
+    result
+}
+
+/// Casts a player's vote to kick another, and tells everyone how the vote
+/// stands, kicking the target once enough seated players agree. Only seated
+/// players vote, admins can't be voted out, and no vote is held while an
+/// admin who can kick is connected. Any problem is reported to the voter.
+fn vote_kick(
+    players: &ConnectionManager,
+    broadcaster: &Broadcaster,
+    kick_votes: &mut KickVotes,
+    leaving: &mut HashMap<Box<str>, LeaveReason>,
+    voter: Box<str>,
+    target: Box<str>,
+) -> Result<(), NoListeners> {
+    let seated = players.get_handshake(&voter).is_some_and(|handshake| !handshake.spectating);
+    let admin_present = players.handshakes()
+        .any(|handshake| handshake.capabilities.contains(Capability::Kick));
+    let result = match players.get_handshake(&target) {
+        _ if !seated => Err(InvalidMessageReason::NotSeated),
+        _ if admin_present => Err(InvalidMessageReason::AdminPresent),
+        None => Err(InvalidMessageReason::PlayerNotConnected),
+        Some(handshake) if handshake.player_name == voter || !handshake.capabilities.is_empty() => {
+            Err(InvalidMessageReason::PermissionDenied)
+        },
+        Some(_) => {
+            let seated = players.players().count();
+            kick_votes.vote(&voter, &target, seated, tokio::time::Instant::now())
+        },
+    };
+
+    match result {
+        Ok(KickTally::Passed) => kick(broadcaster, leaving, target),
+        Ok(KickTally::Open { votes, needed }) => broadcaster.send(ServerBroadcast::KickVoted {
+            player_name: voter, target, votes, needed,
+        }),
+        Err(reason) => broadcaster.send(ServerBroadcast::Private {
+            target_player: voter,
+            message: PrivateBroadcast::Invalid { reason, request_id: None },
+        }),
+    }
+}
+
+/// Starts a game with the players taken from the queue, seating each of them
+/// and leaving everyone else to spectate. The first of them to have queued
+/// stands in for the admin, being told of any problem in starting the game.
+fn start_table(
+    players: &mut ConnectionManager,
+    broadcaster: &Broadcaster,
+    game: &mut ServerGame,
+    table: Vec<Box<str>>,
+) -> Result<(), NoListeners> {
+    let connections: Vec<(Box<str>, bool)> = players.connections()
+        .map(|(name, spectating)| (name.into(), spectating))
+        .collect();
+
+    // Unseat everyone first, so that no seat the table needs is taken
+    for (name, spectating) in connections.iter().filter(|(name, _)| !table.contains(name)) {
+        if !spectating {
+            if let Ok(handshake) = players.set_spectating(name, true) {
+                broadcaster.set_spectating(name, true);
+                broadcaster.send(ServerBroadcast::Seated { handshake })?;
+            }
+        }
+    }
+    for (name, spectating) in connections.iter().filter(|(name, _)| table.contains(name)) {
+        if *spectating {
+            if let Ok(handshake) = players.set_spectating(name, false) {
+                broadcaster.set_spectating(name, false);
+                broadcaster.send(ServerBroadcast::Seated { handshake })?;
+            }
+        }
+    }
+
+    let admin_name = table[0].clone();
+    game.start(Money::new(6000), table, admin_name)
+}
+
+/// Hands the seat of a player who left the game in progress to a connected
+/// player, seating them if they were spectating, and tells everyone. The new
+/// player is sent the state of the game along with their hand. Any problem is
+/// reported to the admin who asked.
+fn substitute(
+    players: &mut ConnectionManager,
+    broadcaster: &Broadcaster,
+    game: &mut ServerGame,
+    admin_name: Box<str>,
+    seat: Box<str>,
+    player_name: Box<str>,
+) -> Result<(), NoListeners> {
+    let reject = |reason| broadcaster.send(ServerBroadcast::Private {
+        target_player: admin_name.clone(),
+        message: PrivateBroadcast::Invalid { reason, request_id: None },
+    });
+
+    if players.get_handshake(&seat).is_some() {
+        return reject(InvalidMessageReason::SeatOccupied);
+    }
+    let Some(handshake) = players.get_handshake(&player_name) else {
+        return reject(InvalidMessageReason::PlayerNotConnected);
+    };
+    if let Err(reason) = players.set_spectating(&player_name, false) {
+        return reject(reason);
+    }
+
+    let (substitution, state_hash) = match game.substitute(&seat, player_name.clone()) {
+        Ok(substituted) => substituted,
+        Err(reason) => {
+            players.set_spectating(&player_name, handshake.spectating).ok();
+            return reject(reason);
+        },
+    };
+
+    broadcaster.set_spectating(&player_name, false);
+    broadcaster.send(ServerBroadcast::Substituted { substitution, state_hash })?;
+    game.resync(player_name)
 }
 
 /// Manages the players that are connected to the server.
 #[derive(Debug, Clone)]
 pub struct ConnectionManager {
     /// Maps the player's name to the remainder of the handshake, respectively
-    /// whether the player is spectating and whether the player is an admin.
-    connections: HashMap<Box<str>, (bool, bool)>,
+    /// whether the player is spectating and what the player may do as an
+    /// admin.
+    connections: HashMap<Box<str>, (bool, Capabilities, Features)>,
     /// Number of players connected that aren't spectating.
     player_count: usize,
     /// Maximum number of players allowed to be connected to the server. Capped
@@ -493,12 +1359,13 @@ impl ConnectionManager {
     /// Gets the handshake of a connected player. If the player requested isn't
     /// connected, [`None`] will be returned.
     pub fn get_handshake(&self, name: &str) -> Option<Handshake> {
-        let (spectating, admin) = *self.connections.get(name)?;
+        let (spectating, capabilities, features) = *self.connections.get(name)?;
 
         Some(Handshake {
             player_name: name.to_owned().into_boxed_str(),
             spectating,
-            admin,
+            capabilities,
+            features,
         })
     }
 
@@ -513,16 +1380,17 @@ impl ConnectionManager {
     /// additional information of whether they're a spectator.
     pub fn connections(&self) -> impl Iterator<Item = (&str, bool)> {
         self.connections.iter()
-            .map(|(name, (spectating, _admin))| (name.borrow(), *spectating))
+            .map(|(name, (spectating, _capabilities, _features))| (name.borrow(), *spectating))
     }
 
     pub fn handshakes(&self) -> impl Iterator<Item = Handshake> + '_ {
         self.connections.iter()
-            .map(|(name, (spectating, admin))| {
+            .map(|(name, (spectating, capabilities, features))| {
                 Handshake {
                     player_name: name.clone(),
                     spectating: *spectating,
-                    admin: *admin
+                    capabilities: *capabilities,
+                    features: *features,
                 }
             })
     }
@@ -533,7 +1401,9 @@ impl ConnectionManager {
     {
         use ConnectionReject::*;
 
-        if self.connections.contains_key(&handshake.player_name) { return Err(NameTaken); }
+        if self.connections.contains_key(&handshake.player_name) {
+            return Err(NameTaken { suggestion: self.suggest_name(&handshake.player_name) });
+        }
         if let Some(max_connections) = self.max_connections {
             if self.connection_count() == max_connections { 
                 return Err(MaxConnectionsReached);
@@ -548,17 +1418,98 @@ impl ConnectionManager {
 
         self.connections.insert(handshake.player_name, (
             handshake.spectating,
-            handshake.admin
+            handshake.capabilities,
+            handshake.features,
         ));
-        self.player_count += 1;
+        if !handshake.spectating {
+            self.player_count += 1;
+        }
 
         Ok(())
     }
 
+    /// Suggests a name like `name` that nobody connected is using, such as
+    /// `alice_2` for `alice`.
+    pub fn suggest_name(&self, name: &str) -> Box<str> {
+        (2..).map(|n| {
+                let suffix = format!("_{n}");
+                let keep = MAX_NAME_LEN.saturating_sub(suffix.len());
+                let base: String = name.chars().take(keep).collect();
+                base + &suffix
+            })
+            .find(|candidate| !self.connections.contains_key(candidate.as_str()))
+            .unwrap()
+            .into_boxed_str()
+    }
+
+    /// Changes the name a connection goes by, keeping its seat and rights.
+    pub fn rename(&mut self, old_name: &str, new_name: Box<str>)
+        -> Result<Handshake, InvalidMessageReason>
+    {
+        if self.connections.contains_key(&new_name) {
+            return Err(InvalidMessageReason::NameTaken);
+        }
+        let (spectating, capabilities, features) = self.connections.remove(old_name)
+            .ok_or(InvalidMessageReason::PlayerNotConnected)?;
+        self.connections.insert(new_name.clone(), (spectating, capabilities, features));
+
+        Ok(Handshake {
+            player_name: new_name,
+            spectating,
+            capabilities,
+            features,
+        })
+    }
+
+    /// Seats a spectator as a player, or unseats a player to spectate.
+    /// Returns the connection's new handshake.
+    pub fn set_spectating(&mut self, name: &str, spectating: bool)
+        -> Result<Handshake, InvalidMessageReason>
+    {
+        let max_players = self.max_players;
+        let player_count = self.player_count;
+        let (was_spectating, capabilities, features) = self.connections.get_mut(name)
+            .ok_or(InvalidMessageReason::PlayerNotConnected)?;
+
+        if *was_spectating && !spectating {
+            if max_players.is_some_and(|max| player_count == max) {
+                return Err(InvalidMessageReason::NoSeatFree);
+            }
+            self.player_count += 1;
+        } else if !*was_spectating && spectating {
+            self.player_count -= 1;
+        }
+        *was_spectating = spectating;
+
+        Ok(Handshake {
+            player_name: name.into(),
+            spectating,
+            capabilities: *capabilities,
+            features: *features,
+        })
+    }
+
+    /// Sets what a connection may do as an admin. Returns the connection's
+    /// new handshake.
+    pub fn set_capabilities(&mut self, name: &str, capabilities: Capabilities)
+        -> Result<Handshake, InvalidMessageReason>
+    {
+        let (spectating, old, features) = self.connections.get_mut(name)
+            .ok_or(InvalidMessageReason::PlayerNotConnected)?;
+        *old = capabilities;
+
+        Ok(Handshake {
+            player_name: name.into(),
+            spectating: *spectating,
+            capabilities,
+            features: *features,
+        })
+    }
+
     /// Disconnects a player. Returns true if any action was needed.
     pub fn disconnect(&mut self, name: &str) -> bool {
         let data = self.connections.remove(name);
-        if matches!(data, Some((false, _))) { self.player_count -= 1; }
+        if matches!(data, Some((false, _, _))) { self.player_count -= 1; }
         data.is_some()
     }
 }
@@ -594,9 +1545,15 @@ impl<'de> Deserialize<'de> for ConnectionManager {
 pub struct Handshake {
     pub player_name: Box<str>,
     pub spectating: bool,
-    /// Flag that indicates whether the player will be permitted to send admin
-    /// commands.
-    pub admin: bool,
+    /// What the player is permitted to do as an admin. Nobody joining over
+    /// the network may ask for any capability; they can only be trusted with
+    /// them once connected.
+    #[serde(default)]
+    pub capabilities: Capabilities,
+    /// What the client can handle, which the server tailors what it sends
+    /// to.
+    #[serde(default, skip_serializing_if = "Features::is_empty")]
+    pub features: Features,
 }
 
 /// Message sent from a client to the server.
@@ -659,23 +1616,56 @@ impl<E> Interface<E> {
 /// Internal messages sent from the server loop to player handlers.
 #[derive(Debug, Clone)]
 pub enum ServerBroadcast {
+    /// A chat message, which reaches only the players seated in the game if
+    /// it was said in its room.
     Chat {
         player_name: Box<str>,
         message: Box<str>,
+        timestamp: u64,
+        sequence: u64,
+        channel: ChatChannel,
     },
     Join {
         handshake: Handshake,
     },
     Quit {
         handshake: Handshake,
+        reason: Option<LeaveReason>,
+    },
+    /// A connection has been seated at the table, or unseated to spectate.
+    Seated {
+        handshake: Handshake,
+    },
+    /// What a connection may do as an admin has changed.
+    Trusted {
+        handshake: Handshake,
+    },
+    /// A connection has changed the name it goes by.
+    Renamed {
+        old_name: Box<str>,
+        new_name: Box<str>,
+    },
+    /// A player has taken over the seat of a player who left.
+    Substituted {
+        substitution: Substitution,
+        state_hash: u64,
     },
     PlayerMove {
         action: TaggedPlayerAction,
+        state_hash: u64,
+        /// The request ID the action was sent with, which only its player is
+        /// told.
+        request_id: Option<RequestId>,
     },
     DeadTile {
         player_name: Box<str>,
         dead_tile: Tile,
     },
+    /// See [`ServerMessage::StartingDraw`].
+    StartingDraw {
+        draws: Box<[StartingDraw]>,
+        random_seating: bool,
+    },
     /// A new game has begun. This message is personalized for each player.
     GameStart {
         info: GameStart,
@@ -686,15 +1676,81 @@ pub enum ServerBroadcast {
         defunct: Company,
         results: Box<[PrincipleShareholderResult]>,
     },
+    /// A player founded a company but received no free share for it.
+    NoFounderShare {
+        player_name: Box<str>,
+        company: Company,
+    },
+    /// A shareholder is next to resolve their stock in a defunct company.
+    ResolvingNext {
+        player: Box<str>,
+        defunct: Company,
+        into: Company,
+    },
     /// The game is over
     GameOver {
         reason: GameOver,
         results: Box<[FinalResult]>,
+        deal_seed: Option<DealSeed>,
+        game_id: Option<GameId>,
+    },
+    /// The clock has moved on to another player.
+    Clock {
+        clock: ClockState,
+    },
+    /// A player has proposed ending the game early.
+    EndProposed {
+        player_name: Box<str>,
+    },
+    /// A player has voted on ending the game early.
+    EndVoted {
+        player_name: Box<str>,
+        end_game: bool,
+    },
+    /// The players have voted not to end the game early.
+    EndRejected,
+    /// A player has handed their seat to the server, or taken it back.
+    Autopilot {
+        player_name: Box<str>,
+        autopilot: bool,
+    },
+    /// A player has been taken to be away, and the server plays their seat.
+    Away {
+        player_name: Box<str>,
+        missed_turns: usize,
+    },
+    /// The next game has been scheduled to start, or is about to.
+    StartScheduled {
+        starts_at: u64,
+        seconds_left: u64,
+    },
+    /// A player has joined or left the queue for a game.
+    Queued {
+        player_name: Box<str>,
+        queued: bool,
+        waiting: usize,
+        table_size: usize,
+    },
+    /// A player has voted to kick another out of the server.
+    KickVoted {
+        player_name: Box<str>,
+        target: Box<str>,
+        votes: usize,
+        needed: usize,
+    },
+    /// A vote to kick a player has failed.
+    KickFailed {
+        target: Box<str>,
+    },
+    /// The server failed while handling a message, and has recovered.
+    ServerError {
+        message: Box<str>,
+        recovery: Recovery,
     },
     /// The server is shutting down.
     Shutdown,
     /// A message sent about a particular player that's meant only for the eyes
-    /// of the targeted player.
+    /// of the targeted player. It is sent to the targeted player alone.
     Private {
         target_player: Box<str>,
         message: PrivateBroadcast,
@@ -705,7 +1761,8 @@ pub enum ServerBroadcast {
 pub enum PrivateBroadcast {
     /// Tells a player it's their turn, and requests a specific game action.
     YourTurn {
-        request: ActionRequest
+        request: ActionRequest,
+        number: ActionNumber,
     },
     /// The player drew a tile.
     TileDraw {
@@ -713,30 +1770,214 @@ pub enum PrivateBroadcast {
     },
     /// An invalid message was sent.
     Invalid {
-        reason: InvalidMessageReason
+        reason: InvalidMessageReason,
+        request_id: Option<RequestId>,
+    },
+    /// The state of the game, requested by the player to resync.
+    Resync {
+        snapshot: Option<Box<GameSnapshot>>,
+        hand: Option<Hand>,
+        clock: Option<ClockState>,
+    },
+    /// The log of the actions accepted in the latest game, requested by an
+    /// admin.
+    ActionLog {
+        entries: Box<[LoggedAction]>,
+    },
+    /// Answers a ping from the player.
+    Pong {
+        pong: u64,
     },
+    /// The player has been kicked out of the server.
+    Kicked,
 }
 
 /// Indicates the current state of the server. This allows players to understand
 /// what is going on after joining at any point in the game.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerState {
-    pub game_history: Option<GameHistory>,
+    pub game_history: Option<PublicGameHistory>,
+    /// The hand of the player joining, if they are rejoining a game in
+    /// progress. This is never sent to anyone else.
+    #[serde(default)]
+    pub hand: Option<Hand>,
     pub connections: ConnectionManager,
+    /// What the server says about itself, left out by servers from before it
+    /// was sent.
+    #[serde(default)]
+    pub info: Option<ServerInfo>,
 }
 
+/// What a server tells each player about itself as they join.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub name: Box<str>,
+    /// The message of the day.
+    pub motd: Option<Box<str>>,
+    /// The number of players connected, counting the one joining.
+    pub players: usize,
+    pub spectators: usize,
+    pub in_progress: bool,
+    /// The code those joining must give. Only the host is told it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invite_code: Option<invite::InviteCode>,
+}
+
+impl ServerInfo {
+    fn new(
+        name: &str,
+        motd: Option<&str>,
+        connections: &ConnectionManager,
+        in_progress: bool,
+    ) -> Self {
+        let players = connections.player_count();
+        Self {
+            name: name.into(),
+            motd: motd.map(Into::into),
+            players,
+            spectators: connections.connection_count() - players,
+            in_progress,
+            invite_code: None,
+        }
+    }
+}
+
+/// Indicates that a broadcast was not sent because every connection to the
+/// server has closed. The server shuts down once this happens.
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("no connections remain to receive the broadcast")]
+pub struct NoListeners;
+
 /// Provides reasons for a [`Server`]'s rejection of a call to `connect_player`.
 #[derive(Debug, thiserror::Error)]
 pub enum ConnectionReject {
-    /// The name picked is already in use. Connecting with a different name
-    /// should work.
+    /// The name picked is already in use. Connecting with a different name,
+    /// such as the one suggested, should work.
     #[error("name is already in use")]
-    NameTaken,
+    NameTaken {
+        suggestion: Box<str>,
+    },
     /// The game is full. Joining as a spectator should work.
     #[error("game is full")]
     FullGame,
     /// The server has reached its maximum number of connections and is
     /// therefore  not accepting any more.
     #[error("maximum connections reached")]
-    MaxConnectionsReached
+    MaxConnectionsReached,
+    /// A game is about to start on schedule, and no more seats are given.
+    /// Joining as a spectator should work.
+    #[error("the game is about to start, so only spectators may join")]
+    SeatsClosed,
+    /// The server is open only to those with its invite code, and the one
+    /// given, if any, is not it.
+    #[error("the invite code is missing or wrong")]
+    WrongInviteCode,
+    /// Too many wrong invite codes were given from the same address, which
+    /// must wait before trying again.
+    #[error("too many wrong invite codes, so try again later")]
+    TooManyInviteAttempts,
+    /// The name holds a seat in the game in progress, which is kept for the
+    /// client that left it. Resuming from that client should work.
+    #[error("that seat is held for the player who left it")]
+    SeatHeld,
+    /// The server is shutting down and is no longer accepting connections.
+    #[error("server is shutting down")]
+    ShuttingDown,
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::game::messages::{AdminCommand, ClientMessage, LeaveReason, ServerMessage};
+
+    use super::capability::Capabilities;
+    use super::feature::Features;
+    use super::{Handshake, Server, ServerOptions};
+
+    fn handshake(name: &str) -> Handshake {
+        Handshake {
+            player_name: name.into(),
+            spectating: false,
+            capabilities: Capabilities::NONE,
+            features: Features::NONE,
+        }
+    }
+
+    #[tokio::test]
+    async fn connections_handled_one_at_a_time() {
+        let (server, _host) = Server::start(ServerOptions::default(), handshake("host"));
+        let joins: Vec<_> = (0..8)
+            .map(|i| {
+                let server = server.clone();
+                tokio::spawn(async move { server.connect_player(handshake(&format!("player{i}"))).await })
+            })
+            .collect();
+
+        // Each connection is made in turn, seeing those made before it
+        let mut counts = Vec::new();
+        for join in joins {
+            let connection = join.await.unwrap().unwrap();
+            counts.push(connection.server_state.connections.connection_count());
+        }
+        counts.sort();
+        assert_eq!(counts, (2..=9).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn joiners_given_only_their_own_hand() {
+        let (server, mut alice) = Server::start(ServerOptions::default(), Handshake {
+            capabilities: Capabilities::ALL,
+            ..handshake("alice")
+        });
+        let bob = server.connect_player(handshake("bob")).await.unwrap();
+        alice.interface.sender().send(ClientMessage::Admin(AdminCommand::StartGame)).await.unwrap();
+        loop {
+            let message = alice.interface.recv().await.expect("the server shut down");
+            if let Ok(ServerMessage::GameStart { .. }) = message { break; }
+        }
+
+        let carol = server.connect_player(Handshake { spectating: true, ..handshake("carol") }).await.unwrap();
+        assert!(carol.server_state.game_history.is_some());
+        assert!(carol.server_state.hand.is_none());
+
+        // A player rejoining is given back their hand
+        drop(bob);
+        let mut rejoined = server.connect_player(handshake("bob")).await;
+        for _ in 0..100 {
+            if rejoined.is_ok() { break; }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            rejoined = server.connect_player(handshake("bob")).await;
+        }
+        let hand = rejoined.unwrap().server_state.hand.unwrap();
+        assert_eq!(hand.iter().count(), 6);
+    }
+
+    #[tokio::test]
+    async fn leaving_told_apart_from_lost_connections() {
+        let (server, mut alice) = Server::start(ServerOptions::default(), handshake("alice"));
+        let mut quits = Vec::new();
+
+        for name in ["bob", "carol"] {
+            let connection = server.connect_player(handshake(name)).await.unwrap();
+            // Let the connection's tasks start listening for it to close
+            tokio::task::yield_now().await;
+            if name == "bob" {
+                let leaving = ClientMessage::Leaving { reason: LeaveReason::EndOfInput };
+                connection.interface.sender().send(leaving).await.unwrap();
+            }
+            drop(connection);
+
+            loop {
+                let message = alice.interface.recv().await.expect("the server shut down");
+                if let Ok(ServerMessage::Quit { handshake, reason }) = message {
+                    quits.push((handshake.player_name, reason));
+                    break;
+                }
+            }
+        }
+
+        // Bob said why they left, while carol just went quiet
+        assert_eq!(quits, [("bob".into(), Some(LeaveReason::EndOfInput)), ("carol".into(), None)]);
+    }
 }
@@ -0,0 +1,266 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::game::game_id::GameId;
+use crate::game::messages::*;
+use crate::game::tile::Tile;
+use crate::game::Company;
+
+use super::capability::Capabilities;
+use super::fanout::{Broadcaster, Subscription};
+use super::ServerBroadcast;
+
+/// Counts the observers ever subscribed, to give each a name of its own.
+static OBSERVERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Something that happened on the server, as anyone watching would see it.
+/// Nothing hidden from spectators, such as a player's tiles, is included.
+#[derive(Debug, Clone)]
+pub enum GameEvent {
+    Joined {
+        player_name: Box<str>,
+        spectating: bool,
+    },
+    Left {
+        player_name: Box<str>,
+        /// Why they left, or [`None`] if their connection was lost.
+        reason: Option<LeaveReason>,
+    },
+    /// A connection has been seated at the table, or unseated to spectate.
+    Seated {
+        player_name: Box<str>,
+        spectating: bool,
+    },
+    /// What a connection may do as an admin has changed.
+    Trusted {
+        player_name: Box<str>,
+        capabilities: Capabilities,
+    },
+    Renamed {
+        old_name: Box<str>,
+        new_name: Box<str>,
+    },
+    Chat {
+        player_name: Box<str>,
+        message: Box<str>,
+        timestamp: u64,
+    },
+    /// The tiles drawn to decide the order of play of the next game.
+    StartingDraw {
+        draws: Box<[StartingDraw]>,
+        random_seating: bool,
+    },
+    GameStarted {
+        start: GameStart,
+    },
+    Moved {
+        action: TaggedPlayerAction,
+    },
+    Substituted {
+        substitution: Substitution,
+    },
+    DeadTile {
+        player_name: Box<str>,
+        tile: Tile,
+    },
+    CompanyDefunct {
+        defunct: Company,
+        results: Box<[PrincipleShareholderResult]>,
+    },
+    NoFounderShare {
+        player_name: Box<str>,
+        company: Company,
+    },
+    GameOver {
+        reason: GameOver,
+        results: Box<[FinalResult]>,
+        game_id: Option<GameId>,
+    },
+    EndProposed {
+        player_name: Box<str>,
+    },
+    EndVoted {
+        player_name: Box<str>,
+        end_game: bool,
+    },
+    EndRejected,
+    Away {
+        player_name: Box<str>,
+        missed_turns: usize,
+    },
+    Autopilot {
+        player_name: Box<str>,
+        autopilot: bool,
+    },
+    Queued {
+        player_name: Box<str>,
+        queued: bool,
+        waiting: usize,
+    },
+    StartScheduled {
+        starts_at: u64,
+        seconds_left: u64,
+    },
+    KickVoted {
+        player_name: Box<str>,
+        target: Box<str>,
+        votes: usize,
+        needed: usize,
+    },
+    KickFailed {
+        target: Box<str>,
+    },
+    /// The server failed, and recovered as described.
+    ServerError {
+        message: Box<str>,
+        recovery: Recovery,
+    },
+}
+
+impl GameEvent {
+    /// Gets the event a broadcast tells of, if it is one worth telling an
+    /// observer. Every kind of broadcast is listed by name, so none can be
+    /// added without deciding whether observers hear of it.
+    fn of(broadcast: ServerBroadcast) -> Option<Self> {
+        let event = match broadcast {
+            ServerBroadcast::Join { handshake } => GameEvent::Joined {
+                player_name: handshake.player_name,
+                spectating: handshake.spectating,
+            },
+            ServerBroadcast::Quit { handshake, reason } => GameEvent::Left {
+                player_name: handshake.player_name,
+                reason,
+            },
+            ServerBroadcast::Seated { handshake } => GameEvent::Seated {
+                player_name: handshake.player_name,
+                spectating: handshake.spectating,
+            },
+            ServerBroadcast::Trusted { handshake } => GameEvent::Trusted {
+                player_name: handshake.player_name,
+                capabilities: handshake.capabilities,
+            },
+            ServerBroadcast::Renamed { old_name, new_name } => GameEvent::Renamed { old_name, new_name },
+            ServerBroadcast::Chat { player_name, message, timestamp, .. } => {
+                GameEvent::Chat { player_name, message, timestamp }
+            },
+            ServerBroadcast::StartingDraw { draws, random_seating } => {
+                GameEvent::StartingDraw { draws, random_seating }
+            },
+            ServerBroadcast::GameStart { info, .. } => GameEvent::GameStarted { start: info },
+            ServerBroadcast::PlayerMove { action, .. } => GameEvent::Moved { action },
+            ServerBroadcast::Substituted { substitution, .. } => GameEvent::Substituted { substitution },
+            ServerBroadcast::DeadTile { player_name, dead_tile } => {
+                GameEvent::DeadTile { player_name, tile: dead_tile }
+            },
+            ServerBroadcast::CompanyDefunct { defunct, results } => {
+                GameEvent::CompanyDefunct { defunct, results }
+            },
+            ServerBroadcast::NoFounderShare { player_name, company } => {
+                GameEvent::NoFounderShare { player_name, company }
+            },
+            ServerBroadcast::GameOver { reason, results, game_id, .. } => {
+                GameEvent::GameOver { reason, results, game_id }
+            },
+            ServerBroadcast::EndProposed { player_name } => GameEvent::EndProposed { player_name },
+            ServerBroadcast::EndVoted { player_name, end_game } => {
+                GameEvent::EndVoted { player_name, end_game }
+            },
+            ServerBroadcast::EndRejected => GameEvent::EndRejected,
+            ServerBroadcast::Away { player_name, missed_turns } => {
+                GameEvent::Away { player_name, missed_turns }
+            },
+            ServerBroadcast::Autopilot { player_name, autopilot } => {
+                GameEvent::Autopilot { player_name, autopilot }
+            },
+            ServerBroadcast::Queued { player_name, queued, waiting, .. } => {
+                GameEvent::Queued { player_name, queued, waiting }
+            },
+            ServerBroadcast::StartScheduled { starts_at, seconds_left } => {
+                GameEvent::StartScheduled { starts_at, seconds_left }
+            },
+            ServerBroadcast::KickVoted { player_name, target, votes, needed } => {
+                GameEvent::KickVoted { player_name, target, votes, needed }
+            },
+            ServerBroadcast::KickFailed { target } => GameEvent::KickFailed { target },
+            ServerBroadcast::ServerError { message, recovery } => {
+                GameEvent::ServerError { message, recovery }
+            },
+            ServerBroadcast::Private { .. }
+                | ServerBroadcast::ResolvingNext { .. }
+                | ServerBroadcast::Clock { .. }
+                | ServerBroadcast::Shutdown => return None,
+        };
+        Some(event)
+    }
+}
+
+/// The events of a server, for tools that run alongside it in the same
+/// process. An observer is not a connection: it takes no seat, is not listed
+/// among the spectators, and cannot send messages.
+#[derive(Debug)]
+pub struct EventStream {
+    subscription: Subscription,
+}
+
+impl EventStream {
+    pub(super) fn new(broadcaster: &Broadcaster) -> Self {
+        // No player can take a name with a control character in it
+        let id = OBSERVERS.fetch_add(1, Ordering::Relaxed);
+        let subscription = broadcaster.subscribe_observer(format!("\0observer {id}").into());
+        Self { subscription }
+    }
+
+    /// Receives the next event. Returns [`None`] once the server has shut
+    /// down, or if the stream fell so far behind that it was dropped, as a
+    /// spectator would be.
+    pub async fn recv(&mut self) -> Option<GameEvent> {
+        loop {
+            match self.subscription.recv().await? {
+                ServerBroadcast::Shutdown => return None,
+                broadcast => if let Some(event) = GameEvent::of(broadcast) {
+                    return Some(event);
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::messages::{ChatChannel, ClientMessage};
+    use crate::server::capability::Capabilities;
+    use crate::server::feature::Features;
+    use crate::server::{Handshake, Server, ServerOptions};
+
+    use super::GameEvent;
+
+    #[tokio::test]
+    async fn events_follow_the_server() {
+        let (server, host) = Server::start(ServerOptions::default(), Handshake {
+            player_name: "alice".into(),
+            spectating: false,
+            capabilities: Capabilities::ALL,
+            features: Features::NONE,
+        });
+        let mut events = server.events();
+
+        let bob = server.connect_player(Handshake {
+            player_name: "bob".into(),
+            spectating: true,
+            capabilities: Capabilities::NONE,
+            features: Features::NONE,
+        }).await.unwrap();
+        let event = events.recv().await;
+        assert!(matches!(&event, Some(GameEvent::Joined { player_name, spectating: true }) if &**player_name == "bob"),
+            "{event:?}");
+
+        bob.interface.sender().send(ClientMessage::Chat { message: "hi".into(), channel: ChatChannel::Lobby }).await.unwrap();
+        let event = events.recv().await;
+        assert!(matches!(&event, Some(GameEvent::Chat { message, .. }) if &**message == "hi"), "{event:?}");
+
+
+        // The stream ends with the server
+        drop(bob);
+        host.interface.close().await.unwrap();
+        while events.recv().await.is_some() {}
+    }
+}
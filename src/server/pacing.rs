@@ -0,0 +1,69 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::game::messages::PlayerAction;
+
+use super::ServerBroadcast;
+
+/// How long each connection is held after a step of a cascade, when the game
+/// is animated.
+const ANIMATED_STEP: Duration = Duration::from_millis(800);
+
+/// How quickly the steps of a cascade, such as a merger that closes several
+/// companies and pays out their shareholders, reach each connection.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Pacing {
+    /// Every broadcast is sent as soon as it is made, as suits games played
+    /// by bots.
+    #[default]
+    Instant,
+    /// Each step of a cascade is followed by a short pause, so that those
+    /// watching can follow it. Only the connection being sent to waits;
+    /// the game itself goes on at once.
+    Animated,
+}
+
+impl Pacing {
+    /// Gets how long to wait after sending `broadcast` before sending the
+    /// next, if at all.
+    pub fn pause_after(self, broadcast: &ServerBroadcast) -> Option<Duration> {
+        if self == Pacing::Instant {
+            return None;
+        }
+        match broadcast {
+            ServerBroadcast::CompanyDefunct { .. } => Some(ANIMATED_STEP),
+            ServerBroadcast::PlayerMove { action, .. } => {
+                matches!(action.action, PlayerAction::ResolveMergeStock { .. }).then_some(ANIMATED_STEP)
+            },
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Pacing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "instant" => Ok(Pacing::Instant),
+            "animated" => Ok(Pacing::Animated),
+            _ => Err(format!("unknown pacing '{s}', expected instant or animated")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::Company;
+    use crate::server::ServerBroadcast;
+
+    use super::Pacing;
+
+    #[test]
+    fn pauses_only_when_animated() {
+        let defunct = ServerBroadcast::CompanyDefunct { defunct: Company::Imperial, results: Box::new([]) };
+        assert_eq!(Pacing::Instant.pause_after(&defunct), None);
+        assert!(Pacing::Animated.pause_after(&defunct).is_some());
+        assert_eq!(Pacing::Animated.pause_after(&ServerBroadcast::EndRejected), None);
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+
+use super::ConnectionReject;
+
+/// The number of hexadecimal digits in a generated token.
+const TOKEN_LEN: usize = 32;
+
+/// A secret a client makes up and gives each time it joins. While a game is
+/// in progress, the seat of a player who gave one is held for them: only a
+/// client giving the same token may take it back after a dropped connection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ResumeToken(Box<str>);
+
+impl ResumeToken {
+    /// Makes up a token at random.
+    pub fn generate() -> Self {
+        let mut rng = rand::thread_rng();
+        let token: String = (0..TOKEN_LEN)
+            .map(|_| char::from_digit(rng.gen_range(0..16), 16).unwrap())
+            .collect();
+        Self(token.into())
+    }
+}
+
+/// The token each player last joined with, by name.
+#[derive(Debug, Clone, Default)]
+pub struct ResumeTokens {
+    tokens: HashMap<Box<str>, ResumeToken>,
+}
+
+impl ResumeTokens {
+    /// Checks the token given by someone joining as `player_name`. If the
+    /// name holds a seat in the game in progress, as told by `seated`, and the
+    /// player who held it gave a token, the same token must be given.
+    pub fn check(&self, player_name: &str, token: Option<&ResumeToken>, seated: bool)
+        -> Result<(), ConnectionReject>
+    {
+        match self.tokens.get(player_name) {
+            Some(held) if seated && Some(held) != token => Err(ConnectionReject::SeatHeld),
+            _ => Ok(()),
+        }
+    }
+
+    /// Remembers the token a player joined with. Joining without one lets
+    /// go of any given before under the same name.
+    pub fn hold(&mut self, player_name: &str, token: Option<ResumeToken>) {
+        match token {
+            Some(token) => self.tokens.insert(player_name.into(), token),
+            None => self.tokens.remove(player_name),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::server::ConnectionReject;
+
+    use super::{ResumeToken, ResumeTokens};
+
+    #[test]
+    fn holds_seats_for_their_token() {
+        let mut tokens = ResumeTokens::default();
+        let token = ResumeToken::generate();
+        assert_ne!(token, ResumeToken::generate());
+        tokens.hold("alice", Some(token.clone()));
+
+        assert!(tokens.check("alice", Some(&token), true).is_ok());
+        assert!(matches!(tokens.check("alice", None, true), Err(ConnectionReject::SeatHeld)));
+        assert!(matches!(
+            tokens.check("alice", Some(&ResumeToken::generate()), true),
+            Err(ConnectionReject::SeatHeld)
+        ));
+
+        // Outside of a game, names are free for anyone to take
+        assert!(tokens.check("alice", None, false).is_ok());
+        tokens.hold("alice", None);
+        assert!(tokens.check("alice", None, true).is_ok());
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use crate::game::messages::ClientMessage;
+use crate::game::tile::Tile;
+
+use super::capability::Capabilities;
+use super::feature::Features;
+use super::{Handshake, Interface, Server, ServerOptions};
+
+/// How long a scripted client waits for an expected message before the
+/// scenario fails.
+const EXPECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A scripted game, played by in-process clients against a real [`Server`].
+#[derive(Debug, Deserialize)]
+struct Scenario {
+    /// The players, in the order in which they connect. The first player is
+    /// the host, and is the only admin.
+    players: Vec<Box<str>>,
+    /// The order in which tiles are dealt. See [`Server::stack_tiles`].
+    tiles: Vec<Tile>,
+    /// See [`ServerOptions::queue_early_actions`].
+    #[serde(default)]
+    queue_early_actions: bool,
+    /// See [`ServerOptions::matchmaking`].
+    #[serde(default)]
+    matchmaking: Option<usize>,
+    script: Vec<Step>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Step {
+    /// A player sends a message to the server.
+    Send {
+        send: Box<str>,
+        message: ClientMessage,
+    },
+    /// Each of the players receives a message from the server next. Only the
+    /// fields present in `message` are compared, so that fields that are
+    /// awkward to predict, such as state hashes, can be left out.
+    Expect {
+        expect: Vec<Box<str>>,
+        message: Value,
+    },
+    /// A player connects partway through the scenario.
+    Connect {
+        connect: Box<str>,
+        #[serde(default)]
+        spectating: bool,
+    },
+    /// A player disconnects, and receives nothing more.
+    Disconnect {
+        disconnect: Box<str>,
+    },
+    /// The server panics on the next action a player sends. See
+    /// [`Server::fail_next_action`].
+    Fail {
+        fail_next_action: bool,
+    },
+}
+
+/// A client that sends the messages it is told to, and records every message
+/// it receives.
+struct ScriptedClient {
+    sender: mpsc::Sender<ClientMessage>,
+    received: mpsc::UnboundedReceiver<Value>,
+    /// The task that owns the interface. Aborting it closes the connection.
+    drain: tokio::task::JoinHandle<()>,
+}
+
+impl ScriptedClient {
+    fn new(mut interface: Interface<std::convert::Infallible>) -> Self {
+        let sender = interface.sender().clone();
+        let (record, received) = mpsc::unbounded_channel();
+
+        // Drain the interface as messages arrive, so the server never waits on
+        // a client that the script has not gotten around to yet
+        let drain = tokio::spawn(async move {
+            while let Some(Ok(message)) = interface.recv().await {
+                let message = serde_json::to_value(message).unwrap();
+                if record.send(message).is_err() { break; }
+            }
+        });
+
+        Self { sender, received, drain }
+    }
+}
+
+/// Plays the scenario in `json`, panicking if any client receives a message
+/// other than the one the script expects.
+async fn play(json: &str) {
+    let scenario: Scenario = serde_json::from_str(json).expect("malformed scenario");
+
+    let mut players = scenario.players.iter();
+    let host = players.next().expect("a scenario needs a host");
+    let options = ServerOptions {
+        queue_early_actions: scenario.queue_early_actions,
+        matchmaking: scenario.matchmaking,
+        ..Default::default()
+    };
+    let (server, host_connection) = Server::start(options, Handshake {
+        player_name: host.clone(),
+        spectating: false,
+        capabilities: Capabilities::ALL,
+        features: Features::NONE,
+    });
+    server.stack_tiles(scenario.tiles).await;
+
+    let mut clients = HashMap::new();
+    clients.insert(host.clone(), ScriptedClient::new(host_connection.interface));
+    for name in players {
+        let connection = server.connect_player(Handshake {
+            player_name: name.clone(),
+            spectating: false,
+            capabilities: Capabilities::NONE,
+            features: Features::NONE,
+        }).await.unwrap();
+        clients.insert(name.clone(), ScriptedClient::new(connection.interface));
+    }
+
+    for (i, step) in scenario.script.into_iter().enumerate() {
+        match step {
+            Step::Send { send, message } => {
+                clients[&send].sender.send(message).await.unwrap();
+            },
+            Step::Connect { connect, spectating } => {
+                let connection = server.connect_player(Handshake {
+                    player_name: connect.clone(),
+                    spectating,
+                    capabilities: Capabilities::NONE,
+                    features: Features::NONE,
+                }).await.unwrap_or_else(|err| panic!("step {i}: {connect} could not connect: {err}"));
+                clients.insert(connect, ScriptedClient::new(connection.interface));
+            },
+            Step::Fail { fail_next_action } => {
+                if fail_next_action {
+                    server.fail_next_action().await;
+                }
+            },
+            Step::Disconnect { disconnect } => {
+                clients.remove(&disconnect)
+                    .unwrap_or_else(|| panic!("step {i}: {disconnect} is not connected"))
+                    .drain
+                    .abort();
+            },
+            Step::Expect { expect, message } => {
+                for name in expect {
+                    let client = clients.get_mut(&name)
+                        .unwrap_or_else(|| panic!("step {i}: {name} is not in the scenario"));
+                    let found = tokio::time::timeout(EXPECT_TIMEOUT, client.received.recv())
+                        .await
+                        .unwrap_or_else(|_| panic!("step {i}: {name} received nothing"))
+                        .unwrap_or_else(|| panic!("step {i}: {name} was disconnected"));
+
+                    assert!(
+                        matches(&message, &found),
+                        "step {i}: {name} expected {message}, but received {found}"
+                    );
+                }
+            },
+        }
+    }
+}
+
+/// Checks if `found` has every field of `expected`, with the same values.
+/// Arrays must match element by element, and all other values must be equal.
+fn matches(expected: &Value, found: &Value) -> bool {
+    match (expected, found) {
+        (Value::Object(expected), Value::Object(found)) => expected.iter()
+            .all(|(key, value)| found.get(key).is_some_and(|found| matches(value, found))),
+        (Value::Array(expected), Value::Array(found)) => {
+            expected.len() == found.len()
+                && expected.iter().zip(found).all(|(expected, found)| matches(expected, found))
+        },
+        _ => expected == found,
+    }
+}
+
+#[tokio::test]
+async fn found_company_and_end_game() {
+    play(include_str!("scenarios/found_company_and_end_game.json")).await;
+}
+
+#[tokio::test]
+async fn queue_early_actions() {
+    play(include_str!("scenarios/queue_early_actions.json")).await;
+}
+
+#[tokio::test]
+async fn seat_spectators() {
+    play(include_str!("scenarios/seat_spectators.json")).await;
+}
+
+#[tokio::test]
+async fn matchmaking() {
+    play(include_str!("scenarios/matchmaking.json")).await;
+}
+
+#[tokio::test]
+async fn vote_kick() {
+    play(include_str!("scenarios/vote_kick.json")).await;
+}
+
+#[tokio::test]
+async fn autopilot() {
+    play(include_str!("scenarios/autopilot.json")).await;
+}
+
+#[tokio::test]
+async fn substitute_player() {
+    play(include_str!("scenarios/substitute_player.json")).await;
+}
+
+#[tokio::test]
+async fn end_by_agreement() {
+    play(include_str!("scenarios/end_by_agreement.json")).await;
+}
+
+#[tokio::test]
+async fn rename_players() {
+    play(include_str!("scenarios/rename_players.json")).await;
+}
+
+#[tokio::test]
+async fn recover_from_server_error() {
+    play(include_str!("scenarios/recover_from_server_error.json")).await;
+}
+
+#[tokio::test]
+async fn chat_channels() {
+    play(include_str!("scenarios/chat_channels.json")).await;
+}
+
+#[tokio::test]
+async fn duplicate_actions() {
+    play(include_str!("scenarios/duplicate_actions.json")).await;
+}
+
+#[tokio::test]
+async fn resolving_next() {
+    play(include_str!("scenarios/resolving_next.json")).await;
+}
+
+#[tokio::test]
+async fn request_ids() {
+    play(include_str!("scenarios/request_ids.json")).await;
+}
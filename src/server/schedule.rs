@@ -0,0 +1,93 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::time::Instant;
+
+/// How long before a scheduled start the seats at the table are closed, so
+/// that the players know who they will be playing with.
+pub const SEATS_CLOSE: Duration = Duration::from_secs(60);
+/// How long before a scheduled start everyone is reminded of it, from the
+/// earliest reminder to the last.
+const REMINDERS: [Duration; 6] = [
+    Duration::from_secs(600),
+    Duration::from_secs(300),
+    Duration::from_secs(120),
+    Duration::from_secs(60),
+    Duration::from_secs(30),
+    Duration::from_secs(10),
+];
+
+/// A game an admin has scheduled to start at a set time.
+#[derive(Debug, Clone)]
+pub struct Countdown {
+    /// The admin who scheduled the game, who is told of any problem in
+    /// starting it.
+    admin_name: Box<str>,
+    /// When the game starts, in seconds since the Unix epoch.
+    starts_at: u64,
+    deadline: Instant,
+}
+
+impl Countdown {
+    /// Counts down to `starts_at`, in seconds since the Unix epoch. Gives
+    /// [`None`] if that time has already come.
+    pub fn new(admin_name: Box<str>, starts_at: u64, now: Instant) -> Option<Self> {
+        let unix_now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let left = starts_at.checked_sub(unix_now).filter(|&left| left > 0)?;
+        Some(Self { admin_name, starts_at, deadline: now + Duration::from_secs(left) })
+    }
+
+    pub fn admin_name(&self) -> &str {
+        &self.admin_name
+    }
+
+    pub fn starts_at(&self) -> u64 {
+        self.starts_at
+    }
+
+    /// Gets the time left until the start, in whole seconds, rounded up.
+    pub fn seconds_left(&self, now: Instant) -> u64 {
+        let left = self.deadline.saturating_duration_since(now);
+        left.as_secs() + u64::from(left.subsec_nanos() > 0)
+    }
+
+    /// Checks if the start is close enough that no more seats are given.
+    pub fn seats_closed(&self, now: Instant) -> bool {
+        self.deadline.saturating_duration_since(now) <= SEATS_CLOSE
+    }
+
+    /// Gets the moment of the next reminder, or of the start if no reminder
+    /// is left before it.
+    pub fn next_tick(&self, now: Instant) -> Instant {
+        let left = self.deadline.saturating_duration_since(now);
+        REMINDERS.iter()
+            .find(|&&before| before < left)
+            .map_or(self.deadline, |&before| self.deadline - before)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use tokio::time::Instant;
+
+    use super::Countdown;
+
+    #[test]
+    fn reminds_on_the_way_to_the_start() {
+        let now = Instant::now();
+        let unix_now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        assert!(Countdown::new("alice".into(), unix_now, now).is_none());
+
+        let countdown = Countdown::new("alice".into(), unix_now + 90, now).unwrap();
+        assert_eq!(countdown.seconds_left(now), 90);
+        assert!(!countdown.seats_closed(now));
+        assert_eq!(countdown.next_tick(now), now + Duration::from_secs(30));
+
+        let later = now + Duration::from_secs(85);
+        assert!(countdown.seats_closed(later));
+        assert_eq!(countdown.next_tick(later), now + Duration::from_secs(90));
+    }
+}
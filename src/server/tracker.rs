@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::game::game_id::GameId;
+use crate::game::messages::{FinalResult, GameOver};
+
+use super::fanout::Subscription;
+use super::webhook::{self, WebhookUrl};
+use super::ServerBroadcast;
+
+/// The number of times a result is submitted before it is given up on.
+const ATTEMPTS: u32 = 3;
+/// How long to wait before submitting a result again, doubled after each
+/// failed attempt.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+/// How long a submission is given to be answered before it counts as failed.
+const SUBMIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The result of a finished game, as submitted to a tracker.
+#[derive(Debug, Serialize)]
+struct Submission<'a> {
+    game_id: GameId,
+    reason: &'a GameOver,
+    standings: &'a [FinalResult],
+}
+
+/// Checks whether a broadcast is one the tracker acts on, so that it is the
+/// only kind queued for it.
+pub(super) fn wants(broadcast: &ServerBroadcast) -> bool {
+    matches!(broadcast,
+        ServerBroadcast::Shutdown | ServerBroadcast::GameOver { game_id: Some(_), .. })
+}
+
+/// Submits the final standings of each game to `url` under its ID as the game
+/// ends, until the server shuts down. Trackers such as community leaderboards
+/// count on every result, so a submission that fails is tried again a few
+/// times before it is dropped.
+pub(super) async fn submit_results(url: WebhookUrl, mut subscription: Subscription) {
+    while let Some(broadcast) = subscription.recv().await {
+        let (reason, results, game_id) = match &broadcast {
+            ServerBroadcast::Shutdown => break,
+            ServerBroadcast::GameOver { reason, results, game_id: Some(game_id), .. } => {
+                (reason, results, *game_id)
+            },
+            _ => continue,
+        };
+        let submission = Submission { game_id, reason, standings: results };
+        let body = serde_json::to_vec(&submission).expect("submissions always serialize");
+
+        let mut delay = RETRY_DELAY;
+        for attempt in 1..=ATTEMPTS {
+            let result = tokio::time::timeout(SUBMIT_TIMEOUT, webhook::post(&url, &body)).await;
+            if let Ok(Ok(())) = result { break; }
+            if attempt < ATTEMPTS {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+        }
+    }
+}
+
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// The share of the players that must agree for a vote to pass.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Majority {
+    /// More than half of the players.
+    #[default]
+    Simple,
+    /// At least two thirds of the players.
+    TwoThirds,
+    /// Every player.
+    Unanimous,
+}
+
+impl Majority {
+    /// Checks if `agree` players out of `voters` make up this majority.
+    pub fn reached(self, agree: usize, voters: usize) -> bool {
+        match self {
+            Majority::Simple => agree * 2 > voters,
+            Majority::TwoThirds => agree * 3 >= voters * 2,
+            Majority::Unanimous => agree >= voters,
+        }
+    }
+}
+
+impl FromStr for Majority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "simple" => Ok(Majority::Simple),
+            "two-thirds" => Ok(Majority::TwoThirds),
+            "unanimous" => Ok(Majority::Unanimous),
+            _ => Err(format!("unknown majority '{s}', expected simple, two-thirds, or unanimous")),
+        }
+    }
+}
+
+/// The ballots cast so far in a vote among the players. A player may change
+/// their ballot until the vote is decided.
+#[derive(Debug, Clone, Default)]
+pub struct Vote {
+    ballots: HashMap<Box<str>, bool>,
+}
+
+impl Vote {
+    pub fn cast(&mut self, voter: &str, agree: bool) {
+        self.ballots.insert(voter.into(), agree);
+    }
+
+    /// Discards the ballot of a player who no longer has a say.
+    pub fn withdraw(&mut self, voter: &str) {
+        self.ballots.remove(voter);
+    }
+
+    /// Decides the vote among `voters` players, once enough ballots have been
+    /// cast that the rest can no longer change the result. Returns [`None`]
+    /// while the vote is still open.
+    pub fn outcome(&self, majority: Majority, voters: usize) -> Option<bool> {
+        let agree = self.ballots.values().filter(|&&agree| agree).count();
+        let disagree = self.ballots.len() - agree;
+
+        if majority.reached(agree, voters) {
+            Some(true)
+        } else if !majority.reached(voters.saturating_sub(disagree), voters) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Majority, Vote};
+
+    #[test]
+    fn vote_decided_once_the_rest_cannot_change_it() {
+        let mut vote = Vote::default();
+        vote.cast("alice", true);
+        vote.cast("bob", true);
+        assert_eq!(vote.outcome(Majority::Simple, 4), None);
+        assert_eq!(vote.outcome(Majority::Simple, 3), Some(true));
+
+        vote.cast("carol", false);
+        assert_eq!(vote.outcome(Majority::TwoThirds, 4), None);
+        assert_eq!(vote.outcome(Majority::Unanimous, 4), Some(false));
+
+        // A changed ballot replaces the old one
+        vote.cast("carol", true);
+        assert_eq!(vote.outcome(Majority::TwoThirds, 4), Some(true));
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::game::messages::InvalidMessageReason;
+
+use super::vote::Majority;
+
+/// How long a vote to kick is open. A vote that hasn't passed by then fails.
+pub const WINDOW: Duration = Duration::from_secs(60);
+/// How long a player who proposed a vote to kick must wait to propose
+/// another, and how long a player who outlasted one is safe from the next.
+pub const COOLDOWN: Duration = Duration::from_secs(180);
+/// The share of the seated players who must agree to kick someone. The
+/// target is counted among them, though they can't vote.
+const MAJORITY: Majority = Majority::TwoThirds;
+/// The fewest seated players, the target among them, who may kick someone.
+/// Any fewer, and one or two players could gang up on the rest.
+pub const MIN_SEATED: usize = 3;
+
+/// The votes by which the seated players kick someone out of the server when
+/// no admin is around to. One vote is open at a time.
+#[derive(Debug, Clone, Default)]
+pub struct KickVotes {
+    open: Option<KickVote>,
+    /// When each player may next propose a vote, or next be voted on.
+    cooldowns: HashMap<Box<str>, Instant>,
+}
+
+#[derive(Debug, Clone)]
+struct KickVote {
+    target: Box<str>,
+    ballots: HashSet<Box<str>>,
+    deadline: Instant,
+}
+
+/// Where a vote to kick stands after a ballot is cast.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KickTally {
+    /// The vote is still open, with `votes` of the `needed` votes cast.
+    Open { votes: usize, needed: usize },
+    /// Enough players agreed, and the vote is closed.
+    Passed,
+}
+
+impl KickVotes {
+    /// Casts `voter`'s vote to kick `target`, proposing it if no vote is
+    /// open. `seated` is the number of seated players, the target among them.
+    pub fn vote(&mut self, voter: &str, target: &str, seated: usize, now: Instant)
+        -> Result<KickTally, InvalidMessageReason>
+    {
+        if seated < MIN_SEATED {
+            return Err(InvalidMessageReason::TooFewToKick);
+        }
+        self.cooldowns.retain(|_, until| *until > now);
+
+        let vote = match &mut self.open {
+            Some(vote) if *vote.target != *target => return Err(InvalidMessageReason::KickVoteOpen),
+            Some(vote) => vote,
+            None => {
+                if self.cooldowns.contains_key(voter) || self.cooldowns.contains_key(target) {
+                    return Err(InvalidMessageReason::KickCooldown);
+                }
+                self.cooldowns.insert(voter.into(), now + COOLDOWN);
+                self.open.insert(KickVote {
+                    target: target.into(),
+                    ballots: HashSet::new(),
+                    deadline: now + WINDOW,
+                })
+            },
+        };
+
+        vote.ballots.insert(voter.into());
+        let votes = vote.ballots.len();
+        if MAJORITY.reached(votes, seated) {
+            self.open = None;
+            return Ok(KickTally::Passed);
+        }
+        let needed = (1..=seated).find(|&agree| MAJORITY.reached(agree, seated)).unwrap_or(seated);
+        Ok(KickTally::Open { votes, needed })
+    }
+
+    /// Gets the moment the open vote, if any, runs out of time.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.open.as_ref().map(|vote| vote.deadline)
+    }
+
+    /// Closes the open vote if its time is up, giving the player who
+    /// outlasted it. They are safe from another vote for a while.
+    pub fn expire(&mut self, now: Instant) -> Option<Box<str>> {
+        if self.deadline()? > now {
+            return None;
+        }
+        let target = self.open.take()?.target;
+        self.cooldowns.insert(target.clone(), now + COOLDOWN);
+        Some(target)
+    }
+
+    /// Forgets a player who has left: their ballot is withdrawn, and a vote
+    /// to kick them is called off.
+    pub fn withdraw(&mut self, player_name: &str) {
+        if let Some(vote) = &mut self.open {
+            if *vote.target == *player_name {
+                self.open = None;
+            } else {
+                vote.ballots.remove(player_name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use crate::game::messages::InvalidMessageReason;
+
+    use super::{KickTally, KickVotes, COOLDOWN, WINDOW};
+
+    #[test]
+    fn kicks_with_a_supermajority_in_time() {
+        let now = Instant::now();
+        let mut votes = KickVotes::default();
+
+        // Four players are seated, so three must agree
+        assert_eq!(votes.vote("alice", "mallory", 4, now).ok(), Some(KickTally::Open { votes: 1, needed: 3 }));
+        assert!(matches!(votes.vote("bob", "carol", 4, now), Err(InvalidMessageReason::KickVoteOpen)));
+        assert_eq!(votes.vote("bob", "mallory", 4, now).ok(), Some(KickTally::Open { votes: 2, needed: 3 }));
+        assert_eq!(votes.vote("carol", "mallory", 4, now).ok(), Some(KickTally::Passed));
+
+        // The proposer must wait before proposing again
+        assert!(matches!(votes.vote("alice", "eve", 4, now), Err(InvalidMessageReason::KickCooldown)));
+
+        // A vote that runs out of time fails, and its target is safe for a
+        // while
+        votes.vote("bob", "eve", 4, now).unwrap();
+        assert_eq!(votes.expire(now), None);
+        assert_eq!(votes.expire(now + WINDOW).as_deref(), Some("eve"));
+        assert!(matches!(votes.vote("carol", "eve", 4, now + WINDOW), Err(InvalidMessageReason::KickCooldown)));
+        let later = now + WINDOW + COOLDOWN + Duration::from_secs(1);
+        assert!(votes.vote("carol", "eve", 4, later).is_ok());
+    }
+
+    #[test]
+    fn two_players_cant_kick_each_other() {
+        let mut votes = KickVotes::default();
+        assert!(matches!(votes.vote("alice", "bob", 2, Instant::now()), Err(InvalidMessageReason::TooFewToKick)));
+        assert_eq!(votes.deadline(), None);
+    }
+}
@@ -0,0 +1,211 @@
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::net::dial;
+
+use super::fanout::Subscription;
+use super::ServerBroadcast;
+
+/// The port posted to when the URL names none.
+const DEFAULT_PORT: u16 = 80;
+/// How long a post is given to be answered before it is given up on.
+const POST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reasons a webhook URL could not be used.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum WebhookUrlError {
+    #[error("only http:// webhooks can be posted to; reach an https:// one through a local relay")]
+    UnsupportedScheme,
+    #[error("'{0}' is not a valid webhook URL")]
+    Invalid(String),
+}
+
+/// Where summaries of each game are posted, as an `http://` URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookUrl {
+    host: Box<str>,
+    port: u16,
+    path: Box<str>,
+}
+
+impl FromStr for WebhookUrl {
+    type Err = WebhookUrlError;
+
+    fn from_str(url: &str) -> Result<Self, Self::Err> {
+        let rest = url.trim().strip_prefix("http://").ok_or_else(|| match url.contains("://") {
+            true => WebhookUrlError::UnsupportedScheme,
+            false => WebhookUrlError::Invalid(url.to_owned()),
+        })?;
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => rest.split_at(slash),
+            None => (rest, "/"),
+        };
+        let (host, port) = dial::split_with_default(authority, DEFAULT_PORT)
+            .map_err(|_| WebhookUrlError::Invalid(url.to_owned()))?;
+        Ok(Self { host: host.into(), port, path: path.into() })
+    }
+}
+
+impl fmt::Display for WebhookUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.host.contains(':') {
+            true => write!(f, "http://[{}]:{}{}", self.host, self.port, self.path),
+            false => write!(f, "http://{}:{}{}", self.host, self.port, self.path),
+        }
+    }
+}
+
+/// A summary of part of a game, posted as JSON. `content` is the text that
+/// chat services such as Discord show, and `details` the same in full for
+/// anything that reads it.
+#[derive(Debug, Serialize)]
+struct Notice {
+    event: &'static str,
+    content: String,
+    details: serde_json::Value,
+}
+
+impl Notice {
+    /// Summarizes the broadcasts worth posting: the start of a game, each
+    /// merger, and the end of a game.
+    fn of(broadcast: &ServerBroadcast) -> Option<Self> {
+        let notice = match broadcast {
+            ServerBroadcast::GameStart { info, .. } => Notice {
+                event: "game_start",
+                content: format!("A game has begun. Order of play: {}.", info.play_order.join(", ")),
+                details: serde_json::json!({
+                    "game_id": info.game_id,
+                    "play_order": info.play_order,
+                    "starting_cash": info.starting_cash,
+                }),
+            },
+            ServerBroadcast::CompanyDefunct { defunct, results } => {
+                let bonuses: Vec<_> = results.iter().map(ToString::to_string).collect();
+                Notice {
+                    event: "merger",
+                    content: format!("{defunct} has gone defunct. {}", bonuses.join(" ")),
+                    details: serde_json::json!({
+                        "defunct": defunct,
+                        "results": results,
+                    }),
+                }
+            },
+            ServerBroadcast::GameOver { reason, results, game_id, .. } => {
+                let standings: Vec<_> = results.iter().map(ToString::to_string).collect();
+                Notice {
+                    event: "game_over",
+                    content: format!("The game is over, as {reason}. {}", standings.join(", ")),
+                    details: serde_json::json!({
+                        "game_id": game_id,
+                        "reason": reason,
+                        "results": results,
+                    }),
+                }
+            },
+            _ => return None,
+        };
+        Some(notice)
+    }
+}
+
+/// Checks whether a broadcast is one the notifier acts on, so that it is the
+/// only kind queued for it.
+pub(super) fn wants(broadcast: &ServerBroadcast) -> bool {
+    matches!(broadcast, ServerBroadcast::Shutdown) || Notice::of(broadcast).is_some()
+}
+
+/// Posts a summary of each game to `url` as it is played, until the server
+/// shuts down. A post that fails is dropped rather than retried, so that an
+/// unreachable webhook costs nothing but its summaries.
+pub(super) async fn notify(url: WebhookUrl, mut subscription: Subscription) {
+    while let Some(broadcast) = subscription.recv().await {
+        if matches!(broadcast, ServerBroadcast::Shutdown) { break; }
+        if let Some(notice) = Notice::of(&broadcast) {
+            let body = serde_json::to_vec(&notice).expect("notices always serialize");
+            tokio::time::timeout(POST_TIMEOUT, post(&url, &body)).await.ok();
+        }
+    }
+}
+
+/// Posts a JSON body to `url`, failing unless it is answered with a status
+/// of 2xx.
+pub(super) async fn post(url: &WebhookUrl, body: &[u8]) -> io::Result<()> {
+    let mut stream = TcpStream::connect((&*url.host, url.port)).await?;
+    let host = match url.host.contains(':') {
+        true => format!("[{}]", url.host),
+        false => url.host.to_string(),
+    };
+    let host = match url.port {
+        DEFAULT_PORT => host,
+        port => format!("{host}:{port}"),
+    };
+    let head = format!(
+        "POST {} HTTP/1.1\r\nHost: {host}\r\nContent-Type: application/json\r\n\
+        Content-Length: {}\r\nConnection: close\r\n\r\n",
+        url.path,
+        body.len(),
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.flush().await?;
+
+    let mut status = String::new();
+    BufReader::new(stream).read_line(&mut status).await?;
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(io::Error::other(format!("webhook answered '{}'", status.trim_end()))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use crate::game::messages::GameStart;
+    use crate::server::ServerBroadcast;
+
+    use super::{post, Notice, WebhookUrl, WebhookUrlError};
+
+    #[tokio::test]
+    async fn posts_summaries() {
+        let url: WebhookUrl = "http://example.com/hooks/1".parse().unwrap();
+        assert_eq!(url.to_string(), "http://example.com:80/hooks/1");
+        assert_eq!("http://[::1]:8080".parse::<WebhookUrl>().unwrap().to_string(), "http://[::1]:8080/");
+        assert!(matches!("https://example.com".parse::<WebhookUrl>(), Err(WebhookUrlError::UnsupportedScheme)));
+        assert!(matches!("example.com".parse::<WebhookUrl>(), Err(WebhookUrlError::Invalid(_))));
+
+        let start = ServerBroadcast::GameStart {
+            info: GameStart::two_player_test(),
+            initial_hands: Default::default(),
+        };
+        let notice = Notice::of(&start).unwrap();
+        assert_eq!(notice.content, "A game has begun. Order of play: alice, bob.");
+        assert!(Notice::of(&ServerBroadcast::EndRejected).is_none());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url: WebhookUrl = format!("http://{}/hook", listener.local_addr().unwrap()).parse().unwrap();
+        let webhook = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            while !request.ends_with(b"{}") {
+                let mut buf = [0; 256];
+                let len = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..len]);
+            }
+            socket.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        post(&url, b"{}").await.unwrap();
+        let request = webhook.await.unwrap();
+        assert!(request.starts_with("POST /hook HTTP/1.1\r\n"), "{request}");
+        assert!(request.ends_with("\r\n\r\n{}"), "{request}");
+    }
+}